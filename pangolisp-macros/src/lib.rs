@@ -0,0 +1,340 @@
+//! Pangolisp inline in rust. `pangolisp! { .. }` and
+//! `#[pangolisp_file("gen.pl")]` evaluate pangolisp as the crate that
+//! uses them compiles, and put the rust items it translates into (see
+//! `pangolisp::codegen`) where they stand.
+//!
+//! The forms in `pangolisp!` must lex as rust tokens, so they cannot
+//! quote a list with `'` or quasiquote with a backtick, and comments in
+//! them are rust comments. `(quote ..)` and `(quasiquote ..)` will do.
+//! Where a form fails, the error is a `compile_error!` at the token it
+//! failed at, or at the whole form where that is not known. The rust
+//! `pangolisp!` expands into is spanned at the pangolisp it came from,
+//! as far as the source map knows, so rustc's errors in it are too.
+
+use pangolisp::codegen::{Codegen, CodegenError};
+use pangolisp::eval::Eval;
+use pangolisp::forms::FormError;
+use pangolisp::rust::{self, Attribute, Expr, Item, Path as RustPath};
+use pangolisp::sourcemap::SourceMap;
+use pangolisp::spans;
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use std::fs;
+use std::path::Path;
+
+// what a module of generated code allows at its top, each item allows
+const ALLOW: &[&str] = &["dead_code", "unreachable_patterns", "unused_variables", "clippy::all"];
+
+/// Evaluates the pangolisp forms given and expands into the rust items
+/// they translate into.
+///
+/// ```
+/// pangolisp_macros::pangolisp! {
+///     (defn gcd [[a i64] [b i64]]
+///       (the i64 (if (= b 0) a (gcd b (mod a b)))))
+/// }
+///
+/// assert_eq!(gcd(12, 18), 6);
+/// ```
+///
+/// A form with no translation fails to compile.
+///
+/// ```compile_fail
+/// pangolisp_macros::pangolisp! {
+///     (defn twice [x] (the i64 (* 2 x)))
+/// }
+/// ```
+#[proc_macro]
+pub fn pangolisp(input: TokenStream) -> TokenStream {
+    let source = Source::new(input);
+    let items = match translate(&source.text) {
+        Ok(items) => items,
+        Err(e) => return compile_error(&e.message, e.span.map_or_else(Span::call_site, |span| source.token_at(span.start))),
+    };
+    let (code, mappings) = rust::mapped_file("", &items);
+    let tokens = parse(&code, Span::call_site());
+    let mut respan = Respan { code: code.chars().collect(), at: 0, pos: (0, 0), map: SourceMap::new("", "", mappings), source };
+    respan.stream(tokens).unwrap_or_else(|tokens| tokens)
+}
+
+/// Evaluates the pangolisp file at the path given, from the directory of
+/// the crate's `Cargo.toml`, and puts the rust items it translates into
+/// in the module it is on, or after the item it is on.
+///
+/// ```ignore
+/// #[pangolisp_file("src/shapes.pl")]
+/// mod shapes {}
+/// ```
+#[proc_macro_attribute]
+pub fn pangolisp_file(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let (name, span) = match string_literal(attr) {
+        Ok(literal) => literal,
+        Err(span) => return compile_error("expected the path of a pangolisp file, as a string", span),
+    };
+    let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let path = Path::new(&dir).join(&name);
+    let src = match fs::read_to_string(&path) {
+        Ok(src) => src,
+        Err(e) => return compile_error(&format!("couldn't read {}: {}", path.display(), e), span),
+    };
+    let items = match translate(&src) {
+        // rebuilt when the file changes
+        Ok(items) => format!("{}\nconst _: &str = include_str!({:?});\n", rust::file(&items), path.display().to_string()),
+        Err(Error { message, span: Some(at) }) =>
+            return compile_error(&format!("{}:{}:{}: {}", name, at.start.line + 1, at.start.column + 1, message), span),
+        Err(Error { message, span: None }) => return compile_error(&format!("{}: {}", name, message), span),
+    };
+    let items = parse(&items, span);
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+    let is_mod = tokens.iter().any(|t| matches!(t, TokenTree::Ident(ident) if ident.to_string() == "mod"));
+    match tokens.last_mut() {
+        Some(TokenTree::Group(body)) if is_mod && body.delimiter() == Delimiter::Brace => {
+            let mut stream = body.stream();
+            stream.extend(items);
+            let mut group = Group::new(Delimiter::Brace, stream);
+            group.set_span(body.span());
+            *body = group;
+            tokens.into_iter().collect()
+        }
+        _ => tokens.into_iter().chain(items).collect(),
+    }
+}
+
+struct Error {
+    message: String,
+    span: Option<spans::Span>,
+}
+
+impl Error {
+    fn new(e: CodegenError, span: Option<spans::Span>) -> Error {
+        Error { span: e.span().or(span), message: e.to_string() }
+    }
+}
+
+// the items the forms of `src` translate into, each allowing what a
+// generated module would
+fn translate(src: &str) -> Result<Vec<Item>, Error> {
+    let mut eval = Eval::new();
+    let mut codegen = Codegen::new();
+    let mut items = Vec::new();
+    let mut forms = eval.forms(src);
+    while let Some(form) = forms.next() {
+        let form = form.map_err(|e| {
+            let span = match &e {
+                FormError::DoesNotComplete(paren, _) => Some(paren.span),
+                _ => None,
+            };
+            Error::new(CodegenError::Read(format!("{:?}", e)), span)
+        })?;
+        let span = form.span();
+        let expr = eval.read(form).map_err(|e| Error::new(CodegenError::Read(format!("{:?}", e)), Some(span)))?;
+        for mut item in codegen.top_level(&mut eval, &expr).map_err(|e| Error::new(e, Some(span)))? {
+            let allow = Attribute { path: RustPath::from("allow"), args: ALLOW.iter().map(|lint| Expr::path(lint)).collect() };
+            match &mut item {
+                Item::Fn(function) => function.header.attrs.insert(0, allow),
+                Item::Struct(header, ..) | Item::Enum(header, ..) | Item::Impl(header, ..) | Item::Use(header, ..) =>
+                    header.attrs.insert(0, allow),
+            }
+            items.push(item);
+        }
+        forms.set_dispatch(eval.dispatch());
+    }
+    Ok(items)
+}
+
+fn parse(items: &str, span: Span) -> TokenStream {
+    match items.parse() {
+        Ok(tokens) => tokens,
+        Err(e) => compile_error(&format!("the generated rust does not lex: {}", e), span),
+    }
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut message = TokenTree::Literal(Literal::string(message));
+    message.set_span(span);
+    let tokens = vec![
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(Punct::new('!', Spacing::Alone)),
+        TokenTree::Group(Group::new(Delimiter::Brace, message.into())),
+    ];
+    tokens.into_iter().map(|mut token| {
+        token.set_span(span);
+        token
+    }).collect()
+}
+
+// the text of an attribute's lone string literal, and its span
+fn string_literal(attr: TokenStream) -> Result<(String, Span), Span> {
+    let tokens: Vec<TokenTree> = attr.into_iter().collect();
+    let literal = match tokens.as_slice() {
+        [TokenTree::Literal(literal)] => literal,
+        [token, ..] => return Err(token.span()),
+        [] => return Err(Span::call_site()),
+    };
+    let text = literal.to_string();
+    match text.strip_prefix('"').and_then(|text| text.strip_suffix('"')) {
+        Some(text) => Ok((text.replace("\\\"", "\"").replace("\\\\", "\\"), literal.span())),
+        None => Err(literal.span()),
+    }
+}
+
+/// The text of the forms given to `pangolisp!`, each as written and on
+/// the same line and column as in the rust file, so that errors give
+/// rust's positions and can be put back on the token they are at.
+struct Source {
+    text: String,
+    // where the end of `text` is, as rust counts lines and columns
+    line: usize,
+    column: usize,
+    // where each token starts, in the order they were written
+    tokens: Vec<(usize, usize, Span)>,
+}
+
+impl Source {
+    fn new(input: TokenStream) -> Source {
+        let mut source = Source { text: String::new(), line: 1, column: 1, tokens: Vec::new() };
+        for token in input {
+            let span = token.span();
+            let (line, column) = (span.line(), span.column());
+            if line > source.line {
+                source.push(&"\n".repeat(line - source.line));
+            }
+            if line == source.line && column >= source.column {
+                source.push(&" ".repeat(column - source.column));
+            } else {
+                // from a macro, not where it was written
+                source.push(" ");
+            }
+            // the text as written, which rust would otherwise space
+            // out, as in `collatz - steps`
+            let text = span.source_text().unwrap_or_else(|| token.to_string());
+            source.push(&text);
+            source.spans(token);
+        }
+        source
+    }
+
+    fn spans(&mut self, token: TokenTree) {
+        let span = token.span();
+        self.tokens.push((span.line(), span.column(), span));
+        if let TokenTree::Group(group) = token {
+            for token in group.stream() {
+                self.spans(token);
+            }
+            let close = group.span_close();
+            self.tokens.push((close.line(), close.column(), close));
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.text.push_str(text);
+    }
+
+    // the last token to start at or before a position in `text`
+    fn token_at(&self, pos: spans::Pos) -> Span {
+        let pos = (pos.line + 1, pos.column + 1);
+        self.tokens.iter().filter(|(line, column, _)| (*line, *column) <= pos)
+            .max_by_key(|(line, column, _)| (*line, *column))
+            .map_or_else(Span::call_site, |(_, _, span)| *span)
+    }
+}
+
+/// Walks the tokens `pangolisp!` expands into alongside the text they
+/// were parsed from, to find where each is in it and so what pangolisp
+/// it came from.
+struct Respan {
+    code: Vec<char>,
+    at: usize,
+    // the line and column of `at`, from 0 as source maps count them
+    pos: (usize, usize),
+    map: SourceMap,
+    source: Source,
+}
+
+impl Respan {
+    // the tokens respanned, or as far as they could be if the text
+    // and the tokens part ways
+    fn stream(&mut self, stream: TokenStream) -> Result<TokenStream, TokenStream> {
+        let mut tokens = Vec::new();
+        let mut rest = stream.into_iter();
+        while let Some(token) = rest.next() {
+            match self.token(token) {
+                Ok(token) => tokens.push(token),
+                Err(token) => {
+                    tokens.push(token);
+                    return Err(tokens.into_iter().chain(rest).collect());
+                }
+            }
+        }
+        Ok(tokens.into_iter().collect())
+    }
+
+    fn token(&mut self, mut token: TokenTree) -> Result<TokenTree, TokenTree> {
+        self.whitespace();
+        let start = self.pos;
+        let parted = match &mut token {
+            TokenTree::Group(group) => {
+                let open = match group.delimiter() {
+                    Delimiter::Parenthesis => "(",
+                    Delimiter::Bracket => "[",
+                    Delimiter::Brace => "{",
+                    Delimiter::None => "",
+                };
+                let inner = self.text(open).then(|| self.stream(group.stream()));
+                self.whitespace();
+                let close = match group.delimiter() {
+                    Delimiter::Parenthesis => ")",
+                    Delimiter::Bracket => "]",
+                    Delimiter::Brace => "}",
+                    Delimiter::None => "",
+                };
+                let parted = !matches!(inner, Some(Ok(_))) || !self.text(close);
+                if let Some(Ok(stream) | Err(stream)) = inner {
+                    let span = group.span();
+                    *group = Group::new(group.delimiter(), stream);
+                    group.set_span(span);
+                }
+                parted
+            }
+            token => !self.text(&token.to_string()),
+        };
+        if parted {
+            return Err(token);
+        }
+        if let Some(mapping) = self.map.lookup(start, self.pos) {
+            token.set_span(Span::call_site().located_at(self.source.token_at(mapping.source.start)));
+        }
+        Ok(token)
+    }
+
+    fn whitespace(&mut self) {
+        while self.code.get(self.at).is_some_and(|c| c.is_whitespace()) {
+            self.advance(1);
+        }
+    }
+
+    // moves past `text`, if it is next
+    fn text(&mut self, text: &str) -> bool {
+        let len = text.chars().count();
+        let next = self.code.get(self.at..self.at + len);
+        let matches = next.is_some_and(|next| next.iter().copied().eq(text.chars()));
+        if matches {
+            self.advance(len);
+        }
+        matches
+    }
+
+    fn advance(&mut self, n: usize) {
+        for c in &self.code[self.at..self.at + n] {
+            self.pos = if *c == '\n' { (self.pos.0 + 1, 0) } else { (self.pos.0, self.pos.1 + 1) };
+        }
+        self.at += n;
+    }
+}
@@ -0,0 +1,56 @@
+#![allow(clippy::result_large_err)]
+
+//! Pangolisp inline and from a file, spliced in as rust items.
+
+use pangolisp_macros::{pangolisp, pangolisp_file};
+
+pangolisp! {
+    (defn collatz-steps [[n i64] [steps i64]]
+      (the i64
+        (if (= n 1)
+          steps
+          (collatz-steps (if (= (mod n 2) 0) (/ n 2) (+ (* 3 n) 1)) (inc steps)))))
+
+    (defn even? [[n i64]]
+      (the bool (not (= (mod n 2) 1))))
+
+    (defstruct Span [start i64] [end i64])
+
+    // built with the rs/ builtins, by a macro and by a call
+    (def getters (macro form
+      (quasiquote
+        (rs/impl (quote (unquote (first form)))
+          [(unquote-splicing
+             (map (lambda field
+                    (quasiquote (rs/fn (quote (unquote field)) [(rs/ref 'self)] 'i64
+                                       (rs/field 'self (quote (unquote field))) ::pub)))
+                  (rest form)))]))))
+
+    (getters Span start end)
+
+    (rs/fn 'len [['span 'Span]] 'i64 (rs/binary "-" (rs/method 'span 'end) (rs/method 'span 'start)) ::pub)
+}
+
+#[pangolisp_file("../tests/codegen/shapes.pl")]
+mod shapes {
+    pub const ORIGIN: Point = Point { x: 0, y: 0 };
+}
+
+#[test]
+fn inline() {
+    assert_eq!(collatz_steps(27, 0), 111);
+    assert!(even_p(10) && !even_p(7));
+    let span = Span { start: 3, end: 10 };
+    assert_eq!((span.start(), span.end()), (3, 10));
+    assert_eq!(len(span), 7);
+}
+
+#[test]
+fn from_a_file() {
+    use shapes::*;
+    let rect = Shape::Rect(ORIGIN, Point { x: 3, y: 4 });
+    assert_eq!(area(rect), 12);
+    assert_eq!(radius(Shape::Circle { radius: 2 }), 2);
+    let tree = insert(insert(insert(Tree::Leaf, 2), 1), 3);
+    assert_eq!((sum(tree.clone()), depth(tree)), (6, 2));
+}
@@ -0,0 +1,44 @@
+//! Rewrites rustc's JSON diagnostics to point at pangolisp source.
+//!
+//!     rustc --error-format=json shapes.rs 2>&1 | remap-diagnostics shapes.rs.map
+//!
+//! Reads the diagnostics from standard input and writes them to
+//! standard output, with spans in the generated rust each source map
+//! describes moved to the pangolisp it came from. With `--rendered`,
+//! writes just the rendered messages instead.
+
+use pangolisp::sourcemap::*;
+use std::io::{self, Read};
+use std::process;
+use std::{env, fs};
+
+fn main() {
+    let mut rendered = false;
+    let mut maps = Vec::new();
+    for arg in env::args().skip(1) {
+        if arg == "--rendered" {
+            rendered = true;
+            continue;
+        }
+        let map = fs::read_to_string(&arg).map_err(|e| e.to_string())
+            .and_then(|json| SourceMap::from_json(&json).map_err(|e| e.to_string()));
+        match map {
+            Ok(map) => maps.push(map),
+            Err(e) => {
+                eprintln!("{}: {}", arg, e);
+                process::exit(2);
+            }
+        }
+    }
+    let mut input = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut input) {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+    let output = remap_diagnostics(&maps, &input);
+    if rendered {
+        print!("{}", rendered_messages(&output));
+    } else {
+        print!("{}", output);
+    }
+}
@@ -0,0 +1,663 @@
+//! Translates a typed subset of pangolisp into rust source: `defn`
+//! with typed parameters and result, `defstruct` and `defenum`, and in
+//! the bodies of functions `let`, `if`, `do`, `match`, integer
+//! arithmetic and comparison, and the constructors, accessors and
+//! variant predicates of the declared types. A form whose value is an
+//! item built with the `rs/` builtins, or a list of them, translates
+//! into those items.
+//!
+//! Everything is passed by value. A variable is cloned where it is
+//! used, unless it is of a `Copy` primitive type, and a field that
+//! holds its own type is boxed. The rust is built as `rust` syntax and
+//! printed at the end.
+
+use crate::data::{DataType, Fields, Variant};
+use crate::eval::*;
+use crate::exprs::*;
+use crate::rust as rs;
+use crate::sourcemap::SourceMap;
+use crate::spans::Span;
+use crate::types::*;
+use im::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "// Generated by pangolisp.
+#![allow(dead_code, unreachable_patterns, unused_variables, clippy::all)]";
+
+#[derive(Clone, Debug)]
+pub enum CodegenError {
+    Read(String),
+    Eval(Box<EvalError>),
+    Type(TypeError),
+    /// A form or expression outside the subset that has a translation.
+    Unsupported(Expr),
+    UnsupportedType(Type),
+    /// A function with a parameter or result of no given type.
+    Untyped(String, Option<Span>),
+    Io(String),
+}
+
+impl From<EvalError> for CodegenError {
+    fn from(e: EvalError) -> CodegenError {
+        CodegenError::Eval(Box::new(e))
+    }
+}
+
+impl From<TypeError> for CodegenError {
+    fn from(e: TypeError) -> CodegenError {
+        CodegenError::Type(e)
+    }
+}
+
+impl CodegenError {
+    /// Where in the source the error is, where that is known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            CodegenError::Eval(e) => e.span(),
+            CodegenError::Type(e) => e.span(),
+            CodegenError::Unsupported(expr) => expr.meta().and_then(|m| m.span),
+            CodegenError::Untyped(_, span) => *span,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodegenError::Read(e) => write!(f, "{}", e),
+            CodegenError::Eval(e) => write!(f, "{}", e),
+            CodegenError::Type(e) => write!(f, "{}", e),
+            CodegenError::Unsupported(expr) => write!(f, "{} has no rust translation", expr),
+            CodegenError::UnsupportedType(typ) => write!(f, "{} has no rust translation", typ),
+            CodegenError::Untyped(name, Some(span)) =>
+                write!(f, "{} at {}:{} needs the type of each parameter and of its result",
+                       name, span.start.line + 1, span.start.column + 1),
+            CodegenError::Untyped(name, None) => write!(f, "{} needs the type of each parameter and of its result", name),
+            CodegenError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// a variable in scope, and whether it can be used without a clone
+#[derive(Clone)]
+struct Local {
+    ident: String,
+    copy: bool,
+}
+
+type Locals = HashMap<String, Local>;
+
+/// Translates modules, remembering what each has defined, so later
+/// modules and expressions may use it.
+#[derive(Default)]
+pub struct Codegen {
+    types: TypeEnv,
+    // the arity of each function translated so far
+    functions: HashMap<String, usize>,
+}
+
+impl Codegen {
+    pub fn new() -> Codegen {
+        Codegen::default()
+    }
+
+    /// Evaluates each form of `src` in `eval`, so that the macros and
+    /// types later forms use are defined, and translates it into a rust
+    /// item. Forms for the compile phase translate into nothing.
+    pub fn module(&mut self, eval: &mut Eval, src: &str) -> Result<String, CodegenError> {
+        let items = self.items(eval, src)?;
+        Ok(rs::mapped_file(&format!("{}\n\n", HEADER), &items).0)
+    }
+
+    /// As `module`, with a map from the rust to `src`, which diagnostics
+    /// will call `source`, as they will call the rust `generated`.
+    pub fn mapped_module(&mut self, eval: &mut Eval, src: &str, source: &str, generated: &str)
+                         -> Result<(String, SourceMap), CodegenError> {
+        let items = self.items(eval, src)?;
+        let (code, mappings) = rs::mapped_file(&format!("{}\n\n", HEADER), &items);
+        Ok((code, SourceMap::new(source, generated, mappings)))
+    }
+
+    /// The items `module` prints, without its header.
+    pub fn items(&mut self, eval: &mut Eval, src: &str) -> Result<Vec<rs::Item>, CodegenError> {
+        let mut items = Vec::new();
+        let mut forms = eval.forms(src);
+        while let Some(form) = forms.next() {
+            let form = form.map_err(|e| CodegenError::Read(format!("{:?}", e)))?;
+            let expr = eval.read(form).map_err(|e| CodegenError::Read(format!("{:?}", e)))?;
+            items.extend(self.top_level(eval, &expr)?);
+            forms.set_dispatch(eval.dispatch());
+        }
+        Ok(items)
+    }
+
+    /// Evaluates a form `eval` has read and translates it into the rust
+    /// items it stands for, as `items` does each form of a module.
+    pub fn top_level(&mut self, eval: &mut Eval, expr: &Expr) -> Result<Vec<rs::Item>, CodegenError> {
+        let val = eval.eval(expr.clone())?;
+        self.types = eval.types().clone();
+        self.item(eval, expr, &val)
+    }
+
+    /// Translates the module at `path` into a file of the same name in
+    /// `out_dir`, with its source map beside it in a `.rs.map`, and
+    /// gives back the rust file's path.
+    pub fn write_module(&mut self, eval: &mut Eval, path: &Path, out_dir: &Path) -> Result<PathBuf, CodegenError> {
+        let io = |e: std::io::Error, path: &Path| CodegenError::Io(format!("{}: {}", path.display(), e));
+        let src = fs::read_to_string(path).map_err(|e| io(e, path))?;
+        let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("module");
+        let file_name = format!("{}.rs", ident(name));
+        let (code, map) = self.mapped_module(eval, &src, &path.display().to_string(), &file_name)?;
+        let out = out_dir.join(&file_name);
+        fs::write(&out, code).map_err(|e| io(e, &out))?;
+        let map_path = out_dir.join(format!("{}.map", file_name));
+        fs::write(&map_path, map.to_json()).map_err(|e| io(e, &map_path))?;
+        Ok(out)
+    }
+
+    /// The rust for an expression, which may use whatever the modules
+    /// translated so far define.
+    pub fn expression(&mut self, eval: &mut Eval, expr: &Expr) -> Result<rs::Expr, CodegenError> {
+        let expr = eval.expand_all(expr.clone())?;
+        self.expr(&expr, &Locals::new())
+    }
+
+    // a form whose value is an item built with the `rs/` builtins, or a
+    // list of them, translates into those items
+    fn item(&mut self, eval: &mut Eval, expr: &Expr, val: &Expr) -> Result<Vec<rs::Item>, CodegenError> {
+        let unsupported = || CodegenError::Unsupported(expr.clone());
+        let list = match expr {
+            Expr::List(list) => list,
+            _ => return Err(unsupported()),
+        };
+        if let Some(built) = built_items(val) {
+            return built.into_iter().map(|val| {
+                let mut item = rs::item(val)?;
+                let header = match &mut item {
+                    rs::Item::Fn(function) => &mut function.header,
+                    rs::Item::Struct(header, ..) | rs::Item::Enum(header, ..)
+                    | rs::Item::Impl(header, ..) | rs::Item::Use(header, ..) => header,
+                };
+                header.origin = header.origin.take().or_else(|| Some(list.meta.clone()));
+                Ok(item)
+            }).collect();
+        }
+        match list.vals.front().and_then(special_name) {
+            Some("defstruct") | Some("defenum") => match val {
+                Expr::Symbol(name) => match self.types.datatype(&name.value) {
+                    Some(data) => Ok(vec![self.datatype(data, &list.meta)?]),
+                    None => Err(unsupported()),
+                },
+                _ => Err(unsupported()),
+            },
+            Some("defn") => Ok(vec![self.function(eval, list)?]),
+            Some("begin-for-syntax") | Some("eval-when") => Ok(vec![]),
+            Some("def") if matches!(list.vals.get(2), Some(Expr::List(val))
+                                    if val.vals.front().and_then(special_name) == Some("macro")) => Ok(vec![]),
+            _ => Err(unsupported()),
+        }
+    }
+
+    // items
+
+    fn datatype(&self, data: &DataType, meta: &Meta) -> Result<rs::Item, CodegenError> {
+        let mut derives: Vec<&str> = vec!["Clone", "Debug", "PartialEq"];
+        for derive in &data.derives {
+            if !derives.contains(&derive.as_str()) {
+                derives.push(derive);
+            }
+        }
+        let header = rs::Header {
+            attrs: vec![rs::Attribute::derive(&derives)],
+            public: true,
+            generics: generics(&data.params, &[]),
+            origin: Some(meta.clone()),
+        };
+        if !data.is_enum {
+            return Ok(rs::Item::Struct(header, data.name.clone(), self.fields(data, &data.variants[0].fields)?));
+        }
+        let variants = data.variants.iter()
+            .map(|variant| Ok((variant.name.clone(), self.fields(data, &variant.fields)?)))
+            .collect::<Result<_, CodegenError>>()?;
+        Ok(rs::Item::Enum(header, data.name.clone(), variants))
+    }
+
+    fn fields(&self, data: &DataType, fields: &Fields) -> Result<rs::Fields, CodegenError> {
+        let types = fields.types().into_iter().map(|typ| self.field_type(data, typ)).collect::<Result<Vec<_>, _>>()?;
+        Ok(match fields {
+            Fields::Unit => rs::Fields::Unit,
+            Fields::Positional(_) => rs::Fields::Tuple(types),
+            Fields::Named(named) => rs::Fields::Named(named.iter().map(|(name, _)| ident(name)).zip(types).collect()),
+        })
+    }
+
+    fn field_type(&self, data: &DataType, typ: &Type) -> Result<rs::Ty, CodegenError> {
+        let rust = self.rust_type(typ)?;
+        Ok(if recursive(typ, &data.name) { rs::Ty::generic("Box", vec![rust]) } else { rust })
+    }
+
+    fn function(&mut self, eval: &mut Eval, list: &List) -> Result<rs::Item, CodegenError> {
+        let defn = Defn::parse(list)?;
+        let untyped = || CodegenError::Untyped(defn.name.value.clone(), defn.name.meta.span);
+        let result = defn.body.last().and_then(|expr| ascription(expr)).ok_or_else(untyped)?.0;
+        let result = self.types.parse(result)?;
+        let mut vars = result.vars();
+        let mut locals = Locals::new();
+        let mut params = Vec::new();
+        for (name, typ) in &defn.params {
+            let typ = self.types.parse(typ.ok_or_else(untyped)?)?;
+            for var in typ.vars() {
+                if !vars.contains(&var) {
+                    vars.push(var);
+                }
+            }
+            let local = Local { ident: ident_of(name), copy: is_copy(&typ) };
+            params.push(rs::Param::Typed(rs::Pat::Ident(false, local.ident.clone()), self.rust_type(&typ)?));
+            locals.insert(name.key(), local);
+        }
+        // known before the body, which may call it
+        self.functions.insert(defn.name.value.clone(), defn.params.len());
+        let mut body = Vec::new();
+        for expr in &defn.body {
+            let expr = eval.expand_all((*expr).clone())?;
+            body.push(self.expr(&expr, &locals)?);
+        }
+        let value = body.pop();
+        Ok(rs::Item::Fn(rs::Function {
+            header: rs::Header {
+                attrs: vec![],
+                public: true,
+                generics: generics(&vars, &["Clone", "PartialEq", "std::fmt::Debug"]),
+                origin: Some(list.meta.clone()),
+            },
+            name: ident(&defn.name.value),
+            params,
+            ret: Some(self.rust_type(&result)?),
+            body: rs::Block::new(body.into_iter().map(rs::Stmt::Semi).collect(), value),
+        }))
+    }
+
+    fn rust_type(&self, typ: &Type) -> Result<rs::Ty, CodegenError> {
+        let unsupported = || CodegenError::UnsupportedType(typ.clone());
+        if let Type::Var(var) = typ {
+            return Ok(rs::Ty::path(&type_param(var)));
+        }
+        let (ctor, args) = typ.unapply().ok_or_else(unsupported)?;
+        let args = args.into_iter().map(|arg| self.rust_type(arg)).collect::<Result<Vec<_>, _>>()?;
+        let name = ctor.name.as_str();
+        if builtin::tuple_arity(name).is_some() || name == "()" {
+            return Ok(rs::Ty::Tuple(args));
+        }
+        match name {
+            "str" => Err(unsupported()),
+            _ if builtin::PRIMITIVES.contains(&name) => Ok(rs::Ty::path(name)),
+            "Vec" | "Option" | "Result" => Ok(rs::Ty::generic(name, args)),
+            _ if self.types.datatype(name).is_some() => Ok(rs::Ty::generic(name, args)),
+            _ => Err(unsupported()),
+        }
+    }
+
+    // expressions
+
+    // the rust, mapped to the expression's span if it has one
+    fn expr(&self, expr: &Expr, locals: &Locals) -> Result<rs::Expr, CodegenError> {
+        let rust = self.unmapped_expr(expr, locals)?;
+        Ok(match expr.meta() {
+            Some(meta) if meta.span.is_some() => rs::Expr::Origin(Box::new(rust), meta.clone()),
+            _ => rust,
+        })
+    }
+
+    fn unmapped_expr(&self, expr: &Expr, locals: &Locals) -> Result<rs::Expr, CodegenError> {
+        let unsupported = || CodegenError::Unsupported(expr.clone());
+        match expr {
+            Expr::Nil => Ok(rs::Expr::Tuple(vec![])),
+            Expr::Bool(b) => Ok(rs::Expr::Lit(rs::Lit::Bool(b.value))),
+            Expr::Int(i) => Ok(rs::Expr::int(i.value)),
+            Expr::String(s) => Ok(rs::Expr::call(rs::Expr::path("String::from"), vec![rs::Expr::Lit(rs::Lit::Str(s.value.clone()))])),
+            Expr::Symbol(sym) => match local(sym, locals) {
+                Some(local) if local.copy => Ok(rs::Expr::path(&local.ident)),
+                Some(local) => Ok(rs::Expr::method(rs::Expr::path(&local.ident), "clone", vec![])),
+                // a variant without fields is a value, not a constructor
+                None => match self.variant(&sym.value) {
+                    Some((data, variant)) if variant.fields.is_empty() => Ok(rs::Expr::Path(path(data, variant))),
+                    _ => Err(unsupported()),
+                },
+            },
+            Expr::List(list) if !list.vals.is_empty() => self.form(expr, list, locals),
+            _ => Err(unsupported()),
+        }
+    }
+
+    fn form(&self, expr: &Expr, list: &List, locals: &Locals) -> Result<rs::Expr, CodegenError> {
+        let unsupported = || CodegenError::Unsupported(expr.clone());
+        let head = &list.vals[0];
+        let operands: Vec<&Expr> = list.vals.iter().skip(1).collect();
+        let name = match head {
+            Expr::Symbol(sym) if local(sym, locals).is_some() => return Err(unsupported()),
+            Expr::Symbol(sym) => sym.value.as_str(),
+            Expr::Special(s) => s.name(),
+            _ => return Err(unsupported()),
+        };
+        match (name, operands.as_slice()) {
+            ("if", [cond, then]) => return Ok(rs::Expr::if_else(self.expr(cond, locals)?, self.expr(then, locals)?, None)),
+            ("if", [cond, then, otherwise]) => return Ok(rs::Expr::if_else(
+                self.expr(cond, locals)?, self.expr(then, locals)?, Some(self.expr(otherwise, locals)?))),
+            ("do", body) => return self.block(body, locals),
+            ("let", [Expr::List(bindings), body @ ..]) => return self.let_form(expr, bindings, body, locals),
+            ("match", [val, clauses @ ..]) => return self.match_form(expr, val, clauses, locals),
+            // a literal takes the type it is given
+            ("the", [Expr::Symbol(typ), Expr::Int(i)]) if builtin::INTEGERS.contains(&typ.value.as_str()) =>
+                return Ok(rs::Expr::Lit(rs::Lit::Int(i.value, Some(typ.value.clone())))),
+            // an int is not a bool or a string, and rust will not make it a float
+            ("the", [Expr::Symbol(typ), Expr::Int(_)]) if builtin::PRIMITIVES.contains(&typ.value.as_str()) =>
+                return Err(unsupported()),
+            ("the", [_, val]) => return self.expr(val, locals),
+            _ if Special::all().iter().any(|s| s.name() == name) => return Err(unsupported()),
+            _ => (),
+        }
+        let args = operands.iter().map(|arg| self.expr(arg, locals)).collect::<Result<Vec<_>, _>>()?;
+        match (name, args.as_slice()) {
+            ("+" | "-" | "*" | "/", [_, _, ..]) => {
+                let mut args = args.into_iter();
+                let first = args.next().expect("two operands");
+                Ok(args.fold(first, |acc, arg| rs::Expr::binary(name, acc, arg)))
+            }
+            ("=", [a, b]) => Ok(rs::Expr::binary("==", a.clone(), b.clone())),
+            ("<" | ">" | "<=" | ">=", [a, b]) => Ok(rs::Expr::binary(name, a.clone(), b.clone())),
+            ("mod", [_, _]) => Ok(rs::Expr::call(rs::Expr::path("i64::rem_euclid"), args)),
+            ("neg", [a]) => Ok(rs::Expr::unary("-", a.clone())),
+            ("inc", [a]) => Ok(rs::Expr::binary("+", a.clone(), rs::Expr::int(1))),
+            ("dec", [a]) => Ok(rs::Expr::binary("-", a.clone(), rs::Expr::int(1))),
+            ("not", [a]) => Ok(rs::Expr::unary("!", a.clone())),
+            _ => {
+                if let Some((data, variant)) = self.variant(name) {
+                    if variant.fields.len() == args.len() && !args.is_empty() {
+                        return Ok(self.construct(data, variant, args));
+                    }
+                }
+                if let (Some((data, variant, i)), [val]) = (self.accessor(name), args.as_slice()) {
+                    return Ok(self.access(data, variant, i, val.clone()));
+                }
+                if let (Some((data, variant)), [val]) = (name.strip_suffix('?').and_then(|v| self.variant(v)), args.as_slice()) {
+                    if data.is_enum {
+                        let pattern = rs::Expr::Struct(path(data, variant), vec![], true);
+                        return Ok(rs::Expr::macro_call("matches", vec![val.clone(), pattern]));
+                    }
+                }
+                match self.functions.get(name) {
+                    Some(arity) if *arity == args.len() => Ok(rs::Expr::call(rs::Expr::path(&ident(name)), args)),
+                    _ => Err(unsupported()),
+                }
+            }
+        }
+    }
+
+    fn block(&self, body: &[&Expr], locals: &Locals) -> Result<rs::Expr, CodegenError> {
+        let mut body = body.iter().map(|expr| self.expr(expr, locals)).collect::<Result<Vec<_>, _>>()?;
+        let value = body.pop();
+        Ok(rs::Expr::Block(rs::Block::new(body.into_iter().map(rs::Stmt::Semi).collect(), value)))
+    }
+
+    fn let_form(&self, expr: &Expr, bindings: &List, body: &[&Expr], locals: &Locals) -> Result<rs::Expr, CodegenError> {
+        let mut locals = locals.clone();
+        let mut stmts = Vec::new();
+        let pairs: Vec<&Expr> = bindings.vals.iter().collect();
+        for pair in pairs.chunks(2) {
+            match pair {
+                [Expr::Symbol(name), val] => {
+                    let val = self.expr(val, &locals)?;
+                    let local = Local { ident: ident_of(name), copy: false };
+                    stmts.push(rs::Stmt::Let(rs::Pat::Ident(false, local.ident.clone()), None, val));
+                    locals.insert(name.key(), local);
+                }
+                _ => return Err(CodegenError::Unsupported(expr.clone())),
+            }
+        }
+        let mut body = body.iter().map(|expr| self.expr(expr, &locals)).collect::<Result<Vec<_>, _>>()?;
+        let value = body.pop();
+        stmts.extend(body.into_iter().map(rs::Stmt::Semi));
+        Ok(rs::Expr::Block(rs::Block::new(stmts, value)))
+    }
+
+    fn match_form(&self, expr: &Expr, val: &Expr, clauses: &[&Expr], locals: &Locals) -> Result<rs::Expr, CodegenError> {
+        if !clauses.len().is_multiple_of(2) {
+            return Err(CodegenError::Unsupported(expr.clone()));
+        }
+        let mut arms = Vec::new();
+        for clause in clauses.chunks(2) {
+            let mut bound = locals.clone();
+            let mut unbox = Vec::new();
+            let pat = self.pattern(clause[0], &mut bound, &mut unbox)?;
+            let body = self.expr(clause[1], &bound)?;
+            let body = match unbox.is_empty() {
+                true => body,
+                false => rs::Expr::Block(rs::Block::new(unbox, Some(body))),
+            };
+            arms.push(rs::Arm { pat, guard: None, body });
+        }
+        // as the interpreter does when nothing matches
+        let panic = rs::Expr::macro_call("panic", vec![rs::Expr::Lit(rs::Lit::Str("no pattern matches".to_string()))]);
+        arms.push(rs::Arm { pat: rs::Pat::Wild, guard: None, body: panic });
+        Ok(rs::Expr::Match(Box::new(self.expr(val, locals)?), arms))
+    }
+
+    // the rust pattern, binding into `locals`. A boxed field can only
+    // be bound to a name, which `unbox` then rebinds to its contents.
+    fn pattern(&self, pattern: &Expr, locals: &mut Locals, unbox: &mut Vec<rs::Stmt>) -> Result<rs::Pat, CodegenError> {
+        let unsupported = || CodegenError::Unsupported(pattern.clone());
+        match pattern {
+            Expr::Symbol(sym) => match sym.value.as_str() {
+                "_" => Ok(rs::Pat::Wild),
+                "true" | "false" => Ok(rs::Pat::Lit(rs::Lit::Bool(sym.value == "true"))),
+                "nil" | "&" => Err(unsupported()),
+                _ => {
+                    let local = Local { ident: ident_of(sym), copy: false };
+                    locals.insert(sym.key(), local.clone());
+                    Ok(rs::Pat::Ident(false, local.ident))
+                }
+            },
+            Expr::Int(i) => Ok(rs::Pat::Lit(rs::Lit::Int(i.value, None))),
+            Expr::List(list) => {
+                let (data, variant) = match list.vals.front() {
+                    Some(Expr::Symbol(head)) => self.variant(&head.value).ok_or_else(unsupported)?,
+                    _ => return Err(unsupported()),
+                };
+                let fields: Vec<&Expr> = list.vals.iter().skip(1).collect();
+                if fields.len() != variant.fields.len() {
+                    return Err(unsupported());
+                }
+                let mut subpatterns = Vec::new();
+                for (field, typ) in fields.into_iter().zip(variant.fields.types()) {
+                    let sub = self.pattern(field, locals, unbox)?;
+                    if recursive(typ, &data.name) {
+                        match &sub {
+                            rs::Pat::Wild => (),
+                            rs::Pat::Ident(_, name) => unbox.push(rs::Stmt::Let(
+                                sub.clone(), None, rs::Expr::unary("*", rs::Expr::path(name)))),
+                            _ => return Err(unsupported()),
+                        }
+                    }
+                    subpatterns.push(sub);
+                }
+                Ok(match &variant.fields {
+                    Fields::Unit => rs::Pat::Path(path(data, variant)),
+                    Fields::Positional(_) => rs::Pat::TupleStruct(path(data, variant), subpatterns),
+                    Fields::Named(named) => rs::Pat::Struct(path(data, variant),
+                        named.iter().map(|(name, _)| ident(name)).zip(subpatterns).collect(), false),
+                })
+            }
+            _ => Err(unsupported()),
+        }
+    }
+
+    // declared types
+
+    fn variant(&self, name: &str) -> Option<(&DataType, &Variant)> {
+        self.types.datatypes().find_map(|data| data.variant(name).map(|variant| (data, variant)))
+    }
+
+    // the variant and field `Variant-field` gets
+    fn accessor(&self, name: &str) -> Option<(&DataType, &Variant, usize)> {
+        self.types.datatypes().find_map(|data| data.variants.iter().find_map(|variant| {
+            let field = name.strip_prefix(variant.name.as_str())?.strip_prefix('-')?;
+            let i = variant.fields.names().iter().position(|f| f == field)?;
+            Some((data, variant, i))
+        }))
+    }
+
+    fn construct(&self, data: &DataType, variant: &Variant, args: Vec<rs::Expr>) -> rs::Expr {
+        let args: Vec<rs::Expr> = args.into_iter().zip(variant.fields.types())
+            .map(|(arg, typ)| match recursive(typ, &data.name) {
+                true => rs::Expr::call(rs::Expr::path("Box::new"), vec![arg]),
+                false => arg,
+            })
+            .collect();
+        match &variant.fields {
+            Fields::Named(named) =>
+                rs::Expr::Struct(path(data, variant), named.iter().map(|(name, _)| ident(name)).zip(args).collect(), false),
+            _ => rs::Expr::call(rs::Expr::Path(path(data, variant)), args),
+        }
+    }
+
+    fn access(&self, data: &DataType, variant: &Variant, i: usize, val: rs::Expr) -> rs::Expr {
+        let deref = |e: rs::Expr| match recursive(variant.fields.types()[i], &data.name) {
+            true => rs::Expr::unary("*", e),
+            false => e,
+        };
+        let field = match &variant.fields {
+            Fields::Named(named) => ident(&named[i].0),
+            _ => i.to_string(),
+        };
+        if !data.is_enum {
+            return deref(rs::Expr::field(val, &field));
+        }
+        let value = rs::Pat::Ident(false, "value".to_string());
+        let pat = match &variant.fields {
+            Fields::Named(_) => rs::Pat::Struct(path(data, variant), vec![(field, value)], true),
+            _ => rs::Pat::TupleStruct(path(data, variant), (0..variant.fields.len())
+                .map(|j| if j == i { value.clone() } else { rs::Pat::Wild }).collect()),
+        };
+        let panic = rs::Expr::macro_call("panic", vec![rs::Expr::Lit(rs::Lit::Str(format!("expected {}", variant.name)))]);
+        rs::Expr::Match(Box::new(val), vec![
+            rs::Arm { pat, guard: None, body: deref(rs::Expr::path("value")) },
+            rs::Arm { pat: rs::Pat::Wild, guard: None, body: panic },
+        ])
+    }
+}
+
+fn special_name(head: &Expr) -> Option<&str> {
+    match head {
+        Expr::Special(s) => Some(s.name()),
+        Expr::Symbol(s) => Some(&s.value),
+        _ => None,
+    }
+}
+
+// the type and value of `(the type value)`
+fn ascription(expr: &Expr) -> Option<(&Expr, &Expr)> {
+    match expr {
+        Expr::List(list) => match list.vals.iter().collect::<Vec<_>>().as_slice() {
+            [head, typ, val] if special_name(head) == Some("the") => Some((typ, val)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// the syntax in a value built with the `rs/` builtins, or in a list of them
+fn built_items(val: &Expr) -> Option<Vec<&Expr>> {
+    let built = |val: &Expr| matches!(val, Expr::Data(data) if data.typ == "rs");
+    match val {
+        val if built(val) => Some(vec![val]),
+        Expr::List(list) if !list.vals.is_empty() && list.vals.iter().all(built) => Some(list.vals.iter().collect()),
+        _ => None,
+    }
+}
+
+fn local<'l>(sym: &Symbol, locals: &'l Locals) -> Option<&'l Local> {
+    locals.get(&sym.key()).or_else(|| locals.get(&sym.value))
+}
+
+fn path(data: &DataType, variant: &Variant) -> rs::Path {
+    match data.is_enum {
+        true => rs::Path::new(vec![data.name.clone(), variant.name.clone()], vec![]),
+        false => rs::Path::from(data.name.as_str()),
+    }
+}
+
+// whether a field of this type, in the type `name`, holds a `name`
+// directly, and so must be boxed. A `Vec` is already indirect.
+fn recursive(typ: &Type, name: &str) -> bool {
+    match typ.unapply() {
+        Some((ctor, _)) if ctor.name == "Vec" => false,
+        Some((ctor, args)) => ctor.name == name || args.into_iter().any(|arg| recursive(arg, name)),
+        None => false,
+    }
+}
+
+fn is_copy(typ: &Type) -> bool {
+    matches!(typ, Type::Constructor(ctor)
+             if builtin::PRIMITIVES.contains(&ctor.name.as_str()) && ctor.name != "String" && ctor.name != "str")
+}
+
+// `'a` as `A`
+fn type_param(var: &TypeVar) -> String {
+    let mut chars = var.name.chars();
+    chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+fn generics(vars: &[TypeVar], bounds: &[&str]) -> Vec<rs::Generic> {
+    vars.iter()
+        .map(|var| rs::Generic { name: type_param(var), bounds: bounds.iter().map(|b| rs::Path::from(*b)).collect() })
+        .collect()
+}
+
+// a symbol a macro introduced keeps its scopes, so it cannot capture
+// a name the user wrote
+fn ident_of(sym: &Symbol) -> String {
+    let scopes: Vec<String> = sym.meta.scopes.iter().map(|s| s.to_string()).collect();
+    match scopes.is_empty() {
+        true => ident(&sym.value),
+        false => format!("{}_{}", ident(&sym.value), scopes.join("_")),
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "try", "type", "unsafe", "use", "where", "while",
+    // reserved
+    "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "typeof",
+    "unsized", "virtual", "yield",
+];
+
+// keywords that cannot be raw identifiers either
+const PATH_KEYWORDS: &[&str] = &["crate", "self", "Self", "super"];
+
+/// A rust identifier for a pangolisp name: `-` becomes `_`, a trailing
+/// `?` becomes `_p`, and other punctuation its code point. A keyword is
+/// a raw identifier, or where it cannot be, gets a trailing `_`.
+pub fn ident(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        match c {
+            c if c.is_ascii_alphanumeric() || c == '_' => out.push(c),
+            '-' => out.push('_'),
+            '?' => out.push_str("_p"),
+            c => out.push_str(&format!("_{:x}", c as u32)),
+        }
+    }
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    if PATH_KEYWORDS.contains(&out.as_str()) {
+        out.push('_');
+    } else if KEYWORDS.contains(&out.as_str()) {
+        out.insert_str(0, "r#");
+    }
+    out
+}
@@ -0,0 +1,239 @@
+use crate::eval::*;
+use crate::exprs::*;
+use crate::types::*;
+use im::Vector;
+
+/// The fields of a struct or of an enum variant, as rust has them.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Fields {
+    Unit,
+    /// Written `[name type]`, in the order declared.
+    Named(Vec<(String, Type)>),
+    /// Written as bare types, as in a tuple struct.
+    Positional(Vec<Type>),
+}
+
+impl Fields {
+    pub fn len(&self) -> usize {
+        self.types().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn types(&self) -> Vec<&Type> {
+        match self {
+            Fields::Unit => Vec::new(),
+            Fields::Named(fields) => fields.iter().map(|(_, typ)| typ).collect(),
+            Fields::Positional(types) => types.iter().collect(),
+        }
+    }
+
+    /// What each field's accessor is named for: the field's own name,
+    /// or its position, as rust does.
+    pub fn names(&self) -> Vec<String> {
+        match self {
+            Fields::Named(fields) => fields.iter().map(|(name, _)| name.clone()).collect(),
+            _ => (0..self.len()).map(|i| i.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Fields,
+}
+
+/// A type declared with `defstruct` or `defenum`: enough to build
+/// its values and take them apart, to type them, and to write it out
+/// as a rust item. A struct has one variant, named like the type.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DataType {
+    pub name: String,
+    pub params: Vec<TypeVar>,
+    pub variants: Vec<Variant>,
+    /// The traits listed in its `(derive ..)` clause.
+    pub derives: Vec<String>,
+    pub is_enum: bool,
+}
+
+impl DataType {
+    /// Reads `(defstruct name clause..)` or `(defenum name clause..)`.
+    /// The name may take parameters, as in `(Pair 'a 'b)`, and any
+    /// clause may be `(derive Trait..)`. The other clauses of a struct
+    /// are its fields, all `[name type]` or all bare types; those of
+    /// an enum are its variants, each a name, or a list of a name and
+    /// the variant's fields. The type may refer to itself.
+    pub fn parse(form: &List, env: &TypeEnv) -> Result<DataType, TypeError> {
+        let bad = || TypeError::BadType(Expr::List(form.clone()));
+        let is_enum = match form.vals.front() {
+            Some(Expr::Special(Special::DefEnum(_))) => true,
+            Some(Expr::Special(Special::DefStruct(_))) => false,
+            Some(Expr::Symbol(s)) if s.value == "defenum" || s.value == "defstruct" => s.value == "defenum",
+            _ => return Err(bad()),
+        };
+        let (name, params) = match form.vals.get(1) {
+            Some(Expr::Symbol(name)) => (name.value.clone(), Vec::new()),
+            Some(Expr::List(head)) => match head.vals.iter().collect::<Vec<_>>().as_slice() {
+                [Expr::Symbol(name), params @ ..] => {
+                    let params = params.iter().map(|param| match param {
+                        Expr::List(quoted) => match quoted.vals.iter().collect::<Vec<_>>().as_slice() {
+                            [Expr::Special(Special::Quote(_)), Expr::Symbol(var)] =>
+                                Ok(TypeVar::new(var.value.clone(), Kind::Star)),
+                            _ => Err(TypeError::BadType((*param).clone())),
+                        },
+                        other => Err(TypeError::BadType((*other).clone())),
+                    }).collect::<Result<Vec<_>, _>>()?;
+                    (name.value.clone(), params)
+                }
+                _ => return Err(bad()),
+            },
+            _ => return Err(bad()),
+        };
+        let mut scope = env.clone();
+        scope.define(TypeConstructor::new(name.clone(), Kind::of_arity(params.len())));
+        let mut derives = Vec::new();
+        let mut clauses = Vec::new();
+        for clause in form.vals.iter().skip(2) {
+            match derive_clause(clause) {
+                Some(traits) => derives.extend(traits?),
+                None => clauses.push(clause),
+            }
+        }
+        let variants = if is_enum {
+            clauses.into_iter().map(|clause| match clause {
+                Expr::Symbol(name) => Ok(Variant { name: name.value.clone(), fields: Fields::Unit }),
+                Expr::List(variant) => match variant.vals.iter().collect::<Vec<_>>().as_slice() {
+                    [Expr::Symbol(name), fields @ ..] =>
+                        Ok(Variant { name: name.value.clone(), fields: parse_fields(fields, &scope, &params)? }),
+                    _ => Err(TypeError::BadType(clause.clone())),
+                },
+                other => Err(TypeError::BadType(other.clone())),
+            }).collect::<Result<Vec<_>, _>>()?
+        } else {
+            vec![Variant { name: name.clone(), fields: parse_fields(&clauses, &scope, &params)? }]
+        };
+        Ok(DataType { name, params, variants, derives, is_enum })
+    }
+
+    /// The type of its values: its constructor applied to its parameters.
+    pub fn typ(&self) -> Type {
+        let ctor = TypeConstructor::new(self.name.clone(), Kind::of_arity(self.params.len()));
+        Type::from(ctor).apply_all(self.params.iter().cloned().map(Type::from))
+            .expect("parameters are of kind *")
+    }
+
+    pub fn variant(&self, name: &str) -> Option<&Variant> {
+        self.variants.iter().find(|v| v.name == name)
+    }
+
+    /// What `define` binds, with their types: a constructor for each
+    /// variant, `Variant-field` to get each field, and `Name?` and
+    /// `Variant?` to tell its values apart.
+    pub fn bindings(&self) -> Vec<(String, Scheme)> {
+        let typ = self.typ();
+        let anything = TypeVar::new("t", Kind::Star).into();
+        let predicate = Scheme::generalize(Type::function(anything, builtin::primitive("bool").into()));
+        let mut bindings = vec![(format!("{}?", self.name), predicate.clone())];
+        for variant in &self.variants {
+            let constructor = variant.fields.types().into_iter().rev()
+                .fold(typ.clone(), |to, from| Type::function(from.clone(), to));
+            bindings.push((variant.name.clone(), Scheme::generalize(constructor)));
+            if variant.name != self.name {
+                bindings.push((format!("{}?", variant.name), predicate.clone()));
+            }
+            for (field, field_type) in variant.fields.names().into_iter().zip(variant.fields.types()) {
+                let accessor = Type::function(typ.clone(), field_type.clone());
+                bindings.push((format!("{}-{}", variant.name, field), Scheme::generalize(accessor)));
+            }
+        }
+        bindings
+    }
+}
+
+// the traits of a `(derive Trait..)` clause, if it is one
+fn derive_clause(clause: &Expr) -> Option<Result<Vec<String>, TypeError>> {
+    let list = match clause {
+        Expr::List(list) => list,
+        _ => return None,
+    };
+    match list.vals.front() {
+        Some(Expr::Symbol(head)) if head.value == "derive" => Some(list.vals.iter().skip(1).map(|val| match val {
+            Expr::Symbol(name) => Ok(name.value.clone()),
+            other => Err(TypeError::BadType(other.clone())),
+        }).collect()),
+        _ => None,
+    }
+}
+
+// `[name x]`, which reads as `(list name x)`
+pub(crate) fn bracketed(clause: &Expr) -> Option<(&Symbol, &Expr)> {
+    match clause {
+        Expr::List(list) => match list.vals.iter().collect::<Vec<_>>().as_slice() {
+            [Expr::Symbol(head), Expr::Symbol(name), val] if head.value == "list" => Some((name, val)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn parse_fields(clauses: &[&Expr], env: &TypeEnv, params: &[TypeVar]) -> Result<Fields, TypeError> {
+    let field_type = |expr: &Expr| {
+        let typ = env.parse(expr)?;
+        match typ.vars().into_iter().find(|var| !params.contains(var)) {
+            Some(var) => Err(TypeError::UnknownType(format!("'{}", var.name), expr.meta().and_then(|m| m.span))),
+            None => Ok(typ),
+        }
+    };
+    if clauses.is_empty() {
+        return Ok(Fields::Unit);
+    }
+    if bracketed(clauses[0]).is_some() {
+        clauses.iter().map(|clause| match bracketed(clause) {
+            Some((name, typ)) => Ok((name.value.clone(), field_type(typ)?)),
+            None => Err(TypeError::BadType((*clause).clone())),
+        }).collect::<Result<_, _>>().map(Fields::Named)
+    } else {
+        clauses.iter().map(|clause| match bracketed(clause) {
+            Some(_) => Err(TypeError::BadType((*clause).clone())),
+            None => field_type(clause),
+        }).collect::<Result<_, _>>().map(Fields::Positional)
+    }
+}
+
+/// Binds what `data.bindings()` describes in `eval`. A variant without
+/// fields is bound to its one value rather than to a constructor.
+pub fn define(eval: &mut Eval, data: &DataType) {
+    let typ = data.name.clone();
+    eval.register_fn(&format!("{}?", data.name), move |val: Expr| {
+        matches!(&val, Expr::Data(d) if d.typ == typ)
+    });
+    for variant in &data.variants {
+        let (typ, name) = (data.name.clone(), variant.name.clone());
+        if variant.fields.is_empty() {
+            eval.define(name.clone(), Expr::Data(Data::new(typ, name, Vector::new(), Meta::default())));
+        } else {
+            eval.register_native(&name.clone(), Arity::Exactly(variant.fields.len()), move |_, args| {
+                let fields = args.iter().cloned().collect();
+                Ok(Expr::Data(Data::new(typ.clone(), name.clone(), fields, Meta::default())))
+            });
+        }
+        if variant.name != data.name {
+            let (typ, name) = (data.name.clone(), variant.name.clone());
+            eval.register_fn(&format!("{}?", variant.name), move |val: Expr| {
+                matches!(&val, Expr::Data(d) if d.typ == typ && d.variant == name)
+            });
+        }
+        for (i, field) in variant.fields.names().into_iter().enumerate() {
+            let (typ, name) = (data.name.clone(), variant.name.clone());
+            eval.register_native(&format!("{}-{}", variant.name, field), Arity::Exactly(1), move |_, args| {
+                match &args[0] {
+                    Expr::Data(d) if d.typ == typ && d.variant == name => Ok(d.fields[i].clone()),
+                    other => Err(EvalError::WrongVariant(name.clone(), other.clone())),
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,880 @@
+//! An experimental dependently typed core calculus: dependent
+//! functions and pairs, a hierarchy of universes, and natural numbers,
+//! length-indexed vectors and `Fin` as built in families.
+//!
+//! Types are checked bidirectionally and compared up to definitional
+//! equality by normalisation by evaluation. Terms are compiled into
+//! lisp and run by an `Eval`, so type-level computation happens in the
+//! evaluator. A lambda becomes a lisp closure; everything else is a
+//! `Data` value, among them the neutral terms that evaluation gets
+//! stuck on for want of a variable's value. Reading a value back
+//! applies closures to fresh variables.
+
+use crate::data::bracketed;
+use crate::eval::*;
+use crate::exprs::*;
+use crate::forms::Forms;
+use crate::reader::read;
+use crate::spans::Span;
+use im::{HashMap, Vector};
+use std::fmt;
+use std::rc::Rc;
+
+/// A term, with variables numbered by how many binders out their own
+/// is. Each also keeps the name it was written with.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Term {
+    Var(usize, String),
+    /// A built in or defined name.
+    Const(String),
+    /// `Type` is `(Type 0)`, which is of type `(Type 1)`, and so on.
+    Universe(usize),
+    Pi(String, Box<Term>, Box<Term>),
+    Lambda(String, Box<Term>),
+    App(Box<Term>, Box<Term>),
+    Sigma(String, Box<Term>, Box<Term>),
+    Pair(Box<Term>, Box<Term>),
+    Fst(Box<Term>),
+    Snd(Box<Term>),
+    /// `(the type term)`
+    The(Box<Term>, Box<Term>),
+}
+
+impl Term {
+    fn app(f: Term, arg: Term) -> Term {
+        Term::App(Box::new(f), Box::new(arg))
+    }
+
+    /// Whether the variable `index` binders out occurs in it.
+    pub fn mentions(&self, index: usize) -> bool {
+        match self {
+            Term::Var(i, _) => *i == index,
+            Term::Const(_) | Term::Universe(_) => false,
+            Term::Pi(_, a, b) | Term::Sigma(_, a, b) => a.mentions(index) || b.mentions(index + 1),
+            Term::Lambda(_, body) => body.mentions(index + 1),
+            Term::App(a, b) | Term::Pair(a, b) | Term::The(a, b) => a.mentions(index) || b.mentions(index),
+            Term::Fst(p) | Term::Snd(p) => p.mentions(index),
+        }
+    }
+
+    // `(succ (succ zero))` as 2
+    fn numeral(&self) -> Option<usize> {
+        match self {
+            Term::Const(c) if c == "zero" => Some(0),
+            Term::App(f, n) if matches!(&**f, Term::Const(c) if c == "succ") => n.numeral().map(|n| n + 1),
+            _ => None,
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, scope: &mut Vec<String>) -> fmt::Result {
+        if let Some(n) = self.numeral() {
+            return write!(f, "{}", n);
+        }
+        match self {
+            Term::Var(i, name) => match scope.len().checked_sub(i + 1) {
+                Some(level) => write!(f, "{}", scope[level]),
+                None => write!(f, "{}", name),
+            },
+            Term::Const(c) => write!(f, "{}", c),
+            Term::Universe(0) => write!(f, "Type"),
+            Term::Universe(n) => write!(f, "(Type {})", n),
+            Term::Pi(_, a, b) if !b.mentions(0) => {
+                // `(-> A B C)` rather than `(-> A (-> B C))`
+                write!(f, "(-> ")?;
+                a.write(f, scope)?;
+                let depth = scope.len();
+                scope.push("_".into());
+                let mut rest = &**b;
+                while let Term::Pi(_, a, b) = rest {
+                    if b.mentions(0) {
+                        break;
+                    }
+                    write!(f, " ")?;
+                    a.write(f, scope)?;
+                    scope.push("_".into());
+                    rest = b;
+                }
+                write!(f, " ")?;
+                rest.write(f, scope)?;
+                scope.truncate(depth);
+                write!(f, ")")
+            }
+            Term::Pi(x, a, b) | Term::Sigma(x, a, b) => {
+                let head = if matches!(self, Term::Pi(..)) { "Pi" } else { "Sigma" };
+                let x = fresh(x, scope);
+                write!(f, "({} [{} ", head, x)?;
+                a.write(f, scope)?;
+                write!(f, "] ")?;
+                scope.push(x);
+                b.write(f, scope)?;
+                scope.pop();
+                write!(f, ")")
+            }
+            Term::Lambda(x, body) => {
+                let x = fresh(x, scope);
+                write!(f, "(lambda {} ", x)?;
+                scope.push(x);
+                body.write(f, scope)?;
+                scope.pop();
+                write!(f, ")")
+            }
+            Term::App(..) => {
+                let mut args = Vec::new();
+                let mut head = self;
+                while let Term::App(g, arg) = head {
+                    args.push(arg);
+                    head = g;
+                }
+                write!(f, "(")?;
+                head.write(f, scope)?;
+                for arg in args.into_iter().rev() {
+                    write!(f, " ")?;
+                    arg.write(f, scope)?;
+                }
+                write!(f, ")")
+            }
+            Term::Pair(a, b) | Term::The(a, b) => {
+                write!(f, "({} ", if matches!(self, Term::Pair(..)) { "pair" } else { "the" })?;
+                a.write(f, scope)?;
+                write!(f, " ")?;
+                b.write(f, scope)?;
+                write!(f, ")")
+            }
+            Term::Fst(p) | Term::Snd(p) => {
+                write!(f, "({} ", if matches!(self, Term::Fst(..)) { "fst" } else { "snd" })?;
+                p.write(f, scope)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+// a name not already in scope, so the term reads back unambiguously
+fn fresh(name: &str, scope: &[String]) -> String {
+    let mut name = name.to_string();
+    while name != "_" && scope.contains(&name) {
+        name.push('\'');
+    }
+    name
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, &mut Vec::new())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum DependentError {
+    Unbound(String, Option<Span>),
+    /// Not something the syntax of terms allows.
+    BadTerm(Expr),
+    /// A lambda or pair whose type is not known from where it is.
+    CannotInfer(Term),
+    /// What should have been a type is of the given type instead.
+    NotAType(Term, Term),
+    /// Something applied is of the given type, not a function type.
+    NotAFunction(Term, Term),
+    NotAPair(Term, Term),
+    /// A lambda or pair was checked against a type it cannot have.
+    NotOfType(Term, Term),
+    Mismatch { term: Term, expected: Term, found: Term },
+    /// Evaluating a term failed, as only an ill typed one should.
+    Eval(Box<EvalError>),
+}
+
+impl From<EvalError> for DependentError {
+    fn from(e: EvalError) -> DependentError {
+        DependentError::Eval(Box::new(e))
+    }
+}
+
+impl fmt::Display for DependentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DependentError::Unbound(name, Some(span)) =>
+                write!(f, "unbound {} at {}:{}", name, span.start.line + 1, span.start.column + 1),
+            DependentError::Unbound(name, None) => write!(f, "unbound {}", name),
+            DependentError::BadTerm(expr) => write!(f, "{} is not a term", expr),
+            DependentError::CannotInfer(term) => write!(f, "cannot infer a type for {}", term),
+            DependentError::NotAType(term, typ) => write!(f, "{} is of type {}, not a universe", term, typ),
+            DependentError::NotAFunction(term, typ) => write!(f, "{} is of type {}, not a function", term, typ),
+            DependentError::NotAPair(term, typ) => write!(f, "{} is of type {}, not a pair", term, typ),
+            DependentError::NotOfType(term, typ) => write!(f, "{} cannot be of type {}", term, typ),
+            DependentError::Mismatch { term, expected, found } =>
+                write!(f, "expected {} to be of type {}, found {}", term, expected, found),
+            DependentError::Eval(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// values
+
+const VALUE: &str = "Value";
+
+fn value(variant: &str, fields: Vec<Expr>) -> Expr {
+    Expr::Data(Data::new(VALUE.into(), variant.into(), fields.into_iter().collect(), Meta::default()))
+}
+
+// the variant and fields of a value that is not a function
+fn view(val: &Expr) -> Option<(&str, &Vector<Expr>)> {
+    match val {
+        Expr::Data(d) if d.typ == VALUE => Some((d.variant.as_str(), &d.fields)),
+        _ => None,
+    }
+}
+
+fn string(val: &Expr) -> &str {
+    match val {
+        Expr::String(s) => &s.value,
+        _ => "_",
+    }
+}
+
+fn level(val: &Expr) -> usize {
+    match val {
+        Expr::Int(i) => i.value as usize,
+        _ => 0,
+    }
+}
+
+fn universe(level: usize) -> Expr {
+    value("Type", vec![Expr::from(level as i64)])
+}
+
+/// The variable bound `level` binders in, which evaluation gets stuck on.
+fn var(level: usize, name: &str) -> Expr {
+    value("Var", vec![Expr::from(level as i64), Expr::from(name)])
+}
+
+/// A built in constructor applied to all of its arguments.
+fn con(name: &str, args: impl IntoIterator<Item = Expr>) -> Expr {
+    value("Con", std::iter::once(Expr::from(name)).chain(args).collect())
+}
+
+fn as_con(val: &Expr) -> Option<(&str, Vec<&Expr>)> {
+    match view(val) {
+        Some(("Con", fields)) => Some((string(&fields[0]), fields.iter().skip(1).collect())),
+        _ => None,
+    }
+}
+
+fn is_neutral(val: &Expr) -> bool {
+    matches!(view(val), Some(("Var" | "App" | "Fst" | "Snd" | "Elim", _)))
+}
+
+fn is_function(val: &Expr) -> bool {
+    matches!(val, Expr::Fun(_) | Expr::Native(_))
+}
+
+fn apply(eval: &mut Eval, f: &Expr, arg: Expr) -> Result<Expr, EvalError> {
+    if is_function(f) {
+        eval.apply(f.clone(), vec![arg])
+    } else if is_neutral(f) {
+        Ok(value("App", vec![f.clone(), arg]))
+    } else {
+        Err(EvalError::NotCallable(f.clone(), List::from(Vector::unit(arg))))
+    }
+}
+
+fn apply_all(eval: &mut Eval, f: &Expr, args: &[&Expr]) -> Result<Expr, EvalError> {
+    args.iter().try_fold(f.clone(), |f, arg| apply(eval, &f, (*arg).clone()))
+}
+
+fn project(val: &Expr, first: bool) -> Result<Expr, EvalError> {
+    match view(val) {
+        Some(("Pair", fields)) => Ok(fields[if first { 0 } else { 1 }].clone()),
+        _ if is_neutral(val) => Ok(value(if first { "Fst" } else { "Snd" }, vec![val.clone()])),
+        _ => Err(EvalError::BadParameter("a pair", val.clone())),
+    }
+}
+
+fn native(name: &str, arity: usize, fun: impl Fn(&mut Eval, &[Expr]) -> Result<Expr, EvalError> + 'static) -> Expr {
+    Expr::Native(Native::new(name, Arity::Exactly(arity), Rc::new(fun), Meta::default()))
+}
+
+fn constructor(name: &'static str, arity: usize) -> Expr {
+    if arity == 0 {
+        con(name, None)
+    } else {
+        native(name, arity, move |_, args| Ok(con(name, args.iter().cloned())))
+    }
+}
+
+// An eliminator takes a motive, a method for each constructor, and
+// the indices and value to eliminate. It follows the value down to
+// its innermost constructor, or to a neutral it is stuck on, then
+// applies the methods on the way back out, so the depth of the value
+// does not nest evaluation.
+
+fn nat_elim(eval: &mut Eval, args: &[Expr]) -> Result<Expr, EvalError> {
+    let (zero, succ) = (&args[1], &args[2]);
+    let mut n = &args[3];
+    let mut preds = Vec::new();
+    let mut result = loop {
+        match as_con(n) {
+            Some(("zero", _)) => break zero.clone(),
+            Some(("succ", k)) => {
+                preds.push(k[0]);
+                n = k[0];
+            }
+            _ => break stuck("nat-elim", &args[..3], &[n]),
+        }
+    };
+    for k in preds.into_iter().rev() {
+        result = apply_all(eval, succ, &[k, &result])?;
+    }
+    Ok(result)
+}
+
+fn vec_elim(eval: &mut Eval, args: &[Expr]) -> Result<Expr, EvalError> {
+    let (nil, cons) = (&args[2], &args[3]);
+    let (mut n, mut xs) = (&args[4], &args[5]);
+    let mut cells = Vec::new();
+    let mut result = loop {
+        match as_con(xs) {
+            Some(("nil", _)) => break nil.clone(),
+            Some(("cons", fields)) => {
+                cells.push(fields.clone());
+                n = fields[1];
+                xs = fields[3];
+            }
+            _ => break stuck("vec-elim", &args[..4], &[n, xs]),
+        }
+    };
+    for fields in cells.into_iter().rev() {
+        result = apply_all(eval, cons, &[fields[1], fields[2], fields[3], &result])?;
+    }
+    Ok(result)
+}
+
+fn fin_elim(eval: &mut Eval, args: &[Expr]) -> Result<Expr, EvalError> {
+    let (fzero, fsucc) = (&args[1], &args[2]);
+    let (mut n, mut i) = (&args[3], &args[4]);
+    let mut steps = Vec::new();
+    let mut result = loop {
+        match as_con(i) {
+            Some(("fzero", m)) => break apply(eval, fzero, m[0].clone())?,
+            Some(("fsucc", fields)) => {
+                steps.push(fields.clone());
+                n = fields[0];
+                i = fields[1];
+            }
+            _ => break stuck("fin-elim", &args[..3], &[n, i]),
+        }
+    };
+    for fields in steps.into_iter().rev() {
+        result = apply_all(eval, fsucc, &[fields[0], fields[1], &result])?;
+    }
+    Ok(result)
+}
+
+fn stuck(name: &str, methods: &[Expr], on: &[&Expr]) -> Expr {
+    let args = methods.iter().cloned().chain(on.iter().map(|v| (*v).clone()));
+    value("Elim", std::iter::once(Expr::from(name)).chain(args).collect())
+}
+
+/// The built in constants, with their types, in the order they are
+/// declared in: each type may only mention those before it.
+const BUILTINS: &[(&str, &str)] = &[
+    ("Nat", "Type"),
+    ("zero", "Nat"),
+    ("succ", "(-> Nat Nat)"),
+    ("nat-elim", "(Pi [P (-> Nat (Type 1))] \
+                     (-> (P zero) (Pi [k Nat] (-> (P k) (P (succ k)))) (Pi [n Nat] (P n))))"),
+    ("Vec", "(-> Type Nat Type)"),
+    ("nil", "(Pi [A Type] (Vec A zero))"),
+    ("cons", "(Pi [A Type] [n Nat] (-> A (Vec A n) (Vec A (succ n))))"),
+    ("vec-elim", "(Pi [A Type] [P (Pi [n Nat] (-> (Vec A n) Type))] \
+                     (-> (P zero (nil A)) \
+                         (Pi [n Nat] [x A] [xs (Vec A n)] (-> (P n xs) (P (succ n) (cons A n x xs)))) \
+                         (Pi [n Nat] [xs (Vec A n)] (P n xs))))"),
+    ("Fin", "(-> Nat Type)"),
+    ("fzero", "(Pi [n Nat] (Fin (succ n)))"),
+    ("fsucc", "(Pi [n Nat] (-> (Fin n) (Fin (succ n))))"),
+    ("fin-elim", "(Pi [P (Pi [n Nat] (-> (Fin n) Type))] \
+                     (-> (Pi [n Nat] (P (succ n) (fzero n))) \
+                         (Pi [n Nat] [i (Fin n)] (-> (P n i) (P (succ n) (fsucc n i)))) \
+                         (Pi [n Nat] [i (Fin n)] (P n i))))"),
+    ("Unit", "Type"),
+    ("tt", "Unit"),
+];
+
+fn builtin(name: &str) -> Expr {
+    match name {
+        "nat-elim" => native(name, 4, nat_elim),
+        "vec-elim" => native(name, 6, vec_elim),
+        "fin-elim" => native(name, 5, fin_elim),
+        "Nat" => constructor("Nat", 0),
+        "zero" => constructor("zero", 0),
+        "succ" => constructor("succ", 1),
+        "Vec" => constructor("Vec", 2),
+        "nil" => constructor("nil", 1),
+        "cons" => constructor("cons", 4),
+        "Fin" => constructor("Fin", 1),
+        "fzero" => constructor("fzero", 1),
+        "fsucc" => constructor("fsucc", 2),
+        "Unit" => constructor("Unit", 0),
+        "tt" => constructor("tt", 0),
+        _ => unreachable!("{} is not built in", name),
+    }
+}
+
+#[derive(Clone)]
+struct Constant {
+    typ: Expr,
+    value: Expr,
+}
+
+// the types of the variables in scope, innermost last
+#[derive(Clone, Default)]
+struct Context {
+    names: Vec<String>,
+    types: Vec<Expr>,
+}
+
+impl Context {
+    fn bind(&self, name: &str, typ: Expr) -> Context {
+        let mut ctx = self.clone();
+        ctx.names.push(name.to_string());
+        ctx.types.push(typ);
+        ctx
+    }
+
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    fn vars(&self) -> Vec<Expr> {
+        self.names.iter().enumerate().map(|(level, name)| var(level, name)).collect()
+    }
+}
+
+/// Checks and normalises terms, against the built in constants and
+/// whatever has been defined.
+pub struct Checker {
+    eval: Eval,
+    constants: HashMap<String, Constant>,
+    // the natives compiled terms call
+    app: Expr,
+    pi: Expr,
+    sigma: Expr,
+    pair: Expr,
+    fst: Expr,
+    snd: Expr,
+}
+
+impl Default for Checker {
+    fn default() -> Checker {
+        Checker::new()
+    }
+}
+
+impl Checker {
+    pub fn new() -> Checker {
+        let binder = |variant: &'static str| native(variant, 3, move |_, args| Ok(value(variant, args.to_vec())));
+        let mut checker = Checker {
+            eval: Eval::new(),
+            constants: HashMap::new(),
+            app: native("app", 2, |eval, args| apply(eval, &args[0], args[1].clone())),
+            pi: binder("Pi"),
+            sigma: binder("Sigma"),
+            pair: native("pair", 2, |_, args| Ok(value("Pair", args.to_vec()))),
+            fst: native("fst", 1, |_, args| project(&args[0], true)),
+            snd: native("snd", 1, |_, args| project(&args[0], false)),
+        };
+        for (name, src) in BUILTINS {
+            let form = Forms::new(src).next().expect("a type").expect("a form");
+            let typ = checker.parse(&read(form).expect("a type")).expect("a term");
+            checker.universe_of(&Context::default(), &typ).expect("a well formed type");
+            let typ = checker.evaluate(&Context::default(), &typ).expect("a value");
+            checker.constants.insert(name.to_string(), Constant { typ, value: builtin(name) });
+        }
+        checker
+    }
+
+    /// Reads a term. A symbol is a variable if one of that name is in
+    /// scope, and otherwise a constant. Lambdas and binders may bind
+    /// several variables at once, as `(lambda x y body)` and
+    /// `(Pi [x A] [y B] C)` do, and `(-> A B)` is a `Pi` whose variable
+    /// does not occur. A number is a `Nat`.
+    pub fn parse(&self, expr: &Expr) -> Result<Term, DependentError> {
+        self.parse_in(expr, &mut Vec::new())
+    }
+
+    fn parse_in(&self, expr: &Expr, scope: &mut Vec<String>) -> Result<Term, DependentError> {
+        let bad = || DependentError::BadTerm(expr.clone());
+        match expr {
+            Expr::Symbol(s) => match scope.iter().rposition(|name| *name == s.value) {
+                Some(level) => Ok(Term::Var(scope.len() - 1 - level, s.value.clone())),
+                None if s.value == "Type" => Ok(Term::Universe(0)),
+                None if self.constants.contains_key(&s.value) => Ok(Term::Const(s.value.clone())),
+                None => Err(DependentError::Unbound(s.value.clone(), s.meta.span)),
+            },
+            Expr::Int(i) if i.value >= 0 => Ok((0..i.value).fold(Term::Const("zero".into()), |n, _| {
+                Term::app(Term::Const("succ".into()), n)
+            })),
+            Expr::List(list) => {
+                let vals: Vec<&Expr> = list.vals.iter().collect();
+                let head = match vals.first() {
+                    Some(Expr::Symbol(s)) if !scope.contains(&s.value) => s.value.as_str(),
+                    Some(_) => "",
+                    None => return Err(bad()),
+                };
+                match (head, &vals[1..]) {
+                    ("Type", [Expr::Int(i)]) if i.value >= 0 => Ok(Term::Universe(i.value as usize)),
+                    ("Pi" | "Sigma", [binders @ .., body]) if !binders.is_empty() =>
+                        self.parse_binders(head, binders, body, scope),
+                    ("->", [args @ .., body]) if !args.is_empty() => {
+                        let mut domains = Vec::new();
+                        for arg in args {
+                            domains.push(self.parse_in(arg, scope)?);
+                            scope.push("_".into());
+                        }
+                        let body = self.parse_in(body, scope);
+                        scope.truncate(scope.len() - args.len());
+                        domains.into_iter().rev().try_fold(body?, |body, domain| {
+                            Ok(Term::Pi("_".into(), Box::new(domain), Box::new(body)))
+                        })
+                    }
+                    ("lambda", [params @ .., body]) if !params.is_empty() => {
+                        let names = params.iter().map(|param| match param {
+                            Expr::Symbol(s) => Ok(s.value.clone()),
+                            _ => Err(DependentError::BadTerm((*param).clone())),
+                        }).collect::<Result<Vec<_>, _>>()?;
+                        scope.extend(names.iter().cloned());
+                        let body = self.parse_in(body, scope);
+                        scope.truncate(scope.len() - names.len());
+                        Ok(names.into_iter().rev().fold(body?, |body, x| Term::Lambda(x, Box::new(body))))
+                    }
+                    ("pair", [a, b]) =>
+                        Ok(Term::Pair(Box::new(self.parse_in(a, scope)?), Box::new(self.parse_in(b, scope)?))),
+                    ("fst", [p]) => Ok(Term::Fst(Box::new(self.parse_in(p, scope)?))),
+                    ("snd", [p]) => Ok(Term::Snd(Box::new(self.parse_in(p, scope)?))),
+                    ("the", [typ, term]) =>
+                        Ok(Term::The(Box::new(self.parse_in(typ, scope)?), Box::new(self.parse_in(term, scope)?))),
+                    ("Type" | "Pi" | "Sigma" | "->" | "lambda" | "pair" | "fst" | "snd" | "the", _) => Err(bad()),
+                    (_, []) => Err(bad()),
+                    (_, args) => args.iter().try_fold(self.parse_in(vals[0], scope)?, |f, arg| {
+                        Ok(Term::app(f, self.parse_in(arg, scope)?))
+                    }),
+                }
+            }
+            _ => Err(bad()),
+        }
+    }
+
+    fn parse_binders(
+        &self,
+        head: &str,
+        binders: &[&Expr],
+        body: &Expr,
+        scope: &mut Vec<String>,
+    ) -> Result<Term, DependentError> {
+        let mut bound = Vec::new();
+        let mut result = Ok(());
+        for binder in binders {
+            let parsed = match bracketed(binder) {
+                Some((x, typ)) => self.parse_in(typ, scope).map(|typ| (x.value.clone(), typ)),
+                None => Err(DependentError::BadTerm((*binder).clone())),
+            };
+            match parsed {
+                Ok((x, typ)) => {
+                    scope.push(x.clone());
+                    bound.push((x, typ));
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        let body = result.and_then(|_| self.parse_in(body, scope));
+        scope.truncate(scope.len() - bound.len());
+        Ok(bound.into_iter().rev().fold(body?, |body, (x, typ)| match head {
+            "Pi" => Term::Pi(x, Box::new(typ), Box::new(body)),
+            _ => Term::Sigma(x, Box::new(typ), Box::new(body)),
+        }))
+    }
+
+    /// The type of a closed term, in normal form.
+    pub fn infer(&mut self, term: &Term) -> Result<Term, DependentError> {
+        let typ = self.infer_in(&Context::default(), term)?;
+        self.quote(0, &typ)
+    }
+
+    /// Checks that `typ` is a type and `term` is of it.
+    pub fn check(&mut self, term: &Term, typ: &Term) -> Result<(), DependentError> {
+        let ctx = Context::default();
+        self.universe_of(&ctx, typ)?;
+        let typ = self.evaluate(&ctx, typ)?;
+        self.check_in(&ctx, term, &typ)
+    }
+
+    /// The normal form of a closed, well typed term.
+    pub fn normalise(&mut self, term: &Term) -> Result<Term, DependentError> {
+        let val = self.evaluate(&Context::default(), term)?;
+        self.quote(0, &val)
+    }
+
+    /// Whether two closed, well typed terms are definitionally equal.
+    pub fn equal(&mut self, a: &Term, b: &Term) -> Result<bool, DependentError> {
+        let ctx = Context::default();
+        let (a, b) = (self.evaluate(&ctx, a)?, self.evaluate(&ctx, b)?);
+        self.convertible(0, &a, &b)
+    }
+
+    /// Checks `body` against `typ`, then makes `name` a constant that
+    /// later terms may use, and that computes.
+    pub fn define(&mut self, name: &str, typ: &Term, body: &Term) -> Result<(), DependentError> {
+        let ctx = Context::default();
+        self.universe_of(&ctx, typ)?;
+        let typ = self.evaluate(&ctx, typ)?;
+        self.check_in(&ctx, body, &typ)?;
+        let value = self.evaluate(&ctx, body)?;
+        self.constants.insert(name.to_string(), Constant { typ, value });
+        Ok(())
+    }
+
+    // checking
+
+    fn infer_in(&mut self, ctx: &Context, term: &Term) -> Result<Expr, DependentError> {
+        match term {
+            Term::Var(i, _) => Ok(ctx.types[ctx.len() - 1 - i].clone()),
+            Term::Const(c) => match self.constants.get(c) {
+                Some(constant) => Ok(constant.typ.clone()),
+                None => Err(DependentError::Unbound(c.clone(), None)),
+            },
+            Term::Universe(n) => Ok(universe(n + 1)),
+            Term::Pi(x, a, b) | Term::Sigma(x, a, b) => {
+                let i = self.universe_of(ctx, a)?;
+                let a = self.evaluate(ctx, a)?;
+                let j = self.universe_of(&ctx.bind(x, a), b)?;
+                Ok(universe(i.max(j)))
+            }
+            Term::App(f, arg) => {
+                let typ = self.infer_in(ctx, f)?;
+                match view(&typ) {
+                    Some(("Pi", fields)) => {
+                        let (domain, codomain) = (fields[1].clone(), fields[2].clone());
+                        self.check_in(ctx, arg, &domain)?;
+                        let arg = self.evaluate(ctx, arg)?;
+                        Ok(apply(&mut self.eval, &codomain, arg)?)
+                    }
+                    _ => Err(DependentError::NotAFunction((**f).clone(), self.quote(ctx.len(), &typ)?)),
+                }
+            }
+            Term::Fst(p) | Term::Snd(p) => {
+                let typ = self.infer_in(ctx, p)?;
+                match view(&typ) {
+                    Some(("Sigma", fields)) if matches!(term, Term::Fst(_)) => Ok(fields[1].clone()),
+                    Some(("Sigma", fields)) => {
+                        let first = self.evaluate(ctx, &Term::Fst(p.clone()))?;
+                        Ok(apply(&mut self.eval, &fields[2].clone(), first)?)
+                    }
+                    _ => Err(DependentError::NotAPair((**p).clone(), self.quote(ctx.len(), &typ)?)),
+                }
+            }
+            Term::The(typ, term) => {
+                self.universe_of(ctx, typ)?;
+                let typ = self.evaluate(ctx, typ)?;
+                self.check_in(ctx, term, &typ)?;
+                Ok(typ)
+            }
+            Term::Lambda(..) | Term::Pair(..) => Err(DependentError::CannotInfer(term.clone())),
+        }
+    }
+
+    fn check_in(&mut self, ctx: &Context, term: &Term, typ: &Expr) -> Result<(), DependentError> {
+        match (term, view(typ)) {
+            (Term::Lambda(x, body), Some(("Pi", fields))) => {
+                let (domain, codomain) = (fields[1].clone(), fields[2].clone());
+                let codomain = apply(&mut self.eval, &codomain, var(ctx.len(), x))?;
+                self.check_in(&ctx.bind(x, domain), body, &codomain)
+            }
+            (Term::Pair(a, b), Some(("Sigma", fields))) => {
+                let (first, second) = (fields[1].clone(), fields[2].clone());
+                self.check_in(ctx, a, &first)?;
+                let a = self.evaluate(ctx, a)?;
+                let second = apply(&mut self.eval, &second, a)?;
+                self.check_in(ctx, b, &second)
+            }
+            (Term::Lambda(..) | Term::Pair(..), _) =>
+                Err(DependentError::NotOfType(term.clone(), self.quote(ctx.len(), typ)?)),
+            _ => {
+                let found = self.infer_in(ctx, term)?;
+                if self.subsumes(ctx.len(), typ, &found)? {
+                    Ok(())
+                } else {
+                    Err(DependentError::Mismatch {
+                        term: term.clone(),
+                        expected: self.quote(ctx.len(), typ)?,
+                        found: self.quote(ctx.len(), &found)?,
+                    })
+                }
+            }
+        }
+    }
+
+    // the level of the universe the type `term` is in
+    fn universe_of(&mut self, ctx: &Context, term: &Term) -> Result<usize, DependentError> {
+        let typ = self.infer_in(ctx, term)?;
+        match view(&typ) {
+            Some(("Type", fields)) => Ok(level(&fields[0])),
+            _ => Err(DependentError::NotAType(term.clone(), self.quote(ctx.len(), &typ)?)),
+        }
+    }
+
+    // universes are cumulative: what is in one is in those above it
+    fn subsumes(&mut self, depth: usize, expected: &Expr, found: &Expr) -> Result<bool, DependentError> {
+        match (view(expected), view(found)) {
+            (Some(("Type", i)), Some(("Type", j))) => Ok(level(&j[0]) <= level(&i[0])),
+            _ => self.convertible(depth, expected, found),
+        }
+    }
+
+    // evaluation
+
+    fn evaluate(&mut self, ctx: &Context, term: &Term) -> Result<Expr, DependentError> {
+        let mut scope: Vec<String> = (0..ctx.len()).map(|level| param(&ctx.names[level], level)).collect();
+        let mut code = self.compile(term, &mut scope);
+        for name in scope.iter().rev() {
+            code = lambda(name, code);
+        }
+        let val = self.eval.eval(code)?;
+        if ctx.names.is_empty() {
+            return Ok(val);
+        }
+        Ok(self.eval.apply(val, ctx.vars())?)
+    }
+
+    // the lisp that evaluates a term, with each variable in scope
+    // bound to a parameter named for the variable and its level
+    fn compile(&self, term: &Term, scope: &mut Vec<String>) -> Expr {
+        let call = |f: &Expr, args: Vec<Expr>| {
+            Expr::List(List::from(std::iter::once(f.clone()).chain(args).collect::<Vector<_>>()))
+        };
+        match term {
+            Term::Var(i, _) => Expr::Symbol(Symbol::from(scope[scope.len() - 1 - i].clone())),
+            Term::Const(c) => self.constants.get(c).map(|c| c.value.clone()).unwrap_or_default(),
+            Term::Universe(n) => universe(*n),
+            Term::Pi(x, a, b) | Term::Sigma(x, a, b) => {
+                let head = if matches!(term, Term::Pi(..)) { &self.pi } else { &self.sigma };
+                let a = self.compile(a, scope);
+                let b = self.compile_binder(x, b, scope);
+                call(head, vec![Expr::from(x.as_str()), a, b])
+            }
+            Term::Lambda(x, body) => self.compile_binder(x, body, scope),
+            Term::App(f, arg) => call(&self.app, vec![self.compile(f, scope), self.compile(arg, scope)]),
+            Term::Pair(a, b) => call(&self.pair, vec![self.compile(a, scope), self.compile(b, scope)]),
+            Term::Fst(p) => call(&self.fst, vec![self.compile(p, scope)]),
+            Term::Snd(p) => call(&self.snd, vec![self.compile(p, scope)]),
+            Term::The(_, term) => self.compile(term, scope),
+        }
+    }
+
+    fn compile_binder(&self, x: &str, body: &Term, scope: &mut Vec<String>) -> Expr {
+        let name = param(x, scope.len());
+        scope.push(name.clone());
+        let body = self.compile(body, scope);
+        scope.pop();
+        lambda(&name, body)
+    }
+
+    // reading back
+
+    // the normal form of a value, under `depth` variables
+    fn quote(&mut self, depth: usize, val: &Expr) -> Result<Term, DependentError> {
+        let quote_all = |checker: &mut Checker, head: Term, args: &[Expr]| {
+            args.iter().try_fold(head, |f, arg| Ok(Term::app(f, checker.quote(depth, arg)?)))
+        };
+        if is_function(val) {
+            let x = match val {
+                Expr::Fun(f) => f.param.value.split('/').next().unwrap_or("x").to_string(),
+                _ => "x".to_string(),
+            };
+            let body = apply(&mut self.eval, val, var(depth, &x))?;
+            return Ok(Term::Lambda(x, Box::new(self.quote(depth + 1, &body)?)));
+        }
+        let (variant, fields) = match view(val) {
+            Some((variant, fields)) => (variant, fields.iter().cloned().collect::<Vec<_>>()),
+            None => return Err(EvalError::BadParameter("a value", val.clone()).into()),
+        };
+        match variant {
+            "Type" => Ok(Term::Universe(level(&fields[0]))),
+            "Pi" | "Sigma" => {
+                let x = string(&fields[0]).to_string();
+                let a = self.quote(depth, &fields[1])?;
+                let body = apply(&mut self.eval, &fields[2], var(depth, &x))?;
+                let b = Box::new(self.quote(depth + 1, &body)?);
+                Ok(if variant == "Pi" { Term::Pi(x, Box::new(a), b) } else { Term::Sigma(x, Box::new(a), b) })
+            }
+            "Pair" => Ok(Term::Pair(Box::new(self.quote(depth, &fields[0])?), Box::new(self.quote(depth, &fields[1])?))),
+            "Var" => Ok(Term::Var(depth - 1 - level(&fields[0]), string(&fields[1]).to_string())),
+            "App" => Ok(Term::app(self.quote(depth, &fields[0])?, self.quote(depth, &fields[1])?)),
+            "Fst" => Ok(Term::Fst(Box::new(self.quote(depth, &fields[0])?))),
+            "Snd" => Ok(Term::Snd(Box::new(self.quote(depth, &fields[0])?))),
+            // constructors and stuck eliminators
+            _ => quote_all(self, Term::Const(string(&fields[0]).to_string()), &fields[1..]),
+        }
+    }
+
+    // definitional equality, up to eta for functions and pairs
+    fn convertible(&mut self, depth: usize, a: &Expr, b: &Expr) -> Result<bool, DependentError> {
+        if is_function(a) || is_function(b) {
+            if !(is_function(a) || is_neutral(a)) || !(is_function(b) || is_neutral(b)) {
+                return Ok(false);
+            }
+            let x = var(depth, "x");
+            let (a, b) = (apply(&mut self.eval, a, x.clone())?, apply(&mut self.eval, b, x)?);
+            return self.convertible(depth + 1, &a, &b);
+        }
+        let pair = |v: &Expr| matches!(view(v), Some(("Pair", _)));
+        if pair(a) || pair(b) {
+            if !(pair(a) || is_neutral(a)) || !(pair(b) || is_neutral(b)) {
+                return Ok(false);
+            }
+            return Ok(self.convertible(depth, &project(a, true)?, &project(b, true)?)?
+                && self.convertible(depth, &project(a, false)?, &project(b, false)?)?);
+        }
+        let (x, y) = match (view(a), view(b)) {
+            (Some(x), Some(y)) if x.0 == y.0 && x.1.len() == y.1.len() => (x, y),
+            _ => return Ok(false),
+        };
+        let (xs, ys): (Vec<Expr>, Vec<Expr>) = (x.1.iter().cloned().collect(), y.1.iter().cloned().collect());
+        match x.0 {
+            "Type" | "Var" => Ok(level(&xs[0]) == level(&ys[0])),
+            "Pi" | "Sigma" => {
+                if !self.convertible(depth, &xs[1], &ys[1])? {
+                    return Ok(false);
+                }
+                let v = var(depth, "x");
+                let a = apply(&mut self.eval, &xs[2], v.clone())?;
+                let b = apply(&mut self.eval, &ys[2], v)?;
+                self.convertible(depth + 1, &a, &b)
+            }
+            "Con" | "Elim" if string(&xs[0]) != string(&ys[0]) => Ok(false),
+            "Con" | "Elim" => self.all_convertible(depth, &xs[1..], &ys[1..]),
+            _ => self.all_convertible(depth, &xs, &ys),
+        }
+    }
+
+    fn all_convertible(&mut self, depth: usize, xs: &[Expr], ys: &[Expr]) -> Result<bool, DependentError> {
+        for (x, y) in xs.iter().zip(ys) {
+            if !self.convertible(depth, x, y)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+// `x/3` for the `x` bound three binders in
+fn param(name: &str, level: usize) -> String {
+    format!("{}/{}", name, level)
+}
+
+fn lambda(param: &str, body: Expr) -> Expr {
+    let vals = vec![Expr::Symbol(Symbol::from("lambda".to_string())), Expr::Symbol(Symbol::from(param.to_string())), body];
+    Expr::List(List::from(vals.into_iter().collect::<Vector<_>>()))
+}
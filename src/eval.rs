@@ -1,196 +1,2032 @@
-// use crate::forms::*;
-// use crate::spans::*;
+use crate::forms::*;
+use crate::reader::*;
+use crate::spans::*;
+use crate::tokens::{default_dispatch, Dispatch};
 use crate::exprs::*;
+use crate::native::*;
+use crate::prelude;
+use crate::rust;
+use crate::host::{self, Capabilities, Capability};
+use crate::data::{self, DataType};
+use crate::traits::{self, Implementation, Instance, Trait};
+use crate::infer::{self, Elaboration};
+use crate::types::{Constraint, Scheme, Type, TypeEnv, TypeError};
 use im::{HashMap, Vector};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum EvalError {
     BadParameter(&'static str, Expr),
+    DivideByZero,
     ExtraArguments(List),
     MissingArguments(Expr, usize),
+    /// No clause of a `match` matched the value.
+    NonExhaustiveMatch(Expr, Option<Span>),
     NotCallable(Expr, List),
+    Overflow(&'static str, Vec<Expr>),
+    StackOverflow(Option<Span>),
     StackUnderflow(Stack),
     UnknownBinding(String),
     UnexpandedMacro(Fun),
-    Reset(Stack),
+    WrongArity(String, Arity, usize),
+    /// A continuation was invoked beneath a native call made by the
+    /// run that captured it. This unwinds the rust stack back to that
+    /// run, which resumes the continuation with the value.
+    Escape(Continuation, Expr),
+    ShiftWithoutReset(Option<Span>),
+    /// A value thrown with `throw` and not caught.
+    Thrown(Expr),
+    /// Evaluation took more steps than `Limits::max_steps`.
+    OutOfFuel(Option<Span>),
+    /// Evaluation built more than `Limits::max_allocation` nodes.
+    OutOfMemory(Option<Span>),
+    DeadlineExceeded(Option<Span>),
+    /// The interrupt handle was set.
+    Interrupted(Option<Span>),
+    /// The builtin exists, but its capability was not granted.
+    CapabilityDenied(String, Capability),
+    /// The path leads out of the root file access is confined to.
+    PathDenied(String),
+    Io(String),
+    /// Checking types before evaluation failed.
+    Type(Box<TypeError>),
+    /// The value of a `the` form was not of its type. The span is of
+    /// the `:` or `the`.
+    TypeMismatch(Type, Expr, Option<Span>),
+    /// A field accessor was given a value of another variant, or not
+    /// of a declared type at all.
+    WrongVariant(String, Expr),
+    /// A trait method was called on a value of a type no `impl` of
+    /// the trait is for.
+    NoImplementation(String, Expr),
 }
 
-#[derive(Clone, Eq, PartialEq)]
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::BadParameter(what, got) => write!(f, "expected {}, got {}", what, got),
+            EvalError::DivideByZero => write!(f, "division by zero"),
+            EvalError::ExtraArguments(list) => write!(f, "too many arguments in {}", list_expr(list)),
+            EvalError::MissingArguments(expr, n) => write!(f, "{} more arguments expected in {}", n, expr),
+            EvalError::NonExhaustiveMatch(val, Some(span)) =>
+                write!(f, "no pattern matches {} at {}:{}", val, span.start.line + 1, span.start.column + 1),
+            EvalError::NonExhaustiveMatch(val, None) => write!(f, "no pattern matches {}", val),
+            EvalError::NotCallable(expr, args) => write!(f, "{} is not callable with {}", expr, list_expr(args)),
+            EvalError::Overflow(op, args) => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                write!(f, "integer overflow in ({} {})", op, args.join(" "))
+            }
+            EvalError::StackOverflow(Some(span)) =>
+                write!(f, "stack overflow at {}:{}", span.start.line + 1, span.start.column + 1),
+            EvalError::StackOverflow(None) => write!(f, "stack overflow"),
+            EvalError::StackUnderflow(_) => write!(f, "stack underflow"),
+            EvalError::UnknownBinding(name) => write!(f, "unknown binding: {}", name),
+            EvalError::UnexpandedMacro(m) => write!(f, "unexpanded macro: {}", Expr::Macro(m.clone())),
+            EvalError::WrongArity(name, arity, got) => write!(f, "{} expects {:?} arguments, got {}", name, arity, got),
+            EvalError::Escape(_, val) => write!(f, "continuation escaped with {}", val),
+            EvalError::ShiftWithoutReset(Some(span)) =>
+                write!(f, "shift without reset at {}:{}", span.start.line + 1, span.start.column + 1),
+            EvalError::ShiftWithoutReset(None) => write!(f, "shift without reset"),
+            EvalError::Thrown(val) => write!(f, "uncaught: {}", val),
+            EvalError::OutOfFuel(span) => write!(f, "out of fuel{}", at(span)),
+            EvalError::OutOfMemory(span) => write!(f, "allocation limit exceeded{}", at(span)),
+            EvalError::DeadlineExceeded(span) => write!(f, "deadline exceeded{}", at(span)),
+            EvalError::Interrupted(span) => write!(f, "interrupted{}", at(span)),
+            EvalError::CapabilityDenied(name, capability) =>
+                write!(f, "{} needs the {} capability, which was not granted", name, capability),
+            EvalError::PathDenied(path) => write!(f, "{} is outside the file root", path),
+            EvalError::Io(message) => write!(f, "{}", message),
+            EvalError::Type(e) => write!(f, "{}", e),
+            EvalError::TypeMismatch(typ, val, span) => write!(f, "expected {}, got {}{}", typ, val, at(span)),
+            EvalError::WrongVariant(variant, val) => write!(f, "expected a {}, got {}", variant, val),
+            EvalError::NoImplementation(name, val) => write!(f, "no impl of {} for {}", name, val),
+        }
+    }
+}
+
+impl EvalError {
+    /// What kind of error this is, as the `::kind` of its value.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EvalError::BadParameter(..) => "bad-parameter",
+            EvalError::DivideByZero => "divide-by-zero",
+            EvalError::ExtraArguments(_) => "extra-arguments",
+            EvalError::MissingArguments(..) => "missing-arguments",
+            EvalError::NonExhaustiveMatch(..) => "non-exhaustive-match",
+            EvalError::NotCallable(..) => "not-callable",
+            EvalError::Overflow(..) => "overflow",
+            EvalError::StackOverflow(_) => "stack-overflow",
+            EvalError::StackUnderflow(_) => "stack-underflow",
+            EvalError::UnknownBinding(_) => "unknown-binding",
+            EvalError::UnexpandedMacro(_) => "unexpanded-macro",
+            EvalError::WrongArity(..) => "wrong-arity",
+            EvalError::Escape(..) => "escape",
+            EvalError::ShiftWithoutReset(_) => "shift-without-reset",
+            EvalError::Thrown(_) => "thrown",
+            EvalError::OutOfFuel(_) => "out-of-fuel",
+            EvalError::OutOfMemory(_) => "out-of-memory",
+            EvalError::DeadlineExceeded(_) => "deadline-exceeded",
+            EvalError::Interrupted(_) => "interrupted",
+            EvalError::CapabilityDenied(..) => "capability-denied",
+            EvalError::PathDenied(_) => "path-denied",
+            EvalError::Io(_) => "io",
+            EvalError::Type(_) => "type",
+            EvalError::TypeMismatch(..) => "type-mismatch",
+            EvalError::WrongVariant(..) => "wrong-variant",
+            EvalError::NoImplementation(..) => "no-implementation",
+        }
+    }
+
+    /// Whether this is a limit being hit, which `try` cannot catch.
+    pub fn is_limit(&self) -> bool {
+        matches!(self,
+            EvalError::OutOfFuel(_) | EvalError::OutOfMemory(_)
+            | EvalError::DeadlineExceeded(_) | EvalError::Interrupted(_))
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            EvalError::NonExhaustiveMatch(_, span)
+            | EvalError::StackOverflow(span)
+            | EvalError::ShiftWithoutReset(span)
+            | EvalError::OutOfFuel(span)
+            | EvalError::OutOfMemory(span)
+            | EvalError::DeadlineExceeded(span)
+            | EvalError::Interrupted(span)
+            | EvalError::TypeMismatch(_, _, span) => *span,
+            EvalError::Type(e) => e.span(),
+            _ => None,
+        }
+    }
+
+    /// The value `catch` sees: what was thrown, or for any other error,
+    /// a map of its `::kind`, `::message` and `::span`. `span` is where
+    /// evaluation was, for errors that do not know.
+    pub fn to_expr(&self, span: Option<Span>) -> Expr {
+        if let EvalError::Thrown(val) = self {
+            return val.clone();
+        }
+        let span = match self.span().or(span) {
+            Some(span) => keyword_map(vec![
+                ("line", Expr::from(span.start.line as i64 + 1)),
+                ("column", Expr::from(span.start.column as i64 + 1)),
+            ]),
+            None => Expr::Nil,
+        };
+        keyword_map(vec![
+            ("kind", Expr::Keyword(Keyword::from(self.kind()))),
+            ("message", Expr::from(self.to_string())),
+            ("span", span),
+        ])
+    }
+}
+
+pub(crate) fn keyword_map(entries: Vec<(&str, Expr)>) -> Expr {
+    let vals: HashMap<Expr, Expr> =
+        entries.into_iter().map(|(k, v)| (Expr::Keyword(Keyword::from(k)), v)).collect();
+    Expr::Map(Map::from(vals))
+}
+
+fn at(span: &Option<Span>) -> String {
+    match span {
+        Some(span) => format!(" at {}:{}", span.start.line + 1, span.start.column + 1),
+        None => String::new(),
+    }
+}
+
+fn list_expr(list: &List) -> Expr {
+    Expr::List(list.clone())
+}
+
+/// How to set up a new `Eval`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Options {
+    /// Whether to load the standard library. Without it, only the
+    /// special forms and `nil`, `true` and `false` are bound.
+    pub prelude: bool,
+    /// How many pending frames evaluation may build up before
+    /// failing with `EvalError::StackOverflow`.
+    pub max_depth: usize,
+    /// How many calls a backtrace shows at most.
+    pub max_backtrace: usize,
+    pub limits: Limits,
+    /// Which builtins that reach outside the evaluator to install.
+    pub capabilities: Capabilities,
+    /// Whether to infer the type of each form passed to `eval`, and
+    /// refuse to run it if it has none.
+    pub typecheck: bool,
+    pub ascriptions: Ascriptions,
+}
+
+/// What `the` does when the program runs. Either way, the type
+/// checker takes it at its word.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Ascriptions {
+    /// Fails with `EvalError::TypeMismatch` if the value is not of
+    /// the type.
+    Checked,
+    /// Ignores the type.
+    Erased,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            prelude: true, max_depth: 100_000, max_backtrace: 32, limits: Limits::default(),
+            capabilities: Capabilities::none(), typecheck: false, ascriptions: Ascriptions::Checked,
+        }
+    }
+}
+
+/// Budgets for each call to `eval` or `apply`, so that untrusted code
+/// cannot run forever. `None` is unlimited. Hitting a limit stops
+/// evaluation outright: `try` does not catch it and `finally` bodies
+/// do not run.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Limits {
+    /// How many steps the evaluator may take.
+    pub max_steps: Option<u64>,
+    /// How many nodes the values natives return, and the closures
+    /// and continuations evaluation makes, may add up to. A list
+    /// counts one for itself and one per element, a string one per
+    /// node's worth of bytes, and a continuation one per frame.
+    pub max_allocation: Option<u64>,
+    /// Lowers `Options::max_depth`.
+    pub max_depth: Option<usize>,
+    pub deadline: Option<Instant>,
+}
+
+// The deadline is only looked at every so many steps.
+const DEADLINE_INTERVAL: u64 = 256;
+
+/// Shared with other threads, so `Eval` compares it by identity.
+#[derive(Clone, Debug, Default)]
+struct Interrupt(Arc<AtomicBool>);
+
+impl PartialEq for Interrupt {
+    fn eq(&self, other: &Interrupt) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Interrupt {}
+
+// Natives calling back into the evaluator, and macros being
+// expanded, do so on the rust stack, so we can only allow so many:
+// few enough to fit the 2MB of a spawned thread in a debug build.
+const MAX_NESTING: usize = 32;
+
+/// When code runs. Macros run while the program is being expanded,
+/// in an environment of their own, so nothing defined for them is
+/// part of the program they expand into.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Phase {
+    /// Running the program.
+    Run,
+    /// Expanding it: macro bodies, `begin-for-syntax` and the
+    /// `::compile` part of `eval-when`.
+    Compile,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Eval {
     pub(crate) stack: Stack,
+    // top level definitions, visible from every function body, for
+    // the run and compile phases
+    pub(crate) globals: HashMap<String, Expr>,
+    pub(crate) syntax_globals: HashMap<String, Expr>,
+    phase: Phase,
+    // frames held by runs further up the rust stack, and how many
+    // of those runs there are
+    depth: usize,
+    nesting: usize,
+    max_depth: usize,
+    // the runs in progress, innermost last, and the next run's id
+    runs: Vec<usize>,
+    next_run: usize,
+    // for macro scopes and gensyms
+    next_id: usize,
+    // reader macros by dispatch character, with how many forms they take
+    reader_macros: HashMap<char, (usize, Expr)>,
+    // the backtrace of the error being unwound, or that was returned
+    backtrace: Option<Backtrace>,
+    max_backtrace: usize,
+    file: Option<String>,
+    // what this call to `eval` or `apply` has used of its limits
+    limits: Limits,
+    steps: u64,
+    allocated: u64,
+    interrupt: Interrupt,
+    // builtins left out for want of a capability
+    denied: HashMap<String, Capability>,
+    // the types of the top level definitions so far
+    types: TypeEnv,
+    typecheck: bool,
+    ascriptions: Ascriptions,
+    // the impls of each trait, in the order they were made
+    implementations: HashMap<String, Vector<Implementation>>,
+}
+
+impl Default for Eval {
+    fn default() -> Eval {
+        Eval::new()
+    }
 }
 
 impl Eval {
 
+    /// An evaluator with nothing bound at all, not even the special forms.
+    pub fn empty() -> Eval {
+        let max_depth = Options::default().max_depth;
+        Eval {
+            stack: Stack::default(), globals: HashMap::new(), syntax_globals: HashMap::new(),
+            phase: Phase::Run, depth: 0, nesting: 0, max_depth,
+            runs: Vec::new(), next_run: 0, next_id: 0, reader_macros: HashMap::new(),
+            backtrace: None, max_backtrace: Options::default().max_backtrace, file: None,
+            limits: Limits::default(), steps: 0, allocated: 0, interrupt: Interrupt::default(),
+            denied: HashMap::new(), types: TypeEnv::builtin(), typecheck: false,
+            ascriptions: Ascriptions::Checked, implementations: HashMap::new(),
+        }
+    }
+
+    /// An evaluator with the special forms and the prelude loaded.
+    pub fn new() -> Eval {
+        Eval::with_options(Options::default())
+    }
+
+    /// An evaluator whose root environment binds every special form
+    /// to its name, so that `(lambda x x)` works from source text.
+    pub fn with_options(options: Options) -> Eval {
+        let mut eval = Eval::empty();
+        eval.max_depth = options.max_depth;
+        eval.max_backtrace = options.max_backtrace;
+        // both phases start out the same
+        for phase in [Phase::Run, Phase::Compile] {
+            eval.with_phase(phase, |eval| {
+                for special in Special::all() {
+                    eval.define(special.name(), Expr::Special(special));
+                }
+                eval.define("nil", Expr::Nil);
+                eval.define("true", true.into());
+                eval.define("false", false.into());
+                if options.prelude {
+                    prelude::load(eval);
+                    rust::load(eval);
+                }
+                host::load(eval, &options.capabilities);
+            });
+        }
+        if options.prelude {
+            prelude::types(&mut eval.types);
+        }
+        eval.typecheck = options.typecheck;
+        eval.ascriptions = options.ascriptions;
+        // loading the prelude does not count against them
+        eval.limits = options.limits;
+        eval
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Sets the limits for later calls to `eval` and `apply`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// A flag another thread can set to stop evaluation, which then
+    /// fails with `EvalError::Interrupted` and clears it again.
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+        self.interrupt.0.clone()
+    }
+
+    /// Fails if `nodes` more would go over the allocation limit,
+    /// for natives to call before building something large.
+    pub fn reserve(&self, nodes: u64) -> Result<(), EvalError> {
+        match self.limits.max_allocation {
+            Some(max) if self.allocated.saturating_add(nodes) > max => Err(EvalError::OutOfMemory(None)),
+            _ => Ok(()),
+        }
+    }
+
+    fn allocate(&mut self, val: &Expr) -> Result<(), EvalError> {
+        let nodes = match val {
+            Expr::List(l) => 1 + l.vals.len(),
+            Expr::Map(m) => 1 + 2 * m.vals.len(),
+            Expr::Data(d) => 1 + d.fields.len(),
+            Expr::String(s) => 1 + s.value.len() / std::mem::size_of::<Expr>(),
+            Expr::Continuation(k) => 1 + k.frames.len(),
+            _ => 1,
+        } as u64;
+        self.reserve(nodes)?;
+        self.allocated += nodes;
+        Ok(())
+    }
+
+    // `val`, built by the machine rather than a native, once charged
+    fn allocated(&mut self, val: Expr) -> Result<Control, EvalError> {
+        self.allocate(&val)?;
+        Ok(Control::Return(val))
+    }
+
+    // called before every step
+    fn tick(&mut self, span: Option<Span>) -> Result<(), EvalError> {
+        self.steps += 1;
+        if self.interrupt.0.swap(false, Ordering::SeqCst) {
+            return Err(EvalError::Interrupted(span));
+        }
+        if self.limits.max_steps.is_some_and(|max| self.steps > max) {
+            return Err(EvalError::OutOfFuel(span));
+        }
+        match self.limits.deadline {
+            Some(deadline) if self.steps.is_multiple_of(DEADLINE_INTERVAL) && Instant::now() >= deadline =>
+                Err(EvalError::DeadlineExceeded(span)),
+            _ => Ok(()),
+        }
+    }
+
+    fn max_depth(&self) -> usize {
+        self.limits.max_depth.map_or(self.max_depth, |max| max.min(self.max_depth))
+    }
+
+    /// Names the file being evaluated, for backtraces.
+    pub fn set_file(&mut self, file: impl Into<String>) {
+        self.file = Some(file.into());
+    }
+
+    /// The calls that were in progress when the last error returned
+    /// from `eval` or `apply` happened.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.backtrace.as_ref()
+    }
+
+    fn capture_backtrace(&self, span: Option<Span>) -> Backtrace {
+        let mut frames = vec![TraceFrame { name: "top level".to_string(), span }];
+        let mut call = self.stack.calls.0.clone();
+        let mut omitted = 0;
+        while let Some(record) = call {
+            // each call is where its caller was, down to the last
+            // frame kept, which the first left out names
+            if omitted == 0 {
+                frames.last_mut().unwrap().name = record.name.clone();
+            }
+            if omitted == 0 && frames.len() < self.max_backtrace {
+                frames.push(TraceFrame { name: "top level".to_string(), span: record.site });
+            } else {
+                omitted += 1;
+            }
+            call = record.parent.clone();
+        }
+        Backtrace { frames, omitted, file: self.file.clone() }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Runs `f` in `phase`, so that what it defines and looks up at
+    /// the top level belongs to that phase.
+    pub fn with_phase<T>(&mut self, phase: Phase, f: impl FnOnce(&mut Eval) -> T) -> T {
+        let saved = self.phase;
+        self.phase = phase;
+        let result = f(self);
+        self.phase = saved;
+        result
+    }
+
+    /// The top level definitions of `phase`. Only those of the run
+    /// phase make up the program.
+    pub fn definitions(&self, phase: Phase) -> &HashMap<String, Expr> {
+        match phase {
+            Phase::Run => &self.globals,
+            Phase::Compile => &self.syntax_globals,
+        }
+    }
+
+    fn top_level(&self) -> &HashMap<String, Expr> {
+        self.definitions(self.phase)
+    }
+
+    /// Defines `name` at the top level of the current phase.
+    pub fn define(&mut self, name: impl Into<String>, value: Expr) {
+        match self.phase {
+            Phase::Run => self.globals.insert(name.into(), value),
+            Phase::Compile => self.syntax_globals.insert(name.into(), value),
+        };
+    }
+
+    /// Looks up a local binding, falling back to the top level.
+    pub fn lookup(&self, name: &str) -> Result<&Expr, EvalError> {
+        self.stack.lookup(name).or_else(|_| {
+            self.top_level().get(name).ok_or_else(|| self.unbound(name))
+        })
+    }
+
+    fn unbound(&self, name: &str) -> EvalError {
+        match self.denied.get(name) {
+            Some(capability) => EvalError::CapabilityDenied(name.to_string(), *capability),
+            None => EvalError::UnknownBinding(name.to_string()),
+        }
+    }
+
+    /// Makes `name` a builtin withheld for want of `capability`.
+    pub fn deny(&mut self, name: &str, capability: Capability) {
+        self.denied.insert(name.to_string(), capability);
+    }
+
+    /// Looks up a symbol. One introduced by a macro sees bindings made
+    /// by symbols with the same scopes, or failing that, fewer of them,
+    /// dropping the innermost expansion first. Once none are left, it
+    /// refers to the top level, where the macro was defined, and not
+    /// to whatever the user bound around the macro call.
+    pub fn lookup_symbol(&self, sym: &Symbol) -> Result<&Expr, EvalError> {
+        if sym.meta.scopes.is_empty() {
+            return self.lookup(&sym.value);
+        }
+        let scopes: Vec<usize> = sym.meta.scopes.iter().copied().collect();
+        for n in (1..=scopes.len()).rev() {
+            if let Ok(val) = self.lookup(&scoped_key(&sym.value, scopes[..n].iter().copied())) {
+                return Ok(val);
+            }
+        }
+        self.top_level().get(&sym.value).ok_or_else(|| self.unbound(&sym.value))
+    }
+
+    fn fresh_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// A symbol that no other symbol is equal to, which a macro may
+    /// bind without fear of capturing anything.
+    pub fn gensym(&mut self, prefix: &str) -> Symbol {
+        let id = self.fresh_id();
+        Symbol::from(scoped_key(prefix, std::iter::once(id)))
+    }
+
+    /// Binds a native function taking a slice of evaluated arguments.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        fun: impl Fn(&mut Eval, &[Expr]) -> Result<Expr, EvalError> + 'static,
+    ) {
+        let native = Native::new(name, arity, Rc::new(fun), Meta::default());
+        self.define(name, Expr::Native(native));
+    }
+
+    /// Binds a plain rust function, converting its arguments and
+    /// result with `FromExpr` and `IntoExpr`.
+    pub fn register_fn<Args>(&mut self, name: &str, fun: impl IntoNative<Args>) {
+        let native = fun.into_native(name);
+        self.define(name, Expr::Native(native));
+    }
+
+    /// Makes `ch` a reader macro: it reads the next `arity` forms and
+    /// calls `f` with them, in the compile phase, to get the form read.
+    pub fn define_reader_macro(&mut self, ch: char, arity: usize, f: Expr) {
+        self.reader_macros.insert(ch, (arity, f));
+    }
+
+    /// Makes `ch` a reader macro implemented in rust.
+    pub fn register_reader_macro(
+        &mut self,
+        ch: char,
+        arity: usize,
+        fun: impl Fn(&mut Eval, &[Expr]) -> Result<Expr, EvalError> + 'static,
+    ) {
+        let native = Native::new(ch.to_string(), Arity::Exactly(arity), Rc::new(fun), Meta::default());
+        self.define_reader_macro(ch, arity, Expr::Native(native));
+    }
+
+    /// The dispatch characters this evaluator reads, built in or not.
+    pub fn dispatch(&self) -> Dispatch {
+        let mut dispatch = default_dispatch();
+        for (ch, (arity, _)) in self.reader_macros.iter() {
+            dispatch.insert(*ch, *arity);
+        }
+        dispatch
+    }
+
+    /// The forms of `source`, read with this evaluator's reader macros.
+    /// After evaluating a form that defines one, `Forms::set_dispatch`
+    /// with `dispatch` to use it for the rest.
+    pub fn forms<'a>(&self, source: &'a str) -> Forms<'a> {
+        Forms::with_dispatch(source, self.dispatch())
+    }
+
+    /// Reads a form, expanding this evaluator's reader macros.
+    pub fn read<'a>(&mut self, form: Form<'a>) -> Result<Expr, ReadError<'a>> {
+        let macros = self.reader_macros.clone();
+        read_with(form, &mut |ch, args| {
+            let (_, f) = macros.get(&ch)?;
+            let result = self.with_phase(Phase::Compile, |eval| eval.apply(f.clone(), args));
+            Some(result.map_err(|e| ReadError::Macro(Box::new(e))))
+        })
+    }
+
+    // Expansion is hygienic: the symbols given to the macro are marked
+    // with a fresh scope, and the mark is flipped on what it returns,
+    // so only the symbols the macro introduced carry the scope.
+    // The expansion's new nodes also record the call in `Meta::old`.
     fn eval_macro(&mut self, m: Fun, mut list: List) -> Result<Expr, EvalError> {
-        list.vals.pop_front();
-        m.call(Expr::List(list), self)
+        let scope = self.fresh_id();
+        let mut call = list.meta.clone();
+        if let Some(Expr::Symbol(s)) = list.vals.pop_front() {
+            call.macro_name = Some(s.value);
+        }
+        let mut args = Expr::List(list);
+        args.flip_scope(scope);
+        let mut expansion = self.with_phase(Phase::Compile, |eval| m.call(args, eval))?;
+        expansion.flip_scope(scope);
+        expansion.record_expansion(scope, &call);
+        Ok(expansion)
+    }
+
+    // the expansion of `expr`, if it is a macro call
+    fn try_expand_once(&mut self, expr: &Expr) -> Result<Option<Expr>, EvalError> {
+        let list = match expr {
+            Expr::List(list) => list,
+            _ => return Ok(None),
+        };
+        let m = match list.vals.front() {
+            Some(Expr::Macro(m)) => m.clone(),
+            Some(Expr::Symbol(s)) => match self.lookup_symbol(s) {
+                Ok(Expr::Macro(m)) => m.clone(),
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        self.eval_macro(m, list.clone()).map(Some)
     }
 
+    /// Expands `expr` once if it is a macro call.
     pub fn expand_once(&mut self, expr: impl Into<Expr>) -> Result<Expr, EvalError> {
         let expr = expr.into();
-        if let Expr::List(list) = expr {
-            match list.vals.front() {
-                Some(Expr::Macro(m)) => self.eval_macro(m.clone(), list),
-                Some(Expr::Symbol(s)) => {
-                    if let Ok(Expr::Macro(m)) = self.stack.lookup(&s.value) {
-                        let m = m.clone();
-                        self.eval_macro(m, list)
-                    } else {
-                        Ok(Expr::List(list))
+        Ok(self.try_expand_once(&expr)?.unwrap_or(expr))
+    }
+
+    /// Expands `expr` until it is no longer a macro call.
+    pub fn expand(&mut self, mut expr: Expr) -> Result<Expr, EvalError> {
+        while let Some(exp) = self.try_expand_once(&expr)? {
+            expr = exp;
+        }
+        Ok(expr)
+    }
+
+    /// Expands every macro call in `expr`, leaving quoted data, binding
+    /// names and patterns alone.
+    pub fn expand_all(&mut self, expr: Expr) -> Result<Expr, EvalError> {
+        let list = match self.expand(expr)? {
+            Expr::List(list) => list,
+            other => return Ok(other),
+        };
+        let special = match list.vals.front() {
+            Some(Expr::Special(s)) => Some(s.clone()),
+            Some(Expr::Symbol(s)) => match self.lookup_symbol(s) {
+                Ok(Expr::Special(s)) => Some(s.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        // how many operands to leave as they are
+        let keep = match special {
+            Some(Special::Quote(_)) | Some(Special::DefEnum(_)) | Some(Special::DefStruct(_))
+                | Some(Special::DefTrait(_)) => return Ok(Expr::List(list)),
+            Some(Special::Impl(_)) => return self.expand_impl(list),
+            Some(Special::Defn(_)) => {
+                let def = Defn::parse(&list)?.desugar(&list.meta);
+                return self.expand_all(def);
+            }
+            Some(Special::Quasiquote(_)) => {
+                let vals = list.vals.iter().map(|v| self.expand_unquoted(v.clone())).collect::<Result<_, _>>()?;
+                return Ok(Expr::List(List::new(vals, list.meta)));
+            }
+            Some(Special::Let(_)) => return self.expand_let(list),
+            Some(Special::BeginForSyntax(_)) => {
+                let mut vals = Vector::new();
+                for (i, val) in list.vals.into_iter().enumerate() {
+                    vals.push_back(if i == 0 { val } else {
+                        self.with_phase(Phase::Compile, |eval| eval.expand_all(val))?
+                    });
+                }
+                return Ok(Expr::List(List::new(vals, list.meta)));
+            }
+            Some(Special::Match(_)) => return self.expand_match(list),
+            Some(Special::Try(_)) => return self.expand_try(list),
+            Some(Special::Def(_)) | Some(Special::EvalWhen(_)) | Some(Special::Lambda(_))
+                | Some(Special::Macro(_)) | Some(Special::Shift(_)) => 2,
+            Some(_) => 1,
+            None => 0,
+        };
+        let mut vals = Vector::new();
+        for (i, val) in list.vals.into_iter().enumerate() {
+            vals.push_back(if i < keep { val } else { self.expand_all(val)? });
+        }
+        Ok(Expr::List(List::new(vals, list.meta)))
+    }
+
+    // within a quasiquote template, only what is unquoted is code
+    fn expand_unquoted(&mut self, template: Expr) -> Result<Expr, EvalError> {
+        if unquoted(&template).is_some() {
+            if let Expr::List(mut list) = template {
+                let code = list.vals.pop_back().unwrap();
+                list.vals.push_back(self.expand_all(code)?);
+                return Ok(Expr::List(list));
+            }
+        }
+        match template {
+            Expr::List(list) => {
+                let vals = list.vals.into_iter().map(|v| self.expand_unquoted(v)).collect::<Result<_, _>>()?;
+                Ok(Expr::List(List::new(vals, list.meta)))
+            }
+            other => Ok(other),
+        }
+    }
+
+    // the names in `(let (name value ..) body..)` are left alone
+    fn expand_let(&mut self, mut list: List) -> Result<Expr, EvalError> {
+        if let Some(Expr::List(bindings)) = list.vals.get(1).cloned() {
+            let mut vals = Vector::new();
+            for (i, val) in bindings.vals.into_iter().enumerate() {
+                vals.push_back(if i % 2 == 0 { val } else { self.expand_all(val)? });
+            }
+            list.vals.set(1, Expr::List(List::new(vals, bindings.meta)));
+        }
+        for i in 2..list.vals.len() {
+            let val = self.expand_all(list.vals[i].clone())?;
+            list.vals.set(i, val);
+        }
+        Ok(Expr::List(list))
+    }
+
+    // the patterns of `catch` clauses are left alone
+    fn expand_try(&mut self, list: List) -> Result<Expr, EvalError> {
+        let mut vals = Vector::new();
+        for (i, val) in list.vals.into_iter().enumerate() {
+            let keep = match try_clause(&val) {
+                _ if i == 0 => { vals.push_back(val); continue; }
+                Some("catch") => 2,
+                Some(_) => 1,
+                None => { vals.push_back(self.expand_all(val)?); continue; }
+            };
+            if let Expr::List(clause) = val {
+                let mut inner = Vector::new();
+                for (j, v) in clause.vals.into_iter().enumerate() {
+                    inner.push_back(if j < keep { v } else { self.expand_all(v)? });
+                }
+                vals.push_back(Expr::List(List::new(inner, clause.meta)));
+            }
+        }
+        Ok(Expr::List(List::new(vals, list.meta)))
+    }
+
+    // only the bodies of the methods of an `impl` are code
+    fn expand_impl(&mut self, mut list: List) -> Result<Expr, EvalError> {
+        for i in 2..list.vals.len() {
+            if let Expr::List(mut clause) = list.vals[i].clone() {
+                if data::bracketed(&list.vals[i]).is_some() {
+                    let body = self.expand_all(clause.vals[2].clone())?;
+                    clause.vals.set(2, body);
+                    list.vals.set(i, Expr::List(clause));
+                }
+            }
+        }
+        Ok(Expr::List(list))
+    }
+
+    // in `(match value pattern [::when guard] body ..)`, patterns are left alone
+    fn expand_match(&mut self, mut list: List) -> Result<Expr, EvalError> {
+        if list.vals.len() > 1 {
+            let val = self.expand_all(list.vals[1].clone())?;
+            list.vals.set(1, val);
+        }
+        let mut next = 2;
+        while next + 1 < list.vals.len() {
+            let mut code = vec![next + 1];
+            if let Expr::Keyword(k) = &list.vals[next + 1] {
+                if k.value == "when" {
+                    code = vec![next + 2, next + 3];
+                }
+            }
+            next = code[code.len() - 1] + 1;
+            for i in code {
+                if let Some(val) = list.vals.get(i).cloned() {
+                    let val = self.expand_all(val)?;
+                    list.vals.set(i, val);
+                }
+            }
+        }
+        Ok(Expr::List(list))
+    }
+
+    /// Applies a callable to already evaluated arguments. Functions
+    /// are curried, so each takes as many arguments as it wants and
+    /// whatever it returns is applied to the rest.
+    pub fn apply(&mut self, head: Expr, args: Vec<Expr>) -> Result<Expr, EvalError> {
+        self.run(Control::Apply(head, args, true, Meta::default()))
+    }
+
+    pub fn eval(&mut self, expr: impl Into<Expr>) -> Result<Expr, EvalError> {
+        let expr = expr.into();
+        if self.typecheck && self.runs.is_empty() {
+            // expanded once, so macros don't run twice
+            let expr = match self.expand_all(expr)? {
+                // a `def` of a lambda, which may take dictionaries
+                Expr::List(list) if list.vals.front().is_some_and(|head| infer::is_special(head, "defn")) =>
+                    Defn::parse(&list)?.desugar(&list.meta),
+                expr => expr,
+            };
+            let (_, elaboration) = self.check_elaborated(&expr)?;
+            let expr = traits::elaborate(&expr, &elaboration, &self.types).map_err(|e| EvalError::Type(Box::new(e)))?;
+            return self.run(Control::Eval(expr));
+        }
+        self.run(Control::Eval(expr))
+    }
+
+    /// Infers the type of a top level form, after macro expansion,
+    /// and records the type of what it defines.
+    pub fn check(&mut self, expr: &Expr) -> Result<Scheme, EvalError> {
+        let mut schemes = infer::check(std::slice::from_ref(expr), &mut self.types)
+            .map_err(|e| EvalError::Type(Box::new(e)))?;
+        Ok(schemes.remove(0))
+    }
+
+    // the type, and how to pass dictionaries in it
+    fn check_elaborated(&mut self, expr: &Expr) -> Result<(Scheme, Elaboration), EvalError> {
+        let (mut schemes, elaboration) = infer::check_elaborated(std::slice::from_ref(expr), &mut self.types)
+            .map_err(|e| EvalError::Type(Box::new(e)))?;
+        Ok((schemes.remove(0), elaboration))
+    }
+
+    /// The types of the definitions so far, and of the prelude.
+    pub fn types(&self) -> &TypeEnv {
+        &self.types
+    }
+
+    pub fn types_mut(&mut self) -> &mut TypeEnv {
+        &mut self.types
+    }
+
+    // Each run gets its own continuation. A native that calls back
+    // into the evaluator starts a fresh run on the rust stack, so
+    // captured continuations only reach as far back as the native.
+    fn run(&mut self, mut control: Control) -> Result<Expr, EvalError> {
+        if self.runs.is_empty() {
+            self.backtrace = None;
+            self.steps = 0;
+            self.allocated = 0;
+        }
+        let saved = self.stack.clone();
+        self.next_run += 1;
+        let id = self.next_run;
+        self.runs.push(id);
+        let mut kont = Vec::new();
+        let result = loop {
+            match self.run_machine(control, &mut kont) {
+                // a continuation of ours was invoked from beneath a native
+                Err(EvalError::Escape(k, val)) if k.run == id => {
+                    kont = k.frames;
+                    control = Control::Return(val);
+                }
+                other => break other,
+            }
+        };
+        self.runs.pop();
+        self.stack = saved;
+        result
+    }
+
+    fn run_machine(&mut self, mut control: Control, kont: &mut Vec<Frame>) -> Result<Expr, EvalError> {
+        // where we are, for errors that do not say
+        let mut span = None;
+        loop {
+            let step = if let Err(e) = self.tick(span) {
+                Err(e)
+            } else {
+                match control {
+                    Control::Eval(expr) => {
+                        span = expr.meta().and_then(|m| m.span).or(span);
+                        self.step(expr, kont)
+                    }
+                    Control::Apply(head, args, direct, meta) => {
+                        span = meta.span.or(span);
+                        self.step_apply(head, args, direct, meta, kont)
+                    }
+                    Control::Return(val) => match kont.pop() {
+                        Some(frame) => self.resume(frame, val, kont),
+                        None => return Ok(val),
+                    },
+                }
+            };
+            control = match step {
+                Ok(control) => control,
+                Err(e) => {
+                    // the first run to see an error is where it happened
+                    if self.backtrace.is_none() && !matches!(e, EvalError::Escape(..)) {
+                        self.backtrace = Some(self.capture_backtrace(e.span().or(span)));
                     }
+                    let control = self.unwind(e, span, kont)?;
+                    self.backtrace = None;
+                    control
                 }
-                _ => Ok(Expr::List(list)),
+            };
+        }
+    }
+
+    // pops frames up to a `try` that catches the error, or a `finally`
+    // that must run first. with neither, the error leaves this run.
+    fn unwind(&mut self, e: EvalError, span: Option<Span>, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        // continuations are not errors, and limits are final
+        if matches!(e, EvalError::Escape(..)) || e.is_limit() {
+            return Err(e);
+        }
+        while let Some(frame) = kont.pop() {
+            match frame {
+                Frame::Try { catches, env } => {
+                    let mut val = e.to_expr(span);
+                    if let (Expr::Map(m), false, Some(trace)) =
+                        (&mut val, matches!(e, EvalError::Thrown(_)), &self.backtrace) {
+                        let lines: Vector<Expr> = trace.to_string().lines().map(Expr::from).collect();
+                        m.vals.insert(Expr::Keyword(Keyword::from("backtrace")), Expr::List(List::from(lines)));
+                    }
+                    for clause in catches.iter() {
+                        if let Expr::List(clause) = clause {
+                            let mut bound = env.clone();
+                            let pattern = clause.vals.get(1).cloned().unwrap_or_default();
+                            if bind_pattern(&pattern, &val, &mut bound)? {
+                                self.stack = bound;
+                                return self.step_body(clause.vals.clone(), 2, kont);
+                            }
+                        }
+                    }
+                }
+                Frame::Finally { body, env } => {
+                    self.push(kont, Frame::Rethrow { error: Box::new(e) }, &Meta::default())?;
+                    self.stack = env;
+                    return self.step_body(body, 1, kont);
+                }
+                _ => (),
             }
+        }
+        Err(e)
+    }
+
+    fn push(&self, kont: &mut Vec<Frame>, frame: Frame, meta: &Meta) -> Result<(), EvalError> {
+        if self.depth + kont.len() >= self.max_depth() {
+            Err(EvalError::StackOverflow(meta.span))
         } else {
-            Ok(expr)
+            kont.push(frame);
+            Ok(())
         }
     }
 
-    pub fn expand(&mut self, expr: Expr) -> Result<Expr, EvalError> {
-        loop {
-            let exp = self.expand_once(expr.clone())?;
-            if expr == exp {
-                return Ok(exp);
+    fn step(&mut self, expr: Expr, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        match expr {
+            Expr::Symbol(sym) => Ok(Control::Return(self.lookup_symbol(&sym)?.clone())),
+            Expr::List(list) => self.step_list(list, kont),
+            Expr::Macro(m) => Err(EvalError::UnexpandedMacro(m)),
+            other => Ok(Control::Return(other)),
+        }
+    }
+
+    fn step_list(&mut self, list: List, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        let head = match list.vals.front() {
+            Some(head) => head.clone(),
+            // the empty list is data
+            None => return Ok(Control::Return(Expr::List(list))),
+        };
+        let resolved = match &head {
+            Expr::Symbol(s) => Some(self.lookup_symbol(s)?.clone()),
+            _ => None,
+        };
+        match (head, resolved) {
+            // for special forms, we do not perturb the expression at
+            // all, as if we were executing a compiler macro. We
+            // haven't needed this so far, we might relax it later
+            (Expr::Special(s), _) => self.step_special(s, list, kont),
+            (Expr::Symbol(sym), Some(Expr::Special(mut s))) => {
+                // the bound special carries no span, the symbol naming it does
+                s.set_meta(sym.meta);
+                self.step_special(s, list, kont)
             }
+            (Expr::Macro(m), _) | (_, Some(Expr::Macro(m))) => {
+                let meta = list.meta.clone();
+                Ok(Control::Eval(self.nested(kont, &meta, |eval| eval.eval_macro(m, list))?))
+            }
+            // a symbol head has already been looked up
+            (_, Some(val)) => self.step_call(list.vals, 1, vec![val], list.meta, kont),
+            (_, None) => self.step_call(list.vals, 0, Vec::new(), list.meta, kont),
         }
     }
 
-    // by this time, symbol resolution and special handling have already occured.
-    fn eval_call(&mut self, head: Expr, list: List) -> Result<Expr, EvalError> {
-        let mut l = list.vals.clone();
-        l.pop_front();
-        l.into_iter().try_fold(head, |callable, arg| {
-            if let Expr::Fun(f) = callable {
-                f.call(self.eval(arg)?, self)
-             } else {
-                Err(EvalError::NotCallable(callable, list.clone()))
+    // `exprs` is the whole call, of which the first `next` are already
+    // evaluated into `done`. We index rather than popping, as popping
+    // from a shared vector copies it.
+    fn step_call(
+        &mut self,
+        exprs: Vector<Expr>,
+        next: usize,
+        mut done: Vec<Expr>,
+        meta: Meta,
+        kont: &mut Vec<Frame>,
+    ) -> Result<Control, EvalError> {
+        match exprs.get(next).cloned() {
+            Some(expr) => {
+                let env = self.stack.clone();
+                let frame = Frame::Call { exprs, next: next + 1, done, env, meta: meta.clone() };
+                self.push(kont, frame, &meta)?;
+                Ok(Control::Eval(expr))
             }
-        })
+            None => {
+                let head = done.remove(0);
+                Ok(Control::Apply(head, done, true, meta))
+            }
+        }
     }
 
-    fn eval_lambda(&mut self, meta: Meta, list: List) -> Result<Expr, EvalError> {
-        let mut l = list.clone();
-        l.vals.pop_front();
-        match l.vals.pop_front() {
-            Some(Expr::Symbol(s)) =>
-                if let Some(body) = l.vals.pop_front() {
-                    if l.vals.is_empty() {
-                        Ok(Expr::Fun(Fun::new(Box::new(s), Box::new(body), meta)))
+    fn step_apply(
+        &mut self,
+        mut callable: Expr,
+        mut args: Vec<Expr>,
+        // a native may be called with no arguments, but only directly
+        mut direct: bool,
+        meta: Meta,
+        kont: &mut Vec<Frame>,
+    ) -> Result<Control, EvalError> {
+        loop {
+            callable = match callable {
+                Expr::Native(mut n) if direct || !args.is_empty() => {
+                    let take = n.arity.remaining(n.args.len())
+                        .map_or(args.len(), |r| r.min(args.len()));
+                    let rest = args.split_off(take);
+                    n.args.extend(args);
+                    args = rest;
+                    if n.arity.accepts(n.args.len()) {
+                        let calls = self.stack.calls.clone();
+                        // natives are never left by a tail call
+                        self.stack.calls = calls.enter(n.name.clone(), meta.span, NATIVE_DEPTH);
+                        let result = self.nested(kont, &meta, |eval| n.call(eval));
+                        self.stack.calls = calls;
+                        let result = result?;
+                        self.allocate(&result)?;
+                        result
                     } else {
-                        Err(EvalError::ExtraArguments(list))
+                        return Ok(Control::Return(Expr::Native(n)));
+                    }
+                }
+                Expr::Fun(f) if !args.is_empty() => {
+                    let arg = args.remove(0);
+                    if !args.is_empty() {
+                        self.push(kont, Frame::Apply { args, meta: meta.clone() }, &meta)?;
+                    }
+                    // the call itself leaves nothing behind: a call in
+                    // tail position does not grow the continuation.
+                    let depth = self.depth + kont.len();
+                    let mut env = f.env.clone();
+                    env.calls = self.stack.calls.call(f.describe(), meta.span, depth);
+                    env.assign(f.param.key(), arg);
+                    self.stack = env;
+                    return Ok(Control::Eval(*f.body));
+                }
+                Expr::Continuation(k) if !args.is_empty() => {
+                    let val = args.remove(0);
+                    return self.resume_continuation(k, val, args, meta, kont);
+                }
+                other if args.is_empty() => return Ok(Control::Return(other)),
+                other => return Err(EvalError::NotCallable(other, List::from(args.into_iter().collect::<Vector<_>>()))),
+            };
+            direct = false;
+        }
+    }
+
+    fn resume_continuation(
+        &mut self,
+        k: Continuation,
+        val: Expr,
+        args: Vec<Expr>,
+        meta: Meta,
+        kont: &mut Vec<Frame>,
+    ) -> Result<Control, EvalError> {
+        if k.composable {
+            // like a function call: the captured frames run inside a
+            // fresh reset and their result comes back to us
+            if !args.is_empty() {
+                self.push(kont, Frame::Apply { args, meta: meta.clone() }, &meta)?;
+            }
+            self.push(kont, Frame::Reset, &meta)?;
+            for frame in k.frames {
+                self.push(kont, frame, &meta)?;
+            }
+            return Ok(Control::Return(val));
+        }
+        if !args.is_empty() {
+            let mut vals: Vector<Expr> = args.into_iter().collect();
+            vals.push_front(val);
+            vals.push_front(Expr::Continuation(k));
+            return Err(EvalError::ExtraArguments(List::new(vals, meta)));
+        }
+        // if the run that captured it is beneath us, unwind to it.
+        // otherwise it has finished, and we take its frames over.
+        if self.runs.last() != Some(&k.run) && self.runs.contains(&k.run) {
+            return Err(EvalError::Escape(k, val));
+        }
+        if self.depth + k.frames.len() >= self.max_depth() {
+            return Err(EvalError::StackOverflow(meta.span));
+        }
+        *kont = k.frames;
+        Ok(Control::Return(val))
+    }
+
+    // runs `f`, which may start a run of its own, beneath the frames
+    // of this one.
+    fn nested<T>(
+        &mut self,
+        kont: &[Frame],
+        meta: &Meta,
+        f: impl FnOnce(&mut Eval) -> Result<T, EvalError>,
+    ) -> Result<T, EvalError> {
+        if self.nesting >= MAX_NESTING || self.depth + kont.len() >= self.max_depth() {
+            return Err(EvalError::StackOverflow(meta.span));
+        }
+        self.depth += kont.len();
+        self.nesting += 1;
+        let result = f(self);
+        self.nesting -= 1;
+        self.depth -= kont.len();
+        result
+    }
+
+    fn resume(&mut self, frame: Frame, val: Expr, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        match frame {
+            Frame::Apply { args, meta } => Ok(Control::Apply(val, args, false, meta)),
+            Frame::Call { exprs, next, mut done, env, meta } => {
+                done.push(val);
+                self.stack = env;
+                self.step_call(exprs, next, done, meta, kont)
+            }
+            Frame::Def { name } => {
+                let val = match val {
+                    Expr::Fun(mut f) if f.name.is_none() => {
+                        f.name = Some(name.value.clone());
+                        Expr::Fun(f)
                     }
+                    Expr::Macro(mut f) if f.name.is_none() => {
+                        f.name = Some(name.value.clone());
+                        Expr::Macro(f)
+                    }
+                    other => other,
+                };
+                self.define(name.key(), val);
+                Ok(Control::Return(Expr::Symbol(name)))
+            }
+            Frame::Do { body, next, env } => {
+                self.stack = env;
+                self.step_body(body, next, kont)
+            }
+            Frame::If { then, otherwise, env } => {
+                self.stack = env;
+                if val.is_truthy() {
+                    Ok(Control::Eval(then))
                 } else {
-                    Err(EvalError::MissingArguments(Expr::List(list), 1))
-                },
-            Some(other) => Err(EvalError::BadParameter("parameter", other)),
-            None => Err(EvalError::MissingArguments(Expr::List(list), 2)),
+                    Ok(otherwise.map_or(Control::Return(Expr::Nil), |e| Control::Eval(*e)))
+                }
+            }
+            Frame::Let { name, bindings, next, body, mut env, meta } => {
+                env.assign(name, val);
+                self.stack = env;
+                self.step_let(bindings, next, body, meta, kont)
+            }
+            Frame::CallCc { meta } => {
+                let frames = kont.clone();
+                let k = self.capture(frames, false, meta.clone())?;
+                Ok(Control::Apply(val, vec![k], false, meta))
+            }
+            Frame::Match { form, env, meta } => {
+                self.stack = env;
+                self.step_clauses(val, form, 2, meta, kont)
+            }
+            Frame::Guard { val: scrutinee, form, next, body, bound, env, meta } => {
+                if val.is_truthy() {
+                    self.stack = bound;
+                    Ok(Control::Eval(*body))
+                } else {
+                    self.stack = env;
+                    self.step_clauses(*scrutinee, form, next, meta, kont)
+                }
+            }
+            Frame::Reset => Ok(Control::Return(val)),
+            Frame::Try { .. } => Ok(Control::Return(val)),
+            Frame::Finally { body, env } => {
+                self.push(kont, Frame::Restore { val: Box::new(val) }, &Meta::default())?;
+                self.stack = env;
+                self.step_body(body, 1, kont)
+            }
+            Frame::Restore { val } => Ok(Control::Return(*val)),
+            Frame::Rethrow { error } => Err(*error),
+            Frame::The { typ, span } => {
+                if typ.admits(&val) {
+                    Ok(Control::Return(val))
+                } else {
+                    Err(EvalError::TypeMismatch(*typ, val, span))
+                }
+            }
         }
     }
 
-    fn eval_quasiquote(&mut self, list: List) -> Result<Expr, EvalError> {
-        // TODO
-        self.eval_quote(list)
+    fn step_special(&mut self, s: Special, list: List, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        match s {
+            Special::BeginForSyntax(_) => {
+                let meta = list.meta.clone();
+                self.nested(kont, &meta, |eval| eval.eval_for_syntax(list.vals.iter().skip(1)))?;
+                Ok(Control::Return(Expr::Nil))
+            }
+            Special::CallWithCurrentContinuation(_) => self.step_call_cc(list, kont),
+            Special::Def(_) => self.step_def(list, kont),
+            Special::Defn(_) => Ok(Control::Eval(Defn::parse(&list)?.desugar(&list.meta))),
+            Special::DefEnum(_) | Special::DefStruct(_) => self.eval_data(list).map(Control::Return),
+            Special::DefTrait(_) => {
+                let tr = Trait::parse(&list, &self.types).map_err(|e| EvalError::Type(Box::new(e)))?;
+                traits::define(self, &tr);
+                let name = Symbol::new(tr.name.clone(), list.meta);
+                self.types.declare_trait(tr);
+                Ok(Control::Return(Expr::Symbol(name)))
+            }
+            Special::Impl(_) => self.eval_impl(list, kont).map(Control::Return),
+            Special::Do(_) => self.step_body(list.vals, 1, kont),
+            Special::EvalWhen(_) => self.step_eval_when(list, kont),
+            Special::If(_) => self.step_if(list, kont),
+            Special::Lambda(meta) => self.eval_fun(meta, list).map(Expr::Fun).and_then(|f| self.allocated(f)),
+            Special::Macro(meta) => self.eval_fun(meta, list).map(Expr::Macro).and_then(|f| self.allocated(f)),
+            Special::Let(_) => self.step_let_form(list, kont),
+            Special::Match(_) => self.step_match(list, kont),
+            Special::Quasiquote(_) => match operands(&list).as_slice() {
+                [template] => Ok(Control::Eval(quasiquote(template)?)),
+                [] => Err(EvalError::MissingArguments(Expr::List(list), 1)),
+                _ => Err(EvalError::ExtraArguments(list)),
+            },
+            Special::Quote(_) => self.eval_quote(list).map(Control::Return),
+            Special::Reset(_) => {
+                self.push(kont, Frame::Reset, &list.meta)?;
+                self.step_body(list.vals, 1, kont)
+            }
+            Special::Shift(_) => self.step_shift(list, kont),
+            Special::The(_) => self.step_the(list, kont),
+            Special::Try(_) => self.step_try(list, kont),
+            Special::Unquote(_) | Special::UnquoteSplicing(_) =>
+                Err(EvalError::BadParameter("an enclosing quasiquote", Expr::List(list))),
+        }
     }
 
-    fn eval_quote(&mut self, list: List) -> Result<Expr, EvalError> {
-        let mut l = list.clone();
-        l.vals.pop_front();
-        if let Some(val) = l.vals.pop_front() {
-            if l.vals.is_empty() {
-                Ok(val)
-            } else {
-                Err(EvalError::ExtraArguments(list))
+    // evaluates top level forms for the compile phase
+    fn eval_for_syntax<'e>(&mut self, forms: impl Iterator<Item = &'e Expr>) -> Result<(), EvalError> {
+        // nor does it see the locals of the run phase
+        let locals = std::mem::take(&mut self.stack);
+        let result = self.with_phase(Phase::Compile, |eval| {
+            for form in forms {
+                eval.eval(form.clone())?;
             }
+            Ok(())
+        });
+        self.stack = locals;
+        result
+    }
+
+    // `(eval-when (::compile ::run) body...)` evaluates the body for
+    // the compile phase, the current one, or both, in that order.
+    fn step_eval_when(&mut self, list: List, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        let situations = match list.vals.get(1) {
+            Some(Expr::List(situations)) => situations.vals.clone(),
+            Some(other) => return Err(EvalError::BadParameter("list of situations", other.clone())),
+            None => return Err(EvalError::MissingArguments(Expr::List(list), 1)),
+        };
+        let (mut compile, mut run) = (false, false);
+        for situation in situations.iter() {
+            match situation {
+                Expr::Keyword(k) if k.value == "compile" => compile = true,
+                Expr::Keyword(k) if k.value == "run" => run = true,
+                other => return Err(EvalError::BadParameter("::compile or ::run", other.clone())),
+            }
+        }
+        if compile {
+            let meta = list.meta.clone();
+            self.nested(kont, &meta, |eval| eval.eval_for_syntax(list.vals.iter().skip(2)))?;
+        }
+        if run {
+            self.step_body(list.vals, 2, kont)
         } else {
-            Err(EvalError::MissingArguments(Expr::List(list), 1))
+            Ok(Control::Return(Expr::Nil))
         }
     }
 
-    fn eval_the(&mut self, list: List) -> Result<Expr, EvalError> {
-        let mut l = list.clone();
-        l.vals.pop_front();
-        if let Some(typ) = l.vals.pop_front() {
-            if let Some(val) = l.vals.pop_front() {
-                if l.vals.is_empty() {
-                    let _typ = self.eval(typ)?;
-                    let val = self.eval(val)?;
-                    Ok(val)
-                } else {
-                    Err(EvalError::ExtraArguments(list))
+    // `(try body.. (catch pattern handler..).. (finally cleanup..))`
+    fn step_try(&mut self, list: List, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        // the body keeps the head, so that it starts at 1 like a `do`
+        let mut body: Vector<Expr> = list.vals.iter().take(1).cloned().collect();
+        let mut catches = Vector::new();
+        let mut finally = None;
+        for val in list.vals.iter().skip(1) {
+            let clause = match val {
+                Expr::List(clause) => clause.vals.clone(),
+                _ => Vector::new(),
+            };
+            match (try_clause(val), &finally) {
+                (_, Some(_)) => return Err(EvalError::BadParameter("finally to come last", val.clone())),
+                (Some("catch"), None) if clause.len() < 2 =>
+                    return Err(EvalError::MissingArguments(val.clone(), 1)),
+                (Some("catch"), None) => catches.push_back(val.clone()),
+                (Some(_), None) => finally = Some(clause),
+                (None, None) if catches.is_empty() => body.push_back(val.clone()),
+                (None, None) => return Err(EvalError::BadParameter("catch or finally", val.clone())),
+            }
+        }
+        let env = self.stack.clone();
+        if let Some(body) = finally {
+            self.push(kont, Frame::Finally { body, env: env.clone() }, &list.meta)?;
+        }
+        if !catches.is_empty() {
+            self.push(kont, Frame::Try { catches, env }, &list.meta)?;
+        }
+        self.step_body(body, 1, kont)
+    }
+
+    fn capture(&mut self, frames: Vec<Frame>, composable: bool, meta: Meta) -> Result<Expr, EvalError> {
+        let run = self.runs.last().copied().unwrap_or_default();
+        let k = Expr::Continuation(Continuation { frames, run, composable, meta });
+        self.allocate(&k)?;
+        Ok(k)
+    }
+
+    // `(call/cc f)` calls `f` with the continuation of the form
+    fn step_call_cc(&mut self, list: List, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        match operands(&list).as_slice() {
+            [f] => {
+                self.push(kont, Frame::CallCc { meta: list.meta.clone() }, &list.meta)?;
+                Ok(Control::Eval(f.clone()))
+            }
+            [] => Err(EvalError::MissingArguments(Expr::List(list), 1)),
+            _ => Err(EvalError::ExtraArguments(list)),
+        }
+    }
+
+    // `(shift k body...)` removes the frames up to the nearest `reset`
+    // and runs the body with them bound to `k`. The body's value is
+    // returned from the `reset`.
+    fn step_shift(&mut self, list: List, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        let name = match list.vals.get(1) {
+            Some(Expr::Symbol(name)) => name.key(),
+            Some(other) => return Err(EvalError::BadParameter("name", other.clone())),
+            None => return Err(EvalError::MissingArguments(Expr::List(list), 1)),
+        };
+        let reset = kont.iter().rposition(|f| matches!(f, Frame::Reset))
+            .ok_or(EvalError::ShiftWithoutReset(list.meta.span))?;
+        let frames = kont.split_off(reset + 1);
+        let k = self.capture(frames, true, list.meta.clone())?;
+        self.stack.assign(name, k);
+        self.step_body(list.vals, 2, kont)
+    }
+
+    fn step_def(&mut self, list: List, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        match operands(&list).as_slice() {
+            [Expr::Symbol(name), val] => {
+                self.push(kont, Frame::Def { name: name.clone() }, &list.meta)?;
+                Ok(Control::Eval(val.clone()))
+            }
+            [Expr::Symbol(_)] => Err(EvalError::MissingArguments(Expr::List(list), 1)),
+            [] => Err(EvalError::MissingArguments(Expr::List(list), 2)),
+            [other, ..] if !matches!(other, Expr::Symbol(_)) =>
+                Err(EvalError::BadParameter("name", other.clone())),
+            _ => Err(EvalError::ExtraArguments(list)),
+        }
+    }
+
+    // declares the type, for the checker as well, whether it is on or not
+    fn eval_data(&mut self, list: List) -> Result<Expr, EvalError> {
+        let data = DataType::parse(&list, &self.types).map_err(|e| EvalError::Type(Box::new(e)))?;
+        data::define(self, &data);
+        let name = Symbol::new(data.name.clone(), list.meta);
+        self.types.declare(data);
+        Ok(Expr::Symbol(name))
+    }
+
+    // the methods are evaluated before anything is added, so an impl
+    // that fails is not half made. Each takes a dictionary for each
+    // constraint of the impl.
+    fn eval_impl(&mut self, list: List, kont: &[Frame]) -> Result<Expr, EvalError> {
+        let (instance, bodies) = Instance::parse(&list, &self.types).map_err(|e| EvalError::Type(Box::new(e)))?;
+        let meta = list.meta.clone();
+        let mut methods = HashMap::new();
+        for (name, body) in bodies {
+            let body = instance.constraints.iter().rev().fold(body, |body, constraint| {
+                let param = Expr::Symbol(Symbol::new(traits::parameter(constraint), Meta::default()));
+                let vals = vec![Expr::Special(Special::Lambda(Meta::default())), param, body];
+                Expr::List(List::new(vals.into_iter().collect(), meta.clone()))
+            });
+            let val = self.nested(kont, &meta, |eval| eval.run(Control::Eval(body)))?;
+            methods.insert(name, val);
+        }
+        let implementation = Implementation { typ: instance.head.typ.clone(), methods };
+        let trait_name = instance.head.trait_name.clone();
+        self.types.add_instance(instance).map_err(|e| EvalError::Type(Box::new(e)))?;
+        let impls = self.implementations.entry(trait_name).or_default();
+        match impls.iter().position(|imp| imp.typ == implementation.typ) {
+            Some(i) => { impls.set(i, implementation); }
+            None => impls.push_back(implementation),
+        }
+        Ok(Expr::Nil)
+    }
+
+    /// The dictionary of the impl meeting `constraint`: the methods of
+    /// its trait, in order, each given the dictionaries the impl's own
+    /// constraints want. Where the type is a variable, as the
+    /// parameters of a value's type are, the methods call the impl for
+    /// their arguments' types instead. None where no impl meets it.
+    pub fn dictionary(&mut self, constraint: &Constraint) -> Result<Option<Expr>, EvalError> {
+        let (head, wanted) = match self.types.instance(constraint) {
+            Some((instance, wanted)) => (instance.head.clone(), wanted),
+            None if matches!(constraint.typ, Type::Var(_)) =>
+                return Ok(self.types.get_trait(&constraint.trait_name).map(traits::dispatching_dictionary)),
+            None => return Ok(None),
+        };
+        let mut dictionaries = Vec::new();
+        for constraint in &wanted {
+            match self.dictionary(constraint)? {
+                Some(dictionary) => dictionaries.push(dictionary),
+                None => return Ok(None),
+            }
+        }
+        self.dictionary_of(&head, dictionaries).map(Some)
+    }
+
+    /// The dictionary of the impl for the type of `head`, given those
+    /// its constraints want.
+    pub fn dictionary_of(&mut self, head: &Constraint, dictionaries: Vec<Expr>) -> Result<Expr, EvalError> {
+        let missing = || EvalError::NoImplementation(head.trait_name.clone(), Expr::from(head.typ.to_string()));
+        let tr = self.types.get_trait(&head.trait_name).cloned().ok_or_else(missing)?;
+        let imp = self.implementations.get(&head.trait_name).into_iter().flatten()
+            .find(|imp| imp.typ == head.typ).cloned().ok_or_else(missing)?;
+        let mut fields = Vector::new();
+        for method in &tr.methods {
+            let fun = imp.methods.get(&method.name).cloned().ok_or_else(missing)?;
+            fields.push_back(match dictionaries.is_empty() {
+                true => fun,
+                false => self.apply(fun, dictionaries.clone())?,
+            });
+        }
+        Ok(Expr::Data(Data::new(tr.name.clone(), head.to_string(), fields, Meta::default())))
+    }
+
+    // evaluates `body` from `next` onwards. the last expression of a
+    // body is in tail position.
+    fn step_body(&mut self, body: Vector<Expr>, next: usize, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        match body.get(next).cloned() {
+            Some(expr) => {
+                if next + 1 < body.len() {
+                    let meta = expr.meta().cloned().unwrap_or_default();
+                    let env = self.stack.clone();
+                    self.push(kont, Frame::Do { body, next: next + 1, env }, &meta)?;
                 }
-            } else {
-                Err(EvalError::MissingArguments(Expr::List(list), 1))
+                Ok(Control::Eval(expr))
             }
-        } else {
-            Err(EvalError::MissingArguments(Expr::List(list), 2))
+            None => Ok(Control::Return(Expr::Nil)),
         }
     }
 
-    fn eval_unquote(&mut self, _list: List) -> Result<Expr, EvalError> {
-        unimplemented!();
+    fn step_if(&mut self, list: List, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        let (cond, then, otherwise) = match operands(&list).as_slice() {
+            [cond, then] => (cond.clone(), then.clone(), None),
+            [cond, then, otherwise] => (cond.clone(), then.clone(), Some(Box::new(otherwise.clone()))),
+            [_] => return Err(EvalError::MissingArguments(Expr::List(list), 1)),
+            [] => return Err(EvalError::MissingArguments(Expr::List(list), 2)),
+            _ => return Err(EvalError::ExtraArguments(list)),
+        };
+        let env = self.stack.clone();
+        self.push(kont, Frame::If { then, otherwise, env }, &list.meta)?;
+        Ok(Control::Eval(cond))
     }
- 
-    fn eval_special_call(&mut self, s: Special, list: List) -> Result<Expr, EvalError> {
-        match s {
-            Special::Lambda(meta) => self.eval_lambda(meta, list),
-            // Special::Match(meta) => { unimplemented!(); }
-            Special::Quasiquote(_) => self.eval_quasiquote(list),
-            Special::Quote(_) => self.eval_quote(list),
-            Special::The(_) => self.eval_the(list),
-            Special::Unquote(_) => self.eval_unquote(list),
+
+    fn step_let_form(&mut self, list: List, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        match list.vals.get(1).cloned() {
+            Some(Expr::List(bindings)) => {
+                if bindings.vals.len() % 2 != 0 {
+                    return Err(EvalError::BadParameter("name value pairs", Expr::List(bindings)));
+                }
+                self.step_let(bindings.vals, 0, list.vals, list.meta, kont)
+            }
+            Some(other) => Err(EvalError::BadParameter("bindings", other)),
+            None => Err(EvalError::MissingArguments(Expr::List(list), 1)),
         }
     }
 
-    fn eval_list_sym(&mut self, expr: Expr, mut list: List) -> Result<Expr, EvalError> {
-        if let Expr::Special(s) = expr {
-            self.eval_special_call(s, list) 
-        } else {
-            list.vals.pop_front();
-            self.eval_call(expr, list)
+    // binds each name in turn, in an environment that already has
+    // the previous ones, then runs the body in tail position. `body`
+    // is the whole `let` form, whose body starts at index 2.
+    fn step_let(
+        &mut self,
+        bindings: Vector<Expr>,
+        next: usize,
+        body: Vector<Expr>,
+        meta: Meta,
+        kont: &mut Vec<Frame>,
+    ) -> Result<Control, EvalError> {
+        match (bindings.get(next).cloned(), bindings.get(next + 1).cloned()) {
+            (Some(Expr::Symbol(name)), Some(val)) => {
+                let env = self.stack.clone();
+                let frame = Frame::Let {
+                    name: name.key(), bindings, next: next + 2, body, env, meta: meta.clone()
+                };
+                self.push(kont, frame, &meta)?;
+                Ok(Control::Eval(val))
+            }
+            (Some(other), Some(_)) => Err(EvalError::BadParameter("name", other)),
+            _ => self.step_body(body, 2, kont),
         }
     }
 
-    fn eval_list(&mut self, mut list: List) -> Result<Expr, EvalError> {
-        match list.vals.front() {
-            // for special forms, we do not perturb the expression at
-            // all, as if we were executing a compiler macro. We
-            // haven't needed this so far, we might relax it later
-            Some(Expr::Special(s)) => self.eval_special_call(s.clone(), list),
-            Some(Expr::Symbol(s)) => {
-                let val = self.stack.lookup(&s.value)?.clone();
-                self.eval_list_sym(val, list)
+    fn step_match(&mut self, list: List, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        match list.vals.get(1).cloned() {
+            Some(scrutinee) => {
+                let frame = Frame::Match { form: list.vals, env: self.stack.clone(), meta: list.meta.clone() };
+                self.push(kont, frame, &list.meta)?;
+                Ok(Control::Eval(scrutinee))
             }
-            Some(other) => {
-                let other = other.clone();
-                list.vals.pop_front();
-                self.eval_call(other, list)
+            None => Err(EvalError::MissingArguments(Expr::List(list), 1)),
+        }
+    }
+
+    // tries the clauses of the `match` form `form` from `next` onwards.
+    // a clause is a pattern, optionally `::when` and a guard, then a body.
+    fn step_clauses(
+        &mut self,
+        val: Expr,
+        form: Vector<Expr>,
+        mut next: usize,
+        meta: Meta,
+        kont: &mut Vec<Frame>,
+    ) -> Result<Control, EvalError> {
+        while let Some(pattern) = form.get(next) {
+            let guard = match form.get(next + 1) {
+                Some(Expr::Keyword(k)) if k.value == "when" => {
+                    next += 2;
+                    Some(form.get(next).cloned().ok_or_else(|| missing_clause(&form, &meta))?)
+                }
+                _ => None,
+            };
+            let body = form.get(next + 1).cloned().ok_or_else(|| missing_clause(&form, &meta))?;
+            let mut bound = self.stack.clone();
+            if !bind_pattern(pattern, &val, &mut bound)? {
+                next += 2;
+                continue;
             }
-            // the empty list is data
-            None => Ok(Expr::List(list))
+            return match guard {
+                Some(guard) => {
+                    let env = self.stack.clone();
+                    let frame = Frame::Guard {
+                        val: Box::new(val), form, next: next + 2, body: Box::new(body),
+                        bound: bound.clone(), env, meta: meta.clone(),
+                    };
+                    self.push(kont, frame, &meta)?;
+                    self.stack = bound;
+                    Ok(Control::Eval(guard))
+                }
+                None => {
+                    self.stack = bound;
+                    Ok(Control::Eval(body))
+                }
+            };
         }
+        Err(EvalError::NonExhaustiveMatch(val, meta.span))
     }
 
-    pub fn eval(&mut self, expr: impl Into<Expr>) -> Result<Expr, EvalError> {
-        let expr = self.expand(expr.into())?;
-        match expr {
-            Expr::Nil => Ok(expr),
-            Expr::Int(_) => Ok(expr),
-            // Expr::Float(_) => Ok(expr),
-            // Expr::String(string) => Ok(expr),
-            Expr::Symbol(sym) => self.stack.lookup(&sym.value).map(|e| e.clone()),
-            Expr::Special(_) => Ok(expr),
-            Expr::List(list) => self.eval_list(list),
-            Expr::Map(_) => Ok(expr),
-            Expr::Fun(_) => Ok(expr),
-            Expr::Macro(m) => Err(EvalError::UnexpandedMacro(m)),
-         }
+    // a `lambda` or a `macro`, which differ only in how they are called
+    fn eval_fun(&mut self, meta: Meta, list: List) -> Result<Fun, EvalError> {
+        match operands(&list).as_slice() {
+            [Expr::Symbol(s), body] => {
+                let (s, body) = (Box::new(s.clone()), Box::new(body.clone()));
+                Ok(Fun::new(s, body, self.stack.clone(), meta))
+            }
+            [Expr::Symbol(_)] => Err(EvalError::MissingArguments(Expr::List(list), 1)),
+            [] => Err(EvalError::MissingArguments(Expr::List(list), 2)),
+            [other, ..] if !matches!(other, Expr::Symbol(_)) =>
+                Err(EvalError::BadParameter("parameter", other.clone())),
+            _ => Err(EvalError::ExtraArguments(list)),
+        }
+    }
+
+    fn eval_quote(&mut self, list: List) -> Result<Expr, EvalError> {
+        match operands(&list).as_slice() {
+            [val] => Ok(val.clone()),
+            [] => Err(EvalError::MissingArguments(Expr::List(list), 1)),
+            _ => Err(EvalError::ExtraArguments(list)),
+        }
+    }
+
+    fn step_the(&mut self, list: List, kont: &mut Vec<Frame>) -> Result<Control, EvalError> {
+        match operands(&list).as_slice() {
+            [_, val] if self.ascriptions == Ascriptions::Erased => Ok(Control::Eval(val.clone())),
+            [typ, val] => {
+                let typ = self.types.parse(typ).map_err(|e| EvalError::Type(Box::new(e)))?;
+                let span = list.vals[0].meta().and_then(|m| m.span).or(list.meta.span);
+                self.push(kont, Frame::The { typ: Box::new(typ), span }, &list.meta)?;
+                Ok(Control::Eval(val.clone()))
+            }
+            [_] => Err(EvalError::MissingArguments(Expr::List(list), 1)),
+            [] => Err(EvalError::MissingArguments(Expr::List(list), 2)),
+            _ => Err(EvalError::ExtraArguments(list)),
+        }
+    }
+}
+
+/// Rewrites a quasiquoted template into the expression that builds
+/// it, so the unquoted parts are evaluated like any other. The list
+/// building natives are inlined so that this works without the
+/// prelude and whatever the user has bound to `list`.
+fn quasiquote(template: &Expr) -> Result<Expr, EvalError> {
+    match template {
+        Expr::Symbol(_) => Ok(quoted(template.clone())),
+        Expr::List(list) if list.vals.is_empty() => Ok(quoted(template.clone())),
+        Expr::List(list) => match unquoted(template) {
+            Some((false, expr)) => Ok(expr),
+            Some((true, _)) =>
+                Err(EvalError::BadParameter("unquote-splicing inside a list", template.clone())),
+            None => {
+                let mut parts = Vector::new();
+                parts.push_back(builder("concat", concat));
+                for val in list.vals.iter() {
+                    match unquoted(val) {
+                        Some((true, expr)) => parts.push_back(expr),
+                        _ => {
+                            let part = vec![builder("list", list_of), quasiquote(val)?];
+                            parts.push_back(Expr::List(List::from(part.into_iter().collect::<Vector<_>>())));
+                        }
+                    }
+                }
+                Ok(Expr::List(List::new(parts, list.meta.clone())))
+            }
+        },
+        other => Ok(other.clone()),
+    }
+}
+
+// `Some((splicing, expr))` if `val` is `~expr` or `~@expr`
+fn unquoted(val: &Expr) -> Option<(bool, Expr)> {
+    let list = match val {
+        Expr::List(list) if list.vals.len() == 2 => list,
+        _ => return None,
+    };
+    let splicing = match &list.vals[0] {
+        Expr::Special(Special::Unquote(_)) => false,
+        Expr::Special(Special::UnquoteSplicing(_)) => true,
+        Expr::Symbol(s) if s.value == "unquote" => false,
+        Expr::Symbol(s) if s.value == "unquote-splicing" => true,
+        _ => return None,
+    };
+    Some((splicing, list.vals[1].clone()))
+}
+
+fn quoted(val: Expr) -> Expr {
+    let vals: Vector<Expr> = vec![Expr::Special(Special::Quote(Meta::default())), val].into_iter().collect();
+    Expr::List(List::from(vals))
+}
+
+fn builder(name: &str, fun: fn(&mut Eval, &[Expr]) -> Result<Expr, EvalError>) -> Expr {
+    Expr::Native(Native::new(name, Arity::AtLeast(0), Rc::new(fun), Meta::default()))
+}
+
+fn list_of(_: &mut Eval, args: &[Expr]) -> Result<Expr, EvalError> {
+    Ok(Expr::List(List::from(args.iter().cloned().collect::<Vector<_>>())))
+}
+
+fn concat(_: &mut Eval, args: &[Expr]) -> Result<Expr, EvalError> {
+    let mut vals = Vector::new();
+    for arg in args {
+        match arg {
+            Expr::Nil => (),
+            Expr::List(l) => vals.append(l.vals.clone()),
+            other => return Err(EvalError::BadParameter("list to splice", other.clone())),
+        }
+    }
+    Ok(Expr::List(List::from(vals)))
+}
+
+// `catch` or `finally`, if `val` is one of the clauses of a `try`
+fn try_clause(val: &Expr) -> Option<&'static str> {
+    match val {
+        Expr::List(list) => match list.vals.front() {
+            Some(Expr::Symbol(s)) if s.value == "catch" => Some("catch"),
+            Some(Expr::Symbol(s)) if s.value == "finally" => Some("finally"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn missing_clause(form: &Vector<Expr>, meta: &Meta) -> EvalError {
+    EvalError::MissingArguments(Expr::List(List::new(form.clone(), meta.clone())), 1)
+}
+
+/// The parts of `(defn name [param..] body..)`. A parameter is a name,
+/// or `[name type]` for one whose type is given.
+pub struct Defn<'a> {
+    pub name: &'a Symbol,
+    pub params: Vec<(&'a Symbol, Option<&'a Expr>)>,
+    pub body: Vec<&'a Expr>,
+}
+
+impl<'a> Defn<'a> {
+    pub fn parse(list: &'a List) -> Result<Defn<'a>, EvalError> {
+        let bad = |what, expr: &Expr| EvalError::BadParameter(what, expr.clone());
+        let vals: Vec<&Expr> = list.vals.iter().collect();
+        let (name, params, body) = match vals.as_slice() {
+            [_, Expr::Symbol(name), Expr::List(params), body @ ..] if !body.is_empty() => (name, params, body),
+            [_, Expr::Symbol(_), params, _, ..] => return Err(bad("parameters", params)),
+            [_, name, _, _, ..] => return Err(bad("a name", name)),
+            _ => return Err(EvalError::MissingArguments(Expr::List(list.clone()), 4 - vals.len())),
+        };
+        let params = match params.vals.iter().collect::<Vec<_>>().as_slice() {
+            [Expr::Symbol(head), params @ ..] if head.value == "list" && !params.is_empty() => params.iter()
+                .map(|param| match param {
+                    Expr::Symbol(name) => Ok((name, None)),
+                    _ => data::bracketed(param).map(|(name, typ)| (name, Some(typ))).ok_or_else(|| bad("a parameter", param)),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err(bad("parameters", &Expr::List(params.clone()))),
+        };
+        Ok(Defn { name, params, body: body.to_vec() })
+    }
+
+    /// The `def` of curried lambdas it stands for. The value of each
+    /// typed parameter is checked with `the` before the body runs.
+    pub fn desugar(&self, meta: &Meta) -> Expr {
+        let special = |s: Special| Expr::Special(s);
+        let form = |vals: Vec<Expr>| Expr::List(List::new(vals.into_iter().collect(), meta.clone()));
+        let mut body = vec![special(Special::Do(Meta::default()))];
+        for (name, typ) in &self.params {
+            if let Some(typ) = typ {
+                body.push(form(vec![special(Special::The(Meta::default())), (*typ).clone(), Expr::Symbol((*name).clone())]));
+            }
+        }
+        body.extend(self.body.iter().map(|expr| (*expr).clone()));
+        let fun = self.params.iter().rev().fold(form(body), |body, (name, _)| {
+            form(vec![special(Special::Lambda(Meta::default())), Expr::Symbol((*name).clone()), body])
+        });
+        form(vec![special(Special::Def(Meta::default())), Expr::Symbol(self.name.clone()), fun])
+    }
+}
+
+/// Matches `val` against `pattern`, binding into `env`. Symbols bind,
+/// except `_`, which matches anything, and `nil`, `true` and `false`.
+/// Lists match lists, with `&` before a pattern for the rest. Since
+/// `[..]` and `{..}` read as calls to `list` and `hash-map`, those
+/// heads make list and map patterns, and `'x` matches `x` as it is.
+/// `(Variant p..)` matches a value of that variant of a declared type,
+/// field by field, so a variant without fields is matched by `(Variant)`.
+pub fn bind_pattern(pattern: &Expr, val: &Expr, env: &mut Stack) -> Result<bool, EvalError> {
+    match pattern {
+        Expr::Symbol(s) => match s.value.as_str() {
+            "_" => Ok(true),
+            "nil" => Ok(matches!(val, Expr::Nil)),
+            "true" => Ok(*val == Expr::from(true)),
+            "false" => Ok(*val == Expr::from(false)),
+            "&" => Err(EvalError::BadParameter("pattern", pattern.clone())),
+            _ => {
+                env.assign(s.key(), val.clone());
+                Ok(true)
+            }
+        },
+        Expr::Nil | Expr::Bool(_) | Expr::Int(_) | Expr::String(_) | Expr::Keyword(_) =>
+            Ok(pattern == val),
+        Expr::List(list) => {
+            let head = list.vals.front();
+            match head {
+                Some(Expr::Special(Special::Quote(_))) => quoted_pattern(list, val),
+                Some(Expr::Symbol(s)) if s.value == "quote" => quoted_pattern(list, val),
+                Some(Expr::Symbol(s)) if s.value == "list" =>
+                    bind_list(list.vals.iter().skip(1).collect(), val, env),
+                Some(Expr::Symbol(s)) if s.value == "hash-map" => bind_map(list, val, env),
+                Some(Expr::Symbol(s)) => match val {
+                    Expr::Data(d) if d.variant == s.value => {
+                        let fields = Expr::List(List::from(d.fields.clone()));
+                        bind_list(list.vals.iter().skip(1).collect(), &fields, env)
+                    }
+                    Expr::Data(_) => Ok(false),
+                    _ => bind_list(list.vals.iter().collect(), val, env),
+                },
+                _ => bind_list(list.vals.iter().collect(), val, env),
+            }
+        }
+        other => Err(EvalError::BadParameter("pattern", other.clone())),
+    }
+}
+
+fn quoted_pattern(list: &List, val: &Expr) -> Result<bool, EvalError> {
+    match operands(list).as_slice() {
+        [quoted] => Ok(quoted == val),
+        _ => Err(EvalError::BadParameter("pattern", Expr::List(list.clone()))),
     }
 }
 
-#[derive(Clone, Default, Eq, Hash, PartialEq)]
+fn bind_list(patterns: Vec<&Expr>, val: &Expr, env: &mut Stack) -> Result<bool, EvalError> {
+    let is_rest = |p: &&Expr| matches!(p, Expr::Symbol(s) if s.value == "&");
+    let (fixed, rest) = match patterns.iter().position(is_rest) {
+        Some(i) if i + 2 == patterns.len() => (&patterns[..i], Some(patterns[i + 1])),
+        Some(i) => return Err(EvalError::BadParameter("one pattern after &", patterns[i].clone())),
+        None => (&patterns[..], None),
+    };
+    let vals = match val {
+        Expr::List(l) => &l.vals,
+        _ => return Ok(false),
+    };
+    let fits = match rest {
+        Some(_) => vals.len() >= fixed.len(),
+        None => vals.len() == fixed.len(),
+    };
+    if !fits {
+        return Ok(false);
+    }
+    for (pattern, val) in fixed.iter().zip(vals.iter()) {
+        if !bind_pattern(pattern, val, env)? {
+            return Ok(false);
+        }
+    }
+    match rest {
+        Some(pattern) => {
+            let rest = vals.clone().split_off(fixed.len());
+            bind_pattern(pattern, &Expr::List(List::from(rest)), env)
+        }
+        None => Ok(true),
+    }
+}
+
+// keys are taken as they are written, values are patterns. the map
+// may have other keys too.
+fn bind_map(list: &List, val: &Expr, env: &mut Stack) -> Result<bool, EvalError> {
+    let pairs = operands(list);
+    if !pairs.len().is_multiple_of(2) {
+        return Err(EvalError::BadParameter("key pattern pairs", Expr::List(list.clone())));
+    }
+    let map = match val {
+        Expr::Map(m) => m,
+        _ => return Ok(false),
+    };
+    for pair in pairs.chunks(2) {
+        match map.vals.get(&pair[0]) {
+            Some(v) if bind_pattern(&pair[1], v, env)? => (),
+            _ => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+// the arguments of a special form, without its head
+fn operands(list: &List) -> Vec<Expr> {
+    list.vals.iter().skip(1).cloned().collect()
+}
+
+/// Work left to do once the value being computed is known. Rather
+/// than recursing on the rust stack, the evaluator keeps these on a
+/// stack of its own, so calls in tail position run in constant space.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Frame {
+    /// Apply the value to the remaining arguments.
+    Apply { args: Vec<Expr>, meta: Meta },
+    /// Evaluating a call: `done` holds the values of `exprs` before `next`.
+    Call { exprs: Vector<Expr>, next: usize, done: Vec<Expr>, env: Stack, meta: Meta },
+    /// Call the value with the continuation of the `call/cc`.
+    CallCc { meta: Meta },
+    Def { name: Symbol },
+    /// Evaluating a body, from `next` onwards.
+    Do { body: Vector<Expr>, next: usize, env: Stack },
+    If { then: Expr, otherwise: Option<Box<Expr>>, env: Stack },
+    /// The value is bound to `name`, then the bindings from `next`.
+    Let { name: String, bindings: Vector<Expr>, next: usize, body: Vector<Expr>, env: Stack, meta: Meta },
+    /// The value of a `match` form is known, now try its clauses.
+    Match { form: Vector<Expr>, env: Stack, meta: Meta },
+    /// A clause matched with the `bound` environment, if its guard holds.
+    /// Otherwise, try the clauses from `next` in `env`.
+    Guard { val: Box<Expr>, form: Vector<Expr>, next: usize, body: Box<Expr>, bound: Stack, env: Stack, meta: Meta },
+    /// Catches errors with the first of the `catch` clauses that matches.
+    Try { catches: Vector<Expr>, env: Stack },
+    /// Runs the cleanup body, whether or not there was an error.
+    Finally { body: Vector<Expr>, env: Stack },
+    /// The cleanup ran, now return the value from before it.
+    Restore { val: Box<Expr> },
+    /// The cleanup ran, now carry on with the error from before it.
+    Rethrow { error: Box<EvalError> },
+    /// Delimits the continuation captured by `shift`.
+    Reset,
+    /// The value must be of the type, as `the` at `span` says.
+    The { typ: Box<Type>, span: Option<Span> },
+}
+
+enum Control {
+    Eval(Expr),
+    Return(Expr),
+    /// Apply a callable to arguments. The flag is whether it is being
+    /// called directly, which lets a native take no arguments.
+    Apply(Expr, Vec<Expr>, bool, Meta),
+}
+
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 pub struct Stack {
     current: HashMap<String, Expr>,
     previous: Vector<HashMap<String, Expr>>,
+    // travels with the environment, so frames restore it too
+    pub(crate) calls: Calls,
+}
+
+// the depth the record of a native's call has, which no tail call is
+// made at, so it stays until the native returns
+const NATIVE_DEPTH: usize = usize::MAX;
+
+#[derive(Debug)]
+pub(crate) struct CallRecord {
+    name: String,
+    // where it was called from
+    site: Option<Span>,
+    // how many frames were pending when it was called
+    depth: usize,
+    parent: Option<Rc<CallRecord>>,
+}
+
+/// The calls in progress, innermost first. Like `Meta`, they are not
+/// part of the value of an environment.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Calls(Option<Rc<CallRecord>>);
+
+impl PartialEq for Calls {
+    fn eq(&self, _other: &Calls) -> bool {
+        true
+    }
+}
+
+impl Eq for Calls {}
+
+impl std::hash::Hash for Calls {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+impl Calls {
+    fn enter(&self, name: String, site: Option<Span>, depth: usize) -> Calls {
+        Calls(Some(Rc::new(CallRecord { name, site, depth, parent: self.0.clone() })))
+    }
+
+    // a call made with nothing left to do in the current one replaces
+    // it, so loops written as tail calls don't pile up records, and
+    // returns to where the current one would have
+    fn call(&self, name: String, site: Option<Span>, depth: usize) -> Calls {
+        // calls made deeper than this one have returned, though those
+        // of natives are only left when the native returns
+        let mut current = &self.0;
+        while let Some(record) = current.as_ref().filter(|r| r.depth > depth && r.depth != NATIVE_DEPTH) {
+            current = &record.parent;
+        }
+        match current {
+            Some(record) if record.depth == depth =>
+                Calls(record.parent.clone()).enter(name, record.site, depth),
+            _ => Calls(current.clone()).enter(name, site, depth),
+        }
+    }
+}
+
+/// The calls in progress when an error happened, innermost first,
+/// each with where it had got to.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Backtrace {
+    pub frames: Vec<TraceFrame>,
+    /// How many outer calls were left out.
+    pub omitted: usize,
+    pub file: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceFrame {
+    pub name: String,
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, frame) in self.frames.iter().enumerate() {
+            if i > 0 { writeln!(f)?; }
+            match (&self.file, frame.span) {
+                (Some(file), Some(span)) =>
+                    write!(f, "at {} ({}:{}:{})", frame.name, file, span.start.line + 1, span.start.column + 1)?,
+                (None, Some(span)) =>
+                    write!(f, "at {} ({}:{})", frame.name, span.start.line + 1, span.start.column + 1)?,
+                (_, None) => write!(f, "at {}", frame.name)?,
+            }
+        }
+        if self.omitted > 0 {
+            write!(f, "\n... {} more", self.omitted)?;
+        }
+        Ok(())
+    }
 }
 
 impl Stack {
@@ -1,17 +1,73 @@
 use crate::spans::*;
 use crate::eval::*;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::cell::RefCell;
 use std::mem::swap;
-use im::{HashMap, Vector};
+use std::rc::Rc;
+use im::{HashMap, OrdSet, Vector};
 
-#[derive(Clone, Default, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Default)]
 pub struct Meta {
     pub span: Option<Span>,
+    /// For a node a macro introduced, the macro call it came from.
     pub old:  Option<Box<Meta>>,
+    /// On the meta of a macro call, the name of the macro.
+    pub macro_name: Option<String>,
+    /// The macro expansions that introduced a symbol, oldest first.
+    /// Names are only bound for symbols with the same scopes.
+    pub scopes: OrdSet<usize>,
+}
+
+// Metadata does not participate in equality: `1` read from two
+// places in a file is still the same value.
+
+impl PartialEq for Meta {
+    fn eq(&self, _other: &Meta) -> bool {
+        true
+    }
+}
+
+impl Eq for Meta {}
+
+impl Hash for Meta {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
 }
 
 impl Meta {
     pub fn new(span: Span) -> Meta {
-        Meta { span: Some(span), old: None }
+        Meta { span: Some(span), old: None, macro_name: None, scopes: OrdSet::new() }
+    }
+
+    /// The macro calls this node was expanded from, innermost first.
+    pub fn expansions(&self) -> Vec<&Meta> {
+        let mut calls = Vec::new();
+        let mut meta = self;
+        while let Some(old) = &meta.old {
+            calls.push(&**old);
+            meta = old;
+        }
+        calls
+    }
+
+    /// Describes each of the `expansions`, as in
+    /// "in expansion of `unless` at 4:2".
+    pub fn provenance(&self) -> Vec<String> {
+        self.expansions().iter().map(|call| {
+            let name = call.macro_name.as_deref().unwrap_or("a macro");
+            match call.span {
+                Some(span) => format!("in expansion of `{}` at {}:{}",
+                                      name, span.start.line + 1, span.start.column + 1),
+                None => format!("in expansion of `{}`", name),
+            }
+        }).collect()
+    }
+
+    /// Adds the scope if it is absent, removes it if it is present.
+    pub fn flip_scope(&mut self, scope: usize) {
+        if self.scopes.remove(&scope).is_none() {
+            self.scopes.insert(scope);
+        }
     }
 }
 
@@ -21,26 +77,117 @@ impl From<Span> for Meta {
     }       
 }
 
-#[derive(Clone, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Special {
-    // CallWithCurrentContinuation(Meta),
+    BeginForSyntax(Meta),
+    CallWithCurrentContinuation(Meta),
+    Def(Meta),
+    DefEnum(Meta),
+    DefStruct(Meta),
+    DefTrait(Meta),
+    Defn(Meta),
+    Do(Meta),
+    EvalWhen(Meta),
+    If(Meta),
+    Impl(Meta),
     Lambda(Meta),
-    // Match(Meta),
+    Let(Meta),
+    Macro(Meta),
+    Match(Meta),
     Quasiquote(Meta),
     Quote(Meta),
+    Reset(Meta),
+    Shift(Meta),
     The(Meta),
+    Try(Meta),
     Unquote(Meta),
-    // UnquoteSplicing(Meta),
+    UnquoteSplicing(Meta),
 }
 
 impl Special {
+    /// Every special form, each with empty metadata.
+    pub fn all() -> Vec<Special> {
+        vec![
+            Special::BeginForSyntax(Meta::default()),
+            Special::CallWithCurrentContinuation(Meta::default()),
+            Special::Def(Meta::default()),
+            Special::DefEnum(Meta::default()),
+            Special::DefStruct(Meta::default()),
+            Special::DefTrait(Meta::default()),
+            Special::Defn(Meta::default()),
+            Special::Do(Meta::default()),
+            Special::EvalWhen(Meta::default()),
+            Special::If(Meta::default()),
+            Special::Impl(Meta::default()),
+            Special::Lambda(Meta::default()),
+            Special::Let(Meta::default()),
+            Special::Macro(Meta::default()),
+            Special::Match(Meta::default()),
+            Special::Quasiquote(Meta::default()),
+            Special::Quote(Meta::default()),
+            Special::Reset(Meta::default()),
+            Special::Shift(Meta::default()),
+            Special::The(Meta::default()),
+            Special::Try(Meta::default()),
+            Special::Unquote(Meta::default()),
+            Special::UnquoteSplicing(Meta::default()),
+        ]
+    }
+
+    /// The symbol the special form is bound to in the root environment.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Special::BeginForSyntax(_) => "begin-for-syntax",
+            Special::CallWithCurrentContinuation(_) => "call/cc",
+            Special::Def(_) => "def",
+            Special::DefEnum(_) => "defenum",
+            Special::DefStruct(_) => "defstruct",
+            Special::DefTrait(_) => "deftrait",
+            Special::Defn(_) => "defn",
+            Special::Do(_) => "do",
+            Special::EvalWhen(_) => "eval-when",
+            Special::If(_) => "if",
+            Special::Impl(_) => "impl",
+            Special::Lambda(_) => "lambda",
+            Special::Let(_) => "let",
+            Special::Macro(_) => "macro",
+            Special::Match(_) => "match",
+            Special::Quasiquote(_) => "quasiquote",
+            Special::Quote(_) => "quote",
+            Special::Reset(_) => "reset",
+            Special::Shift(_) => "shift",
+            Special::The(_) => "the",
+            Special::Try(_) => "try",
+            Special::Unquote(_) => "unquote",
+            Special::UnquoteSplicing(_) => "unquote-splicing",
+        }
+    }
+
     pub fn meta(&self) -> &Meta {
         match self {
-            Special::Lambda(m) => &m,
-            Special::Quasiquote(m) => &m,
-            Special::Quote(m) => &m,
-            Special::The(m) => &m,
-            Special::Unquote(m) => &m,
+            Special::BeginForSyntax(m) => m,
+            Special::CallWithCurrentContinuation(m) => m,
+            Special::Def(m) => m,
+            Special::DefEnum(m) => m,
+            Special::DefStruct(m) => m,
+            Special::DefTrait(m) => m,
+            Special::Defn(m) => m,
+            Special::Do(m) => m,
+            Special::EvalWhen(m) => m,
+            Special::If(m) => m,
+            Special::Impl(m) => m,
+            Special::Lambda(m) => m,
+            Special::Let(m) => m,
+            Special::Macro(m) => m,
+            Special::Match(m) => m,
+            Special::Quasiquote(m) => m,
+            Special::Quote(m) => m,
+            Special::Reset(m) => m,
+            Special::Shift(m) => m,
+            Special::The(m) => m,
+            Special::Try(m) => m,
+            Special::Unquote(m) => m,
+            Special::UnquoteSplicing(m) => m,
         }
     }
 
@@ -49,41 +196,240 @@ impl Special {
     // next best thing and have a setter.
     pub fn set_meta(&mut self, mut meta: Meta) -> Meta {
         match self {
+            Special::BeginForSyntax(ref mut m) => swap(m, &mut meta),
+            Special::CallWithCurrentContinuation(ref mut m) => swap(m, &mut meta),
+            Special::Def(ref mut m) => swap(m, &mut meta),
+            Special::DefEnum(ref mut m) => swap(m, &mut meta),
+            Special::DefStruct(ref mut m) => swap(m, &mut meta),
+            Special::DefTrait(ref mut m) => swap(m, &mut meta),
+            Special::Defn(ref mut m) => swap(m, &mut meta),
+            Special::Do(ref mut m) => swap(m, &mut meta),
+            Special::EvalWhen(ref mut m) => swap(m, &mut meta),
+            Special::If(ref mut m) => swap(m, &mut meta),
+            Special::Impl(ref mut m) => swap(m, &mut meta),
             Special::Lambda(ref mut m) => swap(m, &mut meta),
+            Special::Let(ref mut m) => swap(m, &mut meta),
+            Special::Macro(ref mut m) => swap(m, &mut meta),
+            Special::Match(ref mut m) => swap(m, &mut meta),
             Special::Quasiquote(ref mut m) => swap(m, &mut meta),
             Special::Quote(ref mut m) => swap(m, &mut meta),
+            Special::Reset(ref mut m) => swap(m, &mut meta),
+            Special::Shift(ref mut m) => swap(m, &mut meta),
             Special::The(ref mut m) => swap(m, &mut meta),
+            Special::Try(ref mut m) => swap(m, &mut meta),
             Special::Unquote(ref mut m) => swap(m, &mut meta),
+            Special::UnquoteSplicing(ref mut m) => swap(m, &mut meta),
         };
         meta
     }
 }
 
-#[derive(Clone, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Fun {
+    /// The name it was first defined under, for backtraces.
+    pub name:  Option<String>,
     pub param: Box<Symbol>,
     pub body:  Box<Expr>,
+    // the environment the lambda closed over
+    pub env:   Stack,
     pub meta:  Meta,
 }
 
 impl Fun {
     
-    pub fn new(param: Box<Symbol>, body:  Box<Expr>, meta:  Meta)  -> Fun {
-        Fun { param, body, meta }
+    pub fn new(param: Box<Symbol>, body:  Box<Expr>, env: Stack, meta:  Meta)  -> Fun {
+        Fun { name: None, param, body, env, meta }
     }
 
 
+    /// How a call to it appears in a backtrace.
+    pub fn describe(&self) -> String {
+        match (&self.name, self.meta.span) {
+            (Some(name), _) => name.clone(),
+            (None, Some(span)) =>
+                format!("lambda {}@{}:{}", self.param.value, span.start.line + 1, span.start.column + 1),
+            (None, None) => format!("lambda {}", self.param.value),
+        }
+    }
+
     pub fn call(&self, arg: Expr, eval: &mut Eval) -> Result<Expr, EvalError> {
-        let mut fval = eval.clone();
-        fval.stack.push();
-        fval.stack.assign(&self.param.value, arg);
-        let result = fval.eval(*self.body.clone())?;
-        Ok(result)
+        eval.apply(Expr::Fun(self.clone()), vec![arg])
+    }
+
+}
+
+/// The rest of a computation, captured by `call/cc` or `shift`.
+/// Applying it to a value resumes the computation with that value.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Continuation {
+    pub frames: Vec<Frame>,
+    // the run the frames were captured from
+    pub run: usize,
+    /// Captured by `shift`: calling it returns to the caller, as if
+    /// it were a function, rather than abandoning the current one.
+    pub composable: bool,
+    pub meta: Meta,
+}
+
+/// A mutable reference to a value. Two atoms are equal only if they
+/// are the same atom, whatever they hold.
+#[derive(Clone)]
+pub struct Atom {
+    pub value: Rc<RefCell<Expr>>,
+    pub meta:  Meta,
+}
+
+impl Atom {
+    pub fn new(value: Expr, meta: Meta) -> Atom {
+        Atom { value: Rc::new(RefCell::new(value)), meta }
+    }
+
+    pub fn get(&self) -> Expr {
+        self.value.borrow().clone()
+    }
+
+    pub fn set(&self, value: Expr) -> Expr {
+        self.value.replace(value)
+    }
+
+    /// Sets the value to `new` if it is still `old`.
+    pub fn compare_and_set(&self, old: &Expr, new: Expr) -> bool {
+        let mut value = self.value.borrow_mut();
+        if *value == *old {
+            *value = new;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl PartialEq for Atom {
+    fn eq(&self, other: &Atom) -> bool {
+        Rc::ptr_eq(&self.value, &other.value)
+    }
+}
+
+impl Eq for Atom {}
+
+impl Hash for Atom {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Rc::as_ptr(&self.value) as *const u8).hash(state);
+    }
+}
+
+// an atom may hold itself, so only its address is shown
+impl fmt::Debug for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Atom")
+            .field("value", &Rc::as_ptr(&self.value))
+            .field("meta", &self.meta)
+            .finish()
+    }
+}
+
+/// A value of a type declared with `defstruct` or `defenum`: which
+/// variant of which type it is, and its fields in declaration order.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Data {
+    pub typ:     String,
+    pub variant: String,
+    pub fields:  Vector<Expr>,
+    pub meta:    Meta,
+}
+
+impl Data {
+    pub fn new(typ: String, variant: String, fields: Vector<Expr>, meta: Meta) -> Data {
+        Data { typ, variant, fields, meta }
+    }
+}
+
+/// How many arguments a native function takes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    /// How many more arguments we may take, `None` if unbounded.
+    pub fn remaining(self, applied: usize) -> Option<usize> {
+        match self {
+            Arity::Exactly(n) => Some(n.saturating_sub(applied)),
+            Arity::AtLeast(_) => None,
+        }
+    }
+
+    pub fn accepts(self, count: usize) -> bool {
+        match self {
+            Arity::Exactly(n) => count == n,
+            Arity::AtLeast(n) => count >= n,
+        }
+    }
+}
+
+pub type NativeFn = Rc<dyn Fn(&mut Eval, &[Expr]) -> Result<Expr, EvalError>>;
+
+/// A function implemented in rust. Like `Fun`, it may be partially
+/// applied, in which case `args` holds what it has been given so far.
+#[derive(Clone)]
+pub struct Native {
+    pub name:  String,
+    pub arity: Arity,
+    pub fun:   NativeFn,
+    pub args:  Vec<Expr>,
+    pub meta:  Meta,
+}
+
+impl Native {
+
+    pub fn new(name: impl Into<String>, arity: Arity, fun: NativeFn, meta: Meta) -> Native {
+        Native { name: name.into(), arity, fun, args: Vec::new(), meta }
+    }
+
+    pub fn call(&self, eval: &mut Eval) -> Result<Expr, EvalError> {
+        if self.arity.accepts(self.args.len()) {
+            (self.fun)(eval, &self.args)
+        } else {
+            Err(EvalError::WrongArity(self.name.clone(), self.arity, self.args.len()))
+        }
     }
 
 }
 
-#[derive(Clone, Eq, Hash, PartialEq)]
+// Natives are compared by identity: two are the same function if
+// they share the same closure.
+
+impl PartialEq for Native {
+    fn eq(&self, other: &Native) -> bool {
+        self.name == other.name && Rc::ptr_eq(&self.fun, &other.fun)
+            && self.args == other.args && self.meta == other.meta
+    }
+}
+
+impl Eq for Native {}
+
+impl Hash for Native {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        (Rc::as_ptr(&self.fun) as *const u8).hash(state);
+        self.args.hash(state);
+        self.meta.hash(state);
+    }
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Native")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .field("args", &self.args)
+            .field("meta", &self.meta)
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Int {
     pub value: i64,
     pub meta: Meta,
@@ -101,7 +447,43 @@ impl From<i64> for Int {
     }
 }
 
-#[derive(Clone, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Bool {
+    pub value: bool,
+    pub meta: Meta,
+}
+
+impl Bool {
+    pub fn new(value: bool, meta: Meta) -> Bool {
+        Bool { value, meta }
+    }
+}
+
+impl From<bool> for Bool {
+    fn from(value: bool) -> Bool {
+        Bool::new(value, Meta::default())
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Str {
+    pub value: String,
+    pub meta: Meta,
+}
+
+impl Str {
+    pub fn new(value: String, meta: Meta) -> Str {
+        Str { value, meta }
+    }
+}
+
+impl From<String> for Str {
+    fn from(value: String) -> Str {
+        Str::new(value, Meta::default())
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Symbol {
     pub value: String,
     pub meta: Meta,
@@ -113,13 +495,49 @@ impl Symbol {
     }
 }
 
+impl Symbol {
+    /// The name this symbol is bound under: its value, qualified by
+    /// its scopes. No symbol we read can contain a `:`.
+    pub fn key(&self) -> String {
+        scoped_key(&self.value, self.meta.scopes.iter().copied())
+    }
+}
+
+pub(crate) fn scoped_key(name: &str, scopes: impl Iterator<Item = usize>) -> String {
+    let mut key = name.to_string();
+    for scope in scopes {
+        key.push(':');
+        key.push_str(&scope.to_string());
+    }
+    key
+}
+
 impl From<String> for Symbol {
     fn from(value: String) -> Symbol {
         Symbol::new(value, Meta::default())
     }
 }
 
-#[derive(Clone, Default, Eq, Hash, PartialEq)]
+/// A name that evaluates to itself, written `::name`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Keyword {
+    pub value: String,
+    pub meta: Meta,
+}
+
+impl Keyword {
+    pub fn new(value: String, meta: Meta) -> Keyword {
+        Keyword { value, meta }
+    }
+}
+
+impl From<&str> for Keyword {
+    fn from(value: &str) -> Keyword {
+        Keyword::new(value.to_string(), Meta::default())
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 pub struct List {
     pub vals: Vector<Expr>,
     pub meta: Meta,
@@ -137,7 +555,7 @@ impl From<Vector<Expr>> for List {
     }
 }
 
-#[derive(Clone, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Map {
     pub vals: HashMap<Expr, Expr>,
     pub meta: Meta,
@@ -155,51 +573,223 @@ impl From<HashMap<Expr, Expr>> for Map {
     }
 }
 
-#[derive(Clone, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 pub enum Expr {
+    #[default]
     Nil,
+    Bool(Bool),
     Int(Int),
     Symbol(Symbol),
+    Keyword(Keyword),
     // Float(Float),
-    // String(Str),
+    String(Str),
     List(List),
     Map(Map),
     Fun(Fun),
     Macro(Fun),
+    Native(Native),
     Special(Special),
-    // Continuation(Stack),
+    Continuation(Continuation),
+    Atom(Atom),
+    Data(Data),
 }
 
 impl Expr {
     pub fn meta(&self) -> Option<&Meta> {
         match self {
             Expr::Nil => None,
+            Expr::Bool(e) => Some(&e.meta),
             Expr::Int(e) => Some(&e.meta),
             Expr::Symbol(e) => Some(&e.meta),
+            Expr::Keyword(e) => Some(&e.meta),
+            Expr::String(e) => Some(&e.meta),
             Expr::List(e) => Some(&e.meta),
             Expr::Map(e) => Some(&e.meta),
             Expr::Fun(e) => Some(&e.meta),
             Expr::Macro(e) => Some(&e.meta),
+            Expr::Native(e) => Some(&e.meta),
             Expr::Special(e) => Some(e.meta()),
+            Expr::Continuation(e) => Some(&e.meta),
+            Expr::Atom(e) => Some(&e.meta),
+            Expr::Data(e) => Some(&e.meta),
         }
     }
     pub fn set_meta(&mut self, mut meta: Meta) -> Option<Meta> {
         match self {
             Expr::Nil => return None,
+            Expr::Bool(ref mut e) => swap(&mut e.meta, &mut meta),
             Expr::Int(ref mut e) => swap(&mut e.meta, &mut meta),
             Expr::Symbol(ref mut e) => swap(&mut e.meta, &mut meta),
+            Expr::Keyword(ref mut e) => swap(&mut e.meta, &mut meta),
+            Expr::String(ref mut e) => swap(&mut e.meta, &mut meta),
             Expr::List(ref mut e) => swap(&mut e.meta, &mut meta),
             Expr::Map(ref mut e) => swap(&mut e.meta, &mut meta),
             Expr::Fun(ref mut e) => swap(&mut e.meta, &mut meta),
             Expr::Macro(ref mut e) => swap(&mut e.meta, &mut meta),
+            Expr::Native(ref mut e) => swap(&mut e.meta, &mut meta),
             Expr::Special(ref mut e) => return Some(e.set_meta(meta)),
+            Expr::Continuation(ref mut e) => swap(&mut e.meta, &mut meta),
+            Expr::Atom(ref mut e) => swap(&mut e.meta, &mut meta),
+            Expr::Data(ref mut e) => swap(&mut e.meta, &mut meta),
         };
         Some(meta)
     }
+
+    /// Flips `scope` on every symbol in the expression, which is how
+    /// macro expansion tells the symbols it was given from the ones
+    /// it introduced.
+    pub fn flip_scope(&mut self, scope: usize) {
+        match self {
+            Expr::Symbol(s) => s.meta.flip_scope(scope),
+            Expr::Keyword(k) => k.meta.flip_scope(scope),
+            Expr::Bool(b) => b.meta.flip_scope(scope),
+            Expr::Int(i) => i.meta.flip_scope(scope),
+            Expr::String(s) => s.meta.flip_scope(scope),
+            Expr::List(l) => {
+                l.meta.flip_scope(scope);
+                for val in l.vals.iter_mut() {
+                    val.flip_scope(scope);
+                }
+            }
+            Expr::Map(m) => {
+                m.meta.flip_scope(scope);
+                m.vals = m.vals.iter().map(|(k, v)| {
+                    let (mut k, mut v) = (k.clone(), v.clone());
+                    k.flip_scope(scope);
+                    v.flip_scope(scope);
+                    (k, v)
+                }).collect();
+            }
+            _ => (),
+        }
+    }
+
+    /// Records `call` as the origin of every node that carries `scope`,
+    /// that is, every node introduced by that expansion. Nodes the
+    /// macro built from nothing get the span of the call.
+    pub fn record_expansion(&mut self, scope: usize, call: &Meta) {
+        match self {
+            Expr::List(l) => {
+                for val in l.vals.iter_mut() {
+                    val.record_expansion(scope, call);
+                }
+            }
+            Expr::Map(m) => {
+                m.vals = m.vals.iter().map(|(k, v)| {
+                    let (mut k, mut v) = (k.clone(), v.clone());
+                    k.record_expansion(scope, call);
+                    v.record_expansion(scope, call);
+                    (k, v)
+                }).collect();
+            }
+            _ => (),
+        }
+        if let Some(meta) = self.meta() {
+            if meta.scopes.contains(&scope) {
+                let mut meta = meta.clone();
+                meta.old = Some(Box::new(call.clone()));
+                meta.span = meta.span.or(call.span);
+                self.set_meta(meta);
+            }
+        }
+    }
+
+    /// Gives `span` to every node that does not have one.
+    pub fn fill_spans(&mut self, span: Span) {
+        match self {
+            Expr::List(l) => {
+                for val in l.vals.iter_mut() {
+                    val.fill_spans(span);
+                }
+            }
+            Expr::Map(m) => {
+                m.vals = m.vals.iter().map(|(k, v)| {
+                    let (mut k, mut v) = (k.clone(), v.clone());
+                    k.fill_spans(span);
+                    v.fill_spans(span);
+                    (k, v)
+                }).collect();
+            }
+            _ => (),
+        }
+        if let Some(meta) = self.meta() {
+            if meta.span.is_none() {
+                let mut meta = meta.clone();
+                meta.span = Some(span);
+                self.set_meta(meta);
+            }
+        }
+    }
+
+    /// Only `nil` and `false` are false.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Expr::Nil | Expr::Bool(Bool { value: false, .. }))
+    }
 }
 
-impl Default for Expr {
-    fn default() -> Expr {
-        Expr::Nil
+impl From<bool> for Expr {
+    fn from(value: bool) -> Expr {
+        Expr::Bool(value.into())
+    }
+}
+
+impl From<i64> for Expr {
+    fn from(value: i64) -> Expr {
+        Expr::Int(value.into())
+    }
+}
+
+impl From<String> for Expr {
+    fn from(value: String) -> Expr {
+        Expr::String(value.into())
+    }
+}
+
+impl From<&str> for Expr {
+    fn from(value: &str) -> Expr {
+        Expr::String(value.to_string().into())
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Nil => write!(f, "nil"),
+            Expr::Bool(b) => write!(f, "{}", b.value),
+            Expr::Int(i) => write!(f, "{}", i.value),
+            Expr::Symbol(s) => write!(f, "{}", s.value),
+            Expr::Keyword(k) => write!(f, "::{}", k.value),
+            Expr::String(s) => write!(f, "{:?}", s.value),
+            Expr::List(l) => {
+                write!(f, "(")?;
+                for (i, val) in l.vals.iter().enumerate() {
+                    if i > 0 { write!(f, " ")?; }
+                    write!(f, "{}", val)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Map(m) => {
+                // hashmap order is arbitrary, so sort for a stable rendering
+                let mut entries: Vec<String> =
+                    m.vals.iter().map(|(k, v)| format!("{} {}", k, v)).collect();
+                entries.sort();
+                write!(f, "{{{}}}", entries.join(" "))
+            }
+            Expr::Fun(fun) => write!(f, "#<fn {}>", fun.param.value),
+            Expr::Macro(fun) => write!(f, "#<macro {}>", fun.param.value),
+            Expr::Native(n) => write!(f, "#<native {}>", n.name),
+            Expr::Special(s) => write!(f, "{}", s.name()),
+            Expr::Continuation(_) => write!(f, "#<continuation>"),
+            Expr::Atom(_) => write!(f, "#<atom>"),
+            // as it would be built
+            Expr::Data(d) if d.fields.is_empty() => write!(f, "{}", d.variant),
+            Expr::Data(d) => {
+                write!(f, "({}", d.variant)?;
+                for field in d.fields.iter() {
+                    write!(f, " {}", field)?;
+                }
+                write!(f, ")")
+            }
+        }
     }
 }
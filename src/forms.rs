@@ -24,6 +24,9 @@ pub enum Macro<'a>  {
     Quasiquote(Spanning<Prefix>, Box<Form<'a>>),
     Quote(Spanning<Prefix>, Box<Form<'a>>),
     Unquote(Spanning<Prefix>, Box<Form<'a>>),
+    UnquoteSplicing(Spanning<Prefix>, Box<Form<'a>>),
+    /// A reader macro with the forms that followed its dispatch character.
+    Dispatch(Spanning<char>, Vector<Form<'a>>),
 }
 
 impl<'a> Macro<'a> {
@@ -33,6 +36,11 @@ impl<'a> Macro<'a> {
             Macro::Quasiquote(prefix, form) => prefix.span.start.span(form.span().end),
             Macro::Quote(prefix, form) => prefix.span.start.span(form.span().end),
             Macro::Unquote(prefix, form) => prefix.span.start.span(form.span().end),
+            Macro::UnquoteSplicing(prefix, form) => prefix.span.start.span(form.span().end),
+            Macro::Dispatch(ch, forms) => {
+                let end = forms.back().map_or(ch.span.end, |form| form.span().end);
+                ch.span.start.span(end)
+            }
         }
     }
 }
@@ -49,7 +57,9 @@ pub enum Form<'a> {
     Macro(Macro<'a>),
     Group(Group<Form<'a>>),
     Int(Spanning<i64>),
+    String(Spanning<Cow<'a, str>>),
     Symbol(Spanning<Cow<'a, str>>),
+    Keyword(Spanning<Cow<'a, str>>),
 }
 
 impl<'a> Form<'a> {
@@ -58,7 +68,9 @@ impl<'a> Form<'a> {
             Form::Macro(macr) => macr.span(),
             Form::Group(group) => group.span(),
             Form::Int(int) => int.span,
+            Form::String(s) => s.span,
             Form::Symbol(sym) => sym.span,
+            Form::Keyword(k) => k.span,
         }
     }
 }
@@ -68,17 +80,31 @@ pub enum Partial<T: Clone> {
     HasType(Spanning<Prefix>, Option<T>),
     Quoting(Spanning<Prefix>),
     Group(Spanning<Paren>, Vector<T>),
+    /// A reader macro waiting for `usize` forms in all.
+    Dispatch(Spanning<char>, usize, Vector<T>),
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Forms<'a> {
     tokens: Tokens<'a>,
     partials: Vector<Partial<Form<'a>>>,
+    dispatch: Dispatch,
 }
 
 impl<'a> Forms<'a> {
     pub fn new(source: &'a str) -> Self {
-        Forms { tokens: Tokens::new(source), partials: Vector::new() }
+        Forms::with_dispatch(source, default_dispatch())
+    }
+
+    pub fn with_dispatch(source: &'a str, dispatch: Dispatch) -> Self {
+        Forms { tokens: Tokens::with_dispatch(source, dispatch.clone()), partials: Vector::new(), dispatch }
+    }
+
+    /// Changes the reader macros for the forms not yet read, as when
+    /// one has just been defined.
+    pub fn set_dispatch(&mut self, dispatch: Dispatch) {
+        self.tokens.set_dispatch(dispatch.clone());
+        self.dispatch = dispatch;
     }
 
     fn does_not_complete(&mut self, span: Span, paren: Paren) ->  Result<Form<'a>, FormError<'a>> {
@@ -91,6 +117,10 @@ impl<'a> Forms<'a> {
             match token.inner {
                 Token::Open(open) =>
                     Partial::Group(Spanning::new(open, token.span), Vector::new()),
+                Token::Dispatch(ch) => {
+                    let arity = self.dispatch.get(&ch).copied().unwrap_or_default();
+                    Partial::Dispatch(Spanning::new(ch, token.span), arity, Vector::new())
+                }
                 Token::Prefix(prefix) =>
                     if prefix == Prefix::HasType {
                         Partial::HasType(Spanning::new(prefix, token.span), None)
@@ -125,7 +155,9 @@ impl<'a> Forms<'a> {
         if let Token::Literal(l) = token.inner {
             match l {
                 Literal::Int(int)    => Ok(Form::Int(Spanning::new(int, token.span))),
+                Literal::String(s)   => Ok(Form::String(Spanning::new(s, token.span))),
                 Literal::Symbol(sym) => Ok(Form::Symbol(Spanning::new(sym, token.span))),
+                Literal::Keyword(k)  => Ok(Form::Keyword(Spanning::new(k, token.span))),
             }
         } else { unreachable!() }
     }
@@ -137,9 +169,13 @@ impl<'a> Forms<'a> {
                     match token.inner {
                         Token::Open(_) => { self.push(token); }
                         Token::Prefix(_) => { self.push(token); }
+                        Token::Dispatch(ch) if self.dispatch.get(&ch) == Some(&0) => {
+                            let macr = Macro::Dispatch(Spanning::new(ch, token.span), Vector::new());
+                            return Some(Ok(Form::Macro(macr)));
+                        }
+                        Token::Dispatch(_) => { self.push(token); }
                         Token::Close(_) => return Some(self.close(token)),
                         Token::Literal(_) => return Some(self.literal(token)), 
-                        // Token::String(s) => self.list(Spanning::new(s, token.span), open),
                         _ => {}
                     }
                 }
@@ -174,6 +210,7 @@ impl<'a> Forms<'a> {
                                             match prefix.inner {
                                                 Prefix::Quasiquote => Macro::Quasiquote(prefix, Box::new(gorm)),
                                                 Prefix::Quote => Macro::Quote(prefix, Box::new(gorm)),
+                                                Prefix::UnquoteSplicing => Macro::UnquoteSplicing(prefix, Box::new(gorm)),
                                                 _ => Macro::Unquote(prefix, Box::new(gorm)),
                                             };
                                         form = Some(Form::Macro(macr));
@@ -182,10 +219,21 @@ impl<'a> Forms<'a> {
                                         vals.push_back(gorm);
                                         self.partials.push_back(partial);
                                     }
+                                    Partial::Dispatch(ch, arity, mut vals) => {
+                                        vals.push_back(gorm);
+                                        if vals.len() >= arity {
+                                            form = Some(Form::Macro(Macro::Dispatch(ch, vals)));
+                                        } else {
+                                            self.partials.push_back(Partial::Dispatch(ch, arity, vals));
+                                        }
+                                    }
                                 }
                             }
                             (Some(gorm), None) => return Some(Ok(gorm)),
-                            (None, Some(partial)) => { self.partials.push_back(partial); }
+                            (None, Some(partial)) => {
+                                self.partials.push_back(partial);
+                                break;
+                            }
                             (None, None) => break,
                         }
                     }
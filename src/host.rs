@@ -0,0 +1,234 @@
+use crate::eval::*;
+use crate::exprs::*;
+use im::Vector;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What a group of builtins lets a script do to the world outside.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Capability {
+    FsRead,
+    FsWrite,
+    Env,
+    Process,
+    Time,
+}
+
+impl Capability {
+    pub fn all() -> [Capability; 5] {
+        [Capability::FsRead, Capability::FsWrite, Capability::Env, Capability::Process, Capability::Time]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::FsRead => "fs-read",
+            Capability::FsWrite => "fs-write",
+            Capability::Env => "env",
+            Capability::Process => "process",
+            Capability::Time => "time",
+        }
+    }
+
+    /// The builtins it grants.
+    pub fn builtins(self) -> &'static [&'static str] {
+        match self {
+            Capability::FsRead => &["read-file", "file-exists?", "list-dir"],
+            Capability::FsWrite => &["write-file", "create-dir", "remove-file"],
+            Capability::Env => &["env-var"],
+            Capability::Process => &["run-process"],
+            Capability::Time => &["now-ms"],
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The capabilities granted to an `Eval`. None are by default, so
+/// code can only compute. Granting `Process` lets it do anything the
+/// host can, root or no root.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Capabilities {
+    pub granted: BTreeSet<Capability>,
+    /// Where file access is confined to. Relative paths are taken
+    /// from here, and paths leading out of it are refused.
+    pub root: Option<PathBuf>,
+}
+
+impl Capabilities {
+    pub fn none() -> Capabilities {
+        Capabilities::default()
+    }
+
+    pub fn all() -> Capabilities {
+        Capabilities { granted: Capability::all().iter().copied().collect(), root: None }
+    }
+
+    pub fn grant(mut self, capability: Capability) -> Capabilities {
+        self.granted.insert(capability);
+        self
+    }
+
+    pub fn within(mut self, root: impl Into<PathBuf>) -> Capabilities {
+        self.root = Some(root.into());
+        self
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.granted.contains(&capability)
+    }
+
+    // where `path` is, if it is somewhere we may go
+    fn confine(&self, path: &str) -> Result<PathBuf, EvalError> {
+        let root = match &self.root {
+            Some(root) => root.canonicalize().map_err(|e| io_error(root, e))?,
+            None => return Ok(PathBuf::from(path)),
+        };
+        let denied = || EvalError::PathDenied(path.to_string());
+        // first without touching the disk, so `..` cannot climb out
+        let confined = lexical(&root.join(path));
+        if !confined.starts_with(&root) {
+            return Err(denied());
+        }
+        // then a component at a time, as one that is a symlink, even
+        // to somewhere that does not exist yet, must stay within too
+        let mut real = root.clone();
+        let mut rest: Vec<_> = confined.strip_prefix(&root).unwrap_or(&confined).iter().rev().map(|c| c.to_owned()).collect();
+        let mut links = 0;
+        while let Some(name) = rest.pop() {
+            let next = real.join(&name);
+            match fs::symlink_metadata(&next) {
+                Ok(meta) if meta.file_type().is_symlink() => {
+                    links += 1;
+                    if links > MAX_LINKS {
+                        return Err(denied());
+                    }
+                    let target = fs::read_link(&next).map_err(|e| io_error(&next, e))?;
+                    let target = lexical(&real.join(target));
+                    let within = target.strip_prefix(&root).map_err(|_| denied())?;
+                    rest.extend(within.iter().rev().map(|c| c.to_owned()));
+                    real = root.clone();
+                }
+                Ok(_) => real = next,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => real = next,
+                Err(e) => return Err(io_error(&next, e)),
+            }
+        }
+        Ok(confined)
+    }
+}
+
+// as many symlinks as a path may pass through, as linux allows
+const MAX_LINKS: usize = 40;
+
+// `path` with `.` and `..` taken out, without looking at the disk
+fn lexical(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => { out.pop(); }
+            Component::CurDir => (),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> EvalError {
+    EvalError::Io(format!("{}: {}", path.display(), e))
+}
+
+/// Binds the builtins of each granted capability into `eval`, and
+/// makes the others fail with `EvalError::CapabilityDenied`.
+pub fn load(eval: &mut Eval, capabilities: &Capabilities) {
+    for capability in Capability::all().iter().copied() {
+        if !capabilities.allows(capability) {
+            for name in capability.builtins() {
+                eval.deny(name, capability);
+            }
+            continue;
+        }
+        match capability {
+            Capability::FsRead => fs_read(eval, capabilities),
+            Capability::FsWrite => fs_write(eval, capabilities),
+            Capability::Env => env(eval),
+            Capability::Process => process(eval, capabilities),
+            Capability::Time => time(eval),
+        }
+    }
+}
+
+fn fs_read(eval: &mut Eval, capabilities: &Capabilities) {
+    let caps = capabilities.clone();
+    eval.register_fn("read-file", move |path: String| {
+        let path = caps.confine(&path)?;
+        fs::read_to_string(&path).map_err(|e| io_error(&path, e))
+    });
+    let caps = capabilities.clone();
+    eval.register_fn("file-exists?", move |path: String| Ok(caps.confine(&path)?.exists()));
+    let caps = capabilities.clone();
+    eval.register_fn("list-dir", move |path: String| {
+        let path = caps.confine(&path)?;
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&path).map_err(|e| io_error(&path, e))? {
+            let entry = entry.map_err(|e| io_error(&path, e))?;
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+        // directory order depends on the filesystem
+        names.sort();
+        Ok(names.into_iter().collect::<Vector<String>>())
+    });
+}
+
+fn fs_write(eval: &mut Eval, capabilities: &Capabilities) {
+    let caps = capabilities.clone();
+    eval.register_fn("write-file", move |path: String, contents: String| {
+        let path = caps.confine(&path)?;
+        fs::write(&path, contents).map_err(|e| io_error(&path, e))
+    });
+    let caps = capabilities.clone();
+    eval.register_fn("create-dir", move |path: String| {
+        let path = caps.confine(&path)?;
+        fs::create_dir_all(&path).map_err(|e| io_error(&path, e))
+    });
+    let caps = capabilities.clone();
+    eval.register_fn("remove-file", move |path: String| {
+        let path = caps.confine(&path)?;
+        fs::remove_file(&path).map_err(|e| io_error(&path, e))
+    });
+}
+
+fn env(eval: &mut Eval) {
+    eval.register_fn("env-var", |name: String| std::env::var(name).ok());
+}
+
+fn process(eval: &mut Eval, capabilities: &Capabilities) {
+    let root = capabilities.root.clone();
+    eval.register_fn("run-process", move |program: String, args: Vector<String>| {
+        let mut command = Command::new(&program);
+        command.args(args.iter());
+        if let Some(root) = &root {
+            command.current_dir(root);
+        }
+        let output = command.output().map_err(|e| io_error(Path::new(&program), e))?;
+        Ok(keyword_map(vec![
+            ("status", output.status.code().map_or(Expr::Nil, |code| Expr::from(i64::from(code)))),
+            ("stdout", Expr::from(String::from_utf8_lossy(&output.stdout).into_owned())),
+            ("stderr", Expr::from(String::from_utf8_lossy(&output.stderr).into_owned())),
+        ]))
+    });
+}
+
+fn time(eval: &mut Eval) {
+    eval.register_fn("now-ms", || {
+        let since = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        since.as_millis() as i64
+    });
+}
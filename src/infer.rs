@@ -0,0 +1,861 @@
+use crate::data::DataType;
+use crate::eval::Defn;
+use crate::traits::{self, Instance, Trait};
+use crate::exprs::*;
+use crate::spans::*;
+use crate::types::*;
+use im::HashMap;
+
+/// The principal type of `expr`, given the types of the top level
+/// definitions in `env`.
+pub fn infer(expr: &Expr, env: &TypeEnv) -> Result<Scheme, TypeError> {
+    let mut infer = Infer::default();
+    let typ = infer.infer(expr, &HashMap::new(), env)?;
+    let scheme = infer.generalize(&typ, &HashMap::new(), env)?;
+    Ok(Scheme::qualified(scheme.vars, scheme.constraints, scheme.typ))
+}
+
+/// Whether some type is an instance of both `a` and `b`.
+pub(crate) fn unifiable(a: &Type, b: &Type) -> bool {
+    let mut infer = Infer::default();
+    let mut apart = |typ: &Type| {
+        let subst: HashMap<String, Type> = typ.vars().into_iter().map(|v| (v.name, infer.fresh())).collect();
+        typ.substitute(&subst)
+    };
+    let (a, b) = (apart(a), apart(b));
+    infer.unify(&a, None, &b, None).is_ok()
+}
+
+/// Infers the type of each top level form in turn, binding what each
+/// `def` defines in `env` so the forms after it see it. Every name
+/// defined is known from the start, so definitions may refer to later
+/// ones, but only at one type until they are reached. Likewise every
+/// `defstruct`, `defenum`, `deftrait` and `impl` is declared first, in
+/// order, and has type `()`; the methods of an impl are checked where
+/// it is. Macros and other forms that only run while expanding are
+/// skipped, and have type `()` too. Uses of trait methods at types
+/// without an impl are errors. Only definitions of values, such as
+/// lambdas, are generalised: others keep weak variables, which each
+/// stand for the one type later forms use them at.
+pub fn check(exprs: &[Expr], env: &mut TypeEnv) -> Result<Vec<Scheme>, TypeError> {
+    check_forms(exprs, env).map(|(types, _, _)| types)
+}
+
+/// Dictionaries taken, each named by the parameter it is passed as,
+/// with the constraint it meets: the methods of the impl for it.
+pub type Dictionaries = Vec<(Constraint, String)>;
+
+/// How the forms checked pass impls as dictionaries. Whatever has
+/// constraints in its type takes a dictionary for each, in order,
+/// before its other arguments, as the methods of an impl take one for
+/// each constraint of the impl.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Elaboration {
+    /// Per form, the dictionaries in scope in it: those the
+    /// definition takes, or those the methods of the impl do.
+    pub forms: Vec<Dictionaries>,
+    /// Those each let binding takes, by the span of its name.
+    pub lets: HashMap<Span, Dictionaries>,
+    /// What each use of something with constraints wants passed, by
+    /// its span and name.
+    pub uses: HashMap<(Span, String), Wanted>,
+}
+
+/// The constraints a use wants met, in the order of its scheme. A
+/// method is taken from the dictionary for its trait, rather than
+/// passed it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Wanted {
+    pub method: bool,
+    pub constraints: Vec<Constraint>,
+}
+
+/// As `check`, along with how to pass dictionaries in the forms.
+pub fn check_elaborated(exprs: &[Expr], env: &mut TypeEnv) -> Result<(Vec<Scheme>, Elaboration), TypeError> {
+    let (types, infer, forms) = check_forms(exprs, env)?;
+    Ok((types, infer.elaboration(forms)?))
+}
+
+fn check_forms(exprs: &[Expr], env: &mut TypeEnv) -> Result<(Vec<Scheme>, Infer, Vec<Dictionaries>), TypeError> {
+    for expr in exprs {
+        match declaration(expr) {
+            Some(("defstruct", form)) | Some(("defenum", form)) => {
+                let data = DataType::parse(form, env)?;
+                env.declare(data);
+            }
+            Some(("deftrait", form)) => {
+                let tr = Trait::parse(form, env)?;
+                env.declare_trait(tr);
+            }
+            Some((_, form)) => {
+                let (instance, _) = Instance::parse(form, env)?;
+                env.add_instance(instance)?;
+            }
+            None => (),
+        }
+    }
+    let mut infer = Infer::default();
+    let mut pending = HashMap::new();
+    for expr in exprs {
+        if let Some(name) = definition(expr) {
+            let typ = infer.fresh();
+            if let Type::Var(var) = &typ {
+                infer.pending.insert(var.name.clone(), name.to_string());
+            }
+            pending.insert(name.to_string(), Scheme::mono(typ));
+        }
+    }
+    let mut types = Vec::new();
+    let mut forms = Vec::new();
+    let mut defined = Vec::new();
+    for expr in exprs {
+        if compile_time(expr) || declaration(expr).is_some() {
+            let given = match declaration(expr) {
+                Some(("impl", form)) => infer.infer_impl(form, &pending, env)?,
+                _ => Vec::new(),
+            };
+            forms.push(given);
+            types.push(Scheme::mono(builtin::unit().into()));
+            continue;
+        }
+        let uses = infer.forward.len();
+        let typ = infer.infer(expr, &pending, env)?;
+        let name = definition(expr).map(|name| name.to_string());
+        if let Some(name) = &name {
+            infer.unify(&pending[name].typ, None, &typ, span(expr))?;
+            pending.remove(name);
+        }
+        let weak = defined_value(expr).is_some_and(|val| !is_value(val));
+        let scheme = match weak {
+            true => Scheme::mono(infer.weaken(&typ, &[], env)?),
+            false => infer.generalize(&typ, &pending, env)?,
+        };
+        // only definitions take dictionaries: those a top level
+        // expression wants must be known
+        let taken = match &name {
+            Some(name) => {
+                env.bind(name.clone(), scheme.clone());
+                defined.push(name.clone());
+                dictionaries(&scheme.constraints)
+            }
+            None => Vec::new(),
+        };
+        if let (Some(name), Some((constraint, _))) = (name, taken.first()) {
+            // it calls itself with what it takes
+            let (own, others) = infer.forward.drain(uses..).partition::<Vec<_>, _>(|(used, _)| *used == name);
+            infer.forward.extend(others);
+            for (_, sym) in own {
+                let wanted = Wanted { method: false, constraints: scheme.constraints.clone() };
+                infer.uses.push((sym, wanted));
+            }
+            infer.taking.insert(name, constraint.clone());
+        }
+        forms.push(taken);
+        infer.reduce(env)?;
+        types.push(scheme);
+    }
+    // uses may have pinned down weak variables, from this form or
+    // earlier ones
+    for name in env.weak().cloned().collect::<Vec<_>>() {
+        let scheme = env.value(&name).unwrap().clone();
+        let typ = infer.weaken(&scheme.typ, &scheme.vars, env)?;
+        env.bind(name, Scheme { typ, ..scheme });
+    }
+    // uses of later definitions may have pinned down the earlier ones
+    for name in defined {
+        let scheme = infer.finish(env.value(&name).unwrap());
+        env.bind(name, scheme);
+    }
+    let types = types.iter().map(|scheme| infer.finish(scheme)).collect();
+    Ok((types, infer, forms))
+}
+
+// each constraint, with the parameter its dictionary is passed as
+fn dictionaries(constraints: &[Constraint]) -> Dictionaries {
+    constraints.iter().map(|c| (c.clone(), traits::parameter(c))).collect()
+}
+
+fn span(expr: &Expr) -> Option<Span> {
+    expr.meta().and_then(|m| m.span)
+}
+
+fn operands(list: &List) -> Vec<&Expr> {
+    list.vals.iter().skip(1).collect()
+}
+
+// the name `(def name val)` or `(defn name ..)` defines
+fn definition(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::List(list) => match list.vals.iter().collect::<Vec<_>>().as_slice() {
+            [head, Expr::Symbol(name), _] if is_special(head, "def") => Some(&name.value),
+            [head, Expr::Symbol(name), ..] if is_special(head, "defn") => Some(&name.value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// the value `(def name val)` defines
+fn defined_value(expr: &Expr) -> Option<&Expr> {
+    match expr {
+        Expr::List(list) => match list.vals.iter().collect::<Vec<_>>().as_slice() {
+            [head, Expr::Symbol(_), val] if is_special(head, "def") => Some(val),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// whether `expr` is a value, so its type may be generalised. Anything
+// else, such as `(atom (list))`, may make something that code using
+// it at one type could write to and code using it at another read.
+fn is_value(expr: &Expr) -> bool {
+    match expr {
+        Expr::List(list) => match list.vals.iter().collect::<Vec<_>>().as_slice() {
+            [] => true,
+            [head, ..] if ["lambda", "macro", "quote", "defn"].iter().any(|name| is_special(head, name)) => true,
+            [head, _, val] if is_special(head, "the") => is_value(val),
+            _ => false,
+        },
+        _ => true,
+    }
+}
+
+// a form declaring a type, a trait or an impl, and which
+fn declaration(expr: &Expr) -> Option<(&'static str, &List)> {
+    match expr {
+        Expr::List(list) => ["defstruct", "defenum", "deftrait", "impl"].iter()
+            .find(|name| list.vals.front().is_some_and(|head| is_special(head, name)))
+            .map(|name| (*name, list)),
+        _ => None,
+    }
+}
+
+// whether `pattern` is `typ` with its variables filled in, and with what
+pub(crate) fn match_type(pattern: &Type, typ: &Type, subst: &mut HashMap<String, Type>) -> bool {
+    match (pattern, typ) {
+        (Type::Var(v), _) => match subst.get(&v.name) {
+            Some(bound) => bound == typ,
+            None => {
+                subst.insert(v.name.clone(), typ.clone());
+                true
+            }
+        },
+        (Type::Constructor(a), Type::Constructor(b)) => a == b,
+        (Type::Application(l1, r1), Type::Application(l2, r2)) =>
+            match_type(l1, l2, subst) && match_type(r1, r2, subst),
+        _ => false,
+    }
+}
+
+// whether `name` is still the method of a trait, with `scheme`, and
+// not something defined over it
+fn is_method(name: &str, scheme: &Scheme, env: &TypeEnv) -> bool {
+    match scheme.constraints.as_slice() {
+        [constraint] => env.get_trait(&constraint.trait_name)
+            .and_then(|tr| tr.bindings().into_iter().find(|(method, _)| method == name))
+            .is_some_and(|(_, binding)| binding == *scheme),
+        _ => false,
+    }
+}
+
+fn compile_time(expr: &Expr) -> bool {
+    match expr {
+        Expr::List(list) => match list.vals.iter().collect::<Vec<_>>().as_slice() {
+            [head, ..] if is_special(head, "begin-for-syntax") || is_special(head, "eval-when") => true,
+            [head, _, Expr::List(val)] if is_special(head, "def") =>
+                val.vals.front().is_some_and(|head| is_special(head, "macro")),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+pub(crate) fn is_special(expr: &Expr, name: &str) -> bool {
+    match expr {
+        Expr::Special(s) => s.name() == name,
+        Expr::Symbol(s) => s.value == name,
+        _ => false,
+    }
+}
+
+fn named(name: &str) -> Type {
+    TypeConstructor::new(name, Kind::Star).into()
+}
+
+type Locals = HashMap<String, Scheme>;
+
+#[derive(Default)]
+struct Infer {
+    next: usize,
+    subst: HashMap<String, Type>,
+    // where each variable got its type, for errors
+    origins: HashMap<String, Option<Span>>,
+    // the constraints still to be met, and where each arose
+    wanted: Vec<(Constraint, Option<Span>)>,
+    // each use of something with constraints, and what it wants
+    uses: Vec<(Symbol, Wanted)>,
+    // the dictionaries each let binding takes
+    lets: Vec<(Symbol, Dictionaries)>,
+    // the names defined at the top level, by the variables standing
+    // for their types until they are, and the uses before then
+    pending: HashMap<String, String>,
+    forward: Vec<(String, Symbol)>,
+    // those that take dictionaries, and the first constraint they do
+    taking: HashMap<String, Constraint>,
+}
+
+impl Infer {
+    fn fresh(&mut self) -> Type {
+        self.next += 1;
+        TypeVar::new(format!("t{}", self.next), Kind::Star).into()
+    }
+
+    fn infer(&mut self, expr: &Expr, locals: &Locals, env: &TypeEnv) -> Result<Type, TypeError> {
+        match expr {
+            Expr::Nil => Ok(builtin::unit().into()),
+            Expr::Bool(_) => Ok(named("bool")),
+            Expr::Int(_) => Ok(named("i64")),
+            Expr::String(_) => Ok(named("String")),
+            Expr::Keyword(_) => Ok(named("Keyword")),
+            Expr::Symbol(sym) => self.infer_symbol(sym, locals, env),
+            Expr::List(list) if list.vals.is_empty() => Ok(builtin::unit().into()),
+            Expr::List(list) => {
+                let head = &list.vals[0];
+                match self.special(head, locals, env) {
+                    Some(special) => self.infer_special(special, expr, list, locals, env),
+                    None => self.infer_call(head, operands(list), locals, env),
+                }
+            }
+            other => Err(TypeError::Unsupported(other.clone())),
+        }
+    }
+
+    fn local<'l>(&self, sym: &Symbol, locals: &'l Locals) -> Option<&'l Scheme> {
+        locals.get(&sym.key()).or_else(|| locals.get(&sym.value))
+    }
+
+    fn infer_symbol(&mut self, sym: &Symbol, locals: &Locals, env: &TypeEnv) -> Result<Type, TypeError> {
+        if let Some(scheme) = self.local(sym, locals) {
+            if let Type::Var(var) = &scheme.typ {
+                if let Some(name) = self.pending.get(&var.name) {
+                    self.forward.push((name.clone(), sym.clone()));
+                }
+            }
+            return Ok(self.instantiate_use(sym, scheme, false));
+        }
+        if let Some(scheme) = env.value(&sym.value) {
+            let method = is_method(&sym.value, scheme, env);
+            return Ok(self.instantiate_use(sym, scheme, method));
+        }
+        match env.variadic(&sym.value) {
+            // on its own, it takes as few arguments as it can
+            Some(variadic) => {
+                let (elem, result) = self.instantiate_variadic(variadic);
+                Ok((0..variadic.min.max(1)).fold(result, |to, _| Type::function(elem.clone(), to)))
+            }
+            None => Err(TypeError::Unbound(sym.value.clone(), sym.meta.span)),
+        }
+    }
+
+    // the special form `head` is, unless its name has been rebound
+    fn special(&self, head: &Expr, locals: &Locals, env: &TypeEnv) -> Option<Special> {
+        match head {
+            Expr::Special(s) => Some(s.clone()),
+            Expr::Symbol(sym) if self.local(sym, locals).is_none() && env.value(&sym.value).is_none() =>
+                Special::all().into_iter().find(|s| s.name() == sym.value),
+            _ => None,
+        }
+    }
+
+    fn infer_special(
+        &mut self,
+        special: Special,
+        expr: &Expr,
+        list: &List,
+        locals: &Locals,
+        env: &TypeEnv,
+    ) -> Result<Type, TypeError> {
+        let unsupported = || TypeError::Unsupported(expr.clone());
+        match (special, operands(list).as_slice()) {
+            (Special::Lambda(_), [Expr::Symbol(param), body]) => {
+                let from = self.fresh();
+                let locals = locals.update(param.key(), Scheme::mono(from.clone()));
+                let to = self.infer(body, &locals, env)?;
+                Ok(Type::function(from, to))
+            }
+            (Special::Let(_), [Expr::List(bindings), ..]) => {
+                if bindings.vals.len() % 2 != 0 {
+                    return Err(unsupported());
+                }
+                let mut locals = locals.clone();
+                let pairs: Vec<&Expr> = bindings.vals.iter().collect();
+                for pair in pairs.chunks(2) {
+                    match pair {
+                        [Expr::Symbol(name), val] => {
+                            let typ = self.infer(val, &locals, env)?;
+                            let scheme = match is_value(val) {
+                                true => self.generalize(&typ, &locals, env)?,
+                                false => Scheme::mono(typ),
+                            };
+                            if !scheme.constraints.is_empty() {
+                                self.lets.push((name.clone(), dictionaries(&scheme.constraints)));
+                            }
+                            locals.insert(name.key(), scheme);
+                        }
+                        _ => return Err(unsupported()),
+                    }
+                }
+                self.infer_body(list.vals.iter().skip(2), &locals, env)
+            }
+            (Special::If(_), [cond, then, rest @ ..]) if rest.len() <= 1 => {
+                // any value will do for the condition
+                self.infer(cond, locals, env)?;
+                let then_type = self.infer(then, locals, env)?;
+                match rest {
+                    [otherwise] => {
+                        let otherwise_type = self.infer(otherwise, locals, env)?;
+                        self.unify(&then_type, span(then), &otherwise_type, span(otherwise))?;
+                    }
+                    // without an else, it may be nil
+                    _ => self.unify(&builtin::unit().into(), span(expr), &then_type, span(then))?,
+                }
+                Ok(then_type)
+            }
+            (Special::Do(_), _) => self.infer_body(list.vals.iter().skip(1), locals, env),
+            // the name is in scope in its own definition
+            (Special::Def(_), [Expr::Symbol(name), val]) => {
+                let typ = self.fresh();
+                if let Type::Var(var) = &typ {
+                    self.pending.insert(var.name.clone(), name.value.clone());
+                }
+                let locals = locals.update(name.key(), Scheme::mono(typ.clone()));
+                let val_type = self.infer(val, &locals, env)?;
+                self.unify(&typ, span(val), &val_type, span(val))?;
+                Ok(val_type)
+            }
+            (Special::Defn(_), _) => {
+                let def = Defn::parse(list).map_err(|_| unsupported())?.desugar(&list.meta);
+                self.infer(&def, locals, env)
+            }
+            // taken at its word, with any variables in it fresh
+            (Special::The(_), [typ, val]) => {
+                let annotation = env.parse(typ)?;
+                let annotation = self.instantiate(&Scheme::generalize(annotation), None);
+                let val_type = self.infer(val, locals, env)?;
+                let at = span(&list.vals[0]).or_else(|| span(expr));
+                self.unify(&annotation, at, &val_type, span(val))?;
+                Ok(annotation)
+            }
+            (Special::Match(_), [scrutinee, clauses @ ..]) => self.infer_match(expr, scrutinee, clauses, locals, env),
+            (Special::Quote(_), [val]) => Ok(match val {
+                Expr::Symbol(_) => named("Symbol"),
+                Expr::List(_) | Expr::Map(_) => named("Expr"),
+                literal => self.infer(literal, locals, env)?,
+            }),
+            _ => Err(unsupported()),
+        }
+    }
+
+    // each pattern must fit the scrutinee, with each body, and its
+    // guard, seeing what its pattern binds. the bodies are of one type.
+    fn infer_match(
+        &mut self,
+        expr: &Expr,
+        scrutinee: &Expr,
+        clauses: &[&Expr],
+        locals: &Locals,
+        env: &TypeEnv,
+    ) -> Result<Type, TypeError> {
+        let scrutinee_type = self.infer(scrutinee, locals, env)?;
+        let mut result: Option<(Type, Option<Span>)> = None;
+        let mut rest = clauses;
+        while let [pattern, more @ ..] = rest {
+            let (guard, body, more) = match more {
+                [Expr::Keyword(k), guard, body, more @ ..] if k.value == "when" => (Some(guard), body, more),
+                [body, more @ ..] => (None, body, more),
+                [] => return Err(TypeError::Unsupported(expr.clone())),
+            };
+            let mut bound = locals.clone();
+            self.infer_pattern(pattern, &scrutinee_type, span(scrutinee), &mut bound, env)?;
+            // any value will do for a guard
+            if let Some(guard) = guard {
+                self.infer(guard, &bound, env)?;
+            }
+            let body_type = self.infer(body, &bound, env)?;
+            match &result {
+                Some((typ, at)) => self.unify(typ, *at, &body_type, span(body))?,
+                None => result = Some((body_type, span(body))),
+            }
+            rest = more;
+        }
+        Ok(result.map_or_else(|| self.fresh(), |(typ, _)| typ))
+    }
+
+    // binds what `pattern` binds, as `bind_pattern` does, at the types
+    // they have if it fits a value of `typ`
+    fn infer_pattern(
+        &mut self,
+        pattern: &Expr,
+        typ: &Type,
+        typ_at: Option<Span>,
+        locals: &mut Locals,
+        env: &TypeEnv,
+    ) -> Result<(), TypeError> {
+        let unsupported = || TypeError::Unsupported(pattern.clone());
+        let literal = match pattern {
+            Expr::Symbol(s) => match s.value.as_str() {
+                "_" => return Ok(()),
+                "nil" => builtin::unit().into(),
+                "true" | "false" => named("bool"),
+                "&" => return Err(unsupported()),
+                _ => {
+                    locals.insert(s.key(), Scheme::mono(typ.clone()));
+                    return Ok(());
+                }
+            },
+            Expr::Nil | Expr::Bool(_) | Expr::Int(_) | Expr::String(_) | Expr::Keyword(_) => self.infer(pattern, locals, env)?,
+            Expr::List(list) => match list.vals.front() {
+                Some(head) if is_special(head, "quote") => self.infer(pattern, locals, env)?,
+                Some(head) if is_special(head, "list") =>
+                    return self.infer_list_pattern(list.vals.iter().skip(1).collect(), typ, typ_at, locals, env),
+                Some(head) if is_special(head, "hash-map") => return Err(unsupported()),
+                Some(Expr::Symbol(s)) if env.datatypes().any(|d| d.variant(&s.value).is_some()) => {
+                    let constructor = env.value(&s.value).ok_or_else(unsupported)?;
+                    let mut constructor = self.instantiate(constructor, span(pattern));
+                    for field in list.vals.iter().skip(1) {
+                        let (from, to) = match self.resolve(&constructor).as_function() {
+                            Some((from, to)) if !is_special(field, "&") => (from.clone(), to.clone()),
+                            _ => return Err(unsupported()),
+                        };
+                        self.infer_pattern(field, &from, span(pattern), locals, env)?;
+                        constructor = to;
+                    }
+                    constructor
+                }
+                _ => return self.infer_list_pattern(list.vals.iter().collect(), typ, typ_at, locals, env),
+            },
+            _ => return Err(unsupported()),
+        };
+        self.unify(typ, typ_at, &literal, span(pattern))
+    }
+
+    // a list pattern fits a `Vec`, and a pattern after `&` the rest of it
+    fn infer_list_pattern(
+        &mut self,
+        patterns: Vec<&Expr>,
+        typ: &Type,
+        typ_at: Option<Span>,
+        locals: &mut Locals,
+        env: &TypeEnv,
+    ) -> Result<(), TypeError> {
+        let elem = self.fresh();
+        let vec = Type::from(builtin::vec()).apply(elem.clone())?;
+        self.unify(typ, typ_at, &vec, patterns.first().and_then(|p| span(p)))?;
+        let mut rest = false;
+        for pattern in patterns {
+            if is_special(pattern, "&") {
+                rest = true;
+            } else {
+                self.infer_pattern(pattern, if rest { &vec } else { &elem }, typ_at, locals, env)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn infer_body<'e>(
+        &mut self,
+        body: impl Iterator<Item = &'e Expr>,
+        locals: &Locals,
+        env: &TypeEnv,
+    ) -> Result<Type, TypeError> {
+        let mut typ = builtin::unit().into();
+        for expr in body {
+            typ = self.infer(expr, locals, env)?;
+        }
+        Ok(typ)
+    }
+
+    fn infer_call(&mut self, head: &Expr, args: Vec<&Expr>, locals: &Locals, env: &TypeEnv) -> Result<Type, TypeError> {
+        if let Expr::Symbol(sym) = head {
+            if self.local(sym, locals).is_none() && env.value(&sym.value).is_none() {
+                if let Some(variadic) = env.variadic(&sym.value) {
+                    return self.infer_variadic(sym, variadic, args, locals, env);
+                }
+            }
+        }
+        let mut fun = self.infer(head, locals, env)?;
+        let fun_at = span(head);
+        for arg in args {
+            let arg_type = self.infer(arg, locals, env)?;
+            let resolved = self.resolve(&fun);
+            fun = match resolved.as_function() {
+                Some((from, to)) => {
+                    self.unify(from, fun_at, &arg_type, span(arg))?;
+                    to.clone()
+                }
+                None => {
+                    let to = self.fresh();
+                    self.unify(&Type::function(arg_type, to.clone()), span(arg), &fun, fun_at)?;
+                    to
+                }
+            };
+        }
+        Ok(fun)
+    }
+
+    fn infer_variadic(
+        &mut self,
+        sym: &Symbol,
+        variadic: &Variadic,
+        args: Vec<&Expr>,
+        locals: &Locals,
+        env: &TypeEnv,
+    ) -> Result<Type, TypeError> {
+        let (mut elem, result) = self.instantiate_variadic(variadic);
+        for arg in args.iter() {
+            if !variadic.shared {
+                elem = self.instantiate_variadic(variadic).0;
+            }
+            let arg_type = self.infer(arg, locals, env)?;
+            self.unify(&elem, sym.meta.span, &arg_type, span(arg))?;
+        }
+        // too few arguments is a partial application
+        let missing = variadic.min.saturating_sub(args.len());
+        Ok((0..missing).fold(result, |to, _| Type::function(elem.clone(), to)))
+    }
+
+    // the type `sym` has here, noting what it wants passed
+    fn instantiate_use(&mut self, sym: &Symbol, scheme: &Scheme, method: bool) -> Type {
+        let typ = self.instantiate(scheme, sym.meta.span);
+        if !scheme.constraints.is_empty() {
+            let constraints = self.wanted[self.wanted.len() - scheme.constraints.len()..].iter()
+                .map(|(c, _)| c.clone())
+                .collect();
+            self.uses.push((sym.clone(), Wanted { method, constraints }));
+        }
+        typ
+    }
+
+    // the constraints of the scheme are wanted at `at`
+    fn instantiate(&mut self, scheme: &Scheme, at: Option<Span>) -> Type {
+        let subst: HashMap<String, Type> =
+            scheme.vars.iter().map(|v| (v.name.clone(), self.fresh())).collect();
+        for constraint in &scheme.constraints {
+            self.wanted.push((constraint.substitute(&subst), at));
+        }
+        scheme.typ.substitute(&subst)
+    }
+
+    // a scheme as it stands once inference is over, its variables
+    // renamed
+    fn finish(&self, scheme: &Scheme) -> Scheme {
+        let typ = self.resolve(&scheme.typ);
+        let constraints = scheme.constraints.iter()
+            .map(|c| Constraint::new(c.trait_name.clone(), self.resolve(&c.typ)))
+            .collect();
+        let vars = typ.vars().into_iter().filter(|v| !v.is_weak()).collect();
+        Scheme::qualified(vars, constraints, typ)
+    }
+
+    // how to pass dictionaries, now every type is known
+    fn elaboration(&self, forms: Vec<Dictionaries>) -> Result<Elaboration, TypeError> {
+        let resolve = |dicts: &Dictionaries| -> Dictionaries {
+            dicts.iter().map(|(c, name)| (self.resolve_constraint(c), name.clone())).collect()
+        };
+        let mut elaboration = Elaboration { forms: forms.iter().map(resolve).collect(), ..Elaboration::default() };
+        // a use before the definition could not pass what it takes
+        if let Some((constraint, sym)) = self.forward.iter().find_map(|(name, sym)| Some((self.taking.get(name)?, sym))) {
+            return Err(TypeError::Ambiguous { constraint: self.resolve_constraint(constraint), span: sym.meta.span });
+        }
+        // each use or binding is found again by its span, so one
+        // without, or copied by a macro to where it needs others, is
+        // not something this can do
+        let unsupported = |sym: &Symbol| TypeError::Unsupported(Expr::Symbol(sym.clone()));
+        for (name, dicts) in &self.lets {
+            let span = name.meta.span.ok_or_else(|| unsupported(name))?;
+            let dicts = resolve(dicts);
+            if elaboration.lets.get(&span).is_some_and(|seen| *seen != dicts) {
+                return Err(unsupported(name));
+            }
+            elaboration.lets.insert(span, dicts);
+        }
+        for (sym, wanted) in &self.uses {
+            let key = (sym.meta.span.ok_or_else(|| unsupported(sym))?, sym.value.clone());
+            let constraints = wanted.constraints.iter().map(|c| self.resolve_constraint(c)).collect();
+            let wanted = Wanted { method: wanted.method, constraints };
+            if elaboration.uses.get(&key).is_some_and(|seen| *seen != wanted) {
+                return Err(unsupported(sym));
+            }
+            elaboration.uses.insert(key, wanted);
+        }
+        Ok(elaboration)
+    }
+
+    fn resolve_constraint(&self, constraint: &Constraint) -> Constraint {
+        Constraint::new(constraint.trait_name.clone(), self.resolve(&constraint.typ))
+    }
+
+    // each method must have the type its trait gives it, for the type
+    // of the impl, given the impl's constraints. Gives back the
+    // dictionaries the methods take for those.
+    fn infer_impl(&mut self, form: &List, locals: &Locals, env: &TypeEnv) -> Result<Dictionaries, TypeError> {
+        let (instance, methods) = Instance::parse(form, env)?;
+        let tr = env.get_trait(&instance.head.trait_name).expect("parsed against it");
+        let subst: HashMap<String, Type> =
+            instance.head.typ.vars().into_iter().map(|v| (v.name, self.fresh())).collect();
+        for (name, body) in methods {
+            let expected = instance.method_type(tr, tr.method(&name).expect("parsed against it"));
+            let own = expected.vars().into_iter().filter(|v| !subst.contains_key(&v.name)).collect();
+            let expected = self.instantiate(&Scheme::over(own, expected.substitute(&subst)), None);
+            let found = self.infer(&body, locals, env)?;
+            self.unify(&expected, span(&form.vals[1]), &found, span(&body))?;
+        }
+        Ok(instance.constraints.iter().map(|c| (c.substitute(&subst), traits::parameter(c))).collect())
+    }
+
+    // meets what constraints it can with the impls in `env`, leaving
+    // those on types not yet known
+    fn reduce(&mut self, env: &TypeEnv) -> Result<(), TypeError> {
+        let mut pending = std::mem::take(&mut self.wanted);
+        while let Some((constraint, at)) = pending.pop() {
+            let constraint = Constraint::new(constraint.trait_name, self.resolve(&constraint.typ));
+            if let Type::Var(_) = constraint.typ {
+                self.wanted.push((constraint, at));
+                continue;
+            }
+            match env.instance(&constraint) {
+                Some((_, constraints)) => pending.extend(constraints.into_iter().map(|c| (c, at))),
+                None if env.instances(&constraint.trait_name).any(|i| unifiable(&i.head.typ, &constraint.typ)) =>
+                    self.wanted.push((constraint, at)),
+                None => return Err(TypeError::NoInstance { constraint, span: at }),
+            }
+        }
+        Ok(())
+    }
+
+    // the type of each argument and of the result
+    fn instantiate_variadic(&mut self, variadic: &Variadic) -> (Type, Type) {
+        let typ = self.instantiate(&variadic.scheme, None);
+        match typ.as_function() {
+            Some((elem, result)) => (elem.clone(), result.clone()),
+            None => (self.fresh(), typ),
+        }
+    }
+
+    // generalizes over the variables that are not fixed by `locals`,
+    // taking the constraints on only those
+    fn generalize(&mut self, typ: &Type, locals: &Locals, env: &TypeEnv) -> Result<Scheme, TypeError> {
+        self.reduce(env)?;
+        let typ = self.resolve(typ);
+        let mut fixed = Vec::new();
+        for scheme in locals.values() {
+            let bound = self.resolve(&scheme.typ);
+            fixed.extend(bound.vars().into_iter().filter(|v| !scheme.vars.contains(v)));
+        }
+        for name in env.weak() {
+            fixed.extend(self.resolve(&env.value(name).expect("bound weakly").typ).vars());
+        }
+        let vars: Vec<TypeVar> = typ.vars().into_iter().filter(|v| !v.is_weak() && !fixed.contains(v)).collect();
+        let (constraints, wanted): (Vec<_>, Vec<_>) = std::mem::take(&mut self.wanted).into_iter()
+            .map(|(c, at)| (Constraint::new(c.trait_name, self.resolve(&c.typ)), at))
+            .partition(|(c, _)| {
+                let over = c.typ.vars();
+                !over.is_empty() && over.iter().all(|v| vars.contains(v))
+            });
+        self.wanted = wanted;
+        let mut unique: Vec<Constraint> = Vec::new();
+        for (c, _) in constraints {
+            if !unique.contains(&c) {
+                unique.push(c);
+            }
+        }
+        Ok(Scheme { vars, constraints: unique, typ })
+    }
+
+    // `typ` as it stands, with each variable left in it but those
+    // `bound` made weak, in whatever else it is in too
+    fn weaken(&mut self, typ: &Type, bound: &[TypeVar], env: &mut TypeEnv) -> Result<Type, TypeError> {
+        self.reduce(env)?;
+        let typ = self.resolve(typ);
+        for var in typ.vars().into_iter().filter(|v| !v.is_weak() && !bound.contains(v)) {
+            let weak = env.weak_var(var.kind.clone());
+            self.subst.insert(var.name, weak.into());
+        }
+        Ok(self.resolve(&typ))
+    }
+
+    fn resolve(&self, typ: &Type) -> Type {
+        match typ {
+            Type::Var(v) => match self.subst.get(&v.name) {
+                Some(t) => self.resolve(t),
+                None => typ.clone(),
+            },
+            Type::Constructor(_) => typ.clone(),
+            Type::Application(l, r) => Type::Application(Box::new(self.resolve(l)), Box::new(self.resolve(r))),
+        }
+    }
+
+    // follows bound variables, to the type and where it came from
+    fn walk(&self, typ: &Type) -> (Type, Option<Option<Span>>) {
+        let mut typ = typ.clone();
+        let mut origin = None;
+        while let Type::Var(v) = &typ {
+            match self.subst.get(&v.name) {
+                Some(t) => {
+                    origin = origin.or_else(|| self.origins.get(&v.name).copied());
+                    typ = t.clone();
+                }
+                None => break,
+            }
+        }
+        (typ, origin)
+    }
+
+    /// Makes `found`, at `found_at`, the same type as `expected`, which
+    /// came from `expected_at`. A mismatch is reported at `found_at`,
+    /// and at wherever a variable on either side got its type.
+    fn unify(
+        &mut self,
+        expected: &Type,
+        expected_at: Option<Span>,
+        found: &Type,
+        found_at: Option<Span>,
+    ) -> Result<(), TypeError> {
+        let (expected, expected_origin) = self.walk(expected);
+        let (found, found_origin) = self.walk(found);
+        let other = expected_origin.or(found_origin).unwrap_or(expected_at);
+        match (&expected, &found) {
+            (Type::Var(a), Type::Var(b)) if a == b => Ok(()),
+            // a weak variable keeps its name where it can
+            (Type::Var(weak), Type::Var(var)) if weak.is_weak() => self.bind(var, &expected, expected_at),
+            (Type::Var(var), typ) => self.bind(var, typ, found_at),
+            (typ, Type::Var(var)) => self.bind(var, typ, expected_at),
+            (Type::Constructor(a), Type::Constructor(b)) if a == b => Ok(()),
+            (Type::Application(l1, r1), Type::Application(l2, r2)) => {
+                let result = self.unify(l1, expected_at, l2, found_at)
+                    .and_then(|_| self.unify(r1, expected_at, r2, found_at));
+                // report the whole types, not the parts that differ
+                result.map_err(|e| match e {
+                    TypeError::Mismatch { span, other, .. } => TypeError::Mismatch {
+                        expected: self.resolve(&expected), found: self.resolve(&found), span, other,
+                    },
+                    e => e,
+                })
+            }
+            _ => Err(TypeError::Mismatch {
+                expected: self.resolve(&expected), found: self.resolve(&found), span: found_at, other,
+            }),
+        }
+    }
+
+    fn bind(&mut self, var: &TypeVar, typ: &Type, at: Option<Span>) -> Result<(), TypeError> {
+        let typ = self.resolve(typ);
+        if typ.vars().contains(var) {
+            return Err(TypeError::Infinite { var: var.clone(), typ, span: at });
+        }
+        self.subst.insert(var.name.clone(), typ);
+        self.origins.insert(var.name.clone(), at);
+        Ok(())
+    }
+}
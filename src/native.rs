@@ -0,0 +1,229 @@
+use crate::eval::*;
+use crate::exprs::*;
+use im::Vector;
+use std::rc::Rc;
+
+/// Conversion from a pangolisp value into a rust argument.
+pub trait FromExpr: Sized {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError>;
+}
+
+/// Conversion from a rust return value into a pangolisp value. This
+/// is fallible so that natives may return a `Result`.
+pub trait IntoExpr {
+    fn into_expr(self) -> Result<Expr, EvalError>;
+}
+
+impl FromExpr for Expr {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError> {
+        Ok(expr.clone())
+    }
+}
+
+impl FromExpr for i64 {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError> {
+        match expr {
+            Expr::Int(i) => Ok(i.value),
+            _ => Err(EvalError::BadParameter("int", expr.clone())),
+        }
+    }
+}
+
+impl FromExpr for bool {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError> {
+        match expr {
+            Expr::Bool(b) => Ok(b.value),
+            _ => Err(EvalError::BadParameter("bool", expr.clone())),
+        }
+    }
+}
+
+impl FromExpr for String {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError> {
+        match expr {
+            Expr::String(s) => Ok(s.value.clone()),
+            _ => Err(EvalError::BadParameter("string", expr.clone())),
+        }
+    }
+}
+
+impl FromExpr for Symbol {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError> {
+        match expr {
+            Expr::Symbol(s) => Ok(s.clone()),
+            _ => Err(EvalError::BadParameter("symbol", expr.clone())),
+        }
+    }
+}
+
+impl FromExpr for Keyword {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError> {
+        match expr {
+            Expr::Keyword(k) => Ok(k.clone()),
+            _ => Err(EvalError::BadParameter("keyword", expr.clone())),
+        }
+    }
+}
+
+impl FromExpr for List {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError> {
+        match expr {
+            Expr::List(l) => Ok(l.clone()),
+            _ => Err(EvalError::BadParameter("list", expr.clone())),
+        }
+    }
+}
+
+impl FromExpr for Map {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError> {
+        match expr {
+            Expr::Map(m) => Ok(m.clone()),
+            _ => Err(EvalError::BadParameter("map", expr.clone())),
+        }
+    }
+}
+
+impl FromExpr for Atom {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError> {
+        match expr {
+            Expr::Atom(a) => Ok(a.clone()),
+            _ => Err(EvalError::BadParameter("atom", expr.clone())),
+        }
+    }
+}
+
+impl FromExpr for Data {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError> {
+        match expr {
+            Expr::Data(d) => Ok(d.clone()),
+            _ => Err(EvalError::BadParameter("data", expr.clone())),
+        }
+    }
+}
+
+impl<T: FromExpr + Clone> FromExpr for Vector<T> {
+    fn from_expr(expr: &Expr) -> Result<Self, EvalError> {
+        match expr {
+            Expr::List(l) => l.vals.iter().map(T::from_expr).collect(),
+            _ => Err(EvalError::BadParameter("list", expr.clone())),
+        }
+    }
+}
+
+impl IntoExpr for Expr {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        Ok(self)
+    }
+}
+
+impl IntoExpr for () {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        Ok(Expr::Nil)
+    }
+}
+
+impl IntoExpr for i64 {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        Ok(self.into())
+    }
+}
+
+impl IntoExpr for bool {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        Ok(self.into())
+    }
+}
+
+impl IntoExpr for String {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        Ok(self.into())
+    }
+}
+
+impl IntoExpr for Symbol {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        Ok(Expr::Symbol(self))
+    }
+}
+
+impl IntoExpr for Keyword {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        Ok(Expr::Keyword(self))
+    }
+}
+
+impl IntoExpr for List {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        Ok(Expr::List(self))
+    }
+}
+
+impl IntoExpr for Map {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        Ok(Expr::Map(self))
+    }
+}
+
+impl IntoExpr for Atom {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        Ok(Expr::Atom(self))
+    }
+}
+
+impl IntoExpr for Data {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        Ok(Expr::Data(self))
+    }
+}
+
+impl<T: IntoExpr + Clone> IntoExpr for Vector<T> {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        let vals = self.into_iter().map(T::into_expr).collect::<Result<Vector<_>, _>>()?;
+        Ok(Expr::List(List::from(vals)))
+    }
+}
+
+impl<T: IntoExpr> IntoExpr for Option<T> {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        self.map_or(Ok(Expr::Nil), T::into_expr)
+    }
+}
+
+impl<T: IntoExpr> IntoExpr for Result<T, EvalError> {
+    fn into_expr(self) -> Result<Expr, EvalError> {
+        self?.into_expr()
+    }
+}
+
+/// A rust function that can be registered as a native. `Args` is
+/// the tuple of its argument types and only exists to keep the impls
+/// for each arity apart.
+pub trait IntoNative<Args> {
+    fn into_native(self, name: &str) -> Native;
+}
+
+macro_rules! into_native {
+    ($count:expr; $($arg:ident),*) => {
+        impl<F, R, $($arg),*> IntoNative<($($arg,)*)> for F
+        where F: Fn($($arg),*) -> R + 'static,
+              R: IntoExpr,
+              $($arg: FromExpr),*
+        {
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn into_native(self, name: &str) -> Native {
+                let fun = move |_eval: &mut Eval, args: &[Expr]| {
+                    let mut args = args.iter();
+                    $(let $arg = $arg::from_expr(args.next().unwrap())?;)*
+                    (self)($($arg),*).into_expr()
+                };
+                Native::new(name, Arity::Exactly($count), Rc::new(fun), Meta::default())
+            }
+        }
+    }
+}
+
+into_native!(0;);
+into_native!(1; A);
+into_native!(2; A, B);
+into_native!(3; A, B, C);
+into_native!(4; A, B, C, D);
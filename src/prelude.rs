@@ -0,0 +1,375 @@
+use crate::eval::*;
+use crate::exprs::*;
+use crate::native::*;
+use crate::forms::Forms;
+use crate::reader::read;
+use crate::types::*;
+use im::{HashMap, OrdSet, Vector};
+use std::convert::TryFrom;
+
+/// Binds the standard library into `eval`.
+pub fn load(eval: &mut Eval) {
+    arithmetic(eval);
+    comparison(eval);
+    lists(eval);
+    strings(eval);
+    predicates(eval);
+    macros(eval);
+    errors(eval);
+    atoms(eval);
+}
+
+// Arithmetic is checked: overflow is an error, never a panic or a wrap.
+
+fn ints(args: &[Expr]) -> Result<Vec<i64>, EvalError> {
+    args.iter().map(i64::from_expr).collect()
+}
+
+fn fold_ints(
+    name: &'static str,
+    args: &[Expr],
+    op: fn(i64, i64) -> Option<i64>,
+) -> Result<Expr, EvalError> {
+    let ints = ints(args)?;
+    let mut iter = ints.into_iter();
+    let first = iter.next().unwrap_or_default();
+    iter.try_fold(first, |acc, i| op(acc, i).ok_or_else(|| EvalError::Overflow(name, args.to_vec())))
+        .map(Expr::from)
+}
+
+fn arithmetic(eval: &mut Eval) {
+    // these take at least two arguments so that `(+ 1)` is a partial
+    // application rather than an immediate call.
+    eval.register_native("+", Arity::AtLeast(2), |_, args| fold_ints("+", args, i64::checked_add));
+    eval.register_native("-", Arity::AtLeast(2), |_, args| fold_ints("-", args, i64::checked_sub));
+    eval.register_native("*", Arity::AtLeast(2), |_, args| fold_ints("*", args, i64::checked_mul));
+    eval.register_native("/", Arity::AtLeast(2), |_, args| {
+        if ints(&args[1..])?.contains(&0) {
+            return Err(EvalError::DivideByZero);
+        }
+        fold_ints("/", args, i64::checked_div)
+    });
+    eval.register_fn("mod", |a: i64, b: i64| {
+        if b == 0 {
+            return Err(EvalError::DivideByZero);
+        }
+        a.checked_rem_euclid(b).ok_or_else(|| EvalError::Overflow("mod", vec![a.into(), b.into()]))
+    });
+    eval.register_fn("neg", |a: i64| {
+        a.checked_neg().ok_or_else(|| EvalError::Overflow("neg", vec![a.into()]))
+    });
+    eval.register_fn("inc", |a: i64| {
+        a.checked_add(1).ok_or_else(|| EvalError::Overflow("inc", vec![a.into()]))
+    });
+    eval.register_fn("dec", |a: i64| {
+        a.checked_sub(1).ok_or_else(|| EvalError::Overflow("dec", vec![a.into()]))
+    });
+}
+
+fn compare_ints(args: &[Expr], op: fn(&i64, &i64) -> bool) -> Result<Expr, EvalError> {
+    let ints = ints(args)?;
+    Ok(ints.windows(2).all(|w| op(&w[0], &w[1])).into())
+}
+
+fn comparison(eval: &mut Eval) {
+    eval.register_native("=", Arity::AtLeast(2), |_, args| {
+        Ok(args.windows(2).all(|w| w[0] == w[1]).into())
+    });
+    eval.register_native("<", Arity::AtLeast(2), |_, args| compare_ints(args, i64::lt));
+    eval.register_native(">", Arity::AtLeast(2), |_, args| compare_ints(args, i64::gt));
+    eval.register_native("<=", Arity::AtLeast(2), |_, args| compare_ints(args, i64::le));
+    eval.register_native(">=", Arity::AtLeast(2), |_, args| compare_ints(args, i64::ge));
+    eval.register_fn("not", |e: Expr| !e.is_truthy());
+}
+
+/// The elements of a list, treating `nil` as the empty list.
+fn seq(expr: &Expr) -> Result<Vector<Expr>, EvalError> {
+    match expr {
+        Expr::Nil => Ok(Vector::new()),
+        Expr::List(l) => Ok(l.vals.clone()),
+        _ => Err(EvalError::BadParameter("list", expr.clone())),
+    }
+}
+
+fn list(vals: Vector<Expr>) -> Expr {
+    Expr::List(List::from(vals))
+}
+
+fn lists(eval: &mut Eval) {
+    eval.register_native("list", Arity::AtLeast(0), |_, args| {
+        Ok(list(args.iter().cloned().collect()))
+    });
+    eval.register_native("hash-map", Arity::AtLeast(0), |_, args| {
+        if args.len() % 2 != 0 {
+            return Err(EvalError::BadParameter("key value pairs", list(args.iter().cloned().collect())));
+        }
+        let vals: HashMap<Expr, Expr> =
+            args.chunks(2).map(|kv| (kv[0].clone(), kv[1].clone())).collect();
+        Ok(Expr::Map(Map::from(vals)))
+    });
+    eval.register_fn("get", |m: Map, key: Expr| m.vals.get(&key).cloned());
+    eval.register_fn("cons", |head: Expr, tail: Expr| {
+        let mut vals = seq(&tail)?;
+        vals.push_front(head);
+        Ok(list(vals))
+    });
+    eval.register_fn("first", |l: Expr| Ok(seq(&l)?.front().cloned()));
+    eval.register_fn("rest", |l: Expr| {
+        let mut vals = seq(&l)?;
+        vals.pop_front();
+        Ok(list(vals))
+    });
+    eval.register_native("concat", Arity::AtLeast(0), |_, args| {
+        let mut vals = Vector::new();
+        for arg in args {
+            vals.append(seq(arg)?);
+        }
+        Ok(list(vals))
+    });
+    eval.register_fn("count", |e: Expr| match &e {
+        Expr::String(s) => Ok(s.value.chars().count() as i64),
+        Expr::Map(m) => Ok(m.vals.len() as i64),
+        _ => Ok(seq(&e)?.len() as i64),
+    });
+    eval.register_native("range", Arity::Exactly(2), |eval, args| {
+        let (start, end) = (i64::from_expr(&args[0])?, i64::from_expr(&args[1])?);
+        // it could be far bigger than the limit, so check first
+        eval.reserve(end.saturating_sub(start).max(0) as u64)?;
+        Ok(list((start..end).map(Expr::from).collect()))
+    });
+    eval.register_native("map", Arity::Exactly(2), |eval, args| {
+        let mut vals = Vector::new();
+        for val in seq(&args[1])? {
+            vals.push_back(eval.apply(args[0].clone(), vec![val])?);
+        }
+        Ok(list(vals))
+    });
+    eval.register_native("filter", Arity::Exactly(2), |eval, args| {
+        let mut vals = Vector::new();
+        for val in seq(&args[1])? {
+            if eval.apply(args[0].clone(), vec![val.clone()])?.is_truthy() {
+                vals.push_back(val);
+            }
+        }
+        Ok(list(vals))
+    });
+    eval.register_native("reduce", Arity::Exactly(3), |eval, args| {
+        let mut acc = args[1].clone();
+        for val in seq(&args[2])? {
+            acc = eval.apply(args[0].clone(), vec![acc, val])?;
+        }
+        Ok(acc)
+    });
+}
+
+/// How a value is rendered by `str`: strings are included as they
+/// are, `nil` is empty and everything else is printed.
+fn to_str(expr: &Expr) -> String {
+    match expr {
+        Expr::Nil => String::new(),
+        Expr::String(s) => s.value.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn format(args: &[Expr]) -> Result<Expr, EvalError> {
+    let template = String::from_expr(&args[0])?;
+    let mut vals = args[1..].iter();
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                out.push(ch);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                let val = vals.next()
+                    .ok_or_else(|| EvalError::BadParameter("more format arguments", args[0].clone()))?;
+                out.push_str(&to_str(val));
+            }
+            _ => out.push(ch),
+        }
+    }
+    if let Some(extra) = vals.next() {
+        return Err(EvalError::BadParameter("fewer format arguments", extra.clone()));
+    }
+    Ok(out.into())
+}
+
+fn strings(eval: &mut Eval) {
+    eval.register_native("str", Arity::AtLeast(0), |_, args| {
+        Ok(args.iter().map(to_str).collect::<String>().into())
+    });
+    eval.register_fn("split", |s: String, sep: String| {
+        list(s.split(sep.as_str()).map(Expr::from).collect())
+    });
+    eval.register_fn("join", |sep: String, l: Expr| {
+        let parts: Vec<String> = seq(&l)?.iter().map(to_str).collect();
+        Ok(parts.join(&sep))
+    });
+    eval.register_fn("keyword", |name: String| Keyword::new(name, Meta::default()));
+    eval.register_native("format", Arity::AtLeast(1), |_, args| format(args));
+}
+
+fn predicates(eval: &mut Eval) {
+    eval.register_fn("nil?", |e: Expr| matches!(e, Expr::Nil));
+    eval.register_fn("bool?", |e: Expr| matches!(e, Expr::Bool(_)));
+    eval.register_fn("int?", |e: Expr| matches!(e, Expr::Int(_)));
+    eval.register_fn("string?", |e: Expr| matches!(e, Expr::String(_)));
+    eval.register_fn("symbol?", |e: Expr| matches!(e, Expr::Symbol(_)));
+    eval.register_fn("keyword?", |e: Expr| matches!(e, Expr::Keyword(_)));
+    eval.register_fn("list?", |e: Expr| matches!(e, Expr::List(_)));
+    eval.register_fn("map?", |e: Expr| matches!(e, Expr::Map(_)));
+    eval.register_fn("atom?", |e: Expr| matches!(e, Expr::Atom(_)));
+    eval.register_fn("data?", |e: Expr| matches!(e, Expr::Data(_)));
+    eval.register_fn("fn?", |e: Expr| matches!(e, Expr::Fun(_) | Expr::Native(_) | Expr::Continuation(_)));
+}
+
+/// Gives every symbol in `expr` the given scopes.
+fn with_scopes(expr: &Expr, scopes: &OrdSet<usize>) -> Expr {
+    match expr {
+        Expr::Symbol(s) => {
+            let mut s = s.clone();
+            s.meta.scopes = scopes.clone();
+            Expr::Symbol(s)
+        }
+        Expr::List(l) => {
+            let vals = l.vals.iter().map(|v| with_scopes(v, scopes)).collect();
+            Expr::List(List::new(vals, l.meta.clone()))
+        }
+        other => other.clone(),
+    }
+}
+
+fn errors(eval: &mut Eval) {
+    eval.register_fn("throw", |val: Expr| -> Result<Expr, EvalError> { Err(EvalError::Thrown(val)) });
+}
+
+fn atoms(eval: &mut Eval) {
+    eval.register_fn("atom", |val: Expr| Atom::new(val, Meta::default()));
+    eval.register_fn("deref", |a: Atom| a.get());
+    eval.register_fn("reset!", |a: Atom, val: Expr| {
+        a.set(val.clone());
+        val
+    });
+    eval.register_fn("compare-and-set!", |a: Atom, old: Expr, new: Expr| a.compare_and_set(&old, new));
+    // `f` may itself change the atom, in which case it is run again on
+    // the new value, so an update is never lost.
+    eval.register_native("swap!", Arity::AtLeast(2), |eval, args| {
+        let a = Atom::from_expr(&args[0])?;
+        loop {
+            let old = a.get();
+            let mut call = vec![old.clone()];
+            call.extend_from_slice(&args[2..]);
+            let new = eval.apply(args[1].clone(), call)?;
+            if a.compare_and_set(&old, new.clone()) {
+                return Ok(new);
+            }
+        }
+    });
+}
+
+// a scheme over every variable in the type `src`
+fn sig(env: &TypeEnv, src: &str) -> Scheme {
+    let form = Forms::new(src).next().expect("a type").expect("a form");
+    Scheme::generalize(env.parse(&read(form).expect("a type")).expect("a type"))
+}
+
+/// Gives the standard library its types, for inference. Those that
+/// take any number of arguments have the type of each and of the
+/// result. Not all of it can be typed: `hash-map`, for one.
+pub fn types(env: &mut TypeEnv) {
+    for name in ["Keyword", "Symbol", "Expr"] {
+        env.define(TypeConstructor::new(name, Kind::Star));
+    }
+    env.define(TypeConstructor::new("Atom", Kind::of_arity(1)));
+    let values = [
+        ("nil", "()"),
+        ("true", "bool"),
+        ("false", "bool"),
+        ("mod", "(-> i64 i64 i64)"),
+        ("neg", "(-> i64 i64)"),
+        ("inc", "(-> i64 i64)"),
+        ("dec", "(-> i64 i64)"),
+        ("not", "(-> 'a bool)"),
+        ("cons", "(-> 'a (Vec 'a) (Vec 'a))"),
+        ("first", "(-> (Vec 'a) 'a)"),
+        ("rest", "(-> (Vec 'a) (Vec 'a))"),
+        ("count", "(-> 'a i64)"),
+        ("range", "(-> i64 i64 (Vec i64))"),
+        ("map", "(-> (-> 'a 'b) (Vec 'a) (Vec 'b))"),
+        ("filter", "(-> (-> 'a bool) (Vec 'a) (Vec 'a))"),
+        ("reduce", "(-> (-> 'b 'a 'b) 'b (Vec 'a) 'b)"),
+        ("split", "(-> String String (Vec String))"),
+        ("join", "(-> String (Vec 'a) String)"),
+        ("keyword", "(-> String Keyword)"),
+        ("throw", "(-> 'a 'b)"),
+        ("atom", "(-> 'a (Atom 'a))"),
+        ("deref", "(-> (Atom 'a) 'a)"),
+        ("reset!", "(-> (Atom 'a) 'a 'a)"),
+        ("swap!", "(-> (Atom 'a) (-> 'a 'a) 'a)"),
+        ("compare-and-set!", "(-> (Atom 'a) 'a 'a bool)"),
+    ];
+    for (name, typ) in values.iter() {
+        let scheme = sig(env, typ);
+        env.bind(*name, scheme);
+    }
+    for name in ["nil?", "bool?", "int?", "string?", "symbol?", "keyword?", "list?", "map?", "fn?", "atom?", "data?"] {
+        let scheme = sig(env, "(-> 'a bool)");
+        env.bind(name, scheme);
+    }
+    let variadics = [
+        ("+", "(-> i64 i64)", 2, true),
+        ("-", "(-> i64 i64)", 2, true),
+        ("*", "(-> i64 i64)", 2, true),
+        ("/", "(-> i64 i64)", 2, true),
+        ("=", "(-> 'a bool)", 2, true),
+        ("<", "(-> i64 bool)", 2, true),
+        (">", "(-> i64 bool)", 2, true),
+        ("<=", "(-> i64 bool)", 2, true),
+        (">=", "(-> i64 bool)", 2, true),
+        ("list", "(-> 'a (Vec 'a))", 0, true),
+        ("concat", "(-> (Vec 'a) (Vec 'a))", 0, true),
+        ("str", "(-> 'a String)", 0, false),
+    ];
+    for (name, typ, min, shared) in variadics.iter() {
+        let scheme = sig(env, typ);
+        env.bind_variadic(*name, Variadic { scheme, min: *min, shared: *shared });
+    }
+}
+
+fn macros(eval: &mut Eval) {
+    eval.register_native("gensym", Arity::AtLeast(0), |eval, args| {
+        let prefix = match args {
+            [] => "g".to_string(),
+            [prefix] => String::from_expr(prefix)?,
+            _ => return Err(EvalError::ExtraArguments(List::from(args.iter().cloned().collect::<Vector<_>>()))),
+        };
+        Ok(Expr::Symbol(eval.gensym(&prefix)))
+    });
+    eval.register_native("macroexpand-1", Arity::Exactly(1), |eval, args| eval.expand_once(args[0].clone()));
+    eval.register_native("macroexpand", Arity::Exactly(1), |eval, args| eval.expand(args[0].clone()));
+    eval.register_native("macroexpand-all", Arity::Exactly(1), |eval, args| eval.expand_all(args[0].clone()));
+    eval.register_native("reader-macro", Arity::Exactly(3), |eval, args| {
+        let ch = String::from_expr(&args[0])?;
+        let arity = i64::from_expr(&args[1])?;
+        let mut chars = ch.chars();
+        match (chars.next(), chars.next(), usize::try_from(arity)) {
+            (Some(ch), None, Ok(arity)) => {
+                eval.define_reader_macro(ch, arity, args[2].clone());
+                Ok(Expr::Nil)
+            }
+            (_, _, Err(_)) => Err(EvalError::BadParameter("arity", args[1].clone())),
+            _ => Err(EvalError::BadParameter("one character", args[0].clone())),
+        }
+    });
+    // the escape hatch from hygiene: symbols in `datum` are bound as
+    // if they had been written where `ctx` was, so a macro can
+    // deliberately bind a name for the code it was given.
+    eval.register_fn("datum->syntax", |ctx: Expr, datum: Expr| {
+        let scopes = ctx.meta().map(|m| m.scopes.clone()).unwrap_or_default();
+        with_scopes(&datum, &scopes)
+    });
+}
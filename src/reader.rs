@@ -1,51 +1,100 @@
 use crate::forms::*;
 use crate::tokens::*;
 use crate::exprs::*;
+use crate::eval::EvalError;
+use crate::spans::*;
 use im::Vector;
 
+#[derive(Debug)]
 pub enum ReadError<'a> {
     UnbalancedMap(Group<Form<'a>>),
+    UnknownDispatch(Spanning<char>),
+    /// A reader macro failed.
+    Macro(Box<EvalError>),
 }
 
-fn read_all<'a>(forms: Vector<Form<'a>>, exprs: &mut Vector<Expr>)
+/// Expands the reader macros that are not built in. Given a dispatch
+/// character and what followed it, already read, returns `None` if it
+/// does not know the character.
+pub type DispatchFn<'d, 'a> = dyn FnMut(char, Vec<Expr>) -> Option<Result<Expr, ReadError<'a>>> + 'd;
+
+fn read_all<'a>(forms: Vector<Form<'a>>, exprs: &mut Vector<Expr>, dispatch: &mut DispatchFn<'_, 'a>)
                 -> Result<(), ReadError<'a>> {
     for f in forms {
-        exprs.push_back(read(f)?);
+        exprs.push_back(read_with(f, dispatch)?);
     }
     Ok(())
 }
 
-pub fn read<'a>(form: Form<'a>) -> Result<Expr, ReadError<'a>> {
+/// Reads a form with only the built in reader macros.
+pub fn read(form: Form<'_>) -> Result<Expr, ReadError<'_>> {
+    read_with(form, &mut |_, _| None)
+}
+
+pub fn read_with<'a>(form: Form<'a>, dispatch: &mut DispatchFn<'_, 'a>) -> Result<Expr, ReadError<'a>> {
     match form {
         Form::Int(int) => {
             let int = Int::new(int.inner, int.span.into());
             Ok(Expr::Int(int))
         }
+        Form::String(string) => {
+            let s = Str::new(string.inner.into_owned(), string.span.into());
+            Ok(Expr::String(s))
+        }
         Form::Symbol(symbol) => {
             let sym = Symbol::new(symbol.inner.to_string(), symbol.span.into());
             Ok(Expr::Symbol(sym))
         }
+        Form::Keyword(keyword) => {
+            let k = Keyword::new(keyword.inner.to_string(), keyword.span.into());
+            Ok(Expr::Keyword(k))
+        }
+        Form::Macro(Macro::Dispatch(ch, forms)) => {
+            let span = ch.span.start.span(forms.back().map_or(ch.span.end, |f| f.span().end));
+            let mut args = Vector::new();
+            read_all(forms, &mut args, dispatch)?;
+            let args: Vec<Expr> = args.into_iter().collect();
+            if let Some(expr) = dispatch(ch.inner, args.clone()) {
+                let mut expr = expr?;
+                expr.fill_spans(span);
+                return Ok(expr);
+            }
+            match (ch.inner, args.as_slice()) {
+                // `\x body` is `(lambda x body)`
+                ('\\', [param, body]) => {
+                    let lambda = Expr::Special(Special::Lambda(ch.span.into()));
+                    let vals = vec![lambda, param.clone(), body.clone()].into_iter().collect();
+                    Ok(Expr::List(List::new(vals, span.into())))
+                }
+                _ => Err(ReadError::UnknownDispatch(ch)),
+            }
+        }
         Form::Macro(macr) => {
             let span = macr.span();
             let mut vals = Vector::new();
             match macr {
                 Macro::HasType(prefix, typ, val) => {
                     vals.push_back(Expr::Special(Special::The(prefix.span.into())));
-                    vals.push_back(read(*typ)?);                    
-                    vals.push_back(read(*val)?);
+                    vals.push_back(read_with(*typ, dispatch)?);                    
+                    vals.push_back(read_with(*val, dispatch)?);
                 }
                 Macro::Quasiquote(prefix, val) => {
                     vals.push_back(Expr::Special(Special::Quasiquote(prefix.span.into())));
-                    vals.push_back(read(*val)?);
+                    vals.push_back(read_with(*val, dispatch)?);
                 }
                 Macro::Quote(prefix, val) => {
                     vals.push_back(Expr::Special(Special::Quote(prefix.span.into())));
-                    vals.push_back(read(*val)?);
+                    vals.push_back(read_with(*val, dispatch)?);
                 }
                 Macro::Unquote(prefix, val) => {
                     vals.push_back(Expr::Special(Special::Unquote(prefix.span.into())));
-                    vals.push_back(read(*val)?);
+                    vals.push_back(read_with(*val, dispatch)?);
+                }
+                Macro::UnquoteSplicing(prefix, val) => {
+                    vals.push_back(Expr::Special(Special::UnquoteSplicing(prefix.span.into())));
+                    vals.push_back(read_with(*val, dispatch)?);
                 }
+                Macro::Dispatch(..) => unreachable!(),
             }
             Ok(Expr::List(List::new(vals, span.into())))
         }
@@ -54,19 +103,19 @@ pub fn read<'a>(form: Form<'a>) -> Result<Expr, ReadError<'a>> {
             let mut vals = Vector::new();
             match group.open.inner {
                 Paren::Paren => {
-                    read_all(group.vals, &mut vals)?;
+                    read_all(group.vals, &mut vals, dispatch)?;
                 }
                 Paren::Brace => {
-                    let sym = Symbol::new("map".to_string(), group.open.span.into());
+                    let sym = Symbol::new("hash-map".to_string(), group.open.span.into());
                     let map = Expr::Symbol(sym);
                     vals.push_back(map);
-                    read_all(group.vals, &mut vals)?;
+                    read_all(group.vals, &mut vals, dispatch)?;
                 }
                 Paren::Square => {
                     let sym = Symbol::new("list".to_string(), group.open.span.into());
                     let list = Expr::Symbol(sym);
                     vals.push_back(list);
-                    read_all(group.vals, &mut vals)?;
+                    read_all(group.vals, &mut vals, dispatch)?;
                 }
             }
             Ok(Expr::List(List::new(vals, span.into())))
@@ -0,0 +1,1204 @@
+//! A model of rust syntax: items, functions, structs, enums, impls,
+//! expressions, patterns, types, attributes and paths, with a printer
+//! that lays them out as rustfmt would, near enough.
+//!
+//! The `rs/` builtins build the same syntax as pangolisp values, so a
+//! macro can put a rust item together piece by piece and only turn it
+//! into text at the end, with `rs/print`. Each is a `Data` value that
+//! reads back as the call that built it, `(rs/call f x)` say, checked
+//! when it is built. Where a path or a type is expected a symbol or a
+//! string will do, and where an expression or pattern is, a symbol is
+//! a path and ints, bools and strings are literals.
+
+use crate::eval::*;
+use crate::exprs::{self, Arity, Data, Meta};
+use crate::sourcemap::Mapping;
+use crate::spans::Pos;
+use std::fmt::{self, Write};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Path {
+    /// An empty first segment is a leading `::`.
+    pub segments: Vec<String>,
+    /// The generic arguments of the last segment.
+    pub args: Vec<Ty>,
+}
+
+impl Path {
+    pub fn new(segments: Vec<String>, args: Vec<Ty>) -> Path {
+        Path { segments, args }
+    }
+}
+
+impl From<&str> for Path {
+    fn from(path: &str) -> Path {
+        Path::new(path.split("::").map(String::from).collect(), vec![])
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ty {
+    Path(Path),
+    Ref(bool, Box<Ty>),
+    /// The empty tuple is `()`.
+    Tuple(Vec<Ty>),
+}
+
+impl Ty {
+    pub fn path(path: &str) -> Ty {
+        Ty::Path(Path::from(path))
+    }
+
+    pub fn generic(path: &str, args: Vec<Ty>) -> Ty {
+        Ty::Path(Path { args, ..Path::from(path) })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lit {
+    /// An integer with an optional type suffix, `255u8`.
+    Int(i64, Option<String>),
+    Bool(bool),
+    Str(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Lit(Lit),
+    Path(Path),
+    Tuple(Vec<Expr>),
+    Ref(bool, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    Method(Box<Expr>, String, Vec<Expr>),
+    /// A named field, or a numbered one of a tuple.
+    Field(Box<Expr>, String),
+    Binary(String, Box<Expr>, Box<Expr>),
+    Unary(String, Box<Expr>),
+    Assign(Box<Expr>, Box<Expr>),
+    If(Box<Expr>, Block, Option<Box<Expr>>),
+    Match(Box<Expr>, Vec<Arm>),
+    Block(Block),
+    /// `Point { x: 1, y }`, and `Point { .. }` if it has the rest.
+    Struct(Path, Vec<(String, Expr)>, bool),
+    Macro(Path, Vec<Expr>),
+    Return(Option<Box<Expr>>),
+    /// What the pangolisp at `meta` became, printed as the expression
+    /// and recorded in the source map.
+    Origin(Box<Expr>, Meta),
+}
+
+impl Expr {
+    pub fn int(i: i64) -> Expr {
+        Expr::Lit(Lit::Int(i, None))
+    }
+
+    pub fn path(path: &str) -> Expr {
+        Expr::Path(Path::from(path))
+    }
+
+    pub fn call(f: Expr, args: Vec<Expr>) -> Expr {
+        Expr::Call(Box::new(f), args)
+    }
+
+    pub fn method(receiver: Expr, name: &str, args: Vec<Expr>) -> Expr {
+        Expr::Method(Box::new(receiver), name.to_string(), args)
+    }
+
+    pub fn field(expr: Expr, name: &str) -> Expr {
+        Expr::Field(Box::new(expr), name.to_string())
+    }
+
+    pub fn binary(op: &str, a: Expr, b: Expr) -> Expr {
+        Expr::Binary(op.to_string(), Box::new(a), Box::new(b))
+    }
+
+    pub fn unary(op: &str, a: Expr) -> Expr {
+        Expr::Unary(op.to_string(), Box::new(a))
+    }
+
+    pub fn macro_call(path: &str, args: Vec<Expr>) -> Expr {
+        Expr::Macro(Path::from(path), args)
+    }
+
+    pub fn if_else(cond: Expr, then: Expr, otherwise: Option<Expr>) -> Expr {
+        Expr::If(Box::new(cond), Block::of(then), otherwise.map(|e| Box::new(Expr::Block(Block::of(e)))))
+    }
+
+    /// The expression, without its origin.
+    pub fn inner(&self) -> &Expr {
+        match self {
+            Expr::Origin(expr, _) => expr.inner(),
+            expr => expr,
+        }
+    }
+
+    // how tightly it binds: an operand that binds less tightly than its
+    // operator needs parentheses
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Origin(expr, _) => expr.precedence(),
+            Expr::If(..) | Expr::Match(..) | Expr::Block(_) | Expr::Return(_) => 0,
+            Expr::Assign(..) => 1,
+            Expr::Binary(op, ..) => binary_precedence(op),
+            Expr::Unary(..) | Expr::Lit(Lit::Int(i64::MIN..=-1, _)) => 12,
+            Expr::Call(..) | Expr::Method(..) | Expr::Field(..) => 13,
+            _ => 14,
+        }
+    }
+
+    // whether a struct literal begins it, which would be taken for the
+    // block of an `if` or `match`
+    fn has_struct(&self) -> bool {
+        match self {
+            Expr::Struct(..) => true,
+            Expr::Origin(expr, _) => expr.has_struct(),
+            Expr::Binary(_, a, b) | Expr::Assign(a, b) => a.has_struct() || b.has_struct(),
+            Expr::Unary(_, a) | Expr::Ref(_, a) | Expr::Method(a, ..) | Expr::Field(a, _) => a.has_struct(),
+            _ => false,
+        }
+    }
+}
+
+const BINARY: &[(&str, u8)] = &[
+    ("||", 3), ("&&", 4),
+    ("==", 5), ("!=", 5), ("<", 5), (">", 5), ("<=", 5), (">=", 5),
+    ("|", 6), ("^", 7), ("&", 8), ("<<", 9), (">>", 9),
+    ("+", 10), ("-", 10), ("*", 11), ("/", 11), ("%", 11),
+];
+
+const UNARY: &[&str] = &["-", "!", "*"];
+
+fn binary_precedence(op: &str) -> u8 {
+    BINARY.iter().find(|(o, _)| *o == op).map(|(_, p)| *p).unwrap_or(0)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    /// The value of the block, `()` if there is none.
+    pub expr: Option<Box<Expr>>,
+}
+
+impl Block {
+    pub fn new(stmts: Vec<Stmt>, expr: Option<Expr>) -> Block {
+        Block { stmts, expr: expr.map(Box::new) }
+    }
+
+    /// A block of just `expr`, or `expr` itself if it is a block.
+    pub fn of(expr: Expr) -> Block {
+        match expr.inner() {
+            Expr::Block(block) => block.clone(),
+            _ => Block::new(vec![], Some(expr)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stmt {
+    Let(Pat, Option<Ty>, Expr),
+    /// An expression followed by a `;`.
+    Semi(Expr),
+    Item(Item),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pat {
+    Wild,
+    /// `..`, the rest of a tuple.
+    Rest,
+    Ident(bool, String),
+    Lit(Lit),
+    Path(Path),
+    Tuple(Vec<Pat>),
+    Ref(bool, Box<Pat>),
+    TupleStruct(Path, Vec<Pat>),
+    Struct(Path, Vec<(String, Pat)>, bool),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Arm {
+    pub pat: Pat,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
+
+/// `#[path(args..)]`, or `#[path]` without any.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attribute {
+    pub path: Path,
+    pub args: Vec<Expr>,
+}
+
+impl Attribute {
+    pub fn derive(traits: &[&str]) -> Attribute {
+        Attribute { path: Path::from("derive"), args: traits.iter().map(|t| Expr::path(t)).collect() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Generic {
+    pub name: String,
+    pub bounds: Vec<Path>,
+}
+
+/// What an item is, besides its name and contents.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Header {
+    pub attrs: Vec<Attribute>,
+    pub public: bool,
+    pub generics: Vec<Generic>,
+    /// The pangolisp it came from, for the source map.
+    pub origin: Option<Meta>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Param {
+    /// `self`, `&self` or `&mut self`.
+    Receiver(Option<bool>),
+    Typed(Pat, Ty),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fields {
+    Unit,
+    Tuple(Vec<Ty>),
+    Named(Vec<(String, Ty)>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    pub header: Header,
+    pub name: String,
+    pub params: Vec<Param>,
+    /// The result, `()` if there is none.
+    pub ret: Option<Ty>,
+    pub body: Block,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Item {
+    Fn(Function),
+    /// The fields of a public struct are public.
+    Struct(Header, String, Fields),
+    Enum(Header, String, Vec<(String, Fields)>),
+    /// The trait it implements, if any, and the type.
+    Impl(Header, Option<Path>, Ty, Vec<Item>),
+    Use(Header, Path),
+}
+
+// printing
+
+struct Printer {
+    out: String,
+    indent: usize,
+    // where the end of `out` is, as of `scanned`
+    scanned: usize,
+    pos: Pos,
+    mappings: Vec<Mapping>,
+}
+
+impl Printer {
+    fn new() -> Printer {
+        Printer { out: String::new(), indent: 0, scanned: 0, pos: Pos::default(), mappings: vec![] }
+    }
+
+    fn pos(&mut self) -> Pos {
+        self.pos = self.pos.after(&self.out[self.scanned..]);
+        self.scanned = self.out.len();
+        self.pos
+    }
+
+    // maps what `print` prints to the pangolisp `meta` came from
+    fn mapped(&mut self, meta: &Meta, print: impl FnOnce(&mut Printer)) {
+        let start = self.pos();
+        print(self);
+        if let Some(mapping) = Mapping::new(start.span(self.pos()), meta) {
+            self.mappings.push(mapping);
+        }
+    }
+
+    fn push(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    fn list<T>(&mut self, items: &[T], mut each: impl FnMut(&mut Printer, &T)) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.push(", ");
+            }
+            each(self, item);
+        }
+    }
+
+    fn path(&mut self, path: &Path, turbofish: bool) {
+        self.push(&path.segments.join("::"));
+        if !path.args.is_empty() {
+            self.push(if turbofish { "::<" } else { "<" });
+            self.list(&path.args, Printer::ty);
+            self.push(">");
+        }
+    }
+
+    fn ty(&mut self, ty: &Ty) {
+        match ty {
+            Ty::Path(path) => self.path(path, false),
+            Ty::Ref(mutable, ty) => {
+                self.push(if *mutable { "&mut " } else { "&" });
+                self.ty(ty);
+            }
+            Ty::Tuple(tys) => {
+                self.push("(");
+                self.list(tys, Printer::ty);
+                self.push(if tys.len() == 1 { ",)" } else { ")" });
+            }
+        }
+    }
+
+    fn lit(&mut self, lit: &Lit) {
+        match lit {
+            Lit::Int(i, suffix) => {
+                write!(self.out, "{}", i).unwrap();
+                self.push(suffix.as_deref().unwrap_or(""));
+            }
+            Lit::Bool(b) => write!(self.out, "{}", b).unwrap(),
+            Lit::Str(s) => write!(self.out, "{:?}", s).unwrap(),
+        }
+    }
+
+    // `expr`, in parentheses if it binds less tightly than `precedence`
+    fn operand(&mut self, expr: &Expr, precedence: u8) {
+        if expr.precedence() < precedence {
+            self.push("(");
+            self.expr(expr);
+            self.push(")");
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    // the condition of an `if` or the value of a `match`
+    fn head(&mut self, expr: &Expr) {
+        self.operand(expr, if expr.has_struct() { 15 } else { 0 });
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Lit(lit) => self.lit(lit),
+            Expr::Path(path) => self.path(path, true),
+            Expr::Tuple(exprs) => {
+                self.push("(");
+                self.list(exprs, Printer::expr);
+                self.push(if exprs.len() == 1 { ",)" } else { ")" });
+            }
+            Expr::Ref(mutable, expr) => {
+                self.push(if *mutable { "&mut " } else { "&" });
+                self.operand(expr, 12);
+            }
+            Expr::Call(f, args) => {
+                self.operand(f, 13);
+                self.push("(");
+                self.list(args, Printer::expr);
+                self.push(")");
+            }
+            Expr::Method(receiver, name, args) => {
+                self.operand(receiver, 13);
+                write!(self.out, ".{}(", name).unwrap();
+                self.list(args, Printer::expr);
+                self.push(")");
+            }
+            Expr::Field(expr, name) => {
+                self.operand(expr, 13);
+                write!(self.out, ".{}", name).unwrap();
+            }
+            Expr::Binary(op, a, b) => {
+                let precedence = binary_precedence(op);
+                // comparisons do not chain
+                self.operand(a, if precedence == 5 { 6 } else { precedence });
+                write!(self.out, " {} ", op).unwrap();
+                self.operand(b, precedence + 1);
+            }
+            Expr::Unary(op, a) => {
+                self.push(op);
+                self.operand(a, 12);
+            }
+            Expr::Assign(a, b) => {
+                self.operand(a, 2);
+                self.push(" = ");
+                self.operand(b, 1);
+            }
+            Expr::If(cond, then, otherwise) => {
+                self.push("if ");
+                self.head(cond);
+                self.push(" ");
+                self.block(then);
+                if let Some(otherwise) = otherwise {
+                    self.push(" else ");
+                    match &**otherwise {
+                        _ if matches!(otherwise.inner(), Expr::If(..) | Expr::Block(_)) => self.expr(otherwise),
+                        other => self.block(&Block::of(other.clone())),
+                    }
+                }
+            }
+            Expr::Match(expr, arms) => {
+                self.push("match ");
+                self.head(expr);
+                self.push(" {");
+                self.indent += 1;
+                for arm in arms {
+                    self.newline();
+                    self.pat(&arm.pat);
+                    if let Some(guard) = &arm.guard {
+                        self.push(" if ");
+                        self.expr(guard);
+                    }
+                    self.push(" => ");
+                    self.expr(&arm.body);
+                    if !matches!(arm.body.inner(), Expr::Block(_)) {
+                        self.push(",");
+                    }
+                }
+                self.indent -= 1;
+                self.newline();
+                self.push("}");
+            }
+            Expr::Block(block) => self.block(block),
+            Expr::Struct(path, fields, rest) => {
+                self.path(path, true);
+                self.push(" {");
+                for (i, (name, expr)) in fields.iter().enumerate() {
+                    self.push(if i > 0 { ", " } else { " " });
+                    self.push(name);
+                    // the shorthand, as in `Point { x, y }`
+                    if !matches!(expr, Expr::Path(p) if p.args.is_empty() && p.segments == [name.clone()]) {
+                        self.push(": ");
+                        self.expr(expr);
+                    }
+                }
+                if *rest {
+                    self.push(if fields.is_empty() { " .." } else { ", .." });
+                }
+                self.push(if fields.is_empty() && !rest { "}" } else { " }" });
+            }
+            Expr::Macro(path, args) => {
+                self.path(path, true);
+                let vec = path.segments.last().is_some_and(|s| s == "vec");
+                self.push(if vec { "![" } else { "!(" });
+                self.list(args, Printer::expr);
+                self.push(if vec { "]" } else { ")" });
+            }
+            Expr::Return(None) => self.push("return"),
+            Expr::Return(Some(expr)) => {
+                self.push("return ");
+                self.expr(expr);
+            }
+            Expr::Origin(expr, meta) => self.mapped(meta, |p| p.expr(expr)),
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        if block.stmts.is_empty() && block.expr.is_none() {
+            return self.push("{}");
+        }
+        self.push("{");
+        self.indent += 1;
+        for stmt in &block.stmts {
+            self.newline();
+            match stmt {
+                Stmt::Let(pat, ty, expr) => {
+                    self.push("let ");
+                    self.pat(pat);
+                    if let Some(ty) = ty {
+                        self.push(": ");
+                        self.ty(ty);
+                    }
+                    self.push(" = ");
+                    self.expr(expr);
+                    self.push(";");
+                }
+                Stmt::Semi(expr) => {
+                    self.expr(expr);
+                    self.push(";");
+                }
+                Stmt::Item(item) => self.item(item),
+            }
+        }
+        if let Some(expr) = &block.expr {
+            self.newline();
+            self.expr(expr);
+        }
+        self.indent -= 1;
+        self.newline();
+        self.push("}");
+    }
+
+    fn pat(&mut self, pat: &Pat) {
+        match pat {
+            Pat::Wild => self.push("_"),
+            Pat::Rest => self.push(".."),
+            Pat::Ident(mutable, name) => {
+                self.push(if *mutable { "mut " } else { "" });
+                self.push(name);
+            }
+            Pat::Lit(lit) => self.lit(lit),
+            Pat::Path(path) => self.path(path, true),
+            Pat::Tuple(pats) => {
+                self.push("(");
+                self.list(pats, Printer::pat);
+                self.push(if pats.len() == 1 { ",)" } else { ")" });
+            }
+            Pat::Ref(mutable, pat) => {
+                self.push(if *mutable { "&mut " } else { "&" });
+                self.pat(pat);
+            }
+            Pat::TupleStruct(path, pats) => {
+                self.path(path, true);
+                self.push("(");
+                self.list(pats, Printer::pat);
+                self.push(")");
+            }
+            Pat::Struct(path, fields, rest) => {
+                self.path(path, true);
+                self.push(" {");
+                for (i, (name, pat)) in fields.iter().enumerate() {
+                    self.push(if i > 0 { ", " } else { " " });
+                    self.push(name);
+                    if !matches!(pat, Pat::Ident(false, n) if n == name) {
+                        self.push(": ");
+                        self.pat(pat);
+                    }
+                }
+                if *rest {
+                    self.push(if fields.is_empty() { " .." } else { ", .." });
+                }
+                self.push(if fields.is_empty() && !rest { "}" } else { " }" });
+            }
+        }
+    }
+
+    fn header(&mut self, header: &Header) {
+        for attr in &header.attrs {
+            self.push("#[");
+            self.path(&attr.path, false);
+            if !attr.args.is_empty() {
+                self.push("(");
+                self.list(&attr.args, Printer::expr);
+                self.push(")");
+            }
+            self.push("]");
+            self.newline();
+        }
+        if header.public {
+            self.push("pub ");
+        }
+    }
+
+    fn generics(&mut self, generics: &[Generic]) {
+        if generics.is_empty() {
+            return;
+        }
+        self.push("<");
+        self.list(generics, |p, generic| {
+            p.push(&generic.name);
+            for (i, bound) in generic.bounds.iter().enumerate() {
+                p.push(if i == 0 { ": " } else { " + " });
+                p.path(bound, false);
+            }
+        });
+        self.push(">");
+    }
+
+    fn fields(&mut self, fields: &Fields, public: bool, multiline: bool) {
+        let vis = if public { "pub " } else { "" };
+        match fields {
+            Fields::Unit => (),
+            Fields::Tuple(tys) => {
+                self.push("(");
+                self.list(tys, |p, ty| {
+                    p.push(vis);
+                    p.ty(ty);
+                });
+                self.push(")");
+            }
+            Fields::Named(named) if multiline => {
+                self.push(" {");
+                self.indent += 1;
+                for (name, ty) in named {
+                    self.newline();
+                    write!(self.out, "{}{}: ", vis, name).unwrap();
+                    self.ty(ty);
+                    self.push(",");
+                }
+                self.indent -= 1;
+                self.newline();
+                self.push("}");
+            }
+            Fields::Named(named) => {
+                self.push(" { ");
+                self.list(named, |p, (name, ty)| {
+                    write!(p.out, "{}{}: ", vis, name).unwrap();
+                    p.ty(ty);
+                });
+                self.push(" }");
+            }
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        let header = match item {
+            Item::Fn(function) => &function.header,
+            Item::Struct(header, ..) | Item::Enum(header, ..) | Item::Impl(header, ..) | Item::Use(header, _) => header,
+        };
+        match &header.origin {
+            Some(meta) => self.mapped(meta, |p| p.unmapped_item(item)),
+            None => self.unmapped_item(item),
+        }
+    }
+
+    fn unmapped_item(&mut self, item: &Item) {
+        match item {
+            Item::Fn(function) => {
+                self.header(&function.header);
+                write!(self.out, "fn {}", function.name).unwrap();
+                self.generics(&function.header.generics);
+                self.push("(");
+                self.list(&function.params, |p, param| match param {
+                    Param::Receiver(None) => p.push("self"),
+                    Param::Receiver(Some(false)) => p.push("&self"),
+                    Param::Receiver(Some(true)) => p.push("&mut self"),
+                    Param::Typed(pat, ty) => {
+                        p.pat(pat);
+                        p.push(": ");
+                        p.ty(ty);
+                    }
+                });
+                self.push(")");
+                match &function.ret {
+                    Some(Ty::Tuple(tys)) if tys.is_empty() => (),
+                    Some(ty) => {
+                        self.push(" -> ");
+                        self.ty(ty);
+                    }
+                    None => (),
+                }
+                self.push(" ");
+                self.block(&function.body);
+            }
+            Item::Struct(header, name, fields) => {
+                self.header(header);
+                write!(self.out, "struct {}", name).unwrap();
+                self.generics(&header.generics);
+                self.fields(fields, header.public, true);
+                if !matches!(fields, Fields::Named(_)) {
+                    self.push(";");
+                }
+            }
+            Item::Enum(header, name, variants) => {
+                self.header(header);
+                write!(self.out, "enum {}", name).unwrap();
+                self.generics(&header.generics);
+                self.push(" {");
+                self.indent += 1;
+                for (name, fields) in variants {
+                    self.newline();
+                    self.push(name);
+                    self.fields(fields, false, false);
+                    self.push(",");
+                }
+                self.indent -= 1;
+                self.newline();
+                self.push("}");
+            }
+            Item::Impl(header, tr, ty, items) => {
+                self.header(header);
+                self.push("impl");
+                self.generics(&header.generics);
+                self.push(" ");
+                if let Some(tr) = tr {
+                    self.path(tr, false);
+                    self.push(" for ");
+                }
+                self.ty(ty);
+                if items.is_empty() {
+                    return self.push(" {}");
+                }
+                self.push(" {");
+                self.indent += 1;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.push("\n");
+                    }
+                    self.newline();
+                    self.item(item);
+                }
+                self.indent -= 1;
+                self.newline();
+                self.push("}");
+            }
+            Item::Use(header, path) => {
+                self.header(header);
+                self.push("use ");
+                self.path(path, false);
+                self.push(";");
+            }
+        }
+    }
+}
+
+macro_rules! display {
+    ($($t:ty => $print:ident),*) => {$(
+        impl fmt::Display for $t {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let mut printer = Printer::new();
+                printer.$print(self);
+                f.write_str(&printer.out)
+            }
+        }
+    )*};
+}
+
+display!(Ty => ty, Expr => expr, Pat => pat, Block => block, Item => item);
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut printer = Printer::new();
+        printer.path(self, false);
+        f.write_str(&printer.out)
+    }
+}
+
+/// The items of a file, a blank line between each.
+pub fn file(items: &[Item]) -> String {
+    mapped_file("", items).0
+}
+
+/// The items of a file after `preamble`, and where in it each piece of
+/// syntax with an origin ended up.
+pub fn mapped_file(preamble: &str, items: &[Item]) -> (String, Vec<Mapping>) {
+    let mut printer = Printer::new();
+    printer.push(preamble);
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            printer.push("\n\n");
+        }
+        printer.item(item);
+    }
+    printer.push("\n");
+    (printer.out, printer.mappings)
+}
+
+// reading the builtins' values
+
+fn node(expr: &exprs::Expr) -> Option<(&str, Vec<&exprs::Expr>)> {
+    match expr {
+        exprs::Expr::Data(data) if data.typ == "rs" => Some((&data.variant, data.fields.iter().collect())),
+        _ => None,
+    }
+}
+
+fn bad<T>(what: &'static str, expr: &exprs::Expr) -> Result<T, EvalError> {
+    Err(EvalError::BadParameter(what, expr.clone()))
+}
+
+fn is_ident(s: &str) -> bool {
+    let s = s.strip_prefix("r#").unwrap_or(s);
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn text(expr: &exprs::Expr) -> Option<&str> {
+    match expr {
+        exprs::Expr::Symbol(s) => Some(&s.value),
+        exprs::Expr::String(s) => Some(&s.value),
+        _ => None,
+    }
+}
+
+fn ident(expr: &exprs::Expr) -> Result<String, EvalError> {
+    match text(expr) {
+        Some(name) if is_ident(name) => Ok(name.to_string()),
+        _ => bad("a rust identifier", expr),
+    }
+}
+
+fn seq(expr: &exprs::Expr) -> Result<Vec<&exprs::Expr>, EvalError> {
+    match expr {
+        exprs::Expr::Nil => Ok(vec![]),
+        exprs::Expr::List(list) => Ok(list.vals.iter().collect()),
+        _ => bad("a list", expr),
+    }
+}
+
+fn all<T>(exprs: &[&exprs::Expr], f: fn(&exprs::Expr) -> Result<T, EvalError>) -> Result<Vec<T>, EvalError> {
+    exprs.iter().map(|e| f(e)).collect()
+}
+
+fn boxed<T>(expr: &exprs::Expr, f: fn(&exprs::Expr) -> Result<T, EvalError>) -> Result<Box<T>, EvalError> {
+    f(expr).map(Box::new)
+}
+
+/// A path from `rs/path`, a symbol or a string such as `"std::fmt::Debug"`.
+pub fn path(expr: &exprs::Expr) -> Result<Path, EvalError> {
+    let (name, args) = match (text(expr), node(expr)) {
+        (Some(_), _) => (expr, vec![]),
+        (_, Some(("rs/path", fields))) if !fields.is_empty() => (fields[0], fields[1..].to_vec()),
+        _ => return bad("a rust path", expr),
+    };
+    let segments: Vec<String> = text(name).unwrap_or("").split("::").map(String::from).collect();
+    let valid = segments.iter().enumerate().all(|(i, s)| is_ident(s) || (i == 0 && s.is_empty() && segments.len() > 1));
+    if !valid {
+        return bad("a rust path", expr);
+    }
+    Ok(Path::new(segments, all(&args, ty)?))
+}
+
+pub fn ty(expr: &exprs::Expr) -> Result<Ty, EvalError> {
+    match node(expr) {
+        _ if text(expr).is_some() => Ok(Ty::Path(path(expr)?)),
+        None if matches!(expr, exprs::Expr::Nil) => Ok(Ty::Tuple(vec![])),
+        Some(("rs/path", _)) => Ok(Ty::Path(path(expr)?)),
+        Some(("rs/ref", f)) if f.len() == 1 => Ok(Ty::Ref(false, boxed(f[0], ty)?)),
+        Some(("rs/ref-mut", f)) if f.len() == 1 => Ok(Ty::Ref(true, boxed(f[0], ty)?)),
+        Some(("rs/tuple", f)) => Ok(Ty::Tuple(all(&f, ty)?)),
+        _ => bad("a rust type", expr),
+    }
+}
+
+fn lit(expr: &exprs::Expr) -> Option<Lit> {
+    match expr {
+        exprs::Expr::Int(i) => Some(Lit::Int(i.value, None)),
+        exprs::Expr::Bool(b) => Some(Lit::Bool(b.value)),
+        exprs::Expr::String(s) => Some(Lit::Str(s.value.clone())),
+        _ => None,
+    }
+}
+
+fn suffixed(fields: &[&exprs::Expr], expr: &exprs::Expr) -> Result<Lit, EvalError> {
+    match fields {
+        [exprs::Expr::Int(i), suffix] => Ok(Lit::Int(i.value, Some(ident(suffix)?))),
+        _ => bad("an int and its type", expr),
+    }
+}
+
+// the name of a field, or the number of a tuple's
+fn field_name(expr: &exprs::Expr) -> Result<String, EvalError> {
+    match expr {
+        exprs::Expr::Int(i) if i.value >= 0 => Ok(i.value.to_string()),
+        _ => ident(expr),
+    }
+}
+
+// `[name value]` pairs, then `..` for the rest
+fn struct_fields<T>(
+    fields: &[&exprs::Expr],
+    f: fn(&exprs::Expr) -> Result<T, EvalError>,
+) -> Result<(Vec<(String, T)>, bool), EvalError> {
+    let rest = fields.last().and_then(|e| text(e)) == Some("..");
+    let fields = &fields[..fields.len() - rest as usize];
+    let fields = fields.iter().map(|field| match seq(field)?.as_slice() {
+        [name, value] => Ok((field_name(name)?, f(value)?)),
+        _ => bad("a field and its value", field),
+    });
+    Ok((fields.collect::<Result<_, _>>()?, rest))
+}
+
+pub fn expr(e: &exprs::Expr) -> Result<Expr, EvalError> {
+    if let Some(lit) = lit(e) {
+        return Ok(Expr::Lit(lit));
+    }
+    let (name, f) = match (e, node(e)) {
+        (exprs::Expr::Nil, _) => return Ok(Expr::Tuple(vec![])),
+        (exprs::Expr::Symbol(_), _) => return Ok(Expr::Path(path(e)?)),
+        (_, Some(node)) => node,
+        _ => return bad("a rust expression", e),
+    };
+    let op = |op: &exprs::Expr, ops: &[&str]| match text(op) {
+        Some(op) if ops.contains(&op) => Ok(op.to_string()),
+        _ => bad("a rust operator", op),
+    };
+    let binary: Vec<&str> = BINARY.iter().map(|(op, _)| *op).collect();
+    match (name, f.as_slice()) {
+        ("rs/path", _) => Ok(Expr::Path(path(e)?)),
+        ("rs/lit", fields) => Ok(Expr::Lit(suffixed(fields, e)?)),
+        ("rs/tuple", fields) => Ok(Expr::Tuple(all(fields, expr)?)),
+        ("rs/ref", [x]) => Ok(Expr::Ref(false, boxed(x, expr)?)),
+        ("rs/ref-mut", [x]) => Ok(Expr::Ref(true, boxed(x, expr)?)),
+        ("rs/call", [fun, args @ ..]) => Ok(Expr::Call(boxed(fun, expr)?, all(args, expr)?)),
+        ("rs/method", [receiver, name, args @ ..]) => Ok(Expr::Method(boxed(receiver, expr)?, ident(name)?, all(args, expr)?)),
+        ("rs/field", [x, name]) => Ok(Expr::Field(boxed(x, expr)?, field_name(name)?)),
+        ("rs/binary", [o, a, b]) => Ok(Expr::Binary(op(o, &binary)?, boxed(a, expr)?, boxed(b, expr)?)),
+        ("rs/unary", [o, a]) => Ok(Expr::Unary(op(o, UNARY)?, boxed(a, expr)?)),
+        ("rs/assign", [a, b]) => Ok(Expr::Assign(boxed(a, expr)?, boxed(b, expr)?)),
+        ("rs/if", [cond, then]) => Ok(Expr::If(boxed(cond, expr)?, Block::of(expr(then)?), None)),
+        ("rs/if", [cond, then, otherwise]) =>
+            Ok(Expr::If(boxed(cond, expr)?, Block::of(expr(then)?), Some(boxed(otherwise, expr)?))),
+        ("rs/match", [x, arms @ ..]) => Ok(Expr::Match(boxed(x, expr)?, all(arms, arm)?)),
+        ("rs/block", stmts) => Ok(Expr::Block(block(stmts)?)),
+        ("rs/struct-expr", [p, fields @ ..]) => {
+            let (fields, rest) = struct_fields(fields, expr)?;
+            Ok(Expr::Struct(path(p)?, fields, rest))
+        }
+        ("rs/macro", [p, args @ ..]) => Ok(Expr::Macro(path(p)?, all(args, expr)?)),
+        ("rs/return", []) => Ok(Expr::Return(None)),
+        ("rs/return", [x]) => Ok(Expr::Return(Some(boxed(x, expr)?))),
+        _ => bad("a rust expression", e),
+    }
+}
+
+fn arm(e: &exprs::Expr) -> Result<Arm, EvalError> {
+    match node(e) {
+        Some(("rs/arm", f)) => match f.as_slice() {
+            [p, body] => Ok(Arm { pat: pat(p)?, guard: None, body: expr(body)? }),
+            [p, guard, body] => Ok(Arm { pat: pat(p)?, guard: Some(expr(guard)?), body: expr(body)? }),
+            _ => bad("a match arm", e),
+        },
+        _ => bad("a match arm", e),
+    }
+}
+
+// statements, then the value unless the last is a statement too
+fn block(stmts: &[&exprs::Expr]) -> Result<Block, EvalError> {
+    let mut block = Block::default();
+    for (i, e) in stmts.iter().enumerate() {
+        let last = i + 1 == stmts.len();
+        match node(e) {
+            Some(("rs/let", f)) => block.stmts.push(match f.as_slice() {
+                [p, value] => Stmt::Let(pat(p)?, None, expr(value)?),
+                [p, t, value] => Stmt::Let(pat(p)?, Some(ty(t)?), expr(value)?),
+                _ => return bad("a let statement", e),
+            }),
+            Some(("rs/semi", f)) if f.len() == 1 => block.stmts.push(Stmt::Semi(expr(f[0])?)),
+            Some((name, _)) if ITEMS.contains(&name) => block.stmts.push(Stmt::Item(item(e)?)),
+            _ if last => block.expr = Some(boxed(e, expr)?),
+            _ => block.stmts.push(Stmt::Semi(expr(e)?)),
+        }
+    }
+    Ok(block)
+}
+
+// a block, or a lone statement or expression as one
+fn fn_body(e: &exprs::Expr) -> Result<Block, EvalError> {
+    match node(e) {
+        Some(("rs/let", _)) | Some(("rs/semi", _)) => block(&[e]),
+        _ => Ok(Block::of(expr(e)?)),
+    }
+}
+
+pub fn pat(e: &exprs::Expr) -> Result<Pat, EvalError> {
+    if let Some(lit) = lit(e) {
+        return Ok(Pat::Lit(lit));
+    }
+    if let exprs::Expr::Symbol(sym) = e {
+        return match sym.value.as_str() {
+            "_" => Ok(Pat::Wild),
+            ".." => Ok(Pat::Rest),
+            name if is_ident(name) && name.starts_with(|c: char| c.is_lowercase() || c == '_') =>
+                Ok(Pat::Ident(false, name.to_string())),
+            _ => Ok(Pat::Path(path(e)?)),
+        };
+    }
+    match node(e).as_ref().map(|(name, f)| (*name, f.as_slice())) {
+        Some(("rs/path", _)) => Ok(Pat::Path(path(e)?)),
+        Some(("rs/lit", fields)) => Ok(Pat::Lit(suffixed(fields, e)?)),
+        Some(("rs/tuple", fields)) => Ok(Pat::Tuple(all(fields, pat)?)),
+        Some(("rs/ref", [p])) => Ok(Pat::Ref(false, boxed(p, pat)?)),
+        Some(("rs/ref-mut", [p])) => Ok(Pat::Ref(true, boxed(p, pat)?)),
+        Some(("rs/mut", [name])) => Ok(Pat::Ident(true, ident(name)?)),
+        Some(("rs/call", [p, fields @ ..])) => Ok(Pat::TupleStruct(path(p)?, all(fields, pat)?)),
+        Some(("rs/struct-expr", [p, fields @ ..])) => {
+            let (fields, rest) = struct_fields(fields, pat)?;
+            Ok(Pat::Struct(path(p)?, fields, rest))
+        }
+        _ => bad("a rust pattern", e),
+    }
+}
+
+fn attr(e: &exprs::Expr) -> Result<Attribute, EvalError> {
+    match node(e) {
+        Some(("rs/attr", f)) if !f.is_empty() => Ok(Attribute { path: path(f[0])?, args: all(&f[1..], expr)? }),
+        _ => bad("an attribute", e),
+    }
+}
+
+fn generic(e: &exprs::Expr) -> Result<Generic, EvalError> {
+    match node(e) {
+        Some(("rs/generic", f)) if !f.is_empty() => Ok(Generic { name: ident(f[0])?, bounds: all(&f[1..], path)? }),
+        _ => bad("a generic parameter", e),
+    }
+}
+
+// `::pub`, attributes and generic parameters, in any order
+fn header(modifiers: &[&exprs::Expr]) -> Result<Header, EvalError> {
+    let mut header = Header::default();
+    for modifier in modifiers {
+        match (modifier, node(modifier)) {
+            (exprs::Expr::Keyword(k), _) if k.value == "pub" => header.public = true,
+            (_, Some(("rs/attr", _))) => header.attrs.push(attr(modifier)?),
+            (_, Some(("rs/generic", _))) => header.generics.push(generic(modifier)?),
+            _ => return bad("::pub, an attribute or a generic parameter", modifier),
+        }
+    }
+    Ok(header)
+}
+
+fn param(e: &exprs::Expr) -> Result<Param, EvalError> {
+    let is_self = |e: &exprs::Expr| text(e) == Some("self");
+    match (node(e), e) {
+        (_, _) if is_self(e) => Ok(Param::Receiver(None)),
+        (Some(("rs/ref", f)), _) if f.len() == 1 && is_self(f[0]) => Ok(Param::Receiver(Some(false))),
+        (Some(("rs/ref-mut", f)), _) if f.len() == 1 && is_self(f[0]) => Ok(Param::Receiver(Some(true))),
+        (_, exprs::Expr::List(_)) => match seq(e)?.as_slice() {
+            [p, t] => Ok(Param::Typed(pat(p)?, ty(t)?)),
+            _ => bad("a parameter and its type", e),
+        },
+        _ => bad("a parameter and its type", e),
+    }
+}
+
+// none for a unit struct, `[name type]` pairs for named fields, or
+// just the types
+fn fields(e: &exprs::Expr) -> Result<Fields, EvalError> {
+    let fields = seq(e)?;
+    let named = |field: &&exprs::Expr| matches!(field, exprs::Expr::List(l) if l.vals.len() == 2);
+    if fields.is_empty() {
+        Ok(Fields::Unit)
+    } else if fields.iter().all(named) {
+        let named = fields.iter().map(|field| {
+            let pair = seq(field)?;
+            Ok((ident(pair[0])?, ty(pair[1])?))
+        });
+        Ok(Fields::Named(named.collect::<Result<_, EvalError>>()?))
+    } else {
+        Ok(Fields::Tuple(all(&fields, ty)?))
+    }
+}
+
+fn variant(e: &exprs::Expr) -> Result<(String, Fields), EvalError> {
+    match node(e) {
+        Some(("rs/variant", f)) if f.len() == 2 => Ok((ident(f[0])?, fields(f[1])?)),
+        _ => Ok((ident(e)?, Fields::Unit)),
+    }
+}
+
+const ITEMS: &[&str] = &["rs/fn", "rs/struct", "rs/enum", "rs/impl", "rs/impl-for", "rs/use"];
+
+pub fn item(e: &exprs::Expr) -> Result<Item, EvalError> {
+    let (name, f) = node(e).ok_or_else(|| EvalError::BadParameter("a rust item", e.clone()))?;
+    match (name, f.as_slice()) {
+        ("rs/fn", [name, params, ret, body, modifiers @ ..]) => Ok(Item::Fn(Function {
+            header: header(modifiers)?,
+            name: ident(name)?,
+            params: all(&seq(params)?, param)?,
+            ret: Some(ty(ret)?),
+            body: fn_body(body)?,
+        })),
+        ("rs/struct", [name, f, modifiers @ ..]) => Ok(Item::Struct(header(modifiers)?, ident(name)?, fields(f)?)),
+        ("rs/enum", [name, variants, modifiers @ ..]) =>
+            Ok(Item::Enum(header(modifiers)?, ident(name)?, all(&seq(variants)?, variant)?)),
+        ("rs/impl", [t, items, modifiers @ ..]) =>
+            Ok(Item::Impl(header(modifiers)?, None, ty(t)?, all(&seq(items)?, item)?)),
+        ("rs/impl-for", [tr, t, items, modifiers @ ..]) =>
+            Ok(Item::Impl(header(modifiers)?, Some(path(tr)?), ty(t)?, all(&seq(items)?, item)?)),
+        ("rs/use", [p, modifiers @ ..]) => Ok(Item::Use(header(modifiers)?, path(p)?)),
+        _ => bad("a rust item", e),
+    }
+}
+
+// the builtins
+
+type Reader = fn(&exprs::Expr) -> Result<(), EvalError>;
+
+// which of the readers above a node must satisfy, one of them at least
+// where it may stand for more than one kind of syntax
+fn check(e: &exprs::Expr) -> Result<(), EvalError> {
+    let (name, _) = node(e).expect("a node");
+    let readers: &[Reader] = match name {
+        "rs/path" => &[|e| path(e).map(drop)],
+        "rs/ref" | "rs/ref-mut" | "rs/tuple" =>
+            &[|e| ty(e).map(drop), |e| expr(e).map(drop), |e| pat(e).map(drop)],
+        "rs/call" | "rs/struct-expr" | "rs/lit" => &[|e| expr(e).map(drop), |e| pat(e).map(drop)],
+        "rs/mut" => &[|e| pat(e).map(drop)],
+        "rs/arm" => &[|e| arm(e).map(drop)],
+        "rs/attr" => &[|e| attr(e).map(drop)],
+        "rs/generic" => &[|e| generic(e).map(drop)],
+        "rs/variant" => &[|e| variant(e).map(drop)],
+        "rs/let" | "rs/semi" => &[|e| block(&[e, &exprs::Expr::Nil]).map(drop)],
+        name if ITEMS.contains(&name) => &[|e| item(e).map(drop)],
+        _ => &[|e| expr(e).map(drop)],
+    };
+    let mut first = None;
+    for read in readers {
+        match read(e) {
+            Ok(()) => return Ok(()),
+            Err(err) => first = first.or(Some(err)),
+        }
+    }
+    Err(first.expect("a reader"))
+}
+
+const BUILTINS: &[(&str, Arity)] = &[
+    ("path", Arity::AtLeast(1)),
+    ("ref", Arity::Exactly(1)),
+    ("ref-mut", Arity::Exactly(1)),
+    ("tuple", Arity::AtLeast(0)),
+    ("lit", Arity::Exactly(2)),
+    ("call", Arity::AtLeast(1)),
+    ("method", Arity::AtLeast(2)),
+    ("field", Arity::Exactly(2)),
+    ("binary", Arity::Exactly(3)),
+    ("unary", Arity::Exactly(2)),
+    ("assign", Arity::Exactly(2)),
+    ("if", Arity::AtLeast(2)),
+    ("match", Arity::AtLeast(1)),
+    ("arm", Arity::AtLeast(2)),
+    ("block", Arity::AtLeast(0)),
+    ("let", Arity::AtLeast(2)),
+    ("semi", Arity::Exactly(1)),
+    ("struct-expr", Arity::AtLeast(1)),
+    ("macro", Arity::AtLeast(1)),
+    ("return", Arity::AtLeast(0)),
+    ("mut", Arity::Exactly(1)),
+    ("attr", Arity::AtLeast(1)),
+    ("generic", Arity::AtLeast(1)),
+    ("fn", Arity::AtLeast(4)),
+    ("struct", Arity::AtLeast(2)),
+    ("variant", Arity::Exactly(2)),
+    ("enum", Arity::AtLeast(2)),
+    ("impl", Arity::AtLeast(2)),
+    ("impl-for", Arity::AtLeast(3)),
+    ("use", Arity::AtLeast(1)),
+];
+
+/// Binds the `rs/` builtins into `eval`.
+pub fn load(eval: &mut Eval) {
+    for (name, arity) in BUILTINS {
+        let name = format!("rs/{}", name);
+        let variant = name.clone();
+        eval.register_native(&name, *arity, move |_, args| {
+            let data = Data::new("rs".to_string(), variant.clone(), args.iter().cloned().collect(), Meta::default());
+            let node = exprs::Expr::Data(data);
+            check(&node)?;
+            Ok(node)
+        });
+    }
+    // a list of items as a file, an item, or else an expression or type
+    eval.register_native("rs/print", Arity::Exactly(1), |_, args| {
+        let text = match &args[0] {
+            e @ exprs::Expr::List(_) => file(&all(&seq(e)?, item)?),
+            e if node(e).is_some_and(|(name, _)| ITEMS.contains(&name)) => item(e)?.to_string(),
+            // without a turbofish
+            e if node(e).is_some_and(|(name, _)| name == "rs/path") => ty(e)?.to_string(),
+            e => match expr(e) {
+                Ok(x) => x.to_string(),
+                Err(err) => ty(e).map_err(|_| err)?.to_string(),
+            },
+        };
+        Ok(exprs::Expr::from(text))
+    });
+}
@@ -0,0 +1,491 @@
+//! Source maps from generated rust back to the pangolisp it came from,
+//! and the rewriting of rustc's JSON diagnostics with them.
+//!
+//! A map sits beside the rust it describes, as JSON:
+//!
+//! ```text
+//! {"version": 1, "source": "shapes.pl", "generated": "shapes.rs",
+//!  "mappings": [{"generated": [[0, 4, 4], [0, 9, 9]],
+//!                "source": [[2, 1, 30], [2, 6, 35]],
+//!                "expansions": [{"macro": "unless", "span": [[7, 0, 80], [7, 20, 100]]}]}]}
+//! ```
+//!
+//! A span is its start and end as `[line, column, offset]`, counting
+//! from 0, with offsets and columns in characters, as in `Span`. The
+//! expansions are the macro calls the source came from, innermost
+//! first.
+
+use crate::exprs::Meta;
+use crate::spans::{Pos, Span};
+use std::fmt::{self, Write};
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expansion {
+    /// The macro's name, if it is known.
+    pub name: Option<String>,
+    pub span: Option<Span>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mapping {
+    pub generated: Span,
+    pub source: Span,
+    pub expansions: Vec<Expansion>,
+}
+
+impl Mapping {
+    /// Maps `generated` to where `meta` says its source is, if it says.
+    pub fn new(generated: Span, meta: &Meta) -> Option<Mapping> {
+        let expansions = meta.expansions().iter()
+            .map(|call| Expansion { name: call.macro_name.clone(), span: call.span })
+            .collect();
+        meta.span.map(|source| Mapping { generated, source, expansions })
+    }
+
+    /// Describes each of the `expansions`, as `Meta::provenance` does.
+    pub fn provenance(&self) -> Vec<String> {
+        self.expansions.iter().map(|call| {
+            let name = call.name.as_deref().unwrap_or("a macro");
+            match call.span {
+                Some(span) => format!("in expansion of `{}` at {}:{}", name, span.start.line + 1, span.start.column + 1),
+                None => format!("in expansion of `{}`", name),
+            }
+        }).collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SourceMapError {
+    /// Not JSON, and the character offset where that became clear.
+    Json(usize),
+    /// JSON, but not of a source map.
+    Format(&'static str),
+}
+
+impl fmt::Display for SourceMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceMapError::Json(offset) => write!(f, "invalid JSON at character {}", offset),
+            SourceMapError::Format(what) => write!(f, "expected {} in the source map", what),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceMap {
+    /// The pangolisp file, as diagnostics should name it.
+    pub source: String,
+    /// The rust file, as rustc was given it or a suffix of that.
+    pub generated: String,
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    pub fn new(source: impl Into<String>, generated: impl Into<String>, mappings: Vec<Mapping>) -> SourceMap {
+        SourceMap { source: source.into(), generated: generated.into(), mappings }
+    }
+
+    /// The innermost mapping of a range of the generated rust, from and
+    /// to a line and column.
+    pub fn lookup(&self, start: (usize, usize), end: (usize, usize)) -> Option<&Mapping> {
+        let key = |pos: Pos| (pos.line, pos.column);
+        self.mappings.iter()
+            .filter(|m| key(m.generated.start) <= start && end <= key(m.generated.end))
+            .min_by_key(|m| m.generated.end.offset - m.generated.start.offset)
+    }
+
+    // whether rustc's name for a file is the generated one
+    fn describes(&self, file_name: &str) -> bool {
+        file_name == self.generated || Path::new(file_name).ends_with(&self.generated)
+    }
+
+    pub fn to_json(&self) -> String {
+        let span = |span: &Span| Json::Arr(vec![pos(span.start), pos(span.end)]);
+        let mappings = self.mappings.iter().map(|m| Json::Obj(vec![
+            ("generated".into(), span(&m.generated)),
+            ("source".into(), span(&m.source)),
+            ("expansions".into(), Json::Arr(m.expansions.iter().map(|e| Json::Obj(vec![
+                ("macro".into(), e.name.clone().map(Json::Str).unwrap_or(Json::Null)),
+                ("span".into(), e.span.as_ref().map(span).unwrap_or(Json::Null)),
+            ])).collect())),
+        ])).collect();
+        Json::Obj(vec![
+            ("version".into(), Json::Num("1".into())),
+            ("source".into(), Json::Str(self.source.clone())),
+            ("generated".into(), Json::Str(self.generated.clone())),
+            ("mappings".into(), Json::Arr(mappings)),
+        ]).to_string()
+    }
+
+    pub fn from_json(src: &str) -> Result<SourceMap, SourceMapError> {
+        let json = Json::parse(src)?;
+        let string = |json: &Json, what| json.str().map(String::from).ok_or(SourceMapError::Format(what));
+        let span = |json: &Json| match json.arr() {
+            Some([start, end]) => Ok(Span::new(to_pos(start)?, to_pos(end)?)),
+            _ => Err(SourceMapError::Format("a span")),
+        };
+        let expansion = |json: &Json| Ok(Expansion {
+            name: json.get("macro").and_then(Json::str).map(String::from),
+            span: match json.get("span") {
+                Some(Json::Null) | None => None,
+                Some(s) => Some(span(s)?),
+            },
+        });
+        let mapping = |json: &Json| Ok(Mapping {
+            generated: span(json.get("generated").ok_or(SourceMapError::Format("generated"))?)?,
+            source: span(json.get("source").ok_or(SourceMapError::Format("source"))?)?,
+            expansions: json.get("expansions").and_then(Json::arr).unwrap_or(&[])
+                .iter().map(expansion).collect::<Result<_, _>>()?,
+        });
+        Ok(SourceMap {
+            source: string(json.get("source").unwrap_or(&Json::Null), "source")?,
+            generated: string(json.get("generated").unwrap_or(&Json::Null), "generated")?,
+            mappings: json.get("mappings").and_then(Json::arr).ok_or(SourceMapError::Format("mappings"))?
+                .iter().map(mapping).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+fn pos(pos: Pos) -> Json {
+    Json::Arr([pos.line, pos.column, pos.offset].iter().map(|n| Json::Num(n.to_string())).collect())
+}
+
+fn to_pos(json: &Json) -> Result<Pos, SourceMapError> {
+    match json.arr().map(|ns| ns.iter().map(Json::usize).collect::<Option<Vec<_>>>()) {
+        Some(Some(ns)) if ns.len() == 3 => Ok(Pos { line: ns[0], column: ns[1], offset: ns[2] }),
+        _ => Err(SourceMapError::Format("a position")),
+    }
+}
+
+/// Rewrites the JSON diagnostics rustc prints with `--error-format=json`,
+/// one to a line, so that spans in generated rust point at the pangolisp
+/// they came from, as does the `-->` of the rendered message, which also
+/// gains a note for each macro expansion. Other lines are left alone.
+pub fn remap_diagnostics(maps: &[SourceMap], output: &str) -> String {
+    let mut out = String::new();
+    for line in output.lines() {
+        match Json::parse(line) {
+            Ok(mut diagnostic) if diagnostic.get("spans").is_some() => {
+                remap_diagnostic(maps, &mut diagnostic);
+                writeln!(out, "{}", diagnostic).unwrap();
+            }
+            _ => writeln!(out, "{}", line).unwrap(),
+        }
+    }
+    out
+}
+
+/// Just the rendered messages of JSON diagnostics, as rustc would have
+/// printed them without `--error-format=json`.
+pub fn rendered_messages(output: &str) -> String {
+    let mut out = String::new();
+    for line in output.lines() {
+        match Json::parse(line) {
+            Ok(diagnostic) => out.push_str(diagnostic.get("rendered").and_then(Json::str).unwrap_or("")),
+            Err(_) => writeln!(out, "{}", line).unwrap(),
+        }
+    }
+    out
+}
+
+fn remap_diagnostic(maps: &[SourceMap], diagnostic: &mut Json) {
+    let mut moves = Vec::new();
+    let mut notes = Vec::new();
+    if let Some(Json::Arr(spans)) = diagnostic.get_mut("spans") {
+        for span in spans {
+            if let Some((from, to, provenance)) = remap_span(maps, span) {
+                if span.get("is_primary") == Some(&Json::Bool(true)) {
+                    notes.extend(provenance);
+                }
+                moves.push((from, to));
+            }
+        }
+    }
+    if let Some(Json::Arr(children)) = diagnostic.get_mut("children") {
+        for child in children {
+            remap_diagnostic(maps, child);
+        }
+    }
+    if let Some(Json::Str(rendered)) = diagnostic.get_mut("rendered") {
+        for (from, to) in moves {
+            *rendered = rendered.replace(&format!("--> {}", from), &format!("--> {}", to));
+        }
+        // as wide as the line numbers in the margin, as rustc has it
+        let margin = rendered.lines().find_map(|line| line.find("--> ")).unwrap_or(1);
+        let end = rendered.trim_end().len();
+        let notes: String = notes.iter().map(|note| format!("\n{:>1$}= note: {2}", "", margin + 1, note)).collect();
+        rendered.insert_str(end, &notes);
+    }
+}
+
+// points a span into generated rust at the pangolisp, giving the old
+// and new locations as rustc renders them, and the provenance
+fn remap_span(maps: &[SourceMap], span: &mut Json) -> Option<(String, String, Vec<String>)> {
+    let file = span.get("file_name")?.str()?.to_string();
+    let map = maps.iter().find(|map| map.describes(&file))?;
+    let at = |key| span.get(key).and_then(Json::usize).and_then(|n| n.checked_sub(1));
+    let (line, column) = (at("line_start")?, at("column_start")?);
+    let mapping = map.lookup((line, column), (at("line_end")?, at("column_end")?))?;
+    let mut expansion = Json::Null;
+    for call in mapping.expansions.iter().rev() {
+        let name = call.name.as_deref().unwrap_or("a macro");
+        let site = call.span.map(|s| rustc_span(&map.source, s, expansion.clone())).unwrap_or(Json::Null);
+        expansion = Json::Obj(vec![
+            ("span".into(), site),
+            ("macro_decl_name".into(), Json::Str(name.to_string())),
+            ("def_site_span".into(), Json::Null),
+        ]);
+    }
+    let remapped = rustc_span(&map.source, mapping.source, expansion);
+    for key in ["file_name", "byte_start", "byte_end", "line_start", "line_end", "column_start", "column_end", "text", "expansion"] {
+        span.set(key, remapped.get(key).cloned().unwrap_or(Json::Null));
+    }
+    let to = format!("{}:{}:{}", map.source, mapping.source.start.line + 1, mapping.source.start.column + 1);
+    Some((format!("{}:{}:{}", file, line + 1, column + 1), to, mapping.provenance()))
+}
+
+// a span as rustc writes one, lines and columns counting from 1
+fn rustc_span(file: &str, span: Span, expansion: Json) -> Json {
+    let num = |n: usize| Json::Num(n.to_string());
+    Json::Obj(vec![
+        ("file_name".into(), Json::Str(file.to_string())),
+        ("byte_start".into(), num(span.start.offset)),
+        ("byte_end".into(), num(span.end.offset)),
+        ("line_start".into(), num(span.start.line + 1)),
+        ("line_end".into(), num(span.end.line + 1)),
+        ("column_start".into(), num(span.start.column + 1)),
+        ("column_end".into(), num(span.end.column + 1)),
+        ("is_primary".into(), Json::Bool(false)),
+        ("text".into(), Json::Arr(vec![])),
+        ("label".into(), Json::Null),
+        ("suggested_replacement".into(), Json::Null),
+        ("suggestion_applicability".into(), Json::Null),
+        ("expansion".into(), expansion),
+    ])
+}
+
+// Just enough JSON for source maps and diagnostics. Numbers keep their
+// text, and objects their order, so what passes through is unchanged.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Num(String),
+    Str(String),
+    Arr(Vec<Json>),
+    Obj(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Obj(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut Json> {
+        match self {
+            Json::Obj(fields) => fields.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, key: &str, val: Json) {
+        match self.get_mut(key) {
+            Some(old) => *old = val,
+            None => if let Json::Obj(fields) = self {
+                fields.push((key.to_string(), val));
+            },
+        }
+    }
+
+    fn str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn arr(&self) -> Option<&[Json]> {
+        match self {
+            Json::Arr(vals) => Some(vals),
+            _ => None,
+        }
+    }
+
+    fn usize(&self) -> Option<usize> {
+        match self {
+            Json::Num(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn parse(src: &str) -> Result<Json, SourceMapError> {
+        let mut parser = Parser { chars: src.chars().collect(), at: 0 };
+        let json = parser.value()?;
+        parser.space();
+        match parser.at == parser.chars.len() {
+            true => Ok(json),
+            false => Err(SourceMapError::Json(parser.at)),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl Parser {
+    fn space(&mut self) {
+        while self.chars.get(self.at).is_some_and(|c| c.is_whitespace()) {
+            self.at += 1;
+        }
+    }
+
+    fn next(&mut self) -> Result<char, SourceMapError> {
+        let c = self.chars.get(self.at).copied().ok_or(SourceMapError::Json(self.at))?;
+        self.at += 1;
+        Ok(c)
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), SourceMapError> {
+        for c in word.chars() {
+            if self.next()? != c {
+                return Err(SourceMapError::Json(self.at - 1));
+            }
+        }
+        Ok(())
+    }
+
+    // the values of an array or object, up to `close`
+    fn items<T>(&mut self, close: char, mut item: impl FnMut(&mut Parser) -> Result<T, SourceMapError>)
+                -> Result<Vec<T>, SourceMapError> {
+        let mut items = Vec::new();
+        self.space();
+        if self.chars.get(self.at) == Some(&close) {
+            self.at += 1;
+            return Ok(items);
+        }
+        loop {
+            self.space();
+            items.push(item(self)?);
+            self.space();
+            match self.next()? {
+                ',' => (),
+                c if c == close => return Ok(items),
+                _ => return Err(SourceMapError::Json(self.at - 1)),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, SourceMapError> {
+        self.space();
+        let start = self.at;
+        match self.next()? {
+            'n' => self.expect("ull").map(|_| Json::Null),
+            't' => self.expect("rue").map(|_| Json::Bool(true)),
+            'f' => self.expect("alse").map(|_| Json::Bool(false)),
+            '"' => self.string().map(Json::Str),
+            '[' => self.items(']', Parser::value).map(Json::Arr),
+            '{' => self.items('}', |p| {
+                p.expect("\"")?;
+                let key = p.string()?;
+                p.space();
+                p.expect(":")?;
+                Ok((key, p.value()?))
+            }).map(Json::Obj),
+            c if c == '-' || c.is_ascii_digit() => {
+                while self.chars.get(self.at).is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                    self.at += 1;
+                }
+                Ok(Json::Num(self.chars[start..self.at].iter().collect()))
+            }
+            _ => Err(SourceMapError::Json(start)),
+        }
+    }
+
+    // after the opening quote
+    fn string(&mut self) -> Result<String, SourceMapError> {
+        let mut s = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(s),
+                '\\' => match self.next()? {
+                    'n' => s.push('\n'),
+                    't' => s.push('\t'),
+                    'r' => s.push('\r'),
+                    'b' => s.push('\u{8}'),
+                    'f' => s.push('\u{c}'),
+                    'u' => {
+                        let unit = self.hex()?;
+                        // a surrogate pair
+                        let c = match unit {
+                            0xd800..=0xdbff => {
+                                self.expect("\\u")?;
+                                0x10000 + ((unit - 0xd800) << 10) + (self.hex()? - 0xdc00)
+                            }
+                            _ => unit,
+                        };
+                        s.push(char::from_u32(c).ok_or(SourceMapError::Json(self.at))?);
+                    }
+                    c => s.push(c),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, SourceMapError> {
+        let digits: String = (0..4).map(|_| self.next()).collect::<Result<_, _>>()?;
+        u32::from_str_radix(&digits, 16).map_err(|_| SourceMapError::Json(self.at))
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Num(n) => write!(f, "{}", n),
+            Json::Str(s) => {
+                f.write_char('"')?;
+                for c in s.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\r' => f.write_str("\\r")?,
+                        '\t' => f.write_str("\\t")?,
+                        c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                        c => f.write_char(c)?,
+                    }
+                }
+                f.write_char('"')
+            }
+            Json::Arr(vals) => {
+                f.write_char('[')?;
+                for (i, val) in vals.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", val)?;
+                }
+                f.write_char(']')
+            }
+            Json::Obj(fields) => {
+                f.write_char('{')?;
+                for (i, (key, val)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}:{}", Json::Str(key.clone()), val)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
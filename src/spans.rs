@@ -29,7 +29,7 @@ impl Pos {
     // do not cross a newline boundary with me
     pub fn advance_columns(mut self, columns: usize) -> Pos {
         self.offset += columns;
-        self.column = 0;
+        self.column += columns;
         self
     }
     pub fn span(self, to: Pos) -> Span {
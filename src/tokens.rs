@@ -1,12 +1,15 @@
 use crate::spans::*;
 // use ordered_float::OrderedFloat;
+use im::HashMap;
 use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::hash::Hash;
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum TokenError {
+    BadInt(String),
     InvalidChar(char),
+    InvalidEscape(char),
     Partial,
 }
 
@@ -21,7 +24,7 @@ impl Paren {
     pub fn open(self) -> char {
         match self {
             Paren::Paren => '(',
-            Paren::Brace => '}',
+            Paren::Brace => '{',
             Paren::Square => '[',
         }
     }
@@ -56,6 +59,7 @@ pub enum Prefix {
     Quasiquote,
     Quote,
     Unquote,
+    UnquoteSplicing,
 }
 
 impl TryFrom<char> for Prefix {
@@ -63,43 +67,22 @@ impl TryFrom<char> for Prefix {
     fn try_from(ch: char) -> Result<Self, ()> {
         match ch {
             ':' => Ok(Prefix::HasType),
-            ')' => Ok(Prefix::Quasiquote),
-            '{' => Ok(Prefix::Quote),
-            '}' => Ok(Prefix::Unquote),
+            '`' => Ok(Prefix::Quasiquote),
+            '\'' => Ok(Prefix::Quote),
+            '~' => Ok(Prefix::Unquote),
             _ => Err(()),
         }
     }
 }
 
-// #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-// pub enum StringEscape {
-//     DoubleQuote,
-//     Newline,
-//     Backslash,
-// }
-
-// #[derive(Clone, Debug, Eq, Hash, PartialEq)]
-// pub enum StringToken<'a> {
-//     Delimiter,
-//     Text(Cow<'a, str>),
-//     Escape(StringEscape),
-// }
-
-// impl<'a> StringToken<'a> {
-//     pub fn may_continue(&self) -> bool {
-//         match self {
-//             StringToken::Delimiter => false,
-//             StringToken::Text(_) => true,
-//             StringToken::Escape(_) => false,
-//         }
-//     }
-// }
-
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Literal<'a> {
     Int(i64),
     // Float(OrderedFloat<f64>),
+    String(Cow<'a, str>),
     Symbol(Cow<'a, str>),
+    /// `::name`. A single `:` is the type ascription prefix.
+    Keyword(Cow<'a, str>),
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -108,7 +91,9 @@ pub enum Token<'a> {
     Open(Paren),
     Close(Paren),
     Prefix(Prefix),
-    // String(StringToken<'a>)
+    /// The dispatch character of a reader macro.
+    Dispatch(char),
+    Comment(Cow<'a, str>),
     Whitespace(Cow<'a, str>),
 }
 
@@ -119,22 +104,41 @@ impl<'a> Token<'a> {
             Token::Open(_) => false,
             Token::Close(_) => false,
             Token::Prefix(_) => false,
+            Token::Dispatch(_) => false,
+            Token::Comment(_) => false,
             Token::Whitespace(_) => true,
-            // Token::String(t) => t.may_continue(),
         }
     }
 }
 
+/// The dispatch characters of reader macros, and how many forms
+/// after it each one takes.
+pub type Dispatch = HashMap<char, usize>;
+
+/// The built in reader macros: only `\x body`, which is a lambda.
+pub fn default_dispatch() -> Dispatch {
+    HashMap::unit('\\', 2)
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Tokens<'a> {
-    in_string: bool,
     source: &'a str,
     pub pos: Pos,
+    dispatch: Dispatch,
 }
 
 impl<'a> Tokens<'a> {
     pub fn new(source: &'a str) -> Self {
-        Tokens { source, pos: Pos::default(), in_string: false }
+        Tokens::with_dispatch(source, default_dispatch())
+    }
+
+    pub fn with_dispatch(source: &'a str, dispatch: Dispatch) -> Self {
+        Tokens { source, pos: Pos::default(), dispatch }
+    }
+
+    /// Changes the reader macros from here on.
+    pub fn set_dispatch(&mut self, dispatch: Dispatch) {
+        self.dispatch = dispatch;
     }
 
     pub fn at_end(&self) -> bool {
@@ -142,6 +146,8 @@ impl<'a> Tokens<'a> {
     }
 
     fn span_move_cols(&mut self, cols: usize) -> Span {
+        let bytes = self.source.char_indices().nth(cols).map_or(self.source.len(), |(i, _)| i);
+        self.source = &self.source[bytes..];
         let start = self.pos;
         self.pos = start.advance_columns(cols);
         Span::new(start, self.pos)
@@ -152,53 +158,73 @@ impl<'a> Tokens<'a> {
         Spanning::new(token, span)
     }
 
-    fn parse_number(&mut self) -> Spanning<Token<'a>> {
-        if self.source.starts_with("0x") {
-            let (_, after) = self.source.split_at(2);
-            match after.find(|ch: char| !ch.is_ascii_hexdigit()) {
-                Some(index) => {
-                    let (before, after) = after.split_at(index);
+    fn parse_number(&mut self) -> Result<Spanning<Token<'a>>, TokenError> {
+        let sign = if self.source.starts_with('-') { 1 } else { 0 };
+        let (radix, skip) =
+            if self.source[sign..].starts_with("0x") { (16, sign + 2) } else { (10, sign) };
+        let digits = &self.source[skip..];
+        let len = digits.find(|ch: char| !ch.is_digit(radix)).unwrap_or(digits.len());
+        let (before, after) = self.source.split_at(skip + len);
+        let magnitude = &before[skip..];
+        // parse the sign with the digits so that i64::MIN fits
+        let int =
+            if sign == 1 { i64::from_str_radix(&format!("-{}", magnitude), radix) }
+            else { i64::from_str_radix(magnitude, radix) };
+        let int = int.map_err(|_| TokenError::BadInt(before.to_string()))?;
+        self.source = after;
+        let start = self.pos;
+        let end = start.advance_columns(before.len());
+        self.pos = end;
+        Ok(Spanning::new(Token::Literal(Literal::Int(int)), Span::new(start, end)))
+    }
+
+    fn parse_comment(&mut self) -> Spanning<Token<'a>> {
+        let index = self.source.find('\n').unwrap_or(self.source.len());
+        let (before, after) = self.source.split_at(index);
+        self.source = after;
+        let start = self.pos;
+        let end = start.after(before);
+        self.pos = end;
+        Spanning::new(Token::Comment(before.into()), Span::new(start, end))
+    }
+
+    fn parse_string(&mut self) -> Result<Spanning<Token<'a>>, TokenError> {
+        // we only allocate if we meet an escape
+        let mut owned: Option<String> = None;
+        let mut chars = self.source.char_indices().skip(1);
+        while let Some((index, ch)) = chars.next() {
+            match ch {
+                '"' => {
+                    let (before, after) = self.source.split_at(index + 1);
+                    let text = match owned {
+                        Some(text) => Cow::Owned(text),
+                        None => Cow::Borrowed(&before[1..index]),
+                    };
                     self.source = after;
                     let start = self.pos;
-                    let end = start.advance_columns(before.len() + 2);
-                    let span = Span::new(start, end);
-                    let token = Token::Literal(Literal::Int(i64::from_str_radix(before, 16).unwrap()));
-                    self.pos = end;
-                    Spanning::new(token, span)
-                }
-                None => {
-                    let start = self.pos;
-                    let end = start.advance_columns(self.source.len());
-                    let span = Span::new(start, end);
-                    let token = Token::Literal(Literal::Int(i64::from_str_radix(self.source, 16).unwrap()));
-                    self.source = "";
+                    let end = start.after(before);
                     self.pos = end;
-                    Spanning::new(token, span)
+                    return Ok(Spanning::new(Token::Literal(Literal::String(text)), Span::new(start, end)));
                 }
-            }
-        } else {
-            match self.source.find(|ch: char| !ch.is_ascii_digit()) {
-                Some(index) => {
-                    let (before, after) = self.source.split_at(index);
-                    self.source = after;
-                    let start = self.pos;
-                    let end = start.advance_columns(before.len());
-                    let span = Span::new(start, end);
-                    let token = Token::Literal(Literal::Int(i64::from_str_radix(before, 10).unwrap()));
-                    self.pos = end;
-                    Spanning::new(token, span)
+                '\\' => {
+                    let text = owned.get_or_insert_with(|| self.source[1..index].to_string());
+                    match chars.next() {
+                        Some((_, 'n')) => text.push('\n'),
+                        Some((_, 't')) => text.push('\t'),
+                        Some((_, '"')) => text.push('"'),
+                        Some((_, '\\')) => text.push('\\'),
+                        Some((_, other)) => return Err(TokenError::InvalidEscape(other)),
+                        None => return Err(TokenError::Partial),
+                    }
                 }
-                None => {
-                    let start = self.pos;
-                    let end = start.advance_columns(self.source.len());
-                    let span = Span::new(start, end);
-                    let token = Token::Literal(Literal::Int(i64::from_str_radix(self.source, 10).unwrap()));
-                    self.source = "";
-                    self.pos = end;
-                    Spanning::new(token, span)
+                _ => {
+                    if let Some(text) = owned.as_mut() {
+                        text.push(ch);
+                    }
                 }
             }
         }
+        Err(TokenError::Partial)
     }
 
     fn parse_whitespace(&mut self) -> Spanning<Token<'a>> {
@@ -225,8 +251,19 @@ impl<'a> Tokens<'a> {
         }
     }
 
+    fn parse_keyword(&mut self) -> Spanning<Token<'a>> {
+        let start = self.pos;
+        self.span_move_cols(2);
+        let symbol = self.parse_symbol();
+        let token = match symbol.inner {
+            Token::Literal(Literal::Symbol(name)) => Token::Literal(Literal::Keyword(name)),
+            other => other,
+        };
+        Spanning::new(token, Span::new(start, symbol.span.end))
+    }
+
     fn parse_symbol(&mut self) -> Spanning<Token<'a>> {
-        match self.source.find(is_symbol) {
+        match self.source.find(|ch: char| !continues_symbol(ch)) {
             Some(index) => {
                 let (before, after) = self.source.split_at(index);
                 self.source = after;
@@ -249,18 +286,24 @@ impl<'a> Tokens<'a> {
         }
     }
 
-    fn parse_string_token(&mut self) -> Option<Result<Spanning<Token<'a>>, TokenError>> {
-        unimplemented!()
-    }
-
     fn parse_program_token(&mut self) -> Option<Result<Spanning<Token<'a>>, TokenError>> {
         let ch = self.source.chars().next()?;
-        if ch.is_ascii_digit() {
-            Some(Ok(self.parse_number()))
-        } else if ch.is_control() {
-            Some(Err(TokenError::InvalidChar(ch)))
+        if ch.is_ascii_digit() || starts_negative(self.source) {
+            Some(self.parse_number())
+        } else if ch == '"' {
+            Some(self.parse_string())
+        } else if ch == ';' {
+            Some(Ok(self.parse_comment()))
         } else if ch.is_ascii_whitespace() {
             Some(Ok(self.parse_whitespace()))
+        } else if ch.is_control() {
+            Some(Err(TokenError::InvalidChar(ch)))
+        } else if starts_keyword(self.source) {
+            Some(Ok(self.parse_keyword()))
+        } else if self.dispatch.contains_key(&ch) {
+            Some(Ok(self.spanning_move_cols(1, Token::Dispatch(ch))))
+        } else if self.source.starts_with("~@") {
+            Some(Ok(self.spanning_move_cols(2, Token::Prefix(Prefix::UnquoteSplicing))))
         } else if is_prefix(ch) {
             Some(Ok(self.spanning_move_cols(1, Token::Prefix(Prefix::try_from(ch).unwrap()))))
         } else if is_open(ch) {
@@ -276,11 +319,7 @@ impl<'a> Tokens<'a> {
 impl<'a> Iterator for Tokens<'a> {
     type Item = Result<Spanning<Token<'a>>, TokenError>;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.in_string {
-            self.parse_string_token()
-        } else {
-            self.parse_program_token()
-        }
+        self.parse_program_token()
     }
 }
 
@@ -296,7 +335,21 @@ fn is_prefix(ch: char) -> bool {
     ":`'~".chars().any(|dh| ch == dh)
 }
 
+fn starts_negative(source: &str) -> bool {
+    let mut chars = source.chars();
+    chars.next() == Some('-') && chars.next().is_some_and(|ch| ch.is_ascii_digit())
+}
+
+fn starts_keyword(source: &str) -> bool {
+    source.starts_with("::") && source[2..].chars().next().is_some_and(is_symbol)
+}
+
 fn is_symbol(ch: char) -> bool {
-    !is_prefix(ch) && !is_open(ch) && !is_close(ch)
+    ch != '"' && ch != ';' && !is_prefix(ch) && !is_open(ch) && !is_close(ch)
         && !ch.is_ascii_digit() && !ch.is_ascii_whitespace() && !ch.is_control()
 }
+
+// digits may not start a symbol, but they may appear after the first char
+fn continues_symbol(ch: char) -> bool {
+    is_symbol(ch) || ch.is_ascii_digit()
+}
@@ -0,0 +1,350 @@
+use crate::data::bracketed;
+use crate::eval::*;
+use crate::exprs::*;
+use crate::infer::{is_special, Dictionaries, Elaboration};
+use crate::spans::Span;
+use crate::types::*;
+use im::{HashMap, Vector};
+use std::rc::Rc;
+
+fn span(expr: &Expr) -> Option<Span> {
+    expr.meta().and_then(|m| m.span)
+}
+
+/// A method of a trait, with the argument that picks which
+/// implementation it calls where unchecked, if any.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Method {
+    pub name: String,
+    pub typ: Type,
+    pub arity: usize,
+    pub dispatch: Option<usize>,
+}
+
+/// A trait declared with `deftrait`: methods whose types mention
+/// `param`, which stands for the type implementing it.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Trait {
+    pub name: String,
+    pub param: TypeVar,
+    /// In the order declared.
+    pub methods: Vec<Method>,
+}
+
+impl Trait {
+    /// Reads `(deftrait (Name 'a) [method type]..)`. Checked, the type
+    /// a method is used at picks the implementation to call; unchecked,
+    /// its first argument of type `'a` does, so a method without one,
+    /// such as one only returning an `'a`, can only be called checked.
+    pub fn parse(form: &List, env: &TypeEnv) -> Result<Trait, TypeError> {
+        let bad = || TypeError::BadType(Expr::List(form.clone()));
+        let (name, param) = match form.vals.get(1) {
+            Some(Expr::List(head)) => match head.vals.iter().collect::<Vec<_>>().as_slice() {
+                [Expr::Symbol(name), param] => match env.parse(param)? {
+                    Type::Var(var) => (name.value.clone(), var),
+                    _ => return Err(TypeError::BadType((*param).clone())),
+                },
+                _ => return Err(bad()),
+            },
+            _ => return Err(bad()),
+        };
+        let methods = form.vals.iter().skip(2).map(|clause| {
+            let (method, typ) = bracketed(clause).ok_or_else(|| TypeError::BadType(clause.clone()))?;
+            let typ = env.parse(typ)?;
+            let mut params = Vec::new();
+            let mut rest = &typ;
+            while let Some((from, to)) = rest.as_function() {
+                params.push(from);
+                rest = to;
+            }
+            let param_type = Type::from(param.clone());
+            let dispatch = params.iter().position(|p| **p == param_type);
+            Ok(Method { name: method.value.clone(), arity: params.len(), dispatch, typ: typ.clone() })
+        }).collect::<Result<Vec<_>, TypeError>>()?;
+        Ok(Trait { name, param, methods })
+    }
+
+    /// That its parameter implements it.
+    pub fn constraint(&self) -> Constraint {
+        Constraint::new(self.name.clone(), self.param.clone().into())
+    }
+
+    pub fn method(&self, name: &str) -> Option<&Method> {
+        self.methods.iter().find(|m| m.name == name)
+    }
+
+    /// The type of each method, for any type implementing the trait.
+    pub fn bindings(&self) -> Vec<(String, Scheme)> {
+        self.methods.iter().map(|method| {
+            let scheme = Scheme::qualified(method.typ.vars(), vec![self.constraint()], method.typ.clone());
+            (method.name.clone(), scheme)
+        }).collect()
+    }
+}
+
+/// An implementation of a trait for a type, which may hold only where
+/// its constraints do, as `(impl (Show (Vec 'a)) (where (Show 'a)) ..)`
+/// implements `Show` for vectors of whatever implements it.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Instance {
+    pub head: Constraint,
+    pub constraints: Vec<Constraint>,
+}
+
+impl Instance {
+    /// Reads `(impl (Trait type) (where constraint..) [method body]..)`,
+    /// the `where` being optional, and gives back the body of each
+    /// method. Every method of the trait must be given.
+    pub fn parse(form: &List, env: &TypeEnv) -> Result<(Instance, Vec<(String, Expr)>), TypeError> {
+        let head = match form.vals.get(1) {
+            Some(head) => constraint(head, env)?,
+            None => return Err(TypeError::BadType(Expr::List(form.clone()))),
+        };
+        let kind = head.typ.kind()?;
+        if kind != Kind::Star {
+            return Err(TypeError::KindMismatch { typ: head.typ, expected: Kind::Star, found: kind });
+        }
+        let tr = env.get_trait(&head.trait_name).expect("checked by `constraint`");
+        let vars = head.typ.vars();
+        let mut constraints = Vec::new();
+        let mut methods: Vec<(String, Expr)> = Vec::new();
+        for clause in form.vals.iter().skip(2) {
+            if let Some((name, body)) = bracketed(clause) {
+                if tr.method(&name.value).is_none() {
+                    return Err(TypeError::UnknownMethod {
+                        trait_name: tr.name.clone(), method: name.value.clone(), span: name.meta.span,
+                    });
+                }
+                methods.push((name.value.clone(), body.clone()));
+                continue;
+            }
+            match clause {
+                Expr::List(clause) if matches!(clause.vals.front(), Some(Expr::Symbol(s)) if s.value == "where") => {
+                    for val in clause.vals.iter().skip(1) {
+                        let c = constraint(val, env)?;
+                        if let Some(var) = c.typ.vars().into_iter().find(|v| !vars.contains(v)) {
+                            return Err(TypeError::UnknownType(format!("'{}", var.name), span(val)));
+                        }
+                        constraints.push(c);
+                    }
+                }
+                other => return Err(TypeError::BadType(other.clone())),
+            }
+        }
+        if let Some(missing) = tr.methods.iter().find(|m| !methods.iter().any(|(name, _)| *name == m.name)) {
+            return Err(TypeError::MissingMethod { constraint: head, method: missing.name.clone() });
+        }
+        Ok((Instance { head, constraints }, methods))
+    }
+
+    /// The type `method` of `tr` has in this implementation.
+    pub fn method_type(&self, tr: &Trait, method: &Method) -> Type {
+        let subst: HashMap<String, Type> = std::iter::once((tr.param.name.clone(), self.head.typ.clone())).collect();
+        method.typ.substitute(&subst)
+    }
+}
+
+// `(Trait type)`
+fn constraint(expr: &Expr, env: &TypeEnv) -> Result<Constraint, TypeError> {
+    match expr {
+        Expr::List(list) => match list.vals.iter().collect::<Vec<_>>().as_slice() {
+            [Expr::Symbol(name), typ] => match env.get_trait(&name.value) {
+                Some(_) => Ok(Constraint::new(name.value.clone(), env.parse(typ)?)),
+                None => Err(TypeError::UnknownTrait(name.value.clone(), name.meta.span)),
+            },
+            _ => Err(TypeError::BadType(expr.clone())),
+        },
+        other => Err(TypeError::BadType(other.clone())),
+    }
+}
+
+/// The methods of an `impl`, as the evaluator calls them. Those of an
+/// impl with constraints first take a dictionary for each.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Implementation {
+    pub typ: Type,
+    pub methods: HashMap<String, Expr>,
+}
+
+/// The parameter a dictionary for `constraint` is passed as.
+pub fn parameter(constraint: &Constraint) -> String {
+    format!("#{}", constraint)
+}
+
+/// Binds each method of `tr` in `eval` to a function that calls the
+/// implementation for the type of its dispatch argument. Checked code
+/// takes methods from the dictionaries `elaborate` passes instead.
+pub fn define(eval: &mut Eval, tr: &Trait) {
+    for method in &tr.methods {
+        eval.define(method.name.clone(), Expr::Native(dispatching(tr, method)));
+    }
+}
+
+// calls `method` of the impl for the type of its dispatch argument
+fn dispatching(tr: &Trait, method: &Method) -> Native {
+    let (trait_name, name, dispatch) = (tr.name.clone(), method.name.clone(), method.dispatch);
+    let index = tr.methods.iter().position(|m| m.name == method.name).expect("a method of `tr`");
+    Native::new(method.name.clone(), Arity::Exactly(method.arity), Rc::new(move |eval: &mut Eval, args: &[Expr]| {
+        let dispatch = dispatch.ok_or_else(|| EvalError::Type(Box::new(TypeError::Undispatchable(name.clone(), None))))?;
+        let val = &args[dispatch];
+        let dictionary = match eval.types().type_of(val) {
+            Some(typ) => eval.dictionary(&Constraint::new(trait_name.clone(), typ))?,
+            None => None,
+        };
+        let fun = dictionary.and_then(|dictionary| field(&dictionary, index))
+            .ok_or_else(|| EvalError::NoImplementation(trait_name.clone(), val.clone()))?;
+        eval.apply(fun, args.to_vec())
+    }), Meta::default())
+}
+
+/// A dictionary for `tr` whose methods each call the implementation
+/// for the type of their dispatch argument, for where that type is
+/// only known once running.
+pub fn dispatching_dictionary(tr: &Trait) -> Expr {
+    let fields = tr.methods.iter().map(|method| Expr::Native(dispatching(tr, method))).collect();
+    Expr::Data(Data::new(tr.name.clone(), tr.constraint().to_string(), fields, Meta::default()))
+}
+
+fn field(dictionary: &Expr, index: usize) -> Option<Expr> {
+    match dictionary {
+        Expr::Data(data) => data.fields.get(index).cloned(),
+        _ => None,
+    }
+}
+
+/// `expr`, the form `elaboration` was made for, passing impls as
+/// dictionaries: a definition or let binding with constraints takes a
+/// dictionary for each, and each use is passed those it wants, from
+/// the dictionaries in scope or made from the impls in `env`. A method
+/// is taken from the dictionary for its trait.
+pub fn elaborate(expr: &Expr, elaboration: &Elaboration, env: &TypeEnv) -> Result<Expr, TypeError> {
+    let scope = elaboration.forms.first().cloned().unwrap_or_default();
+    let elaborator = Elaborator { elaboration, env };
+    match expr {
+        Expr::List(list) if !scope.is_empty() && list.vals.len() == 3 && is_special(&list.vals[0], "def") => {
+            let val = elaborator.taking(&scope, &list.vals[2], &Vec::new())?;
+            Ok(Expr::List(List::new(list.vals.update(2, val), list.meta.clone())))
+        }
+        _ => elaborator.expr(expr, &scope),
+    }
+}
+
+struct Elaborator<'e> {
+    elaboration: &'e Elaboration,
+    env: &'e TypeEnv,
+}
+
+impl Elaborator<'_> {
+    fn expr(&self, expr: &Expr, scope: &Dictionaries) -> Result<Expr, TypeError> {
+        match expr {
+            Expr::Symbol(sym) => self.symbol(sym, scope),
+            Expr::List(list) if list.vals.front().is_some_and(|head| is_special(head, "quote")) => Ok(expr.clone()),
+            Expr::List(list) if list.vals.front().is_some_and(|head| is_special(head, "let")) => self.let_form(list, scope),
+            Expr::List(list) => {
+                let vals = list.vals.iter().map(|val| self.expr(val, scope)).collect::<Result<_, _>>()?;
+                Ok(Expr::List(List::new(vals, list.meta.clone())))
+            }
+            _ => Ok(expr.clone()),
+        }
+    }
+
+    fn symbol(&self, sym: &Symbol, scope: &Dictionaries) -> Result<Expr, TypeError> {
+        let wanted = match sym.meta.span.and_then(|span| self.elaboration.uses.get(&(span, sym.value.clone()))) {
+            Some(wanted) => wanted,
+            None => return Ok(Expr::Symbol(sym.clone())),
+        };
+        let mut vals = wanted.constraints.iter()
+            .map(|constraint| self.dictionary(constraint, scope, sym.meta.span))
+            .collect::<Result<Vec<_>, _>>()?;
+        let method = wanted.constraints.first().filter(|_| wanted.method)
+            .and_then(|constraint| self.env.get_trait(&constraint.trait_name))
+            .and_then(|tr| Some((tr, tr.methods.iter().position(|m| m.name == sym.value)?)));
+        match method {
+            Some((tr, index)) => {
+                vals.truncate(1);
+                vals.insert(0, Expr::Native(method_of(tr, index, sym.meta.clone())));
+            }
+            None => vals.insert(0, Expr::Symbol(sym.clone())),
+        }
+        Ok(Expr::List(List::new(vals.into_iter().collect(), sym.meta.clone())))
+    }
+
+    // each binding that takes dictionaries is made a function of them
+    fn let_form(&self, list: &List, scope: &Dictionaries) -> Result<Expr, TypeError> {
+        let mut vals = Vector::new();
+        for (i, val) in list.vals.iter().enumerate() {
+            let val = match val {
+                Expr::List(bindings) if i == 1 => {
+                    let mut name: Option<&Expr> = None;
+                    let mut elaborated = Vector::new();
+                    for val in &bindings.vals {
+                        elaborated.push_back(match name.take() {
+                            None => {
+                                name = Some(val);
+                                val.clone()
+                            }
+                            Some(Expr::Symbol(name)) => match name.meta.span.and_then(|span| self.elaboration.lets.get(&span)) {
+                                Some(taken) => self.taking(taken, val, scope)?,
+                                None => self.expr(val, scope)?,
+                            },
+                            Some(_) => self.expr(val, scope)?,
+                        });
+                    }
+                    Expr::List(List::new(elaborated, bindings.meta.clone()))
+                }
+                _ if i == 0 => val.clone(),
+                _ => self.expr(val, scope)?,
+            };
+            vals.push_back(val);
+        }
+        Ok(Expr::List(List::new(vals, list.meta.clone())))
+    }
+
+    // `val` as a function of the dictionaries it takes
+    fn taking(&self, taken: &Dictionaries, val: &Expr, scope: &Dictionaries) -> Result<Expr, TypeError> {
+        let inner = taken.iter().chain(scope.iter()).cloned().collect();
+        let body = self.expr(val, &inner)?;
+        Ok(taken.iter().rev().fold(body, |body, (_, param)| {
+            let param = Expr::Symbol(Symbol::new(param.clone(), Meta::default()));
+            let vals = vec![Expr::Special(Special::Lambda(Meta::default())), param, body];
+            Expr::List(List::new(vals.into_iter().collect(), Meta::default()))
+        }))
+    }
+
+    // the dictionary meeting `constraint`: one in scope, or one made
+    // from the impl for its type
+    fn dictionary(&self, constraint: &Constraint, scope: &Dictionaries, span: Option<Span>) -> Result<Expr, TypeError> {
+        if let Some((_, param)) = scope.iter().find(|(given, _)| given == constraint) {
+            return Ok(Expr::Symbol(Symbol::new(param.clone(), Meta::default())));
+        }
+        match self.env.instance(constraint) {
+            Some((instance, wanted)) => {
+                let mut vals = vec![Expr::Native(building(&instance.head, wanted.len()))];
+                for constraint in &wanted {
+                    vals.push(self.dictionary(constraint, scope, span)?);
+                }
+                Ok(Expr::List(List::new(vals.into_iter().collect(), Meta::default())))
+            }
+            None if constraint.typ.vars().is_empty() =>
+                Err(TypeError::NoInstance { constraint: constraint.clone(), span }),
+            None => Err(TypeError::Ambiguous { constraint: constraint.clone(), span }),
+        }
+    }
+}
+
+// makes the dictionary of the impl for `head`, given those its
+// constraints want
+fn building(head: &Constraint, wanted: usize) -> Native {
+    let head = head.clone();
+    Native::new(head.to_string(), Arity::Exactly(wanted), Rc::new(move |eval: &mut Eval, args: &[Expr]| {
+        eval.dictionary_of(&head, args.to_vec())
+    }), Meta::default())
+}
+
+// takes the method at `index` from a dictionary for `tr`
+fn method_of(tr: &Trait, index: usize, meta: Meta) -> Native {
+    let method = tr.methods[index].name.clone();
+    Native::new(method.clone(), Arity::Exactly(1), Rc::new(move |_: &mut Eval, args: &[Expr]| {
+        field(&args[0], index).ok_or_else(|| EvalError::BadParameter("dictionary", args[0].clone()))
+    }), meta)
+}
@@ -0,0 +1,758 @@
+use crate::data::DataType;
+use crate::traits::{Instance, Trait};
+use crate::exprs::*;
+use crate::spans::*;
+use im::{HashMap, HashSet, Vector};
+use std::fmt;
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Kind {
+    Star,
+    Arrow(Box<Kind>, Box<Kind>),
+}
+
+impl Kind {
+    pub fn arrow(from: Kind, to: Kind) -> Kind {
+        Kind::Arrow(Box::new(from), Box::new(to))
+    }
+
+    /// The kind of a constructor taking `n` types.
+    pub fn of_arity(n: usize) -> Kind {
+        (0..n).fold(Kind::Star, |kind, _| Kind::arrow(Kind::Star, kind))
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Star => write!(f, "*"),
+            Kind::Arrow(from, to) if **from == Kind::Star => write!(f, "* -> {}", to),
+            Kind::Arrow(from, to) => write!(f, "({}) -> {}", from, to),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TypeError {
+    /// A type was applied to one of the wrong kind.
+    KindMismatch { typ: Type, expected: Kind, found: Kind },
+    /// A type of kind `*` was applied to something.
+    NotAConstructor(Type),
+    UnknownType(String, Option<Span>),
+    /// Not something the type syntax allows.
+    BadType(Expr),
+    /// Two types that had to be the same were not. `span` is where
+    /// `found` came from and `other` where `expected` did.
+    Mismatch { expected: Type, found: Type, span: Option<Span>, other: Option<Span> },
+    /// A type would have to contain itself.
+    Infinite { var: TypeVar, typ: Type, span: Option<Span> },
+    Unbound(String, Option<Span>),
+    /// A form inference does not handle.
+    Unsupported(Expr),
+    UnknownTrait(String, Option<Span>),
+    /// An `impl` gives a method its trait does not declare.
+    UnknownMethod { trait_name: String, method: String, span: Option<Span> },
+    /// An `impl` leaves out a method of its trait.
+    MissingMethod { constraint: Constraint, method: String },
+    /// A trait method, called unchecked, has no argument of the
+    /// implementing type to choose an implementation by.
+    Undispatchable(String, Option<Span>),
+    /// Nothing pins down the type a constraint is on, so which impl
+    /// meets it is not known.
+    Ambiguous { constraint: Constraint, span: Option<Span> },
+    /// Some type would have two implementations of the trait.
+    Overlap { first: Constraint, second: Constraint },
+    /// A type was used as if it implemented a trait it does not.
+    NoInstance { constraint: Constraint, span: Option<Span> },
+}
+
+impl TypeError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            TypeError::UnknownType(_, span)
+            | TypeError::Mismatch { span, .. }
+            | TypeError::Infinite { span, .. }
+            | TypeError::Unbound(_, span)
+            | TypeError::UnknownTrait(_, span)
+            | TypeError::UnknownMethod { span, .. }
+            | TypeError::Undispatchable(_, span)
+            | TypeError::Ambiguous { span, .. }
+            | TypeError::NoInstance { span, .. } => *span,
+            TypeError::BadType(expr) | TypeError::Unsupported(expr) => expr.meta().and_then(|m| m.span),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeError::KindMismatch { typ, expected, found } =>
+                write!(f, "{} has kind {}, expected {}", typ, found, expected),
+            TypeError::NotAConstructor(typ) => write!(f, "{} takes no type arguments", typ),
+            TypeError::UnknownType(name, Some(span)) =>
+                write!(f, "unknown type {} at {}:{}", name, span.start.line + 1, span.start.column + 1),
+            TypeError::UnknownType(name, None) => write!(f, "unknown type {}", name),
+            TypeError::BadType(expr) => write!(f, "{} is not a type", expr),
+            TypeError::Mismatch { expected, found, span, other } => {
+                write!(f, "expected {}, found {}", expected, found)?;
+                if let Some(span) = span {
+                    write!(f, " at {}:{}", span.start.line + 1, span.start.column + 1)?;
+                }
+                if let Some(other) = other {
+                    write!(f, ", expected because of {}:{}", other.start.line + 1, other.start.column + 1)?;
+                }
+                Ok(())
+            }
+            TypeError::Infinite { var, typ, span: Some(span) } =>
+                write!(f, "'{} would be the infinite type {} at {}:{}",
+                       var.name, typ, span.start.line + 1, span.start.column + 1),
+            TypeError::Infinite { var, typ, span: None } =>
+                write!(f, "'{} would be the infinite type {}", var.name, typ),
+            TypeError::Unbound(name, Some(span)) =>
+                write!(f, "no type for {} at {}:{}", name, span.start.line + 1, span.start.column + 1),
+            TypeError::Unbound(name, None) => write!(f, "no type for {}", name),
+            TypeError::Unsupported(expr) => write!(f, "cannot infer a type for {}", expr),
+            TypeError::UnknownTrait(name, span) => write!(f, "unknown trait {}{}", name, at(span)),
+            TypeError::UnknownMethod { trait_name, method, span } =>
+                write!(f, "{} is not a method of {}{}", method, trait_name, at(span)),
+            TypeError::MissingMethod { constraint, method } =>
+                write!(f, "the impl of {} has no {}", constraint, method),
+            TypeError::Undispatchable(method, span) =>
+                write!(f, "{} takes nothing of its trait's type to dispatch on{}", method, at(span)),
+            TypeError::Ambiguous { constraint, span } =>
+                write!(f, "which impl of {} is meant is not known{}", constraint, at(span)),
+            TypeError::Overlap { first, second } => write!(f, "the impls of {} and {} overlap", first, second),
+            TypeError::NoInstance { constraint, span } => write!(f, "no impl of {}{}", constraint, at(span)),
+        }
+    }
+}
+
+fn at(span: &Option<Span>) -> String {
+    match span {
+        Some(span) => format!(" at {}:{}", span.start.line + 1, span.start.column + 1),
+        None => String::new(),
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TypeConstructor {
+    pub name: String,
+    pub kind: Kind,
+}
+
+impl TypeConstructor {
+    pub fn new(name: impl Into<String>, kind: Kind) -> Self {
+        TypeConstructor { name: name.into(), kind }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TypeVar {
+    pub name: String,
+    pub kind: Kind,
+}
+
+impl TypeVar {
+    pub fn new(name: impl Into<String>, kind: Kind) -> Self {
+        TypeVar { name: name.into(), kind }
+    }
+
+    /// Whether it is one of `TypeEnv::weak_var`'s, which are never
+    /// generalised.
+    pub fn is_weak(&self) -> bool {
+        self.name.starts_with(WEAK)
+    }
+}
+
+// what the names of weak variables start with
+const WEAK: &str = "_weak";
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Type {
+    Var(TypeVar),
+    Constructor(TypeConstructor),
+    Application(Box<Type>, Box<Type>),
+}
+
+impl From<TypeVar> for Type {
+    fn from(var: TypeVar) -> Type {
+        Type::Var(var)
+    }
+}
+
+impl From<TypeConstructor> for Type {
+    fn from(ctor: TypeConstructor) -> Type {
+        Type::Constructor(ctor)
+    }
+}
+
+impl Type {
+    /// Applies a type constructor to an argument of the kind it takes.
+    pub fn apply(self, to: impl Into<Type>) -> Result<Type, TypeError> {
+        let to = to.into();
+        match self.kind()? {
+            Kind::Arrow(from, _) => {
+                let found = to.kind()?;
+                if *from == found {
+                    Ok(Type::Application(Box::new(self), Box::new(to)))
+                } else {
+                    Err(TypeError::KindMismatch { typ: to, expected: *from, found })
+                }
+            }
+            Kind::Star => Err(TypeError::NotAConstructor(self)),
+        }
+    }
+
+    /// Applies a type constructor to each argument in turn.
+    pub fn apply_all(self, args: impl IntoIterator<Item = Type>) -> Result<Type, TypeError> {
+        args.into_iter().try_fold(self, Type::apply)
+    }
+
+    pub fn kind(&self) -> Result<Kind, TypeError> {
+        match self {
+            Type::Var(v) => Ok(v.kind.clone()),
+            Type::Constructor(c) => Ok(c.kind.clone()),
+            Type::Application(l, r) => {
+                match l.kind()? {
+                    Kind::Arrow(from, to) => {
+                        let found = r.kind()?;
+                        if *from == found {
+                            Ok(*to)
+                        } else {
+                            Err(TypeError::KindMismatch { typ: (**r).clone(), expected: *from, found })
+                        }
+                    }
+                    Kind::Star => Err(TypeError::NotAConstructor((**l).clone())),
+                }
+            }
+        }
+    }
+
+    /// The constructor at the head of the type and what it is applied
+    /// to, if the head is a constructor and not a variable.
+    pub fn unapply(&self) -> Option<(&TypeConstructor, Vec<&Type>)> {
+        match self {
+            Type::Constructor(c) => Some((c, Vec::new())),
+            Type::Application(l, r) => {
+                let (c, mut args) = l.unapply()?;
+                args.push(r);
+                Some((c, args))
+            }
+            Type::Var(_) => None,
+        }
+    }
+
+    /// The variables in the type, in the order they first appear.
+    pub fn vars(&self) -> Vec<TypeVar> {
+        let mut vars = Vec::new();
+        self.collect_vars(&mut vars);
+        vars
+    }
+
+    fn collect_vars(&self, vars: &mut Vec<TypeVar>) {
+        match self {
+            Type::Var(v) if !vars.contains(v) => vars.push(v.clone()),
+            Type::Application(l, r) => {
+                l.collect_vars(vars);
+                r.collect_vars(vars);
+            }
+            _ => (),
+        }
+    }
+
+    /// Replaces variables by name.
+    pub fn substitute(&self, subst: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::Var(v) => subst.get(&v.name).cloned().unwrap_or_else(|| self.clone()),
+            Type::Constructor(_) => self.clone(),
+            Type::Application(l, r) =>
+                Type::Application(Box::new(l.substitute(subst)), Box::new(r.substitute(subst))),
+        }
+    }
+
+    /// Whether `val` is of this type, as far as can be told without
+    /// running anything: a function need only be callable. Values of
+    /// types with no representation here, such as floats, never are.
+    pub fn admits(&self, val: &Expr) -> bool {
+        let (ctor, args) = match self.unapply() {
+            Some(unapplied) => unapplied,
+            // a variable, or one applied to something
+            None => return true,
+        };
+        match (ctor.name.as_str(), args.as_slice(), val) {
+            ("()", [], Expr::Nil) => true,
+            ("bool", [], Expr::Bool(_)) => true,
+            ("String", [], Expr::String(_)) | ("str", [], Expr::String(_)) => true,
+            ("char", [], Expr::String(s)) => s.value.chars().count() == 1,
+            ("Expr", [], _) => true,
+            (name, [], Expr::Int(i)) => int_fits(name, i.value),
+            ("Keyword", [], Expr::Keyword(_)) | ("Symbol", [], Expr::Symbol(_)) => true,
+            // nil is the empty list as well as nothing
+            ("Vec", [_], Expr::Nil) | ("Option", [_], Expr::Nil) => true,
+            ("Vec", [elem], Expr::List(l)) => l.vals.iter().all(|val| elem.admits(val)),
+            ("Option", [inner], val) | ("&", [inner], val) | ("&mut", [inner], val) => inner.admits(val),
+            ("Atom", [inner], Expr::Atom(a)) => inner.admits(&a.get()),
+            // the fields are not checked
+            (name, _, Expr::Data(d)) => d.typ == name,
+            (builtin::ARROW, [_, _], val) =>
+                matches!(val, Expr::Fun(_) | Expr::Native(_) | Expr::Continuation(_)),
+            (name, args, Expr::List(l)) if builtin::tuple_arity(name) == Some(args.len()) =>
+                l.vals.len() == args.len() && args.iter().zip(l.vals.iter()).all(|(t, val)| t.admits(val)),
+            _ => false,
+        }
+    }
+
+    /// If this is a function type, what it takes and what it returns.
+    pub fn as_function(&self) -> Option<(&Type, &Type)> {
+        match self.unapply() {
+            Some((c, args)) if c.name == builtin::ARROW && args.len() == 2 => Some((args[0], args[1])),
+            _ => None,
+        }
+    }
+
+    /// A function type from `from` to `to`, which must both be of
+    /// kind `*`.
+    pub fn function(from: Type, to: Type) -> Type {
+        let arrow = Type::from(builtin::arrow());
+        Type::Application(Box::new(Type::Application(Box::new(arrow), Box::new(from))), Box::new(to))
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (head, mut args) = match self.unapply() {
+            Some((c, args)) => (c, args),
+            None => {
+                return match self {
+                    Type::Var(v) => write!(f, "'{}", v.name),
+                    Type::Application(l, r) => write!(f, "({} {})", l, r),
+                    Type::Constructor(_) => unreachable!(),
+                };
+            }
+        };
+        if args.is_empty() {
+            return write!(f, "{}", head.name);
+        }
+        // function types are written with all their arguments at once
+        if head.name == builtin::ARROW && args.len() == 2 {
+            write!(f, "(-> {}", args[0])?;
+            let mut to = args[1];
+            while let Some((c, next)) = to.unapply() {
+                if c.name != builtin::ARROW || next.len() != 2 {
+                    break;
+                }
+                write!(f, " {}", next[0])?;
+                to = next[1];
+            }
+            return write!(f, " {})", to);
+        }
+        let name = if builtin::tuple_arity(&head.name).is_some() { builtin::TUPLE } else { &head.name };
+        write!(f, "({}", name)?;
+        for arg in args.drain(..) {
+            write!(f, " {}", arg)?;
+        }
+        write!(f, ")")
+    }
+}
+
+fn int_fits(name: &str, value: i64) -> bool {
+    use std::convert::TryFrom;
+    match name {
+        "i8" => i8::try_from(value).is_ok(),
+        "i16" => i16::try_from(value).is_ok(),
+        "i32" => i32::try_from(value).is_ok(),
+        "i64" | "i128" => true,
+        "isize" => isize::try_from(value).is_ok(),
+        "u8" => u8::try_from(value).is_ok(),
+        "u16" => u16::try_from(value).is_ok(),
+        "u32" => u32::try_from(value).is_ok(),
+        "u64" | "u128" => value >= 0,
+        "usize" => usize::try_from(value).is_ok(),
+        _ => false,
+    }
+}
+
+/// That a type implements a trait, written `(Show 'a)`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Constraint {
+    pub trait_name: String,
+    pub typ: Type,
+}
+
+impl Constraint {
+    pub fn new(trait_name: impl Into<String>, typ: Type) -> Constraint {
+        Constraint { trait_name: trait_name.into(), typ }
+    }
+
+    pub fn substitute(&self, subst: &HashMap<String, Type>) -> Constraint {
+        Constraint::new(self.trait_name.clone(), self.typ.substitute(subst))
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({} {})", self.trait_name, self.typ)
+    }
+}
+
+/// A type that holds whatever its variables are, as the type of a
+/// definition does: `(forall ('a) (-> 'a 'a))` is the identity. It
+/// may hold only for those that meet its constraints, as in
+/// `(forall ('a) (where (Show 'a)) (-> 'a String))`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Scheme {
+    pub vars: Vec<TypeVar>,
+    pub constraints: Vec<Constraint>,
+    pub typ: Type,
+}
+
+impl Scheme {
+    /// A scheme that holds for nothing but `typ`.
+    pub fn mono(typ: Type) -> Scheme {
+        Scheme { vars: Vec::new(), constraints: Vec::new(), typ }
+    }
+
+    /// A scheme over every variable in `typ`, renamed `'a`, `'b` and
+    /// so on, so that equal schemes compare equal.
+    pub fn generalize(typ: Type) -> Scheme {
+        Scheme::over(typ.vars(), typ)
+    }
+
+    /// A scheme over those of `vars` that are in `typ`, renamed as
+    /// `generalize` does. Any other variables in it must not be named
+    /// like the renamed ones.
+    pub fn over(vars: Vec<TypeVar>, typ: Type) -> Scheme {
+        Scheme::qualified(vars, Vec::new(), typ)
+    }
+
+    /// As `over`, holding only where `constraints` do.
+    pub fn qualified(vars: Vec<TypeVar>, constraints: Vec<Constraint>, typ: Type) -> Scheme {
+        let vars: Vec<TypeVar> = typ.vars().into_iter().filter(|v| vars.contains(v)).collect();
+        let renamed: Vec<TypeVar> = vars.iter().enumerate()
+            .map(|(i, v)| TypeVar::new(var_name(i), v.kind.clone()))
+            .collect();
+        let subst: HashMap<String, Type> = vars.iter().zip(renamed.iter())
+            .map(|(v, r)| (v.name.clone(), r.clone().into()))
+            .collect();
+        let constraints = constraints.iter().map(|c| c.substitute(&subst)).collect();
+        Scheme { typ: typ.substitute(&subst), vars: renamed, constraints }
+    }
+}
+
+fn var_name(i: usize) -> String {
+    let letter = (b'a' + (i % 26) as u8) as char;
+    if i < 26 { letter.to_string() } else { format!("{}{}", letter, i / 26) }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.vars.is_empty() {
+            return write!(f, "{}", self.typ);
+        }
+        let vars: Vec<String> = self.vars.iter().map(|v| format!("'{}", v.name)).collect();
+        write!(f, "(forall ({}) ", vars.join(" "))?;
+        if !self.constraints.is_empty() {
+            let constraints: Vec<String> = self.constraints.iter().map(|c| c.to_string()).collect();
+            write!(f, "(where {}) ", constraints.join(" "))?;
+        }
+        write!(f, "{})", self.typ)
+    }
+}
+
+/// The type of a native taking any number of arguments, at least
+/// `min`, each of type `elem`, as `(-> elem result)` in `scheme`.
+/// Unless `shared`, each argument gets the scheme afresh, so they
+/// may be of different types.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Variadic {
+    pub scheme: Scheme,
+    pub min: usize,
+    pub shared: bool,
+}
+
+/// The constructors for rust's own types.
+pub mod builtin {
+    use super::*;
+
+    pub const ARROW: &str = "->";
+    /// Tuple constructors are named for their arity: `tuple2` takes two.
+    pub const TUPLE: &str = "tuple";
+    pub const PRIMITIVES: &[&str] = &[
+        "bool", "char", "str", "String", "f32", "f64",
+        "i8", "i16", "i32", "i64", "i128", "isize",
+        "u8", "u16", "u32", "u64", "u128", "usize",
+    ];
+    pub const INTEGERS: &[&str] = &[
+        "i8", "i16", "i32", "i64", "i128", "isize",
+        "u8", "u16", "u32", "u64", "u128", "usize",
+    ];
+
+    pub fn unit() -> TypeConstructor {
+        TypeConstructor::new("()", Kind::Star)
+    }
+
+    pub fn arrow() -> TypeConstructor {
+        TypeConstructor::new(ARROW, Kind::of_arity(2))
+    }
+
+    pub fn tuple(arity: usize) -> TypeConstructor {
+        TypeConstructor::new(format!("{}{}", TUPLE, arity), Kind::of_arity(arity))
+    }
+
+    /// The arity of a tuple constructor, from its name.
+    pub fn tuple_arity(name: &str) -> Option<usize> {
+        name.strip_prefix(TUPLE)?.parse().ok()
+    }
+
+    pub fn reference() -> TypeConstructor {
+        TypeConstructor::new("&", Kind::of_arity(1))
+    }
+
+    pub fn mutable_reference() -> TypeConstructor {
+        TypeConstructor::new("&mut", Kind::of_arity(1))
+    }
+
+    pub fn vec() -> TypeConstructor {
+        TypeConstructor::new("Vec", Kind::of_arity(1))
+    }
+
+    pub fn option() -> TypeConstructor {
+        TypeConstructor::new("Option", Kind::of_arity(1))
+    }
+
+    pub fn result() -> TypeConstructor {
+        TypeConstructor::new("Result", Kind::of_arity(2))
+    }
+
+    pub fn primitive(name: &str) -> TypeConstructor {
+        TypeConstructor::new(name, Kind::Star)
+    }
+}
+
+/// The type constructors in scope, by name, for reading types, and
+/// the types of the top level definitions.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TypeEnv {
+    constructors: HashMap<String, TypeConstructor>,
+    values: HashMap<String, Scheme>,
+    variadics: HashMap<String, Variadic>,
+    // in the order they were declared
+    datatypes: Vector<DataType>,
+    traits: HashMap<String, Trait>,
+    instances: Vector<Instance>,
+    // the definitions whose types have weak variables
+    weak: HashSet<String>,
+    weak_vars: usize,
+}
+
+impl TypeEnv {
+    /// An environment with rust's types in it.
+    pub fn builtin() -> TypeEnv {
+        let mut env = TypeEnv::default();
+        for name in builtin::PRIMITIVES {
+            env.define(builtin::primitive(name));
+        }
+        for ctor in [builtin::unit(), builtin::arrow(), builtin::reference(), builtin::mutable_reference(),
+                     builtin::vec(), builtin::option(), builtin::result()] {
+            env.define(ctor);
+        }
+        env
+    }
+
+    pub fn define(&mut self, ctor: TypeConstructor) {
+        self.constructors.insert(ctor.name.clone(), ctor);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TypeConstructor> {
+        self.constructors.get(name)
+    }
+
+    /// Gives the definition `name` a type.
+    /// Gives the definition `name` a type. Any weak variables in it
+    /// each stand for one type that later uses may pin down.
+    pub fn bind(&mut self, name: impl Into<String>, scheme: Scheme) {
+        let name = name.into();
+        self.variadics.remove(&name);
+        match scheme.typ.vars().iter().any(TypeVar::is_weak) {
+            true => self.weak.insert(name.clone()),
+            false => self.weak.remove(&name),
+        };
+        self.values.insert(name, scheme);
+    }
+
+    /// The definitions whose types have weak variables.
+    pub fn weak(&self) -> impl Iterator<Item = &String> {
+        self.weak.iter()
+    }
+
+    /// A weak variable of `kind` no other type has.
+    pub fn weak_var(&mut self, kind: Kind) -> TypeVar {
+        self.weak_vars += 1;
+        TypeVar::new(format!("{}{}", WEAK, self.weak_vars), kind)
+    }
+
+    pub fn bind_variadic(&mut self, name: impl Into<String>, variadic: Variadic) {
+        let name = name.into();
+        self.values.remove(&name);
+        self.variadics.insert(name, variadic);
+    }
+
+    /// Defines the type `data` declares, and binds what it defines at
+    /// runtime. Declaring it again replaces it.
+    pub fn declare(&mut self, data: DataType) {
+        self.define(TypeConstructor::new(data.name.clone(), Kind::of_arity(data.params.len())));
+        for (name, scheme) in data.bindings() {
+            self.bind(name, scheme);
+        }
+        match self.datatypes.iter().position(|d| d.name == data.name) {
+            Some(i) => { self.datatypes.set(i, data); }
+            None => self.datatypes.push_back(data),
+        }
+    }
+
+    pub fn datatype(&self, name: &str) -> Option<&DataType> {
+        self.datatypes.iter().find(|d| d.name == name)
+    }
+
+    /// The types declared with `defstruct` and `defenum`.
+    pub fn datatypes(&self) -> impl Iterator<Item = &DataType> {
+        self.datatypes.iter()
+    }
+
+    /// Declares the trait, and binds each of its methods.
+    pub fn declare_trait(&mut self, tr: Trait) {
+        for (name, scheme) in tr.bindings() {
+            self.bind(name, scheme);
+        }
+        self.traits.insert(tr.name.clone(), tr);
+    }
+
+    pub fn get_trait(&self, name: &str) -> Option<&Trait> {
+        self.traits.get(name)
+    }
+
+    /// Adds an implementation of a trait, unless it overlaps another:
+    /// that is, unless some type would have both. One for the same
+    /// type as before replaces it.
+    pub fn add_instance(&mut self, instance: Instance) -> Result<(), TypeError> {
+        let mut replaces = None;
+        for (i, other) in self.instances.iter().enumerate() {
+            if other.head.trait_name != instance.head.trait_name {
+                continue;
+            }
+            if other.head.typ == instance.head.typ {
+                replaces = Some(i);
+            } else if crate::infer::unifiable(&other.head.typ, &instance.head.typ) {
+                return Err(TypeError::Overlap { first: other.head.clone(), second: instance.head });
+            }
+        }
+        match replaces {
+            Some(i) => { self.instances.set(i, instance); }
+            None => self.instances.push_back(instance),
+        }
+        Ok(())
+    }
+
+    /// The implementation meeting `constraint`, and the constraints it
+    /// wants met in turn.
+    pub fn instance(&self, constraint: &Constraint) -> Option<(&Instance, Vec<Constraint>)> {
+        let trait_name = &constraint.trait_name;
+        self.instances.iter().filter(|i| i.head.trait_name == *trait_name).find_map(|instance| {
+            let mut subst = HashMap::new();
+            crate::infer::match_type(&instance.head.typ, &constraint.typ, &mut subst)
+                .then(|| (instance, instance.constraints.iter().map(|c| c.substitute(&subst)).collect()))
+        })
+    }
+
+    /// The type `val` has, as far as it shows: the parameters of a
+    /// declared type are variables. Functions, and lists whose
+    /// elements differ, have none.
+    pub fn type_of(&self, val: &Expr) -> Option<Type> {
+        let named = |name: &str| Type::from(TypeConstructor::new(name, Kind::Star));
+        match val {
+            Expr::Nil => Some(builtin::unit().into()),
+            Expr::Bool(_) => Some(named("bool")),
+            Expr::Int(_) => Some(named("i64")),
+            Expr::String(_) => Some(named("String")),
+            Expr::Keyword(_) => Some(named("Keyword")),
+            Expr::Symbol(_) => Some(named("Symbol")),
+            Expr::List(list) => {
+                let mut elems = list.vals.iter().map(|val| self.type_of(val));
+                let first = elems.next()??;
+                match elems.all(|elem| elem.as_ref() == Some(&first)) {
+                    true => Some(Type::from(builtin::vec()).apply(first).ok()?),
+                    false => None,
+                }
+            }
+            Expr::Data(data) => {
+                let ctor = self.get(&data.typ)?.clone();
+                let mut kind = &ctor.kind;
+                let mut params = Vec::new();
+                while let Kind::Arrow(from, to) = kind {
+                    params.push(Type::from(TypeVar::new(format!("p{}", params.len()), (**from).clone())));
+                    kind = to;
+                }
+                Type::from(ctor).apply_all(params).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// The implementations of the trait, in the order they were added.
+    pub fn instances<'e>(&'e self, trait_name: &'e str) -> impl Iterator<Item = &'e Instance> {
+        self.instances.iter().filter(move |i| i.head.trait_name == trait_name)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&Scheme> {
+        self.values.get(name)
+    }
+
+    pub fn variadic(&self, name: &str) -> Option<&Variadic> {
+        self.variadics.get(name)
+    }
+
+    /// Reads a type: a constructor's name, `'a` for a variable, `()`
+    /// for unit, or a constructor applied to types, as in `(Vec u8)`.
+    /// `(-> a b c)` is a function from `a` to one from `b` to `c`, and
+    /// `(tuple a b)` is a pair.
+    pub fn parse(&self, expr: &Expr) -> Result<Type, TypeError> {
+        self.parse_applied(expr, 0)
+    }
+
+    // `applied` is how many arguments the type is given, which is
+    // the only way to know the kind of a variable.
+    fn parse_applied(&self, expr: &Expr, applied: usize) -> Result<Type, TypeError> {
+        match expr {
+            Expr::Symbol(sym) => match self.get(&sym.value) {
+                Some(ctor) => Ok(ctor.clone().into()),
+                None => Err(TypeError::UnknownType(sym.value.clone(), sym.meta.span)),
+            },
+            Expr::List(list) => match list.vals.iter().collect::<Vec<_>>().as_slice() {
+                [] => Ok(builtin::unit().into()),
+                [Expr::Special(Special::Quote(_)), Expr::Symbol(var)] =>
+                    Ok(TypeVar::new(var.value.clone(), Kind::of_arity(applied)).into()),
+                [Expr::Symbol(head), args @ ..] if head.value == builtin::ARROW && args.len() >= 2 => {
+                    let mut args = args.iter().rev();
+                    let last = self.parse(args.next().unwrap())?;
+                    args.try_fold(last, |to, from| {
+                        Type::from(builtin::arrow()).apply_all(vec![self.parse(from)?, to])
+                    })
+                }
+                [Expr::Symbol(head), args @ ..] if head.value == builtin::TUPLE => {
+                    let args = args.iter().map(|arg| self.parse(arg)).collect::<Result<Vec<_>, _>>()?;
+                    if args.is_empty() {
+                        return Ok(builtin::unit().into());
+                    }
+                    Type::from(builtin::tuple(args.len())).apply_all(args)
+                }
+                [head, args @ ..] => {
+                    let head = self.parse_applied(head, args.len())?;
+                    let args = args.iter().map(|arg| self.parse(arg)).collect::<Result<Vec<_>, _>>()?;
+                    head.apply_all(args)
+                }
+            },
+            other => Err(TypeError::BadType(other.clone())),
+        }
+    }
+}
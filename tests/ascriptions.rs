@@ -0,0 +1,74 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+use pangolisp::exprs::*;
+use pangolisp::spans::Span;
+
+fn mismatch(eval: &mut Eval, src: &str) -> (String, Expr, Span) {
+    match eval_str(eval, src) {
+        Err(EvalError::TypeMismatch(typ, val, Some(span))) => (typ.to_string(), val, span),
+        other => panic!("expected a type mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn values_are_checked() {
+    let mut eval = Eval::new();
+    assert_eq!(int(eval_str(&mut eval, ":i64 1").unwrap()), 1);
+    assert_eq!(int(eval_str(&mut eval, "(the u8 255)").unwrap()), 255);
+    let (typ, val, span) = mismatch(&mut eval, "(do 1\n  :i64 \"hello\")");
+    assert_eq!(typ, "i64");
+    assert_eq!(val, Expr::from("hello".to_string()));
+    assert_eq!((span.start.line, span.start.column), (1, 2));
+    assert_eq!(mismatch(&mut eval, "(the u8 256)").0, "u8");
+}
+
+#[test]
+fn structured_types() {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, ":(Vec i64) (list 1 2 3)").unwrap();
+    eval_str(&mut eval, ":(Vec i64) nil").unwrap();
+    mismatch(&mut eval, ":(Vec i64) (list 1 \"2\")");
+    eval_str(&mut eval, ":(Option String) nil").unwrap();
+    eval_str(&mut eval, ":(Option String) \"a\"").unwrap();
+    eval_str(&mut eval, ":(tuple i64 bool) (list 1 true)").unwrap();
+    mismatch(&mut eval, ":(tuple i64 bool) (list 1 true 2)");
+    eval_str(&mut eval, ":(-> i64 i64) (lambda x x)").unwrap();
+    mismatch(&mut eval, ":(-> i64 i64) 1");
+    // variables admit anything
+    eval_str(&mut eval, ":(Vec 'a) (list 1 \"2\")").unwrap();
+    // as does Expr
+    assert_eq!(int(eval_str(&mut eval, "(the Expr 5)").unwrap()), 5);
+    eval_str(&mut eval, "(the Expr \"s\")").unwrap();
+}
+
+#[test]
+fn mismatches_can_be_caught() {
+    let mut eval = Eval::new();
+    let caught = eval_str(&mut eval, "(try :bool 1 (catch {::kind k} k))").unwrap();
+    assert_eq!(caught.to_string(), "::type-mismatch");
+}
+
+#[test]
+fn erased_ascriptions_are_ignored() {
+    let mut eval = Eval::with_options(Options { ascriptions: Ascriptions::Erased, ..Options::default() });
+    assert_eq!(eval_str(&mut eval, ":i64 \"hello\"").unwrap(), Expr::from("hello".to_string()));
+    // the type is not even read
+    assert_eq!(int(eval_str(&mut eval, "(the (nonsense 1 2) 1)").unwrap()), 1);
+}
+
+#[test]
+fn the_checker_trusts_ascriptions() {
+    let mut eval = Eval::new();
+    let scheme = eval.check(&read_str("(lambda x :u8 x)")).unwrap();
+    assert_eq!(scheme.to_string(), "(-> u8 u8)");
+    let scheme = eval.check(&read_str(":(Vec 'a) (list)")).unwrap();
+    assert_eq!(scheme.to_string(), "(forall ('a) (Vec 'a))");
+    assert!(matches!(eval.check(&read_str(":String 1")), Err(EvalError::Type(_))));
+
+    let mut eval = Eval::with_options(Options { typecheck: true, ascriptions: Ascriptions::Erased, ..Options::default() });
+    assert!(matches!(eval_str(&mut eval, ":i64 \"hello\""), Err(EvalError::Type(_))));
+}
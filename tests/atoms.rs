@@ -0,0 +1,80 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+use pangolisp::exprs::*;
+
+fn show(src: &str) -> String {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, src).unwrap().to_string()
+}
+
+#[test]
+fn deref_and_reset() {
+    assert_eq!(show("(def a (atom 1)) (reset! a 2) (deref a)"), "2");
+    assert_eq!(show("(atom? (atom nil))"), "true");
+    assert_eq!(show("(atom 1)"), "#<atom>");
+}
+
+#[test]
+fn swap_applies_extra_arguments() {
+    assert_eq!(show("(def a (atom 1)) (swap! a + 10 100) (swap! a inc)"), "112");
+}
+
+#[test]
+fn compare_and_set() {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, "(def a (atom 1))").unwrap();
+    assert_eq!(eval_str(&mut eval, "(compare-and-set! a 2 3)").unwrap(), false.into());
+    assert_eq!(eval_str(&mut eval, "(compare-and-set! a 1 3)").unwrap(), true.into());
+    assert_eq!(int(eval_str(&mut eval, "(deref a)").unwrap()), 3);
+}
+
+#[test]
+fn swap_retries_when_the_function_changes_the_atom() {
+    let src = "
+      (def a (atom 0))
+      (def calls (atom 0))
+      (swap! a (lambda x (do (if (= (swap! calls inc) 1) (reset! a 10)) (inc x))))
+      (list (deref a) (deref calls))";
+    assert_eq!(show(src), "(11 2)");
+}
+
+#[test]
+fn equality_is_identity() {
+    assert_eq!(show("(= (atom 1) (atom 1))"), "false");
+    assert_eq!(show("(def a (atom 1)) (= a a)"), "true");
+    let src = "
+      (def a (atom 1))
+      (def m (hash-map a 'found))
+      (reset! a 2)
+      (get m a)";
+    assert_eq!(show(src), "found");
+}
+
+#[test]
+fn collecting_across_a_file() {
+    let src = "
+      (def items (atom (list)))
+      (def emit (lambda item (swap! items (lambda xs (concat xs (list item))))))
+      (emit 'a)
+      (map emit (list 'b 'c))
+      (deref items)";
+    assert_eq!(show(src), "(a b c)");
+}
+
+#[test]
+fn an_atom_may_hold_itself() {
+    let mut eval = Eval::new();
+    let a = eval_str(&mut eval, "(def a (atom nil)) (reset! a a) a").unwrap();
+    assert!(format!("{:?}", a).starts_with("Atom"));
+    assert!(matches!(eval_str(&mut eval, "(deref a)").unwrap(), Expr::Atom(_)));
+}
+
+#[test]
+fn not_an_atom() {
+    let mut eval = Eval::new();
+    assert!(matches!(eval_str(&mut eval, "(deref 1)"), Err(EvalError::BadParameter("atom", _))));
+}
@@ -0,0 +1,104 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+
+fn trace(eval: &mut Eval, src: &str) -> String {
+    assert!(eval_str(eval, src).is_err());
+    eval.backtrace().expect("a backtrace").to_string()
+}
+
+#[test]
+fn names_each_call() {
+    let mut eval = Eval::new();
+    eval.set_file("src/gen.pl");
+    let src = "
+(def inner (lambda x (car x)))
+(def outer (lambda x (inc (inner x))))
+(outer 1)";
+    assert_eq!(trace(&mut eval, src), "\
+at inner (src/gen.pl:2:22)
+at outer (src/gen.pl:3:27)
+at top level (src/gen.pl:4:1)");
+}
+
+#[test]
+fn anonymous_lambdas_are_named_by_where_they_are() {
+    let mut eval = Eval::new();
+    let src = "((lambda x (car x)) 1)";
+    assert_eq!(trace(&mut eval, src), "\
+at lambda x@1:3 (1:12)
+at top level (1:1)");
+}
+
+#[test]
+fn tail_calls_replace_their_caller() {
+    let mut eval = Eval::new();
+    let src = "
+(def count-down (lambda n (if (= n 0) (car n) (count-down (dec n)))))
+(count-down 100)";
+    assert_eq!(trace(&mut eval, src), "\
+at count-down (2:39)
+at top level (3:1)");
+}
+
+#[test]
+fn curried_tail_calls_replace_their_caller() {
+    let mut eval = Eval::new();
+    let src = "
+(def count-down (lambda n (lambda step (if (= n 0) (car n) ((count-down (dec n)) step)))))
+((count-down 1000) 1)";
+    assert_eq!(trace(&mut eval, src), "\
+at lambda step@2:28 (2:52)
+at top level (3:1)");
+}
+
+#[test]
+fn depth_is_capped() {
+    let mut eval = Eval::with_options(Options { max_backtrace: 4, ..Options::default() });
+    let src = "
+(def sum (lambda n (if (= n 0) (car n) (+ n (sum (dec n))))))
+(sum 10)";
+    let trace = trace(&mut eval, src);
+    assert_eq!(trace.lines().count(), 5);
+    assert!(trace.ends_with("... 8 more"));
+}
+
+#[test]
+fn the_last_frame_kept_is_named_by_its_own_caller() {
+    let mut eval = Eval::with_options(Options { max_backtrace: 3, ..Options::default() });
+    let src = "
+(def a (lambda x (car x)))
+(def b (lambda x (inc (a x))))
+(def c (lambda x (inc (b x))))
+(def d (lambda x (inc (c x))))
+(def e (lambda x (inc (d x))))
+(e 1)";
+    assert_eq!(trace(&mut eval, src), "\
+at a (2:18)
+at b (3:23)
+at c (4:23)
+... 3 more");
+}
+
+#[test]
+fn cleared_by_success() {
+    let mut eval = Eval::new();
+    assert!(eval_str(&mut eval, "(car 1)").is_err());
+    assert!(eval.backtrace().is_some());
+    eval_str(&mut eval, "(+ 1 2)").unwrap();
+    assert!(eval.backtrace().is_none());
+}
+
+#[test]
+fn caught_errors_carry_it() {
+    let mut eval = Eval::new();
+    let src = "
+(def f (lambda x (car x)))
+(try (f 1) (catch (hash-map ::backtrace trace) trace))";
+    assert_eq!(eval_str(&mut eval, src).unwrap().to_string(),
+               r#"("at f (2:18)" "at top level (3:6)")"#);
+    assert!(eval.backtrace().is_none());
+}
@@ -0,0 +1,127 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+use pangolisp::host::*;
+use std::fs;
+use std::path::PathBuf;
+
+fn granted(capabilities: Capabilities) -> Eval {
+    Eval::with_options(Options { capabilities, ..Options::default() })
+}
+
+// a fresh directory for each test
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pangolisp-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn nothing_is_granted_by_default() {
+    let mut eval = Eval::new();
+    match eval_str(&mut eval, "(read-file \"x\")") {
+        Err(EvalError::CapabilityDenied(name, Capability::FsRead)) => assert_eq!(name, "read-file"),
+        other => panic!("expected CapabilityDenied, got {:?}", other),
+    }
+    let e = eval_str(&mut eval, "now-ms").unwrap_err();
+    assert_eq!(e.to_string(), "now-ms needs the time capability, which was not granted");
+    assert!(matches!(eval_str(&mut eval, "no-such-thing"), Err(EvalError::UnknownBinding(_))));
+}
+
+#[test]
+fn only_what_is_granted() {
+    let mut eval = granted(Capabilities::none().grant(Capability::Time));
+    assert!(int(eval_str(&mut eval, "(now-ms)").unwrap()) > 0);
+    assert!(matches!(eval_str(&mut eval, "(env-var \"HOME\")"),
+                     Err(EvalError::CapabilityDenied(_, Capability::Env))));
+}
+
+#[test]
+fn denial_is_catchable() {
+    let mut eval = Eval::new();
+    let src = "(try (env-var \"HOME\") (catch (hash-map ::kind k) k))";
+    assert_eq!(eval_str(&mut eval, src).unwrap().to_string(), "::capability-denied");
+}
+
+#[test]
+fn reading_and_writing_within_a_root() {
+    let root = scratch("rw");
+    let capabilities = Capabilities::none()
+        .grant(Capability::FsRead).grant(Capability::FsWrite).within(&root);
+    let mut eval = granted(capabilities);
+    let src = "
+      (create-dir \"out\")
+      (write-file \"out/gen.rs\" \"fn main() {}\")
+      (list (read-file \"out/gen.rs\") (list-dir \"out\") (file-exists? \"nope\"))";
+    assert_eq!(eval_str(&mut eval, src).unwrap().to_string(), r#"("fn main() {}" ("gen.rs") false)"#);
+    assert_eq!(fs::read_to_string(root.join("out/gen.rs")).unwrap(), "fn main() {}");
+    eval_str(&mut eval, "(remove-file \"out/gen.rs\")").unwrap();
+    assert!(!root.join("out/gen.rs").exists());
+}
+
+#[test]
+fn paths_cannot_leave_the_root() {
+    let root = scratch("confined");
+    let mut eval = granted(Capabilities::all().within(&root));
+    for path in ["../escape", "/etc/passwd", "a/../../escape"] {
+        let src = format!("(read-file \"{}\")", path);
+        assert!(matches!(eval_str(&mut eval, &src), Err(EvalError::PathDenied(_))), "{}", path);
+    }
+    assert!(matches!(eval_str(&mut eval, "(write-file \"../escape\" \"\")"), Err(EvalError::PathDenied(_))));
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_cannot_leave_the_root() {
+    let root = scratch("symlink");
+    let outside = scratch("symlink-outside");
+    std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+    let mut eval = granted(Capabilities::all().within(&root));
+    assert!(matches!(eval_str(&mut eval, "(write-file \"link/x\" \"\")"), Err(EvalError::PathDenied(_))));
+    assert!(!outside.join("x").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn dangling_symlinks_cannot_leave_the_root() {
+    let root = scratch("dangling");
+    let outside = scratch("dangling-outside");
+    std::os::unix::fs::symlink(outside.join("new"), root.join("link")).unwrap();
+    std::os::unix::fs::symlink(outside.join("dir"), root.join("dirlink")).unwrap();
+    let mut eval = granted(Capabilities::all().within(&root));
+    assert!(matches!(eval_str(&mut eval, "(write-file \"link\" \"\")"), Err(EvalError::PathDenied(_))));
+    assert!(matches!(eval_str(&mut eval, "(create-dir \"dirlink\")"), Err(EvalError::PathDenied(_))));
+    assert!(!outside.join("new").exists() && !outside.join("dir").exists());
+    // one that stays within is followed
+    std::os::unix::fs::symlink("inside", root.join("within")).unwrap();
+    eval_str(&mut eval, "(write-file \"within\" \"x\")").unwrap();
+    assert_eq!(fs::read_to_string(root.join("inside")).unwrap(), "x");
+}
+
+#[test]
+fn io_errors() {
+    let root = scratch("io");
+    let mut eval = granted(Capabilities::all().within(&root));
+    assert!(matches!(eval_str(&mut eval, "(read-file \"missing\")"), Err(EvalError::Io(_))));
+}
+
+#[test]
+fn macros_are_sandboxed_too() {
+    let mut eval = Eval::new();
+    let src = "
+      (def m (macro x (env-var \"HOME\")))
+      (m 1)";
+    assert!(matches!(eval_str(&mut eval, src), Err(EvalError::CapabilityDenied(_, Capability::Env))));
+}
+
+#[cfg(unix)]
+#[test]
+fn running_a_process() {
+    let mut eval = granted(Capabilities::none().grant(Capability::Process));
+    let src = "(run-process \"echo\" (list \"hi\"))";
+    assert_eq!(eval_str(&mut eval, src).unwrap().to_string(), r#"{::status 0 ::stderr "" ::stdout "hi\n"}"#);
+}
@@ -0,0 +1,140 @@
+#![allow(clippy::result_large_err)]
+
+//! Translates each `tests/codegen/*.pl` module into rust, compiles it
+//! with `rustc` alongside a `main` that prints some expressions, and
+//! compares what it prints with what the interpreter gives for the
+//! same expressions.
+
+mod common;
+use common::*;
+
+use pangolisp::codegen::*;
+use pangolisp::eval::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn module_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/codegen").join(format!("{}.pl", name))
+}
+
+fn rustc_available() -> bool {
+    Command::new("rustc").arg("--version").output().is_ok_and(|out| out.status.success())
+}
+
+// the output of the compiled module and of the interpreter, a line per
+// expression
+fn compare(name: &str, exprs: &[&str]) {
+    if !rustc_available() {
+        eprintln!("skipping {}: no rustc", name);
+        return;
+    }
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("codegen-{}", name));
+    fs::create_dir_all(&dir).unwrap();
+    let mut eval = Eval::new();
+    let mut codegen = Codegen::new();
+    codegen.write_module(&mut eval, &module_path(name), &dir).unwrap();
+
+    let mut main = format!("mod {};\nuse {}::*;\n\nfn main() {{\n", name, name);
+    let mut expected = String::new();
+    for src in exprs {
+        let expr = read_str(src);
+        main.push_str(&format!("    println!(\"{{:?}}\", {});\n", codegen.expression(&mut eval, &expr).unwrap()));
+        expected.push_str(&format!("{}\n", eval.eval(expr).unwrap()));
+    }
+    main.push_str("}\n");
+    fs::write(dir.join("main.rs"), main).unwrap();
+
+    let binary = dir.join(name);
+    let compiled = Command::new("rustc")
+        .args(["--edition", "2018", "-o"]).arg(&binary).arg(dir.join("main.rs"))
+        .output().unwrap();
+    assert!(compiled.status.success(), "rustc failed:\n{}", String::from_utf8_lossy(&compiled.stderr));
+    let run = Command::new(&binary).output().unwrap();
+    assert!(run.status.success());
+    assert_eq!(String::from_utf8(run.stdout).unwrap(), expected);
+}
+
+#[test]
+fn arithmetic_matches_the_interpreter() {
+    compare("arithmetic", &[
+        "(factorial 10)",
+        "(gcd 1071 462)",
+        "(gcd -12 18)",
+        "(collatz-steps 27 0)",
+        "(clamp 0 10 -5)",
+        "(clamp 0 10 5)",
+        "(clamp 0 10 50)",
+        "(sign -7)",
+        "(sign 0)",
+        "(even? -3)",
+        "(mod -7 3)",
+    ]);
+}
+
+#[test]
+fn data_matches_the_interpreter() {
+    compare("shapes", &[
+        "(area (Circle 2))",
+        "(area (Rect (Point 1 1) (Point 4 3)))",
+        "(area Empty)",
+        "(manhattan (Point 3 -4))",
+        "(radius (Circle 7))",
+        "(radius Empty)",
+        "(long? (Meters 250))",
+        "(sum (insert (insert (insert Leaf 5) 2) 8))",
+        "(depth (insert (insert (insert (insert Leaf 1) 2) 3) 0))",
+    ]);
+}
+
+#[test]
+fn modules_are_written_as_rust_files() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("codegen-files");
+    fs::create_dir_all(&dir).unwrap();
+    let out = Codegen::new().write_module(&mut Eval::new(), &module_path("arithmetic"), &dir).unwrap();
+    assert_eq!(out, dir.join("arithmetic.rs"));
+    let code = fs::read_to_string(out).unwrap();
+    assert!(code.starts_with("// Generated by pangolisp."));
+    assert!(code.contains("pub fn gcd(a: i64, b: i64) -> i64 {"));
+    assert!(code.contains("pub fn even_p(n: i64) -> bool {"));
+}
+
+#[test]
+fn outside_the_subset() {
+    let mut eval = Eval::new();
+    let mut codegen = Codegen::new();
+    let untyped = codegen.module(&mut eval, "(defn twice [x] (the i64 (* 2 x)))").unwrap_err();
+    assert!(matches!(&untyped, CodegenError::Untyped(name, Some(_)) if name == "twice"));
+    assert!(untyped.to_string().starts_with("twice at 1:7 needs the type"));
+    // the result must be given too
+    let err = codegen.module(&mut eval, "(defn twice [[x i64]] (* 2 x))").unwrap_err();
+    assert!(matches!(err, CodegenError::Untyped(..)));
+    // a lambda has no translation
+    let err = codegen.module(&mut eval, "(defn adder [[x i64]] (the i64 ((lambda y (+ x y)) 1)))").unwrap_err();
+    assert!(matches!(err, CodegenError::Unsupported(_)));
+    assert_eq!(codegen.expression(&mut eval, &read_str("(ident 1)")).unwrap_err().to_string(),
+               "(ident 1) has no rust translation");
+    assert_eq!(ident("list->vec!"), "list__3evec_21");
+    assert_eq!(ident("type"), "r#type");
+    assert_eq!(ident("yield"), "r#yield");
+    assert_eq!(ident("super"), "super_");
+    assert_eq!(ident("Self"), "Self_");
+    // only ints are suffixed
+    assert_eq!(codegen.expression(&mut eval, &read_str("(the u8 5)")).unwrap().to_string(), "5u8");
+    for src in ["(the String 5)", "(the bool 1)"] {
+        assert!(matches!(codegen.expression(&mut eval, &read_str(src)), Err(CodegenError::Unsupported(_))), "{}", src);
+    }
+}
+
+#[test]
+fn items_built_with_rs() {
+    let mut eval = Eval::new();
+    let mut codegen = Codegen::new();
+    let code = codegen.module(&mut eval, "(defstruct Meters i64)
+                                          [(rs/struct 'Marker nil) (rs/impl 'Meters [(rs/fn 'zero nil 'Self (rs/call 'Meters 0))])]")
+        .unwrap();
+    assert!(code.ends_with("pub struct Meters(pub i64);\n\nstruct Marker;\n\nimpl Meters {\n    fn zero() -> Self {\n        Meters(0)\n    }\n}\n"),
+            "{}", code);
+    let err = codegen.module(&mut eval, "(rs/path 'x)").unwrap_err();
+    assert_eq!(err.to_string(), "expected a rust item, got (rs/path x)");
+}
@@ -0,0 +1,24 @@
+; Integer functions, with the type of every parameter and result given.
+
+(defn factorial [[n i64]]
+  (the i64 (if (<= n 1) 1 (* n (factorial (dec n))))))
+
+(defn gcd [[a i64] [b i64]]
+  (the i64 (if (= b 0) a (gcd b (mod a b)))))
+
+(defn collatz-steps [[n i64] [steps i64]]
+  (the i64
+    (if (= n 1)
+      steps
+      (collatz-steps (if (= (mod n 2) 0) (/ n 2) (+ (* 3 n) 1)) (inc steps)))))
+
+(defn clamp [[lo i64] [hi i64] [x i64]]
+  (the i64
+    (let (below (< x lo) above (> x hi))
+      (if below lo (if above hi x)))))
+
+(defn sign [[x i64]]
+  (the i64 (match (< x 0) true (neg 1) false (if (= x 0) 0 1))))
+
+(defn even? [[n i64]]
+  (the bool (not (= (mod n 2) 1))))
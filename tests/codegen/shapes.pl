@@ -0,0 +1,47 @@
+; Structs and enums, with a recursive and generic type among them.
+
+(defstruct Point [x i64] [y i64])
+
+(defstruct Meters i64)
+
+(defenum Shape
+  (Circle [radius i64])
+  (Rect Point Point)
+  Empty)
+
+(defenum (Tree 'a) Leaf (Node (Tree 'a) 'a (Tree 'a)))
+
+(defn area [[s Shape]]
+  (the i64
+    (match s
+      (Circle r) (* 3 (* r r))
+      (Rect a b) (* (- (Point-x b) (Point-x a)) (- (Point-y b) (Point-y a)))
+      (Empty) 0)))
+
+(defn manhattan [[p Point]]
+  (the i64 (+ (Point-x p) (Point-y p))))
+
+(defn radius [[s Shape]]
+  (the i64 (if (Circle? s) (Circle-radius s) 0)))
+
+(defn long? [[m Meters]]
+  (the bool (> (Meters-0 m) 100)))
+
+(defn sum [[t (Tree i64)]]
+  (the i64
+    (match t
+      (Leaf) 0
+      (Node l v r) (+ (sum l) v (sum r)))))
+
+(defn insert [[t (Tree i64)] [x i64]]
+  (the (Tree i64)
+    (match t
+      (Leaf) (Node Leaf x Leaf)
+      (Node l v r) (if (< x v) (Node (insert l x) v r) (Node l v (insert r x))))))
+
+(defn depth [[t (Tree 'a)]]
+  (the i64
+    (match t
+      (Leaf) 0
+      (Node l _ r) (let (dl (depth l) dr (depth r))
+                     (inc (if (> dl dr) dl dr))))))
@@ -0,0 +1,38 @@
+#![allow(dead_code)]
+
+use pangolisp::eval::*;
+use pangolisp::exprs::*;
+use pangolisp::forms::*;
+use pangolisp::reader::*;
+
+/// Reads and evaluates every form in `src`, returning the last value.
+pub fn eval_str(eval: &mut Eval, src: &str) -> Result<Expr, EvalError> {
+    let mut result = Expr::Nil;
+    let mut forms = eval.forms(src);
+    while let Some(form) = forms.next() {
+        let expr = eval.read(form.expect("form")).expect("read");
+        result = eval.eval(expr)?;
+        forms.set_dispatch(eval.dispatch());
+    }
+    Ok(result)
+}
+
+pub fn int(expr: Expr) -> i64 {
+    match expr {
+        Expr::Int(i) => i.value,
+        other => panic!("expected an int, got {:?}", other),
+    }
+}
+
+pub fn symbol(expr: Expr) -> String {
+    match expr {
+        Expr::Symbol(s) => s.value,
+        other => panic!("expected a symbol, got {:?}", other),
+    }
+}
+
+/// Reads the first form in `src`.
+pub fn read_str(src: &str) -> Expr {
+    let form = Forms::new(src).next().expect("a form").expect("form");
+    read(form).expect("read")
+}
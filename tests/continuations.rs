@@ -0,0 +1,91 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+use pangolisp::exprs::*;
+
+fn show(src: &str) -> String {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, src).unwrap().to_string()
+}
+
+#[test]
+fn call_cc_returns_normally() {
+    assert_eq!(show("(+ 1 (call/cc (lambda k 41)))"), "42");
+}
+
+#[test]
+fn call_cc_escapes() {
+    assert_eq!(show("(+ 1 (call/cc (lambda k (+ 10 (k 2)))))"), "3");
+}
+
+#[test]
+fn continuations_are_values() {
+    let mut eval = Eval::new();
+    let k = eval_str(&mut eval, "(call/cc (lambda k k))").unwrap();
+    assert!(matches!(k, Expr::Continuation(_)));
+    assert_eq!(k.to_string(), "#<continuation>");
+}
+
+#[test]
+fn early_exit_through_natives() {
+    let src = "
+      (def find-first (lambda pred (lambda xs
+        (call/cc (lambda return
+          (do (map (lambda x (if (pred x) (return x))) xs)
+              nil))))))
+      (find-first (lambda x (> x 2)) (list 1 2 3 4 5))";
+    assert_eq!(show(src), "3");
+}
+
+#[test]
+fn reentering_a_finished_continuation() {
+    let mut eval = Eval::new();
+    let src = "
+      (def saved nil)
+      (+ 100 (call/cc (lambda k (do (def saved k) 1))))";
+    assert_eq!(int(eval_str(&mut eval, src).unwrap()), 101);
+    assert_eq!(int(eval_str(&mut eval, "(saved 5)").unwrap()), 105);
+}
+
+#[test]
+fn shift_composes() {
+    assert_eq!(show("(reset (+ 1 (shift k (k (k 1)))))"), "3");
+    assert_eq!(show("(* 2 (reset (+ 1 (shift k (k 10)))))"), "22");
+}
+
+#[test]
+fn shift_can_abort() {
+    assert_eq!(show("(+ 1 (reset (+ 1000 (shift k 41))))"), "42");
+}
+
+#[test]
+fn generators() {
+    let src = "
+      (def yield (lambda x (shift k (cons x (k nil)))))
+      (reset (do (yield 1) (yield 2) (yield 3) nil))";
+    assert_eq!(show(src), "(1 2 3)");
+}
+
+#[test]
+fn backtracking_search() {
+    let src = "
+      (def amb (lambda xs
+        (shift k (reduce (lambda acc (lambda x (concat acc (k x)))) (list) xs))))
+      (reset
+        (let (x (amb (list 1 2 3))
+              y (amb (list 4 5 6)))
+          (if (= (+ x y) 7) (list (list x y)) (list))))";
+    assert_eq!(show(src), "((1 6) (2 5) (3 4))");
+}
+
+#[test]
+fn shift_needs_a_reset() {
+    let mut eval = Eval::new();
+    match eval_str(&mut eval, "(+ 1 (shift k (k 1)))") {
+        Err(EvalError::ShiftWithoutReset(Some(_))) => (),
+        other => panic!("expected ShiftWithoutReset, got {:?}", other),
+    }
+}
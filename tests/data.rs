@@ -0,0 +1,145 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::data::*;
+use pangolisp::eval::*;
+use pangolisp::infer;
+use pangolisp::types::*;
+
+fn show(eval: &mut Eval, src: &str) -> String {
+    eval_str(eval, src).unwrap().to_string()
+}
+
+#[test]
+fn structs() {
+    let mut eval = Eval::new();
+    assert_eq!(show(&mut eval, "(defstruct Point [x i64] [y i64])"), "Point");
+    assert_eq!(show(&mut eval, "(def p (Point 1 2))"), "p");
+    assert_eq!(show(&mut eval, "p"), "(Point 1 2)");
+    assert_eq!(int(eval_str(&mut eval, "(+ (Point-x p) (Point-y p))").unwrap()), 3);
+    assert_eq!(show(&mut eval, "(list (Point? p) (Point? 1) (data? p))"), "(true false true)");
+    assert_eq!(show(&mut eval, "(= p (Point 1 2))"), "true");
+    assert_eq!(show(&mut eval, "((Point 1) 5)"), "(Point 1 5)");
+
+    eval_str(&mut eval, "(defstruct Meters i64) (defstruct Marker)").unwrap();
+    assert_eq!(int(eval_str(&mut eval, "(Meters-0 (Meters 7))").unwrap()), 7);
+    assert_eq!(show(&mut eval, "Marker"), "Marker");
+}
+
+#[test]
+fn enums_and_patterns() {
+    let mut eval = Eval::new();
+    let src = "
+      (defenum Shape
+        (derive Debug Clone)
+        (Circle [radius i64])
+        (Rect [w i64] [h i64])
+        Empty)
+      (def area (lambda s
+        (match s
+          (Circle r) (* 3 (* r r))
+          (Rect w h) (* w h)
+          (Empty) 0)))
+      (list (area (Circle 2)) (area (Rect 2 5)) (area Empty))";
+    assert_eq!(show(&mut eval, src), "(12 10 0)");
+    assert_eq!(show(&mut eval, "(list (Shape? Empty) (Circle? Empty) (Empty? Empty))"), "(true false true)");
+    // other lists are still list patterns
+    assert_eq!(show(&mut eval, "(match (list 1 2) (a b) (+ a b))"), "3");
+    match eval_str(&mut eval, "(Circle-radius (Rect 1 2))") {
+        Err(EvalError::WrongVariant(variant, _)) => assert_eq!(variant, "Circle"),
+        other => panic!("expected the wrong variant, got {:?}", other),
+    }
+    let caught = eval_str(&mut eval, "(try (Rect-w Empty) (catch {::kind k} k))").unwrap();
+    assert_eq!(caught.to_string(), "::wrong-variant");
+}
+
+#[test]
+fn generic_and_recursive_types() {
+    let mut eval = Eval::new();
+    let src = "
+      (defenum (Tree 'a) Leaf (Node (Tree 'a) 'a (Tree 'a)))
+      (def sum (lambda t
+        (match t
+          (Leaf) 0
+          (Node l v r) (+ (sum l) (+ v (sum r))))))
+      (sum (Node (Node Leaf 1 Leaf) 2 (Node Leaf 3 Leaf)))";
+    assert_eq!(int(eval_str(&mut eval, src).unwrap()), 6);
+    assert_eq!(eval.types().value("Node").unwrap().to_string(),
+               "(forall ('a) (-> (Tree 'a) 'a (Tree 'a) (Tree 'a)))");
+    assert_eq!(eval.types().value("Node-1").unwrap().to_string(), "(forall ('a) (-> (Tree 'a) 'a))");
+}
+
+#[test]
+fn declarations_describe_rust_items() {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, "
+      (defstruct (Pair 'a 'b) (derive Clone PartialEq) [first 'a] [second 'b])
+      (defenum Op Add (Lit i64))").unwrap();
+    let names: Vec<&str> = eval.types().datatypes().map(|d| d.name.as_str()).collect();
+    assert_eq!(names, ["Pair", "Op"]);
+    let pair = eval.types().datatype("Pair").unwrap();
+    assert!(!pair.is_enum);
+    assert_eq!(pair.derives, ["Clone", "PartialEq"]);
+    assert_eq!(pair.params.len(), 2);
+    match &pair.variants[0].fields {
+        Fields::Named(fields) => {
+            let fields: Vec<String> = fields.iter().map(|(name, typ)| format!("{} {}", name, typ)).collect();
+            assert_eq!(fields, ["first 'a", "second 'b"]);
+        }
+        other => panic!("expected named fields, got {:?}", other),
+    }
+    let op = eval.types().datatype("Op").unwrap();
+    assert!(op.is_enum);
+    assert_eq!(op.variant("Add").unwrap().fields, Fields::Unit);
+    assert_eq!(op.variant("Lit").unwrap().fields.types()[0].to_string(), "i64");
+}
+
+#[test]
+fn bad_declarations() {
+    let mut eval = Eval::new();
+    let bad = |eval: &mut Eval, src: &str| match eval_str(eval, src) {
+        Err(EvalError::Type(e)) => *e,
+        other => panic!("expected a type error, got {:?}", other),
+    };
+    assert!(matches!(bad(&mut eval, "(defstruct Mixed [x i64] bool)"), TypeError::BadType(_)));
+    assert!(matches!(bad(&mut eval, "(defstruct Loose [x 'a])"), TypeError::UnknownType(name, _) if name == "'a"));
+    assert!(matches!(bad(&mut eval, "(defenum Bad (Some Nope))"), TypeError::UnknownType(name, _) if name == "Nope"));
+}
+
+#[test]
+fn declared_types_are_checked() {
+    let mut eval = Eval::with_options(Options { typecheck: true, ..Options::default() });
+    eval_str(&mut eval, "(defstruct Point [x i64] [y i64])").unwrap();
+    assert_eq!(int(eval_str(&mut eval, "(Point-y (Point 1 2))").unwrap()), 2);
+    assert!(matches!(eval_str(&mut eval, "(Point \"one\" 2)"), Err(EvalError::Type(_))));
+    assert_eq!(show(&mut eval, ":Point (Point 1 2)"), "(Point 1 2)");
+
+    // declared ahead of the definitions, wherever they are
+    let mut env = eval.types().clone();
+    let forms = [read_str("(def origin Origin)"), read_str("(defstruct Origin)")];
+    infer::check(&forms, &mut env).unwrap();
+    assert_eq!(env.value("origin").unwrap().to_string(), "Origin");
+}
+
+#[test]
+fn matches_are_checked() {
+    let mut eval = Eval::with_options(Options { typecheck: true, ..Options::default() });
+    let src = "
+      (defenum Shape (Circle [radius i64]) (Rect i64 i64) Empty)
+      (def area (lambda s (match s (Circle r) (* 3 (* r r)) (Rect w h) (* w h) (Empty) 0)))";
+    eval_str(&mut eval, src).unwrap();
+    assert_eq!(eval.types().value("area").unwrap().to_string(), "(-> Shape i64)");
+    assert_eq!(int(eval_str(&mut eval, "(area (Rect 2 3))").unwrap()), 6);
+    assert!(matches!(eval_str(&mut eval, "(area 1)"), Err(EvalError::Type(_))));
+    assert!(matches!(eval_str(&mut eval, "(match (Circle 1) (Circle \"r\") 0 _ 1)"), Err(EvalError::Type(_))));
+}
+
+#[test]
+fn values_are_admitted_by_their_type() {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, "(defstruct Point [x i64] [y i64]) (defstruct Size [w i64] [h i64])").unwrap();
+    assert_eq!(show(&mut eval, ":Point (Point 1 2)"), "(Point 1 2)");
+    assert!(matches!(eval_str(&mut eval, ":Point (Size 1 2)"), Err(EvalError::TypeMismatch(..))));
+}
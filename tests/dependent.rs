@@ -0,0 +1,146 @@
+#![cfg(feature = "dependent")]
+#![allow(clippy::result_large_err)]
+
+mod common;
+use common::*;
+
+use pangolisp::dependent::*;
+
+fn term(checker: &Checker, src: &str) -> Term {
+    checker.parse(&read_str(src)).unwrap()
+}
+
+fn define(checker: &mut Checker, name: &str, typ: &str, body: &str) {
+    let (typ, body) = (term(checker, typ), term(checker, body));
+    checker.define(name, &typ, &body).unwrap();
+}
+
+fn check(checker: &mut Checker, src: &str, typ: &str) -> Result<(), DependentError> {
+    let (src, typ) = (term(checker, src), term(checker, typ));
+    checker.check(&src, &typ)
+}
+
+fn normalise(checker: &mut Checker, src: &str) -> String {
+    let src = term(checker, src);
+    checker.infer(&src).unwrap();
+    checker.normalise(&src).unwrap().to_string()
+}
+
+// the usual definitions: addition, then the head, tail and appending
+// of vectors whose lengths say they can be taken
+fn prelude() -> Checker {
+    let mut checker = Checker::new();
+    define(&mut checker, "plus", "(-> Nat Nat Nat)",
+           "(lambda m n (nat-elim (lambda _ Nat) n (lambda k r (succ r)) m))");
+    define(&mut checker, "pred", "(-> Nat Nat)",
+           "(lambda n (nat-elim (lambda _ Nat) 0 (lambda k r k) n))");
+    define(&mut checker, "head", "(Pi [A Type] [n Nat] (-> (Vec A (succ n)) A))",
+           "(lambda A n xs
+              (vec-elim A (lambda k _ (nat-elim (lambda _ Type) Unit (lambda _ _ A) k))
+                        tt (lambda k x xs r x) (succ n) xs))");
+    define(&mut checker, "tail", "(Pi [A Type] [n Nat] (-> (Vec A (succ n)) (Vec A n)))",
+           "(lambda A n xs
+              (vec-elim A (lambda k _ (Vec A (pred k))) (nil A) (lambda k x xs r xs) (succ n) xs))");
+    define(&mut checker, "append", "(Pi [A Type] [m Nat] [n Nat] (-> (Vec A m) (Vec A n) (Vec A (plus m n))))",
+           "(lambda A m n xs ys
+              (vec-elim A (lambda k _ (Vec A (plus k n))) ys
+                        (lambda k x xs r (cons A (plus k n) x r)) m xs))");
+    checker
+}
+
+const THREE: &str = "(cons Nat 2 5 (cons Nat 1 6 (cons Nat 0 7 (nil Nat))))";
+
+#[test]
+fn universes() {
+    let mut checker = Checker::new();
+    assert_eq!(checker.infer(&term(&checker, "Type")).unwrap().to_string(), "(Type 1)");
+    assert_eq!(checker.infer(&term(&checker, "(-> Type Nat)")).unwrap().to_string(), "(Type 1)");
+    assert!(check(&mut checker, "Nat", "(Type 2)").is_ok());
+    assert!(matches!(check(&mut checker, "Type", "Type"), Err(DependentError::Mismatch { .. })));
+}
+
+#[test]
+fn dependent_functions() {
+    let mut checker = Checker::new();
+    let id = "(the (Pi [A Type] (-> A A)) (lambda A x x))";
+    assert_eq!(checker.infer(&term(&checker, id)).unwrap().to_string(), "(Pi [A Type] (-> A A))");
+    assert_eq!(normalise(&mut checker, &format!("({} Nat 3)", id)), "3");
+    assert!(matches!(check(&mut checker, &format!("({} Nat Nat)", id), "Nat"),
+                     Err(DependentError::Mismatch { .. })));
+    assert!(matches!(checker.infer(&term(&checker, "(lambda x x)")), Err(DependentError::CannotInfer(_))));
+}
+
+#[test]
+fn dependent_pairs() {
+    let mut checker = Checker::new();
+    let sized = "(Sigma [n Nat] (Vec Nat n))";
+    assert!(check(&mut checker, "(pair 1 (cons Nat 0 4 (nil Nat)))", sized).is_ok());
+    assert!(check(&mut checker, "(pair 2 (cons Nat 0 4 (nil Nat)))", sized).is_err());
+    let pair = format!("(the {} (pair 1 (cons Nat 0 4 (nil Nat))))", sized);
+    assert_eq!(checker.infer(&term(&checker, &format!("(snd {})", pair))).unwrap().to_string(), "(Vec Nat 1)");
+}
+
+#[test]
+fn definitional_equality() {
+    let mut checker = prelude();
+    assert!(checker.equal(&term(&checker, "(plus 2 2)"), &term(&checker, "4")).unwrap());
+    assert!(!checker.equal(&term(&checker, "(plus 2 2)"), &term(&checker, "5")).unwrap());
+    // the same function, up to eta
+    assert!(checker.equal(&term(&checker, "plus"), &term(&checker, "(lambda a b (plus a b))")).unwrap());
+    // type-level computation: a vector of three is a vector of 2 + 1
+    assert!(check(&mut checker, THREE, "(Vec Nat (plus 2 1))").is_ok());
+    assert!(check(&mut checker, THREE, "(Vec Nat (plus 2 2))").is_err());
+}
+
+#[test]
+fn normal_forms_of_open_terms() {
+    let mut checker = prelude();
+    // stuck on `n`, but not on the `succ` around it
+    assert_eq!(normalise(&mut checker, "(the (-> Nat Nat) (lambda n (plus 1 n)))"), "(lambda n (succ n))");
+    assert_eq!(normalise(&mut checker, "(the (-> Nat Nat) (lambda n (plus n 0)))"),
+               "(lambda n (nat-elim (lambda _ Nat) 0 (lambda k (lambda r (succ r))) n))");
+}
+
+#[test]
+fn length_indexed_vectors() {
+    let mut checker = prelude();
+    assert_eq!(normalise(&mut checker, &format!("(head Nat 2 {})", THREE)), "5");
+    assert_eq!(normalise(&mut checker, &format!("(head Nat 1 (tail Nat 2 {}))", THREE)), "6");
+    let appended = format!("(append Nat 1 3 (cons Nat 0 4 (nil Nat)) {})", THREE);
+    assert_eq!(checker.infer(&term(&checker, &appended)).unwrap().to_string(), "(Vec Nat 4)");
+    assert_eq!(normalise(&mut checker, &format!("(head Nat 3 {})", appended)), "4");
+    // there is no head of an empty vector to take
+    assert!(matches!(check(&mut checker, "(head Nat 0 (nil Nat))", "Nat"),
+                     Err(DependentError::Mismatch { .. })));
+}
+
+#[test]
+fn finite_sets() {
+    let mut checker = prelude();
+    assert!(check(&mut checker, "(fsucc 2 (fzero 1))", "(Fin 3)").is_ok());
+    // `Fin 0` is empty
+    assert!(check(&mut checker, "(fzero 0)", "(Fin 0)").is_err());
+    define(&mut checker, "to-nat", "(Pi [n Nat] (-> (Fin n) Nat))",
+           "(lambda n i (fin-elim (lambda _ _ Nat) (lambda _ 0) (lambda _ _ r (succ r)) n i))");
+    assert_eq!(normalise(&mut checker, "(to-nat 3 (fsucc 2 (fsucc 1 (fzero 0))))"), "2");
+    // looking up an index below a vector's length cannot fail
+    define(&mut checker, "lookup", "(Pi [A Type] [n Nat] (-> (Vec A n) (Fin n) A))",
+           "(lambda A n xs i
+              (fin-elim (lambda k _ (-> (Vec A k) A))
+                        (lambda k ys (head A k ys))
+                        (lambda k j r ys (r (tail A k ys)))
+                        n i xs))");
+    assert_eq!(normalise(&mut checker, &format!("(lookup Nat 3 {} (fsucc 2 (fzero 1)))", THREE)), "6");
+    assert!(check(&mut checker, &format!("(lookup Nat 3 {} (fzero 3))", THREE), "Nat").is_err());
+}
+
+#[test]
+fn errors() {
+    let checker = Checker::new();
+    assert!(matches!(checker.parse(&read_str("(succ y)")), Err(DependentError::Unbound(name, Some(_))) if name == "y"));
+    assert!(matches!(checker.parse(&read_str("(Pi x Nat)")), Err(DependentError::BadTerm(_))));
+    let mut checker = checker;
+    assert!(matches!(checker.infer(&term(&checker, "(zero 1)")), Err(DependentError::NotAFunction(..))));
+    let err = check(&mut checker, "(succ Nat)", "Nat").unwrap_err();
+    assert_eq!(err.to_string(), "expected Nat to be of type Nat, found Type");
+}
@@ -0,0 +1,86 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+
+fn show(src: &str) -> String {
+    let mut eval = Eval::new();
+    match eval_str(&mut eval, src) {
+        Ok(val) => val.to_string(),
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[test]
+fn throw_and_catch() {
+    assert_eq!(show("(try (+ 1 (throw 41)) (catch e (+ e 1)))"), "42");
+    assert_eq!(show("(try 1 2 (catch e 'caught))"), "2");
+}
+
+#[test]
+fn uncaught() {
+    let mut eval = Eval::new();
+    match eval_str(&mut eval, "(throw 'oops)") {
+        Err(EvalError::Thrown(val)) => assert_eq!(symbol(val), "oops"),
+        other => panic!("expected Thrown, got {:?}", other),
+    }
+    // a catch that does not match lets the error through untouched
+    assert!(matches!(eval_str(&mut eval, "(try nope (catch 1 'one))"), Err(EvalError::UnknownBinding(_))));
+}
+
+#[test]
+fn internal_errors_are_maps() {
+    assert_eq!(show("(try nope (catch {::kind k} k))"), "::unknown-binding");
+    assert_eq!(show("(try nope (catch {::message m} m))"), "\"unknown binding: nope\"");
+    assert_eq!(show("(try\n  (+ 1 nope) (catch {::span s} s))"), "{::column 8 ::line 2}");
+    assert_eq!(show("(try (1 2) (catch {::kind k} k))"), "::not-callable");
+    assert_eq!(show("(try (/ 1 0) (catch {::kind k} k))"), "::divide-by-zero");
+}
+
+#[test]
+fn catch_matches_on_kind() {
+    let src = "
+      (def risky (lambda x
+        (try (if (= x 0) (throw {::kind ::zero}) (/ 10 x))
+          (catch {::kind ::zero} 'zero)
+          (catch {::kind ::divide-by-zero} 'impossible))))
+      (list (risky 0) (risky 5))";
+    assert_eq!(show(src), "(zero 2)");
+}
+
+#[test]
+fn errors_from_inside_natives_are_caught() {
+    let src = "
+      (map (lambda x (try (if (= x 2) (throw x) x) (catch e (list 'failed e))))
+           (list 1 2 3))";
+    assert_eq!(show(src), "(1 (failed 2) 3)");
+    assert_eq!(show("(try (map (lambda x (throw x)) (list 7)) (catch e e))"), "7");
+}
+
+#[test]
+fn finally_always_runs() {
+    let src = "
+      (def log (list))
+      (def note (lambda x (def log (cons x log))))
+      (try 1 (finally (note 'a)))
+      (try (throw 2) (catch e (note e)) (finally (note 'b)))
+      (try (try (throw 3) (finally (note 'c))) (catch e (note e)))
+      log";
+    assert_eq!(show(src), "(3 c b 2 a)");
+    assert_eq!(show("(try 1 (finally 2))"), "1");
+    assert_eq!(show("(try (throw 1) (catch e (throw 2)) (finally 3))"), "error: uncaught: 2");
+}
+
+#[test]
+fn malformed_try() {
+    assert!(show("(try 1 (finally 2) (catch e 3))").starts_with("error: expected finally to come last"));
+    assert!(show("(try 1 (catch e 2) 3)").starts_with("error: expected catch or finally"));
+}
+
+#[test]
+fn continuations_pass_through_try() {
+    assert_eq!(show("(call/cc (lambda k (try (k 1) (catch e 2))))"), "1");
+    assert_eq!(show("(call/cc (lambda k (try (map k (list 5)) (catch e 2))))"), "5");
+}
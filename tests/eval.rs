@@ -0,0 +1,101 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+use pangolisp::exprs::*;
+
+#[test]
+fn specials_are_bound() {
+    let eval = Eval::new();
+    for special in Special::all() {
+        assert_eq!(eval.lookup(special.name()).unwrap(), &Expr::Special(special));
+    }
+}
+
+#[test]
+fn lambda() {
+    let mut eval = Eval::new();
+    match eval_str(&mut eval, "(lambda x x)").unwrap() {
+        Expr::Fun(f) => {
+            assert_eq!(f.param.value, "x");
+            let span = f.meta.span.unwrap();
+            assert_eq!((span.start.column, span.end.column), (1, 7));
+        }
+        other => panic!("expected a function, got {:?}", other),
+    }
+}
+
+#[test]
+fn apply() {
+    let mut eval = Eval::new();
+    assert_eq!(int(eval_str(&mut eval, "((lambda x x) 42)").unwrap()), 42);
+}
+
+#[test]
+fn closures() {
+    let mut eval = Eval::new();
+    let src = "((lambda x (lambda y x)) 1 2)";
+    assert_eq!(int(eval_str(&mut eval, src).unwrap()), 1);
+}
+
+#[test]
+fn quote() {
+    let mut eval = Eval::new();
+    assert_eq!(symbol(eval_str(&mut eval, "(quote foo)").unwrap()), "foo");
+    assert_eq!(symbol(eval_str(&mut eval, "'foo").unwrap()), "foo");
+    match eval_str(&mut eval, "'(a 1)").unwrap() {
+        Expr::List(l) => assert_eq!(l.vals.len(), 2),
+        other => panic!("expected a list, got {:?}", other),
+    }
+}
+
+#[test]
+fn the() {
+    let mut eval = Eval::new();
+    assert_eq!(int(eval_str(&mut eval, "(the 'i64 1)").unwrap()), 1);
+    assert_eq!(int(eval_str(&mut eval, ":'i64 1").unwrap()), 1);
+}
+
+#[test]
+fn empty_has_no_specials() {
+    let mut eval = Eval::empty();
+    match eval_str(&mut eval, "(lambda x x)") {
+        Err(EvalError::UnknownBinding(name)) => assert_eq!(name, "lambda"),
+        other => panic!("expected an unknown binding, got {:?}", other),
+    }
+}
+
+#[test]
+fn multiple_forms() {
+    let mut eval = Eval::new();
+    assert_eq!(int(eval_str(&mut eval, "'a\n((lambda x x) 7)").unwrap()), 7);
+}
+
+#[test]
+fn prelude_is_optional() {
+    let mut eval = Eval::with_options(Options { prelude: false, ..Options::default() });
+    assert!(matches!(eval_str(&mut eval, "(+ 1 2)"), Err(EvalError::UnknownBinding(_))));
+    assert_eq!(int(eval_str(&mut eval, "(if true 1 2)").unwrap()), 1);
+    let mut eval = Eval::new();
+    assert_eq!(int(eval_str(&mut eval, "(+ 1 2)").unwrap()), 3);
+}
+
+#[test]
+fn let_does_not_leak() {
+    let mut eval = Eval::new();
+    assert_eq!(int(eval_str(&mut eval, "(let (x 1) x)").unwrap()), 1);
+    assert!(matches!(eval_str(&mut eval, "x"), Err(EvalError::UnknownBinding(_))));
+    assert!(eval_str(&mut eval, "(let (x 1) (undefined))").is_err());
+    assert!(matches!(eval_str(&mut eval, "x"), Err(EvalError::UnknownBinding(_))));
+}
+
+#[test]
+fn equality_ignores_spans() {
+    let mut eval = Eval::new();
+    let a = eval_str(&mut eval, "'(a 1)").unwrap();
+    let b = eval_str(&mut eval, "\n  '(a 1)").unwrap();
+    assert_eq!(a, b);
+    assert_ne!(a.meta().unwrap().span, b.meta().unwrap().span);
+}
@@ -0,0 +1,41 @@
+//! Runs every `tests/golden/*.pl` file and compares what it prints
+//! with the `.out` file beside it. Each top level form prints its
+//! value on its own line; an error prints `error: ...` and stops.
+
+use pangolisp::eval::*;
+use std::fs;
+use std::path::Path;
+
+fn run(src: &str) -> String {
+    let mut eval = Eval::new();
+    let mut out = String::new();
+    let mut forms = eval.forms(src);
+    while let Some(form) = forms.next() {
+        let expr = eval.read(form.expect("form")).expect("read");
+        match eval.eval(expr) {
+            Ok(val) => out.push_str(&format!("{}\n", val)),
+            Err(e) => {
+                out.push_str(&format!("error: {}\n", e));
+                break;
+            }
+        }
+        forms.set_dispatch(eval.dispatch());
+    }
+    out
+}
+
+#[test]
+fn golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut paths: Vec<_> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "pl"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    for path in paths {
+        let src = fs::read_to_string(&path).unwrap();
+        let expected = fs::read_to_string(path.with_extension("out")).unwrap();
+        assert_eq!(run(&src), expected, "in {}", path.display());
+    }
+}
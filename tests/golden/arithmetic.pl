@@ -0,0 +1,14 @@
+;;; checked integer arithmetic
+(+ 1 2)
+(+ 1 2 3 4)
+(- 10 3 2)
+(* 6 7)
+(/ 20 3)
+(mod -7 3)
+(neg 5)
+(inc 41)
+(dec -41)
+((+ 1) 2)
+(map (* 2) [1 2 3])
+0x10
+-12
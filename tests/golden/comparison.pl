@@ -0,0 +1,10 @@
+(= 1 1)
+(= 'a 'a 'b)
+(= [1 2] '(1 2))
+(< 1 2 3)
+(< 1 3 2)
+(>= 3 3 1)
+(not nil)
+(not 0)
+(if (> 2 1) 'yes 'no)
+(if false 'yes)
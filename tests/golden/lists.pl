@@ -0,0 +1,14 @@
+[1 2 3]
+(cons 0 [1 2])
+(cons 0 nil)
+(first [1 2 3])
+(first [])
+(rest [1 2 3])
+(concat [1] [] [2 3])
+(count [1 2 3])
+(range 0 5)
+(filter (lambda x (> x 2)) (range 0 6))
+(reduce + 0 (range 1 11))
+(map (lambda x [x x]) [1 2])
+(get {'a 1} 'a)
+(get {'a 1} 'b)
@@ -0,0 +1,3 @@
+;;; overflow is an error, not a panic
+(* 4611686018427387904 2)
+'unreachable
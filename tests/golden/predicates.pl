@@ -0,0 +1,11 @@
+(int? 1)
+(int? "1")
+(symbol? 'a)
+(list? [])
+(list? nil)
+(string? "s")
+(nil? nil)
+(map? {})
+(fn? first)
+(fn? (lambda x x))
+(bool? false)
@@ -0,0 +1,14 @@
+;;; top level definitions are visible to function bodies
+(def fact
+  (lambda n
+    (if (= n 0)
+      1
+      (* n (fact (dec n))))))
+(fact 10)
+(def compose (lambda f (lambda g (lambda x (f (g x))))))
+((compose inc (* 2)) 5)
+(let (x 2
+      y (* x 3))
+  (def ignored 0)
+  (+ x y))
+(do 1 2 3)
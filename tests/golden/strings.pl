@@ -0,0 +1,8 @@
+"hello"
+"tab\there \"quoted\""
+(str "a" 1 'b nil [1 2])
+(split "a,b,c" ",")
+(join ", " ["x" "y" 3])
+(format "{} + {} = {}" 1 2 (+ 1 2))
+(format "{{}}")
+(count "héllo")
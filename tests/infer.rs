@@ -0,0 +1,178 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+use pangolisp::forms::*;
+use pangolisp::infer::*;
+use pangolisp::prelude;
+use pangolisp::reader::*;
+use pangolisp::types::*;
+
+fn env() -> TypeEnv {
+    let mut env = TypeEnv::builtin();
+    prelude::types(&mut env);
+    env
+}
+
+fn type_of(src: &str) -> String {
+    infer(&read_str(src), &env()).unwrap().to_string()
+}
+
+fn error(src: &str) -> TypeError {
+    infer(&read_str(src), &env()).unwrap_err()
+}
+
+fn read_all(src: &str) -> Vec<pangolisp::exprs::Expr> {
+    Forms::new(src).map(|form| read(form.expect("form")).expect("read")).collect()
+}
+
+#[test]
+fn literals() {
+    assert_eq!(type_of("1"), "i64");
+    assert_eq!(type_of("\"a\""), "String");
+    assert_eq!(type_of("true"), "bool");
+    assert_eq!(type_of("::k"), "Keyword");
+    assert_eq!(type_of("'x"), "Symbol");
+}
+
+#[test]
+fn principal_types() {
+    assert_eq!(type_of("(lambda x x)"), "(forall ('a) (-> 'a 'a))");
+    assert_eq!(type_of("(lambda f (lambda x (f (f x))))"), "(forall ('a) (-> (-> 'a 'a) 'a 'a))");
+    assert_eq!(type_of("(lambda f (lambda g (lambda x (f (g x)))))"),
+               "(forall ('a 'b 'c) (-> (-> 'a 'b) (-> 'c 'a) 'c 'b))");
+    assert_eq!(type_of("\\x (+ x 1)"), "(-> i64 i64)");
+}
+
+#[test]
+fn the_prelude() {
+    assert_eq!(type_of("(map inc (list 1 2 3))"), "(Vec i64)");
+    assert_eq!(type_of("(reduce + 0)"), "(-> (Vec i64) i64)");
+    assert_eq!(type_of("(+ 1)"), "(-> i64 i64)");
+    assert_eq!(type_of("(str 1 \"a\" true)"), "String");
+    assert_eq!(type_of("(filter (lambda x (> x 1)))"), "(-> (Vec i64) (Vec i64))");
+}
+
+#[test]
+fn let_polymorphism() {
+    assert_eq!(type_of("(let (id (lambda x x)) (list (id 1) (id 2)))"), "(Vec i64)");
+    assert_eq!(type_of("(let (id (lambda x x)) (do (id \"a\") (id 1)))"), "i64");
+    // lambda bound names are not generalized
+    assert!(matches!(error("(lambda id (do (id \"a\") (id 1)))"), TypeError::Mismatch { .. }));
+    // nor are those bound to anything but a value
+    assert!(matches!(error("(let (r (atom [])) (do (reset! r [1]) (reset! r [\"a\"])))"), TypeError::Mismatch { .. }));
+    assert_eq!(type_of("(let (r (atom [])) (do (reset! r [1]) (first (deref r))))"), "i64");
+}
+
+#[test]
+fn conditionals() {
+    assert_eq!(type_of("(lambda x (if x 1 2))"), "(forall ('a) (-> 'a i64))");
+    assert_eq!(type_of("(if true nil)"), "()");
+    assert!(matches!(error("(if true 1)"), TypeError::Mismatch { .. }));
+}
+
+#[test]
+fn matching() {
+    assert_eq!(type_of("(lambda x (match x 0 \"zero\" n \"other\"))"), "(-> i64 String)");
+    assert_eq!(type_of("(lambda xs (match xs (list a & rest) a _ 0))"), "(-> (Vec i64) i64)");
+    assert_eq!(type_of("(lambda x (match x n ::when (> n 0) n _ 0))"), "(-> i64 i64)");
+    assert_eq!(type_of("(lambda x (match x 'a true _ false))"), "(-> Symbol bool)");
+    // the patterns fit one type, and the bodies are of one type
+    assert!(matches!(error("(lambda x (match x 0 1 \"a\" 2))"), TypeError::Mismatch { .. }));
+    assert!(matches!(error("(match 1 0 1 _ \"a\")"), TypeError::Mismatch { .. }));
+}
+
+#[test]
+fn mismatches_point_at_both_sides() {
+    let src = "(lambda x\n  (do (+ x 1)\n      (concat x x)))";
+    match error(src) {
+        TypeError::Mismatch { expected, found, span: Some(span), other: Some(other) } => {
+            // `concat` wants a list, but `x` is an int because of `+`
+            assert!(expected.to_string().starts_with("(Vec"));
+            assert_eq!(found.to_string(), "i64");
+            assert_eq!((span.start.line, span.start.column), (2, 14));
+            assert_eq!((other.start.line, other.start.column), (1, 7));
+        }
+        other => panic!("expected a mismatch, got {:?}", other),
+    }
+    assert_eq!(error("(+ 1 \"a\")").to_string(), "expected i64, found String at 1:6, expected because of 1:2");
+}
+
+#[test]
+fn infinite_types() {
+    assert!(matches!(error("(lambda x (x x))"), TypeError::Infinite { .. }));
+}
+
+#[test]
+fn unbound_and_unsupported() {
+    assert!(matches!(error("nope"), TypeError::Unbound(name, Some(_)) if name == "nope"));
+    assert!(matches!(error("(call/cc (lambda k 1))"), TypeError::Unsupported(_)));
+}
+
+#[test]
+fn checking_a_program() {
+    let mut env = env();
+    let src = "
+      (def even? (lambda n (if (= n 0) true (odd? (dec n)))))
+      (def odd? (lambda n (if (= n 0) false (even? (dec n)))))
+      (def compose (lambda f (lambda g (lambda x (f (g x))))))
+      (compose even? inc)";
+    let types: Vec<String> = check(&read_all(src), &mut env).unwrap().iter().map(|s| s.to_string()).collect();
+    assert_eq!(types[3], "(-> i64 bool)");
+    assert_eq!(env.value("even?").unwrap().to_string(), "(-> i64 bool)");
+    assert_eq!(env.value("compose").unwrap().to_string(), "(forall ('a 'b 'c) (-> (-> 'a 'b) (-> 'c 'a) 'c 'b))");
+}
+
+#[test]
+fn recursive_definitions() {
+    let mut env = env();
+    let src = "(def sum (lambda n (if (= n 0) 0 (+ n (sum (dec n))))))";
+    check(&read_all(src), &mut env).unwrap();
+    assert_eq!(env.value("sum").unwrap().to_string(), "(-> i64 i64)");
+}
+
+#[test]
+fn checked_before_evaluation() {
+    let mut eval = Eval::with_options(Options { typecheck: true, ..Options::default() });
+    let src = "
+      (def hits (atom 0))
+      (def f (lambda x (do (swap! hits inc) (+ x 1))))";
+    eval_str(&mut eval, src).unwrap();
+    assert!(matches!(eval_str(&mut eval, "(f \"a\")"), Err(EvalError::Type(_))));
+    // nothing ran
+    assert_eq!(int(eval_str(&mut eval, "(deref hits)").unwrap()), 0);
+    assert_eq!(int(eval_str(&mut eval, "(f 1)").unwrap()), 2);
+    assert_eq!(eval.types().value("f").unwrap().to_string(), "(-> i64 i64)");
+}
+
+#[test]
+fn only_values_are_generalised() {
+    let mut eval = Eval::with_options(Options { typecheck: true, ..Options::default() });
+    eval_str(&mut eval, "(def r (atom []))").unwrap();
+    assert_eq!(eval.types().value("r").unwrap().to_string(), "(Atom (Vec '_weak1))");
+    let src = "(do (reset! r [1]) (reset! r [\"a\"]) (+ 1 (first (deref r))))";
+    assert!(matches!(eval_str(&mut eval, src), Err(EvalError::Type(_))));
+    // a later form pins it down
+    eval_str(&mut eval, "(reset! r [1])").unwrap();
+    assert_eq!(eval.types().value("r").unwrap().to_string(), "(Atom (Vec i64))");
+    assert!(matches!(eval_str(&mut eval, "(reset! r [\"a\"])"), Err(EvalError::Type(_))));
+    assert_eq!(int(eval_str(&mut eval, "(+ 1 (first (deref r)))").unwrap()), 2);
+    // and code using it does not generalise over it
+    eval_str(&mut eval, "(def s (atom []))").unwrap();
+    eval_str(&mut eval, "(def put (lambda x (reset! s [x])))").unwrap();
+    assert_eq!(eval.types().value("put").unwrap().to_string(), "(-> '_weak2 (Vec '_weak2))");
+    eval_str(&mut eval, "(put 1)").unwrap();
+    assert!(matches!(eval_str(&mut eval, "(put \"a\")"), Err(EvalError::Type(_))));
+}
+
+#[test]
+fn macros_are_expanded_before_checking() {
+    let mut eval = Eval::with_options(Options { typecheck: true, ..Options::default() });
+    let src = "
+      (def twice (macro x `(+ ~x ~x)))
+      (twice 21)";
+    assert_eq!(int(eval_str(&mut eval, src).unwrap()), 42);
+    assert!(matches!(eval_str(&mut eval, "(twice \"a\")"), Err(EvalError::Type(_))));
+}
@@ -0,0 +1,114 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn limited(limits: Limits) -> Eval {
+    let mut eval = Eval::with_options(Options { limits, ..Options::default() });
+    eval_str(&mut eval, "(def loop (lambda x (loop x)))").unwrap();
+    eval
+}
+
+#[test]
+fn fuel_runs_out() {
+    let mut eval = limited(Limits { max_steps: Some(10_000), ..Limits::default() });
+    match eval_str(&mut eval, "(loop 1)") {
+        Err(EvalError::OutOfFuel(Some(span))) => assert_eq!(span.start.line, 0),
+        other => panic!("expected to run out of fuel, got {:?}", other),
+    }
+    // each call gets the whole budget again
+    assert_eq!(int(eval_str(&mut eval, "(reduce + 0 (range 0 100))").unwrap()), 4950);
+}
+
+#[test]
+fn limits_cannot_be_caught() {
+    let mut eval = limited(Limits { max_steps: Some(10_000), ..Limits::default() });
+    let src = "
+      (def cleaned (atom false))
+      (try (loop 1) (catch _ 'caught) (finally (reset! cleaned true)))";
+    assert!(matches!(eval_str(&mut eval, src), Err(EvalError::OutOfFuel(_))));
+    assert_eq!(eval_str(&mut eval, "(deref cleaned)").unwrap(), false.into());
+}
+
+#[test]
+fn fuel_counts_nested_runs() {
+    let mut eval = limited(Limits { max_steps: Some(10_000), ..Limits::default() });
+    assert!(matches!(eval_str(&mut eval, "(map loop (list 1))"), Err(EvalError::OutOfFuel(_))));
+}
+
+#[test]
+fn allocation_runs_out() {
+    let mut eval = limited(Limits { max_allocation: Some(100_000), ..Limits::default() });
+    let src = "
+      (def grow (lambda xs (grow (concat xs xs))))
+      (grow (list 1))";
+    assert!(matches!(eval_str(&mut eval, src), Err(EvalError::OutOfMemory(_))));
+    assert!(matches!(eval_str(&mut eval, "(range 0 1000000000000)"), Err(EvalError::OutOfMemory(_))));
+    assert_eq!(int(eval_str(&mut eval, "(count (range 0 1000))").unwrap()), 1000);
+}
+
+#[test]
+fn evaluated_code_is_charged_for_what_it_builds() {
+    // no natives run in the loops, so only what they build is charged
+    for src in [
+        "(def grow (lambda acc (grow (lambda _ acc)))) (grow nil)",
+        "(def grow (lambda acc (call/cc (lambda k (grow k))))) (grow nil)",
+    ] {
+        let mut eval = limited(Limits { max_allocation: Some(1_000), max_steps: Some(1_000_000), ..Limits::default() });
+        assert!(matches!(eval_str(&mut eval, src), Err(EvalError::OutOfMemory(_))), "{}", src);
+    }
+    let mut eval = limited(Limits { max_allocation: Some(10_000), ..Limits::default() });
+    let src = "
+      (def build (lambda n (lambda acc (if (= n 0) acc ((build (dec n)) (cons n acc))))))
+      ((build 1000000) nil)";
+    assert!(matches!(eval_str(&mut eval, src), Err(EvalError::OutOfMemory(_))));
+}
+
+#[test]
+fn depth_can_be_lowered() {
+    let mut eval = limited(Limits { max_depth: Some(100), ..Limits::default() });
+    let src = "
+      (def sum (lambda n (if (= n 0) 0 (+ n (sum (dec n))))))
+      (sum 1000)";
+    assert!(matches!(eval_str(&mut eval, src), Err(EvalError::StackOverflow(_))));
+    assert_eq!(int(eval_str(&mut eval, "(sum 10)").unwrap()), 55);
+}
+
+#[test]
+fn native_nesting_is_capped() {
+    // within the stack of a spawned thread, in a debug build too
+    thread::spawn(|| {
+        let mut eval = Eval::new();
+        eval_str(&mut eval, "(def nest (lambda n (if (= n 0) 0 (first (map nest (list (dec n)))))))").unwrap();
+        assert_eq!(int(eval_str(&mut eval, "(nest 31)").unwrap()), 0);
+        assert!(matches!(eval_str(&mut eval, "(nest 32)"), Err(EvalError::StackOverflow(_))));
+    }).join().unwrap();
+}
+
+#[test]
+fn deadline_passes() {
+    let mut eval = limited(Limits::default());
+    eval.set_limits(Limits { deadline: Some(Instant::now() + Duration::from_millis(50)), ..Limits::default() });
+    assert!(matches!(eval_str(&mut eval, "(loop 1)"), Err(EvalError::DeadlineExceeded(_))));
+}
+
+#[test]
+fn interrupted_from_another_thread() {
+    let mut eval = limited(Limits::default());
+    let handle = eval.interrupt_handle();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.store(true, Ordering::SeqCst);
+    });
+    let result = eval_str(&mut eval, "(loop 1)");
+    interrupter.join().unwrap();
+    assert!(matches!(result, Err(EvalError::Interrupted(_))));
+    // the flag is cleared and the evaluator still works
+    assert!(!eval.interrupt_handle().load(Ordering::SeqCst));
+    assert_eq!(int(eval_str(&mut eval, "(+ 1 2)").unwrap()), 3);
+}
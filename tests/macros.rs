@@ -0,0 +1,124 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+use pangolisp::exprs::*;
+
+const MACROS: &str = "
+  (def unless (macro form
+    `(if ~(first form) nil ~(first (rest form)))))
+  (def my-or (macro form
+    `(let (tmp ~(first form)) (if tmp tmp ~(first (rest form))))))
+  (def aif (macro form
+    (let (it (datum->syntax form 'it))
+      `(let (~it ~(first form)) (if ~it ~(first (rest form)) nil)))))
+  (def when-not (macro form `(unless ~@form)))";
+
+fn with_macros() -> Eval {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, MACROS).unwrap();
+    eval
+}
+
+fn show(src: &str) -> String {
+    eval_str(&mut with_macros(), src).unwrap().to_string()
+}
+
+#[test]
+fn quasiquote() {
+    assert_eq!(show("`(1 ~(+ 1 1) ~@(list 3 4) x)"), "(1 2 3 4 x)");
+    assert_eq!(show("`x"), "x");
+    assert_eq!(show("`()"), "()");
+    assert_eq!(show("`(a (b ~(+ 1 2)))"), "(a (b 3))");
+}
+
+#[test]
+fn quasiquote_without_the_prelude() {
+    let mut eval = Eval::with_options(Options { prelude: false, ..Options::default() });
+    let result = eval_str(&mut eval, "(let (x 1) `(a ~x))").unwrap();
+    assert_eq!(result.to_string(), "(a 1)");
+}
+
+#[test]
+fn unquote_outside_quasiquote() {
+    let mut eval = Eval::new();
+    assert!(matches!(eval_str(&mut eval, "~x"), Err(EvalError::BadParameter(_, _))));
+}
+
+#[test]
+fn macros_expand() {
+    assert_eq!(show("(unless false 5)"), "5");
+    assert_eq!(show("(unless true 5)"), "nil");
+}
+
+#[test]
+fn introduced_bindings_do_not_capture() {
+    // an unhygienic `my-or` would see its own `tmp`, which is false
+    assert_eq!(show("(let (tmp 5) (my-or false tmp))"), "5");
+    assert_eq!(show("(my-or false (my-or false 3))"), "3");
+}
+
+#[test]
+fn introduced_references_ignore_user_bindings() {
+    assert_eq!(show("(let (if 1 nil 2) (unless false 7))"), "7");
+}
+
+#[test]
+fn intentional_capture() {
+    assert_eq!(show("(aif (+ 1 2) (+ it 1))"), "4");
+    assert_eq!(show("(let (x 10) (aif x (* it 2)))"), "20");
+}
+
+#[test]
+fn gensyms_are_unique() {
+    assert_eq!(show("(= (gensym) (gensym))"), "false");
+    assert_eq!(show("(symbol? (gensym \"tmp\"))"), "true");
+    let src = "
+      (def twice (macro form
+        (let (v (gensym \"v\"))
+          `(let (~v ~(first form)) (+ ~v ~v)))))
+      (twice 21)";
+    assert_eq!(show(src), "42");
+}
+
+#[test]
+fn macroexpand_one_and_all_the_way() {
+    assert_eq!(show("(macroexpand-1 '(when-not a b))"), "(unless a b)");
+    assert_eq!(show("(macroexpand '(when-not a b))"), "(if a nil b)");
+    assert_eq!(show("(macroexpand '(f a b))"), "(f a b)");
+}
+
+#[test]
+fn macroexpand_all_walks_the_tree() {
+    let src = "(macroexpand-all '(do (unless a (when-not b c)) '(unless x y)))";
+    assert_eq!(show(src), "(do (if a nil (if b nil c)) (quote (unless x y)))");
+    let src = "(macroexpand-all '(let (unless (unless a b)) (lambda unless (unless c d))))";
+    assert_eq!(show(src), "(let (unless (if a nil b)) (lambda unless (if c nil d)))");
+    let src = "(macroexpand-all '(match x (unless a) (unless a b) _ `(unless ~(unless c d))))";
+    assert_eq!(show(src), "(match x (unless a) (if a nil b) _ (quasiquote (unless (unquote (if c nil d)))))");
+}
+
+#[test]
+fn expand_terminates() {
+    let mut eval = with_macros();
+    let expr = read_str("(+ 1 2)");
+    assert_eq!(eval.expand(expr.clone()).unwrap(), expr);
+}
+
+#[test]
+fn expansions_record_their_origin() {
+    let mut eval = with_macros();
+    let expr = read_str("\n  (when-not a b)");
+    let expansion = eval.expand(expr).unwrap();
+    assert_eq!(expansion.to_string(), "(if a nil b)");
+    assert_eq!(expansion.meta().unwrap().provenance(), vec![
+        "in expansion of `unless` at 2:3".to_string(),
+        "in expansion of `when-not` at 2:3".to_string(),
+    ]);
+    // what the user wrote is not from the expansion
+    if let Expr::List(list) = expansion {
+        assert!(list.vals[1].meta().unwrap().old.is_none());
+    }
+}
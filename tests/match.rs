@@ -0,0 +1,91 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+
+fn show(src: &str) -> String {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, src).unwrap().to_string()
+}
+
+#[test]
+fn literals_and_wildcards() {
+    let src = "
+      (def describe (lambda x
+        (match x
+          0 'zero
+          \"hi\" 'greeting
+          ::none 'nothing
+          nil 'nil
+          _ 'other)))
+      (list (describe 0) (describe \"hi\") (describe ::none) (describe nil) (describe 7))";
+    assert_eq!(show(src), "(zero greeting nothing nil other)");
+}
+
+#[test]
+fn symbols_bind() {
+    assert_eq!(show("(match 20 n (+ n 1))"), "21");
+}
+
+#[test]
+fn list_patterns_with_rest() {
+    assert_eq!(show("(match (list 1 2 3) (a b) 'two (a & more) more)"), "(2 3)");
+    assert_eq!(show("(match (list 1 2) [a b] (+ a b))"), "3");
+    assert_eq!(show("(match (list) () 'empty)"), "empty");
+    assert_eq!(show("(match (list 1) [a & more] more)"), "()");
+}
+
+#[test]
+fn quoted_patterns_take_forms_apart() {
+    let src = "
+      (match '(let (x 1) body)
+        ('let bindings & body) (list bindings body)
+        _ 'no)";
+    assert_eq!(show(src), "((x 1) (body))");
+}
+
+#[test]
+fn map_patterns() {
+    let src = "(match {::kind ::point ::x 1 ::y 2} {::kind ::point ::x x} x)";
+    assert_eq!(show(src), "1");
+    assert_eq!(show("(match {::x 1} {::y y} y _ 'missing)"), "missing");
+}
+
+#[test]
+fn guards() {
+    let src = "
+      (def sign (lambda n
+        (match n
+          0 'zero
+          n ::when (< n 0) 'negative
+          _ 'positive)))
+      (list (sign -5) (sign 0) (sign 5))";
+    assert_eq!(show(src), "(negative zero positive)");
+}
+
+#[test]
+fn nested_patterns() {
+    let src = "(match (list 1 (list 2 {::v 3})) (a (b {::v c})) (+ a b c))";
+    assert_eq!(show(src), "6");
+}
+
+#[test]
+fn bindings_do_not_leak() {
+    let mut eval = Eval::new();
+    let result = eval_str(&mut eval, "(def a 1) (match 2 a a) a");
+    assert_eq!(int(result.unwrap()), 1);
+}
+
+#[test]
+fn non_exhaustive() {
+    let mut eval = Eval::new();
+    match eval_str(&mut eval, "(match 3 1 'one 2 'two)") {
+        Err(EvalError::NonExhaustiveMatch(val, Some(span))) => {
+            assert_eq!(int(val), 3);
+            assert_eq!(span.start.column, 0);
+        }
+        other => panic!("expected NonExhaustiveMatch, got {:?}", other),
+    }
+}
@@ -0,0 +1,112 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+use pangolisp::exprs::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+fn add(a: i64, b: i64) -> i64 {
+    a + b
+}
+
+fn checked_neg(a: i64) -> Result<i64, EvalError> {
+    a.checked_neg().ok_or(EvalError::BadParameter("negatable int", Expr::Int(a.into())))
+}
+
+#[test]
+fn register_fn() {
+    let mut eval = Eval::new();
+    eval.register_fn("add", add);
+    assert_eq!(int(eval_str(&mut eval, "(add 1 2)").unwrap()), 3);
+}
+
+#[test]
+fn partial_application() {
+    let mut eval = Eval::new();
+    eval.register_fn("add", add);
+    assert_eq!(int(eval_str(&mut eval, "((add 1) 2)").unwrap()), 3);
+    match eval_str(&mut eval, "(add 1)").unwrap() {
+        Expr::Native(n) => assert_eq!(n.args.len(), 1),
+        other => panic!("expected a native, got {:?}", other),
+    }
+}
+
+#[test]
+fn curried_through_lambda() {
+    let mut eval = Eval::new();
+    eval.register_fn("add", add);
+    assert_eq!(int(eval_str(&mut eval, "((lambda x (add x)) 1 2)").unwrap()), 3);
+}
+
+#[test]
+fn bad_parameter() {
+    let mut eval = Eval::new();
+    eval.register_fn("add", add);
+    match eval_str(&mut eval, "(add 'a 1)") {
+        Err(EvalError::BadParameter(what, _)) => assert_eq!(what, "int"),
+        other => panic!("expected a bad parameter, got {:?}", other),
+    }
+}
+
+#[test]
+fn fallible() {
+    let mut eval = Eval::new();
+    eval.register_fn("neg", checked_neg);
+    assert_eq!(int(eval_str(&mut eval, "(neg 3)").unwrap()), -3);
+    eval.define("min", Expr::Int(i64::MIN.into()));
+    assert!(matches!(eval_str(&mut eval, "(neg min)"), Err(EvalError::BadParameter(..))));
+}
+
+#[test]
+fn zero_arity() {
+    let mut eval = Eval::new();
+    let count = Rc::new(Cell::new(0));
+    let counter = count.clone();
+    eval.register_fn("tick", move || { counter.set(counter.get() + 1); counter.get() });
+    assert_eq!(int(eval_str(&mut eval, "(tick)").unwrap()), 1);
+    assert_eq!(int(eval_str(&mut eval, "(tick)").unwrap()), 2);
+    assert_eq!(count.get(), 2);
+    assert!(matches!(eval_str(&mut eval, "tick").unwrap(), Expr::Native(_)));
+}
+
+#[test]
+fn variadic() {
+    let mut eval = Eval::new();
+    eval.register_native("sum", Arity::AtLeast(0), |_, args| {
+        let mut total = 0;
+        for arg in args {
+            if let Expr::Int(i) = arg {
+                total += i.value;
+            } else {
+                return Err(EvalError::BadParameter("int", arg.clone()));
+            }
+        }
+        Ok(Expr::Int(total.into()))
+    });
+    assert_eq!(int(eval_str(&mut eval, "(sum)").unwrap()), 0);
+    assert_eq!(int(eval_str(&mut eval, "(sum 1 2 3 4)").unwrap()), 10);
+}
+
+#[test]
+fn natives_call_back() {
+    let mut eval = Eval::new();
+    eval.register_native("twice", Arity::Exactly(2), |eval, args| {
+        let once = eval.apply(args[0].clone(), vec![args[1].clone()])?;
+        eval.apply(args[0].clone(), vec![once])
+    });
+    eval.register_fn("add", add);
+    assert_eq!(int(eval_str(&mut eval, "(twice (add 3) 1)").unwrap()), 7);
+}
+
+#[test]
+fn identity_equality() {
+    let mut eval = Eval::new();
+    eval.register_fn("add", add);
+    let a = eval.lookup("add").unwrap().clone();
+    assert_eq!(a, a.clone());
+    eval.register_fn("add", add);
+    assert_ne!(&a, eval.lookup("add").unwrap());
+}
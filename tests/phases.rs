@@ -0,0 +1,70 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+
+#[test]
+fn helpers_for_syntax_are_visible_to_macros_only() {
+    let mut eval = Eval::new();
+    let src = "
+      (begin-for-syntax
+        (def wrap (lambda x `(list ~x ~x))))
+      (def twice (macro form (wrap (first form))))
+      (twice 3)";
+    assert_eq!(eval_str(&mut eval, src).unwrap().to_string(), "(3 3)");
+    assert!(matches!(eval_str(&mut eval, "(wrap 1)"), Err(EvalError::UnknownBinding(_))));
+    assert!(eval.definitions(Phase::Compile).contains_key("wrap"));
+    assert!(!eval.definitions(Phase::Run).contains_key("wrap"));
+}
+
+#[test]
+fn runtime_definitions_are_invisible_to_macros() {
+    let mut eval = Eval::new();
+    let src = "
+      (def runtime-only 1)
+      (def peek (macro form runtime-only))
+      (peek)";
+    match eval_str(&mut eval, src) {
+        Err(EvalError::UnknownBinding(name)) => assert_eq!(name, "runtime-only"),
+        other => panic!("expected UnknownBinding, got {:?}", other),
+    }
+}
+
+#[test]
+fn expansions_refer_to_the_run_phase() {
+    let mut eval = Eval::new();
+    let src = "
+      (begin-for-syntax (def x 'compile))
+      (def x 'run)
+      (def which (macro form `(list '~x x)))
+      (which)";
+    assert_eq!(eval_str(&mut eval, src).unwrap().to_string(), "(compile run)");
+}
+
+#[test]
+fn eval_when() {
+    let mut eval = Eval::new();
+    let src = "
+      (eval-when (::compile ::run) (def both 1))
+      (eval-when (::compile) (def compile-only 2))
+      (eval-when (::run) (def run-only 3))";
+    eval_str(&mut eval, src).unwrap();
+    let compile = eval.definitions(Phase::Compile);
+    let run = eval.definitions(Phase::Run);
+    assert!(compile.contains_key("both") && run.contains_key("both"));
+    assert!(compile.contains_key("compile-only") && !run.contains_key("compile-only"));
+    assert!(!compile.contains_key("run-only") && run.contains_key("run-only"));
+    assert_eq!(int(eval_str(&mut eval, "(eval-when (::run) 1 2)").unwrap()), 2);
+    assert!(matches!(eval_str(&mut eval, "(eval-when (::later) 1)"), Err(EvalError::BadParameter(..))));
+}
+
+#[test]
+fn natives_can_be_registered_for_macros() {
+    let mut eval = Eval::new();
+    eval.with_phase(Phase::Compile, |eval| eval.register_fn("shout", |s: String| s.to_uppercase()));
+    let src = "(def loud (macro form (shout (first form)))) (loud \"hi\")";
+    assert_eq!(eval_str(&mut eval, src).unwrap().to_string(), "\"HI\"");
+    assert!(eval_str(&mut eval, "(shout \"hi\")").is_err());
+}
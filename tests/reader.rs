@@ -0,0 +1,92 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+use pangolisp::exprs::*;
+use pangolisp::forms::*;
+use pangolisp::reader::*;
+
+fn list(expr: &Expr) -> &List {
+    match expr {
+        Expr::List(l) => l,
+        other => panic!("expected a list, got {:?}", other),
+    }
+}
+
+#[test]
+fn backslash_is_lambda() {
+    let mut eval = Eval::new();
+    assert_eq!(int(eval_str(&mut eval, "((\\x (+ x x)) 21)").unwrap()), 42);
+    assert_eq!(read_str("\\x (+ x x)").to_string(), "(lambda x (+ x x))");
+}
+
+#[test]
+fn nested_backslashes_curry() {
+    let mut eval = Eval::new();
+    let expr = read_str("\\x \\y (- x y)");
+    assert_eq!(expr.to_string(), "(lambda x (lambda y (- x y)))");
+    assert_eq!(int(eval_str(&mut eval, "((\\x \\y (- x y)) 5 3)").unwrap()), 2);
+    assert_eq!(int(eval_str(&mut eval, "(((\\x \\y (- x y)) 5) 3)").unwrap()), 2);
+}
+
+#[test]
+fn backslash_spans() {
+    let outer = read_str("\\x \\y (- x y)");
+    let outer = list(&outer);
+    let span = outer.meta.span.unwrap();
+    assert_eq!((span.start.column, span.end.column), (0, 13));
+    let lambda = outer.vals[0].meta().unwrap().span.unwrap();
+    assert_eq!((lambda.start.column, lambda.end.column), (0, 1));
+    let inner = list(&outer.vals[2]);
+    let span = inner.meta.span.unwrap();
+    assert_eq!((span.start.column, span.end.column), (3, 13));
+    let param = inner.vals[1].meta().unwrap().span.unwrap();
+    assert_eq!((param.start.column, param.end.column), (4, 5));
+}
+
+#[test]
+fn incomplete_backslash() {
+    let mut forms = Forms::new("(\\x)");
+    assert!(matches!(forms.next(), Some(Err(FormError::DoesNotComplete(..)))));
+}
+
+#[test]
+fn reader_macros_from_rust() {
+    let mut eval = Eval::new();
+    eval.register_reader_macro('%', 1, |_, args| {
+        let vals = vec![Expr::Symbol(Symbol::from("list".to_string())), args[0].clone(), args[0].clone()];
+        Ok(Expr::List(List::from(vals.into_iter().collect::<im::Vector<_>>())))
+    });
+    assert_eq!(eval_str(&mut eval, "%(+ 1 2)").unwrap().to_string(), "(3 3)");
+    // spans the macro did not give come from the dispatch form
+    let form = eval.forms(" %1").next().unwrap().unwrap();
+    let expr = eval.read(form).unwrap();
+    assert_eq!(list(&expr).meta.span.unwrap().start.column, 1);
+}
+
+#[test]
+fn reader_macros_from_pangolisp() {
+    let mut eval = Eval::new();
+    let src = "
+      (reader-macro \"#\" 1 (lambda x `(count ~x)))
+      #(list 1 2 3)";
+    assert_eq!(int(eval_str(&mut eval, src).unwrap()), 3);
+}
+
+#[test]
+fn non_ascii_reader_macros() {
+    let mut eval = Eval::new();
+    assert_eq!(int(eval_str(&mut eval, "(reader-macro \"λ\" 1 (lambda x x)) λ5").unwrap()), 5);
+    // columns count chars
+    let form = eval.forms("λ5").next().unwrap().unwrap();
+    assert_eq!(form.span().end.column, 2);
+}
+
+#[test]
+fn unknown_dispatch() {
+    let dispatch = pangolisp::tokens::default_dispatch().update('%', 1);
+    let form = Forms::with_dispatch("%x", dispatch).next().unwrap().unwrap();
+    assert!(matches!(read(form), Err(ReadError::UnknownDispatch(_))));
+}
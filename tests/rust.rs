@@ -0,0 +1,168 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+use common::*;
+
+use pangolisp::eval::*;
+use pangolisp::exprs::Expr;
+use pangolisp::rust::{self, Block, Function, Header, Item, Param, Pat, Ty};
+
+fn print(eval: &mut Eval, src: &str) -> String {
+    match eval_str(eval, &format!("(rs/print {})", src)).unwrap() {
+        Expr::String(s) => s.value,
+        other => panic!("expected a string, got {}", other),
+    }
+}
+
+#[test]
+fn functions() {
+    let mut eval = Eval::new();
+    let area = "(rs/fn 'area [['s 'Shape]] 'i64
+                  (rs/match 's
+                    (rs/arm (rs/struct-expr \"Shape::Circle\" ['radius 'r]) (rs/binary '* 3 (rs/binary '* 'r 'r)))
+                    (rs/arm (rs/call \"Shape::Rect\" 'w 'h) (rs/block (rs/let 'a (rs/binary '* 'w 'h)) 'a))
+                    (rs/arm 'n (rs/binary '> 'n 0) 'n)
+                    (rs/arm '_ 0))
+                  ::pub (rs/attr 'inline) (rs/generic 'T 'Clone \"std::fmt::Debug\"))";
+    assert_eq!(print(&mut eval, area), "\
+#[inline]
+pub fn area<T: Clone + std::fmt::Debug>(s: Shape) -> i64 {
+    match s {
+        Shape::Circle { radius: r } => 3 * (r * r),
+        Shape::Rect(w, h) => {
+            let a = w * h;
+            a
+        }
+        n if n > 0 => n,
+        _ => 0,
+    }
+}");
+    // a lone statement is a block of it, and nil is `()`
+    assert_eq!(print(&mut eval, "(rs/fn 'log [['msg (rs/ref 'str)]] nil (rs/semi (rs/macro 'println \"{}\" 'msg)))"), "\
+fn log(msg: &str) {
+    println!(\"{}\", msg);
+}");
+}
+
+#[test]
+fn types_and_impls() {
+    let mut eval = Eval::new();
+    let src = "[(rs/use \"std::fmt\")
+                (rs/struct 'Point [['x 'i64] ['y 'i64]] ::pub (rs/attr 'derive 'Clone 'Debug))
+                (rs/struct 'Meters ['i64])
+                (rs/struct 'Marker nil)
+                (rs/enum 'Shape ['Empty (rs/variant 'Circle [['radius 'i64]]) (rs/variant 'Rect ['Point 'Point])])
+                (rs/impl-for \"fmt::Display\" 'Point
+                  [(rs/fn 'fmt [(rs/ref 'self) ['f (rs/ref-mut \"fmt::Formatter\")]] \"fmt::Result\"
+                     (rs/macro 'write 'f \"({}, {})\" (rs/field 'self 'x) (rs/field 'self 'y)))])
+                (rs/impl (rs/path 'Stack 'T)
+                  [(rs/fn 'new nil 'Self (rs/struct-expr 'Self ['items (rs/macro 'vec)]) ::pub)
+                   (rs/fn 'push [(rs/ref-mut 'self) ['x 'T]] nil
+                     (rs/block (rs/semi (rs/method (rs/field 'self 'items) 'push 'x))))]
+                  (rs/generic 'T))]";
+    assert_eq!(print(&mut eval, src), "\
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+struct Meters(i64);
+
+struct Marker;
+
+enum Shape {
+    Empty,
+    Circle { radius: i64 },
+    Rect(Point, Point),
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, \"({}, {})\", self.x, self.y)
+    }
+}
+
+impl<T> Stack<T> {
+    pub fn new() -> Self {
+        Self { items: vec![] }
+    }
+
+    fn push(&mut self, x: T) {
+        self.items.push(x);
+    }
+}
+");
+}
+
+#[test]
+fn expressions() {
+    let mut eval = Eval::new();
+    assert_eq!(print(&mut eval, "(rs/binary '* (rs/binary '+ 1 2) (rs/unary '- (rs/method 'x 'abs)))"), "(1 + 2) * -x.abs()");
+    assert_eq!(print(&mut eval, "(rs/binary '- 1 (rs/binary '- 2 3))"), "1 - (2 - 3)");
+    assert_eq!(print(&mut eval, "(rs/binary '- (rs/binary '- 1 2) 3)"), "1 - 2 - 3");
+    assert_eq!(print(&mut eval, "(rs/method -1 'abs)"), "(-1).abs()");
+    assert_eq!(print(&mut eval, "(rs/field (rs/tuple 1 \"a\") 0)"), "(1, \"a\").0");
+    assert_eq!(print(&mut eval, "(rs/call (rs/path \"Vec::new\") )"), "Vec::new()");
+    assert_eq!(print(&mut eval, "(rs/call (rs/path 'parse 'i64))"), "parse::<i64>()");
+    assert_eq!(print(&mut eval, "(rs/lit 255 'u8)"), "255u8");
+    // a struct literal where a block could begin
+    assert_eq!(print(&mut eval, "(rs/if (rs/binary '== 'p (rs/struct-expr 'P ['x 'x] '..)) 1 (rs/if true 2 3))"), "\
+if (p == P { x, .. }) {
+    1
+} else if true {
+    2
+} else {
+    3
+}");
+    assert_eq!(print(&mut eval, "(rs/path 'Vec (rs/tuple 'i64 'bool))"), "Vec<(i64, bool)>");
+}
+
+#[test]
+fn values_read_as_built() {
+    let mut eval = Eval::new();
+    assert_eq!(eval_str(&mut eval, "(rs/call 'f 1 \"a\")").unwrap().to_string(), "(rs/call f 1 \"a\")");
+    // built up by pangolisp functions, and only printed at the end
+    let src = "(def getter (lambda field (rs/fn field [(rs/ref 'self)] 'i64 (rs/field 'self field) ::pub)))
+               (def getters (lambda type (lambda fields (rs/impl type (map getter fields)))))";
+    eval_str(&mut eval, src).unwrap();
+    assert_eq!(print(&mut eval, "(getters 'Point ['x 'y])"), "\
+impl Point {
+    pub fn x(&self) -> i64 {
+        self.x
+    }
+
+    pub fn y(&self) -> i64 {
+        self.y
+    }
+}");
+}
+
+#[test]
+fn checked_when_built() {
+    let mut eval = Eval::new();
+    let bad = |eval: &mut Eval, src: &str| eval_str(eval, src).unwrap_err().to_string();
+    assert_eq!(bad(&mut eval, "(rs/binary 'plus 1 2)"), "expected a rust operator, got plus");
+    assert_eq!(bad(&mut eval, "(rs/fn 'f [['x]] 'i64 1)"), "expected a parameter and its type, got (x)");
+    assert_eq!(bad(&mut eval, "(rs/struct \"not an ident\" nil)"), "expected a rust identifier, got \"not an ident\"");
+    assert_eq!(bad(&mut eval, "(rs/path \"a::b c\")"), "expected a rust path, got (rs/path \"a::b c\")");
+    assert_eq!(bad(&mut eval, "(rs/ref (rs/arm 1 2))"), "expected a rust type, got (rs/arm 1 2)");
+    assert_eq!(bad(&mut eval, "(rs/fn 'f nil 'i64 1 ::public)"),
+               "expected ::pub, an attribute or a generic parameter, got ::public");
+    assert_eq!(bad(&mut eval, "(rs/print (rs/arm 1 2))"), "expected a rust expression, got (rs/arm 1 2)");
+}
+
+#[test]
+fn built_from_rust() {
+    let function = Item::Fn(Function {
+        header: Header { public: true, ..Header::default() },
+        name: "double".to_string(),
+        params: vec![Param::Typed(Pat::Ident(false, "x".to_string()), Ty::path("i64"))],
+        ret: Some(Ty::path("i64")),
+        body: Block::of(rust::Expr::binary("*", rust::Expr::int(2), rust::Expr::path("x"))),
+    });
+    assert_eq!(rust::file(&[function]), "pub fn double(x: i64) -> i64 {\n    2 * x\n}\n");
+    assert_eq!(Ty::generic("Option", vec![Ty::Ref(false, Box::new(Ty::path("str")))]).to_string(), "Option<&str>");
+}
@@ -0,0 +1,119 @@
+#![allow(clippy::result_large_err)]
+
+use pangolisp::codegen::*;
+use pangolisp::eval::*;
+use pangolisp::sourcemap::*;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const MODULE: &str = "(def double (macro form `(* 2 ~(first form))))
+
+(defn wrong [[x i64]]
+  (the bool (+ x 1)))
+
+(defn via-macro [[x i64]]
+  (the bool (double x)))
+";
+
+fn mapped() -> (String, SourceMap) {
+    Codegen::new().mapped_module(&mut Eval::new(), MODULE, "bad.pl", "bad.rs").unwrap()
+}
+
+// the line and column of the first `needle` in `text`, from 0
+fn find(text: &str, needle: &str) -> (usize, usize) {
+    let offset = text.find(needle).unwrap();
+    let line = text[..offset].matches('\n').count();
+    (line, offset - text[..offset].rfind('\n').map_or(0, |nl| nl + 1))
+}
+
+#[test]
+fn expressions_map_to_their_forms() {
+    let (code, map) = mapped();
+    let (line, column) = find(&code, "x + 1");
+    let mapping = map.lookup((line, column), (line, column + 5)).unwrap();
+    assert_eq!(&MODULE[mapping.source.start.offset..mapping.source.end.offset], "(+ x 1)");
+    assert_eq!((mapping.source.start.line, mapping.source.start.column), (3, 12));
+    // just the `x` of it
+    let mapping = map.lookup((line, column), (line, column + 1)).unwrap();
+    assert_eq!(&MODULE[mapping.source.start.offset..mapping.source.end.offset], "x");
+    // and the whole function, to its definition
+    let (line, _) = find(&code, "pub fn wrong");
+    let mapping = map.lookup((line, 0), (line + 2, 1)).unwrap();
+    assert_eq!(mapping.source.start.line, 2);
+}
+
+#[test]
+fn macro_expansions_are_recorded() {
+    let (code, map) = mapped();
+    let (line, column) = find(&code, "2 * x");
+    let mapping = map.lookup((line, column), (line, column + 5)).unwrap();
+    assert_eq!(mapping.provenance(), vec!["in expansion of `double` at 7:13"]);
+    // the `2` is from the macro's template
+    let mapping = map.lookup((line, column), (line, column + 1)).unwrap();
+    assert_eq!(&MODULE[mapping.source.start.offset..mapping.source.end.offset], "2");
+    assert_eq!(mapping.expansions.len(), 1);
+}
+
+#[test]
+fn maps_read_back_as_written() {
+    let (_, map) = mapped();
+    assert_eq!(SourceMap::from_json(&map.to_json()).unwrap(), map);
+    assert_eq!(SourceMap::from_json("{\"source\": \"a.pl\"").unwrap_err(), SourceMapError::Json(17));
+    assert_eq!(SourceMap::from_json("{\"source\": \"a.pl\", \"generated\": \"a.rs\"}").unwrap_err().to_string(),
+               "expected mappings in the source map");
+}
+
+// a diagnostic as rustc would give one for the `x + 1`
+fn diagnostic(code: &str) -> String {
+    let (line, column) = find(code, "x + 1");
+    let span = format!("{{\"file_name\":\"out/bad.rs\",\"byte_start\":0,\"byte_end\":0,\"line_start\":{0},\"line_end\":{0},\
+                        \"column_start\":{1},\"column_end\":{2},\"is_primary\":true,\"text\":[],\"label\":\"found `i64`\",\
+                        \"suggested_replacement\":null,\"suggestion_applicability\":null,\"expansion\":null}}",
+                       line + 1, column + 1, column + 6);
+    format!("{{\"$message_type\":\"diagnostic\",\"message\":\"mismatched types\",\"level\":\"error\",\"spans\":[{}],\
+             \"children\":[],\"rendered\":\"error: mismatched types\\n --> out/bad.rs:{}:{}\\n\"}}",
+            span, line + 1, column + 1)
+}
+
+#[test]
+fn diagnostics_point_at_pangolisp() {
+    let (code, map) = mapped();
+    let output = remap_diagnostics(&[map], &format!("not json\n{}\n", diagnostic(&code)));
+    let mut lines = output.lines();
+    assert_eq!(lines.next(), Some("not json"));
+    let remapped = lines.next().unwrap();
+    assert!(remapped.contains("\"file_name\":\"bad.pl\""), "{}", remapped);
+    assert!(remapped.contains("\"line_start\":4,\"line_end\":4,\"column_start\":13,\"column_end\":20"), "{}", remapped);
+    assert!(remapped.contains("\"label\":\"found `i64`\""));
+    assert_eq!(rendered_messages(&output), "not json\nerror: mismatched types\n --> bad.pl:4:13\n");
+}
+
+#[test]
+fn the_tool_remaps_rustc() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("sourcemap");
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("bad.pl");
+    fs::write(&source, MODULE).unwrap();
+    let generated = Codegen::new().write_module(&mut Eval::new(), &source, &dir).unwrap();
+    let map = dir.join("bad.rs.map");
+    assert!(map.exists());
+    let rustc = Command::new("rustc")
+        .args(["--edition", "2018", "--crate-type", "lib", "--error-format=json", "--out-dir"]).arg(&dir)
+        .arg(&generated)
+        .output();
+    let diagnostics = match rustc {
+        Ok(out) => out.stderr,
+        Err(_) => return eprintln!("skipping: no rustc"),
+    };
+    let mut tool = Command::new(env!("CARGO_BIN_EXE_remap-diagnostics"))
+        .arg("--rendered").arg(&map)
+        .stdin(Stdio::piped()).stdout(Stdio::piped())
+        .spawn().unwrap();
+    tool.stdin.take().unwrap().write_all(&diagnostics).unwrap();
+    let rendered = String::from_utf8(tool.wait_with_output().unwrap().stdout).unwrap();
+    assert!(rendered.contains(&format!("--> {}:4:13", source.display())), "{}", rendered);
+    assert!(rendered.contains(&format!("--> {}:7:13", source.display())), "{}", rendered);
+    assert!(rendered.contains("= note: in expansion of `double` at 7:13"), "{}", rendered);
+}
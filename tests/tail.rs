@@ -0,0 +1,86 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+
+// if tail calls grew the continuation, these would overflow
+fn shallow() -> Eval {
+    Eval::with_options(Options { max_depth: 50, ..Options::default() })
+}
+
+#[test]
+fn self_tail_call() {
+    let mut eval = shallow();
+    let src = "
+      (def count-down (lambda n (if (= n 0) 'done (count-down (dec n)))))
+      (count-down 10000)";
+    assert_eq!(symbol(eval_str(&mut eval, src).unwrap()), "done");
+}
+
+#[test]
+fn mutual_tail_calls() {
+    let mut eval = shallow();
+    let src = "
+      (def even? (lambda n (if (= n 0) true (odd? (dec n)))))
+      (def odd? (lambda n (if (= n 0) false (even? (dec n)))))
+      (even? 10001)";
+    assert_eq!(eval_str(&mut eval, src).unwrap(), false.into());
+}
+
+#[test]
+fn tail_calls_in_let_and_do() {
+    let mut eval = shallow();
+    let src = "
+      (def sum (lambda acc (lambda n
+        (if (= n 0)
+          acc
+          (let (acc (+ acc n)
+                n (dec n))
+            (do 'ignored
+                (sum acc n)))))))
+      (sum 0 10000)";
+    assert_eq!(int(eval_str(&mut eval, src).unwrap()), 50005000);
+}
+
+#[test]
+fn curried_tail_calls() {
+    let mut eval = shallow();
+    let src = "
+      (def loop (lambda a (lambda b (if (= b 0) a (loop (inc a) (dec b))))))
+      (loop 0 10000)";
+    assert_eq!(int(eval_str(&mut eval, src).unwrap()), 10000);
+}
+
+#[test]
+fn deep_recursion_uses_the_heap() {
+    let mut eval = Eval::new();
+    let src = "
+      (def sum (lambda n (if (= n 0) 0 (+ n (sum (dec n))))))
+      (sum 20000)";
+    assert_eq!(int(eval_str(&mut eval, src).unwrap()), 200010000);
+}
+
+#[test]
+fn stack_overflow() {
+    let mut eval = Eval::with_options(Options { max_depth: 1000, ..Options::default() });
+    let src = "
+      (def sum (lambda n (if (= n 0) 0 (+ n (sum (dec n))))))
+      (sum 100000)";
+    match eval_str(&mut eval, src) {
+        Err(EvalError::StackOverflow(Some(span))) => assert_eq!(span.start.line, 1),
+        other => panic!("expected a stack overflow, got {:?}", other),
+    }
+    // the evaluator is still usable afterwards
+    assert_eq!(int(eval_str(&mut eval, "(sum 10)").unwrap()), 55);
+}
+
+#[test]
+fn stack_overflow_through_natives() {
+    let mut eval = Eval::new();
+    let src = "
+      (def deep (lambda n (if (= n 0) 0 (first (map deep [(dec n)])))))
+      (deep 1000000)";
+    assert!(matches!(eval_str(&mut eval, src), Err(EvalError::StackOverflow(_))));
+}
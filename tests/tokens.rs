@@ -0,0 +1,65 @@
+use pangolisp::tokens::*;
+
+fn literals(src: &str) -> Vec<Literal<'_>> {
+    Tokens::new(src)
+        .filter_map(|token| match token.expect("token").inner {
+            Token::Literal(l) => Some(l),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn strings() {
+    assert_eq!(literals(r#""plain""#), vec![Literal::String("plain".into())]);
+    assert_eq!(literals(r#""a\n\"b\"\\""#), vec![Literal::String("a\n\"b\"\\".into())]);
+    let mut tokens = Tokens::new(r#""open"#);
+    assert_eq!(tokens.next(), Some(Err(TokenError::Partial)));
+    let mut tokens = Tokens::new(r#""\q""#);
+    assert_eq!(tokens.next(), Some(Err(TokenError::InvalidEscape('q'))));
+}
+
+#[test]
+fn ints() {
+    assert_eq!(literals("12 -3 0x1f"), vec![Literal::Int(12), Literal::Int(-3), Literal::Int(31)]);
+    assert_eq!(literals("-9223372036854775808"), vec![Literal::Int(i64::MIN)]);
+    let mut tokens = Tokens::new("9223372036854775808");
+    assert!(matches!(tokens.next(), Some(Err(TokenError::BadInt(_)))));
+}
+
+#[test]
+fn symbols() {
+    assert_eq!(literals("- x1 int? -a"), vec![
+        Literal::Symbol("-".into()),
+        Literal::Symbol("x1".into()),
+        Literal::Symbol("int?".into()),
+        Literal::Symbol("-a".into()),
+    ]);
+}
+
+#[test]
+fn comments() {
+    let tokens: Vec<_> = Tokens::new("a ; note\nb").map(|t| t.unwrap()).collect();
+    assert!(tokens.iter().any(|t| t.inner == Token::Comment("; note".into())));
+    assert_eq!(literals("a ; note\nb"), vec![Literal::Symbol("a".into()), Literal::Symbol("b".into())]);
+}
+
+#[test]
+fn spans() {
+    let tokens: Vec<_> = Tokens::new("(ab\n \"c\")").map(|t| t.unwrap()).collect();
+    let string = &tokens[3];
+    assert_eq!(string.inner, Token::Literal(Literal::String("c".into())));
+    assert_eq!((string.span.start.line, string.span.start.column), (1, 1));
+    assert_eq!((string.span.end.offset, string.span.end.column), (8, 4));
+}
+
+#[test]
+fn keywords() {
+    assert_eq!(literals("::kind ::a1"), vec![
+        Literal::Keyword("kind".into()),
+        Literal::Keyword("a1".into()),
+    ]);
+    // a single colon is still the type ascription prefix
+    let mut tokens = Tokens::new(":int");
+    assert_eq!(tokens.next().unwrap().unwrap().inner, Token::Prefix(Prefix::HasType));
+}
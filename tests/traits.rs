@@ -0,0 +1,195 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::eval::*;
+use pangolisp::types::*;
+
+const SHOW: &str = "
+  (defstruct Point [x i64] [y i64])
+  (deftrait (Show 'a) [show (-> 'a String)])
+  (impl (Show i64) [show (lambda n (str n))])
+  (impl (Show Point) [show (lambda p (str \"(\" (show (Point-x p)) \", \" (show (Point-y p)) \")\"))])
+  (impl (Show (Vec 'a)) (where (Show 'a))
+    [show (lambda xs (str \"[\" (join \", \" (map show xs)) \"]\"))])";
+
+fn type_error(eval: &mut Eval, src: &str) -> TypeError {
+    match eval_str(eval, src) {
+        Err(EvalError::Type(e)) => *e,
+        other => panic!("expected a type error, got {:?}", other),
+    }
+}
+
+#[test]
+fn methods_dispatch_on_their_argument() {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, SHOW).unwrap();
+    assert_eq!(eval_str(&mut eval, "(show 1)").unwrap(), "1".into());
+    assert_eq!(eval_str(&mut eval, "(show (Point 1 2))").unwrap(), "(1, 2)".into());
+    assert_eq!(eval_str(&mut eval, "(show (list (Point 1 2) (Point 3 4)))").unwrap(), "[(1, 2), (3, 4)]".into());
+    match eval_str(&mut eval, "(show true)") {
+        Err(EvalError::NoImplementation(name, val)) => {
+            assert_eq!(name, "Show");
+            assert_eq!(val, true.into());
+        }
+        other => panic!("expected no implementation, got {:?}", other),
+    }
+}
+
+#[test]
+fn the_dispatch_argument_need_not_be_first() {
+    let mut eval = Eval::new();
+    let src = "
+      (deftrait (Scale 'a) [scale (-> i64 'a 'a)])
+      (impl (Scale i64) [scale (lambda k (lambda n (* k n)))])
+      (impl (Scale (Vec 'a)) (where (Scale 'a)) [scale (lambda k (lambda xs (map (scale k) xs)))])
+      (scale 3 (list 1 2))";
+    assert_eq!(eval_str(&mut eval, src).unwrap().to_string(), "(3 6)");
+}
+
+#[test]
+fn checked_uses_call_the_impl_for_their_inferred_type() {
+    let src = "
+      (deftrait (Show 'a) [show (-> 'a String)])
+      (impl (Show u8) [show (lambda n (str \"u8 \" n))])
+      (impl (Show i64) [show (lambda n (str \"i64 \" n))])
+      (impl (Show (Vec i64)) [show (lambda xs \"vec\")])
+      (impl (Show (Option i64)) [show (lambda x \"option\")])";
+    let mut eval = Eval::with_options(Options { typecheck: true, ..Options::default() });
+    eval_str(&mut eval, src).unwrap();
+    assert_eq!(eval_str(&mut eval, "(show 5)").unwrap(), "i64 5".into());
+    assert_eq!(eval_str(&mut eval, "(show 300)").unwrap(), "i64 300".into());
+    assert_eq!(eval_str(&mut eval, "(show (list 1 2))").unwrap(), "vec".into());
+    assert_eq!(eval_str(&mut eval, "(map show (list 1 2))").unwrap().to_string(), "(\"i64 1\" \"i64 2\")");
+}
+
+#[test]
+fn unchecked_calls_use_the_impl_for_the_type_of_the_value() {
+    let ints = "
+      (deftrait (Show 'a) [show (-> 'a String)])
+      (impl (Show u8) [show (lambda n \"u8\")])
+      (impl (Show i64) [show (lambda n \"i64\")])";
+    let options = "
+      (deftrait (Show 'a) [show (-> 'a String)])
+      (impl (Show i64) [show (lambda n \"i64\")])
+      (impl (Show (Option 'a)) [show (lambda x \"option\")])";
+    for src in [ints, options] {
+        let mut eval = Eval::new();
+        eval_str(&mut eval, src).unwrap();
+        assert_eq!(eval_str(&mut eval, "(show 5)").unwrap(), "i64".into());
+    }
+}
+
+#[test]
+fn polymorphic_callers_pass_the_impl_along() {
+    let src = "
+      (deftrait (Show 'a) [show (-> 'a String)])
+      (impl (Show u8) [show (lambda n (str \"u8 \" n))])
+      (impl (Show i64) [show (lambda n (str \"i64 \" n))])
+      (impl (Show String) [show (lambda s (str \"string \" s))])
+      (impl (Show (Vec 'a)) (where (Show 'a)) [show (lambda xs (join \", \" (map show xs)))])
+      (def twice (lambda x (str (show x) \"; \" (show x))))
+      (defn countdown [n x] (if (= n 0) (show x) (countdown (- n 1) x)))";
+    let mut eval = Eval::with_options(Options { typecheck: true, ..Options::default() });
+    eval_str(&mut eval, src).unwrap();
+    assert_eq!(eval_str(&mut eval, "(twice 5)").unwrap(), "i64 5; i64 5".into());
+    assert_eq!(eval_str(&mut eval, "(twice \"a\")").unwrap(), "string a; string a".into());
+    assert_eq!(eval_str(&mut eval, "(twice (list 1 2))").unwrap(), "i64 1, i64 2; i64 1, i64 2".into());
+    assert_eq!(eval_str(&mut eval, "(countdown 3 \"b\")").unwrap(), "string b".into());
+    let let_bound = "(let (both (lambda x (str (show x) (show x)))) (str (both 1) \" \" (both \"c\")))";
+    assert_eq!(eval_str(&mut eval, let_bound).unwrap(), "i64 1i64 1 string cstring c".into());
+}
+
+#[test]
+fn methods_may_return_the_type_they_are_for() {
+    let src = "
+      (deftrait (Default 'a) [default (-> () 'a)])
+      (impl (Default i64) [default (lambda _ 0)])
+      (impl (Default String) [default (lambda _ \"\")])
+      (impl (Default (Vec 'a)) (where (Default 'a)) [default (lambda u (list (default u) (default u)))])";
+    let mut eval = Eval::with_options(Options { typecheck: true, ..Options::default() });
+    eval_str(&mut eval, src).unwrap();
+    assert_eq!(eval_str(&mut eval, "(the i64 (default nil))").unwrap(), 0.into());
+    assert_eq!(eval_str(&mut eval, "(the String (default nil))").unwrap(), "".into());
+    assert_eq!(eval_str(&mut eval, "(the (Vec i64) (default nil))").unwrap().to_string(), "(0 0)");
+    match type_error(&mut eval, "(default nil)") {
+        TypeError::Ambiguous { constraint, span: Some(_) } => assert_eq!(constraint.trait_name, "Default"),
+        other => panic!("expected an ambiguous constraint, got {:?}", other),
+    }
+    // unchecked, nothing says which impl is meant
+    let mut eval = Eval::new();
+    eval_str(&mut eval, src).unwrap();
+    assert!(matches!(type_error(&mut eval, "(default nil)"),
+                     TypeError::Undispatchable(name, _) if name == "default"));
+}
+
+#[test]
+fn methods_have_qualified_types() {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, SHOW).unwrap();
+    assert_eq!(eval.check(&read_str("show")).unwrap().to_string(), "(forall ('a) (where (Show 'a)) (-> 'a String))");
+    assert_eq!(eval.check(&read_str("(show (list 1 2))")).unwrap().to_string(), "String");
+    eval.check(&read_str("(def twice (lambda x (str (show x) (show x))))")).unwrap();
+    assert_eq!(eval.types().value("twice").unwrap().to_string(),
+               "(forall ('a) (where (Show 'a)) (-> 'a String))");
+}
+
+#[test]
+fn missing_instances_are_type_errors() {
+    let mut eval = Eval::with_options(Options { typecheck: true, ..Options::default() });
+    eval_str(&mut eval, SHOW).unwrap();
+    match type_error(&mut eval, "(show true)") {
+        TypeError::NoInstance { constraint, span: Some(span) } => {
+            assert_eq!(constraint.to_string(), "(Show bool)");
+            assert_eq!(span.start.column, 1);
+        }
+        other => panic!("expected no instance, got {:?}", other),
+    }
+    // through the where clause of the impl for vectors
+    assert!(matches!(type_error(&mut eval, "(show (list true))"), TypeError::NoInstance { .. }));
+    // and through the constraints of a definition
+    eval_str(&mut eval, "(def twice (lambda x (str (show x) (show x))))").unwrap();
+    assert_eq!(eval_str(&mut eval, "(twice 4)").unwrap(), "44".into());
+    assert!(matches!(type_error(&mut eval, "(twice false)"), TypeError::NoInstance { .. }));
+}
+
+#[test]
+fn impls_must_be_coherent() {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, SHOW).unwrap();
+    match type_error(&mut eval, "(impl (Show (Vec i64)) [show (lambda xs \"ints\")])") {
+        TypeError::Overlap { first, second } => {
+            assert_eq!(first.to_string(), "(Show (Vec 'a))");
+            assert_eq!(second.to_string(), "(Show (Vec i64))");
+        }
+        other => panic!("expected an overlap, got {:?}", other),
+    }
+    // the impl that overlapped was not made
+    assert_eq!(eval_str(&mut eval, "(show (list 1))").unwrap(), "[1]".into());
+    // an impl for the same type again replaces the first
+    eval_str(&mut eval, "(impl (Show i64) [show (lambda n \"int\")])").unwrap();
+    assert_eq!(eval_str(&mut eval, "(show 1)").unwrap(), "int".into());
+}
+
+#[test]
+fn malformed_traits_and_impls() {
+    let mut eval = Eval::new();
+    eval_str(&mut eval, SHOW).unwrap();
+    assert!(matches!(type_error(&mut eval, "(impl (Show bool))"),
+                     TypeError::MissingMethod { method, .. } if method == "show"));
+    assert!(matches!(type_error(&mut eval, "(impl (Show bool) [show str] [shout str])"),
+                     TypeError::UnknownMethod { method, .. } if method == "shout"));
+    assert!(matches!(type_error(&mut eval, "(impl (Eq bool) [eq =])"),
+                     TypeError::UnknownTrait(name, _) if name == "Eq"));
+    assert!(matches!(type_error(&mut eval, "(impl (Show Vec) [show str])"), TypeError::KindMismatch { .. }));
+}
+
+#[test]
+fn impl_methods_are_checked_against_the_trait() {
+    let mut eval = Eval::with_options(Options { typecheck: true, ..Options::default() });
+    eval_str(&mut eval, SHOW).unwrap();
+    assert!(matches!(type_error(&mut eval, "(impl (Show bool) [show (lambda b 1)])"), TypeError::Mismatch { .. }));
+    eval_str(&mut eval, "(impl (Show bool) [show (lambda b (if b \"yes\" \"no\"))])").unwrap();
+    assert_eq!(eval_str(&mut eval, "(show (list true false))").unwrap(), "[yes, no]".into());
+}
@@ -0,0 +1,90 @@
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::*;
+use pangolisp::types::*;
+
+fn parse(src: &str) -> Result<Type, TypeError> {
+    TypeEnv::builtin().parse(&read_str(src))
+}
+
+fn show(src: &str) -> String {
+    parse(src).unwrap().to_string()
+}
+
+#[test]
+fn primitives_and_unit() {
+    assert_eq!(parse("u8").unwrap(), builtin::primitive("u8").into());
+    assert_eq!(parse("()").unwrap(), builtin::unit().into());
+    assert_eq!(parse("(tuple)").unwrap(), builtin::unit().into());
+    assert_eq!(parse("i64").unwrap().kind().unwrap(), Kind::Star);
+}
+
+#[test]
+fn applications() {
+    let vec_u8 = Type::from(builtin::vec()).apply(builtin::primitive("u8")).unwrap();
+    assert_eq!(parse("(Vec u8)").unwrap(), vec_u8);
+    assert_eq!(show("(Result (Option String) (& str))"), "(Result (Option String) (& str))");
+    assert_eq!(show("(tuple i64 bool (&mut (Vec u8)))"), "(tuple i64 bool (&mut (Vec u8)))");
+}
+
+#[test]
+fn functions_nest_to_the_right() {
+    let i64 = || Type::from(builtin::primitive("i64"));
+    assert_eq!(parse("(-> i64 i64 i64)").unwrap(), Type::function(i64(), Type::function(i64(), i64())));
+    assert_eq!(show("(-> i64 i64 i64)"), "(-> i64 i64 i64)");
+    assert_eq!(show("(-> (-> i64 i64) i64)"), "(-> (-> i64 i64) i64)");
+}
+
+#[test]
+fn variables() {
+    assert_eq!(parse("'a").unwrap(), TypeVar::new("a", Kind::Star).into());
+    assert_eq!(show("(-> 'a (Option 'a))"), "(-> 'a (Option 'a))");
+    let applied = parse("('f u8)").unwrap();
+    assert_eq!(applied.kind().unwrap(), Kind::Star);
+    assert_eq!(applied.to_string(), "('f u8)");
+}
+
+#[test]
+fn kinds() {
+    assert_eq!(builtin::result().kind.to_string(), "* -> * -> *");
+    assert_eq!(Kind::arrow(Kind::of_arity(1), Kind::Star).to_string(), "(* -> *) -> *");
+    assert_eq!(parse("Vec").unwrap().kind().unwrap(), Kind::of_arity(1));
+    assert_eq!(parse("(Result u8)").unwrap().kind().unwrap(), Kind::of_arity(1));
+}
+
+#[test]
+fn kind_errors() {
+    match parse("(Vec Option)") {
+        Err(TypeError::KindMismatch { expected, found, .. }) => {
+            assert_eq!(expected, Kind::Star);
+            assert_eq!(found, Kind::of_arity(1));
+        }
+        other => panic!("expected a kind mismatch, got {:?}", other),
+    }
+    assert!(matches!(parse("(u8 u8)"), Err(TypeError::NotAConstructor(_))));
+    // applications built by hand are checked when asked for their kind
+    let bad = Type::Application(Box::new(builtin::primitive("u8").into()), Box::new(builtin::unit().into()));
+    assert!(matches!(bad.kind(), Err(TypeError::NotAConstructor(_))));
+    assert_eq!(parse("(Vec Option)").unwrap_err().to_string(), "Option has kind * -> *, expected *");
+}
+
+#[test]
+fn syntax_errors() {
+    match parse("(Vec Bytes)") {
+        Err(TypeError::UnknownType(name, Some(span))) => {
+            assert_eq!(name, "Bytes");
+            assert_eq!(span.start.column, 5);
+        }
+        other => panic!("expected an unknown type, got {:?}", other),
+    }
+    assert!(matches!(parse("\"u8\""), Err(TypeError::BadType(_))));
+}
+
+#[test]
+fn user_constructors() {
+    let mut env = TypeEnv::builtin();
+    env.define(TypeConstructor::new("Tree", Kind::of_arity(1)));
+    assert_eq!(env.parse(&read_str("(Tree (Vec u8))")).unwrap().to_string(), "(Tree (Vec u8))");
+}